@@ -0,0 +1,67 @@
+//! Benchmarks for the work done on every line typed at the prompt.
+//!
+//! Compare against a saved baseline to catch regressions before a release:
+//!
+//!     cargo bench --bench hot_paths -- --save-baseline main    # on the reference commit
+//!     cargo bench --bench hot_paths -- --baseline main         # on the change
+
+use std::fs;
+use std::hint::black_box;
+use criterion::{criterion_group, criterion_main, Criterion};
+use rush::history::History;
+use rush::path_utils::find_in_path;
+use rush::tokenizer::tokenize;
+use rush::{Shell, ShellConfig};
+
+fn bench_tokenize(c: &mut Criterion) {
+    let words = "ls -l 'single quoted' \"double $HOME\" escaped\\ space ";
+    let line = words.repeat(10_000 / words.len());
+    c.bench_function("tokenize 10k line", |b| b.iter(|| tokenize(black_box(&line)).unwrap()));
+    // the common case: nothing to unquote, so every word borrows from the line
+    let words = "grep -rn --include=*.rs pattern src/ ";
+    let line = words.repeat(10_000 / words.len());
+    c.bench_function("tokenize 10k line, unquoted", |b| b.iter(|| tokenize(black_box(&line)).unwrap()));
+}
+
+fn bench_find_in_path(c: &mut Criterion) {
+    // a PATH of 200 directories with the program only in the last one
+    let root = std::env::temp_dir().join(format!("rush-bench-path-{}", std::process::id()));
+    let dirs: Vec<String> = (0..200).map(|i| root.join(i.to_string()).to_string_lossy().into_owned()).collect();
+    for dir in &dirs {
+        fs::create_dir_all(dir).unwrap();
+    }
+    let program = root.join("199").join("rush-bench-program");
+    fs::write(&program, "").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let dirs: Vec<&str> = dirs.iter().map(|d| d.as_str()).collect();
+
+    c.bench_function("find_in_path 200 dirs", |b| b.iter(|| find_in_path(black_box("rush-bench-program"), &dirs).unwrap()));
+    c.bench_function("find_in_path 200 dirs, missing", |b| b.iter(|| find_in_path(black_box("rush-bench-missing"), &dirs)));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+fn bench_history(c: &mut Criterion) {
+    let path = std::env::temp_dir().join(format!("rush-bench-history-{}", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut history = History::open(&path).unwrap();
+    for i in 0..100_000 {
+        history.add(&format!("git commit -m 'change number {}'", i)).unwrap();
+    }
+
+    c.bench_function("history open 100k", |b| b.iter(|| History::open(black_box(&path)).unwrap().len()));
+    let history = History::open(&path).unwrap();
+    c.bench_function("history search 100k, missing", |b| b.iter(|| history.search(black_box("cargo"), history.len())));
+    fs::remove_file(&path).unwrap();
+}
+
+fn bench_prompt(c: &mut Criterion) {
+    let shell = Shell::new(ShellConfig::default());
+    c.bench_function("prompt", |b| b.iter(|| black_box(shell.prompt())));
+}
+
+criterion_group!(benches, bench_tokenize, bench_find_in_path, bench_history, bench_prompt);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rush::tokenizer::{quote, tokenize};
+
+// Quoting any list of words and tokenizing the result must give back exactly those words.
+fuzz_target!(|words: Vec<String>| {
+    let line = words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ");
+    let tokens = tokenize(&line).unwrap_or_else(|e| panic!("{:?} doesn't tokenize: {}", line, e));
+    assert_eq!(tokens, words, "line: {:?}", line);
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rush::syntax::{check_line, display_command};
+use rush::tokenizer::{split_pipeline, tokenize};
+
+// Any line a user might type: none of the front end may panic on it, and errors must point inside the line.
+fuzz_target!(|data: &[u8]| {
+    let Ok(line) = std::str::from_utf8(data) else {
+        return;
+    };
+    let columns = line.chars().count() + 1;
+
+    if let Err(e) = tokenize(line) {
+        assert!(e.column >= 1 && e.column <= columns, "column {} out of range", e.column);
+    }
+    for stage in split_pipeline(line) {
+        let _ = tokenize(stage);
+    }
+    for e in check_line(line) {
+        assert!(e.column <= columns, "column {} out of range", e.column);
+    }
+    let _ = display_command(line);
+});
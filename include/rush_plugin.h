@@ -0,0 +1,42 @@
+/*
+ * The rush plugin ABI.
+ *
+ * A plugin is a shared library (.so, .dylib or .dll) placed in the `plugins` directory of rush's config
+ * directory ($XDG_CONFIG_HOME/rush/plugins or ~/.config/rush/plugins). Plugins are loaded in name order
+ * when the shell starts. Each one exports:
+ *
+ *     uint32_t rush_plugin_abi_version(void);            returns RUSH_PLUGIN_ABI_VERSION
+ *     int rush_plugin_init(const RushPluginHost *host);  registers everything, returns 0 on success
+ *
+ * Strings passed to the host are copied, so they only need to live for the duration of the call. Output is
+ * written through the `write` callback handed to each function, passing along the `sink` pointer it came with.
+ */
+#ifndef RUSH_PLUGIN_H
+#define RUSH_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define RUSH_PLUGIN_ABI_VERSION 1
+
+typedef void (*RushWriteFn)(void *sink, const uint8_t *data, size_t len);
+
+/* Runs a builtin. argv holds the arguments after the builtin's name. Returns the exit status. */
+typedef int (*RushBuiltinFn)(size_t argc, const char *const *argv, void *sink, RushWriteFn write);
+
+/* Writes the text shown in front of the prompt; writing nothing hides the segment. */
+typedef void (*RushPromptSegmentFn)(void *sink, RushWriteFn write);
+
+/* Writes completion candidates for the last word of argv (the command line up to the cursor), one per line. */
+typedef void (*RushCompletionFn)(size_t argc, const char *const *argv, void *sink, RushWriteFn write);
+
+typedef struct RushPluginHost {
+    uint32_t abi_version;
+    void *host;
+    void (*register_builtin)(void *host, const char *name, const char *synopsis, const char *summary,
+                             RushBuiltinFn run);
+    void (*register_prompt_segment)(void *host, const char *name, RushPromptSegmentFn render);
+    void (*register_completion)(void *host, const char *command, RushCompletionFn complete);
+} RushPluginHost;
+
+#endif
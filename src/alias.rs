@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::tokenizer::{quote, split_words, tokenize};
+
+/// Replaces a leading alias in a command's words, as written in the source, with the words of its value.
+/// The new first word is checked again, but a name is never expanded twice, so `alias ls='ls -F'` works.
+/// Quoting or escaping any part of the command word (`\ls`, `'ls'`, `l's'`) suppresses expansion.
+pub fn expand_alias(stage: &str, words: &mut Vec<String>, aliases: &BTreeMap<String, String>) {
+    if stage.trim_start().starts_with(['\\', '\'', '"']) {
+        return;
+    }
+
+    let mut expanded: Vec<String> = Vec::new();
+    while let Some(value) = words.first().and_then(|word| aliases.get(word)) {
+        if expanded.contains(&words[0]) {
+            break;
+        }
+        expanded.push(words[0].clone());
+        words.splice(0..1, split_words(value).into_iter().map(String::from));
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '$', '`', '=', '\'', '"', '\\', ' ', '\t', '|'])
+}
+
+/// Formats an alias definition so that running it recreates the alias.
+pub fn format_alias(name: &str, value: &str) -> String {
+    format!("alias {}={}", name, quote(value))
+}
+
+/// Reads the definitions from an aliases file. Lines that aren't alias definitions are ignored.
+pub fn read_alias_file(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut aliases = BTreeMap::new();
+    for line in contents.lines() {
+        let Ok(words) = tokenize(line) else {
+            continue;
+        };
+        if words.first().map(|w| w.as_ref()) != Some("alias") {
+            continue;
+        }
+        for definition in &words[1..] {
+            if let Some((name, value)) = definition.split_once('=') {
+                aliases.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+pub fn write_alias_file(path: &Path, aliases: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut contents = String::from("# Written by `alias --save` and `unalias --save`\n");
+    for (name, value) in aliases {
+        contents.push_str(&format_alias(name, value));
+        contents.push('\n');
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use crate::alias::{expand_alias, format_alias, is_valid_name, read_alias_file, write_alias_file};
+
+    fn aliases(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    fn expand(stage: &str, aliases: &BTreeMap<String, String>) -> Vec<String> {
+        let mut words: Vec<String> = stage.split_whitespace().map(|w| w.to_string()).collect();
+        expand_alias(stage, &mut words, aliases);
+        words
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases = aliases(&[("ll", "ls -l"), ("ls", "ls -F"), ("l", "ll -a")]);
+        assert_eq!(expand("ll src", &aliases), ["ls", "-F", "-l", "src"]);
+        assert_eq!(expand("l", &aliases), ["ls", "-F", "-l", "-a"]);
+        assert_eq!(expand("echo ll", &aliases), ["echo", "ll"]);
+        assert_eq!(expand(r"\ll", &aliases), [r"\ll"]);
+    }
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("ll"));
+        assert!(is_valid_name("git-st"));
+        assert!(!is_valid_name("a/b"));
+        assert!(!is_valid_name(""));
+    }
+
+    #[test]
+    fn test_alias_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("rush-aliases-{}", std::process::id()));
+        let saved = aliases(&[("ll", "ls -l"), ("q", "it's")]);
+        write_alias_file(&path, &saved).unwrap();
+        assert_eq!(read_alias_file(&path).unwrap(), saved);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(format_alias("ll", "ls -l"), "alias ll='ls -l'");
+    }
+}
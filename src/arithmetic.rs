@@ -0,0 +1,493 @@
+use crate::locale::tr;
+use crate::variables::Variables;
+
+// Operators ordered longest-first so the lexer always takes the greedy match.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=",
+    "**", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "^", "|", "?", ":", ",",
+];
+
+const ASSIGNMENT_OPERATORS: &[&str] = &[
+    "=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(String, &'static str, Box<Expr>),
+    PreIncrement(String, i64),
+    PostIncrement(String, i64),
+    Comma(Box<Expr>, Box<Expr>),
+}
+
+/// Recognizes a standalone `(( expr ))` command and returns the inner expression.
+pub fn arithmetic_command(input: &str) -> Option<&str> {
+    input.trim().strip_prefix("((")?.strip_suffix("))")
+}
+
+/// Evaluates an arithmetic expression using shell variables for identifiers.
+/// Assignments and increments write back into `vars`.
+pub fn evaluate(expr: &str, vars: &mut Variables) -> anyhow::Result<i64> {
+    match parse(expr)? {
+        Some(ast) => eval(&ast, vars),
+        None => Ok(0),
+    }
+}
+
+/// Parses an arithmetic expression without evaluating it, reporting syntax errors only.
+pub fn check(expr: &str) -> anyhow::Result<()> {
+    parse(expr).map(|_| ())
+}
+
+fn parse(expr: &str) -> anyhow::Result<Option<Expr>> {
+    let tokens = lex(expr)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let ast = parser.parse_comma()?;
+    if let Some(token) = parser.peek() {
+        anyhow::bail!(tr!("syntax error in expression (error token is \"{}\")", token_text(token)));
+    }
+
+    Ok(Some(ast))
+}
+
+fn lex(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, ch)) = chars.peek() {
+                if !ch.is_ascii_alphanumeric() {
+                    break;
+                }
+                end = i + ch.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Number(parse_number(&input[start..end])?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, ch)) = chars.peek() {
+                if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                    break;
+                }
+                end = i + ch.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Ident(input[start..end].to_string()));
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RParen);
+        } else {
+            let rest = &input[start..];
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    for _ in 0..op.len() {
+                        chars.next();
+                    }
+                    tokens.push(Token::Op(op));
+                }
+                None => anyhow::bail!(tr!("syntax error: operand expected (error token is \"{}\")", rest)),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> anyhow::Result<i64> {
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8)
+    } else {
+        text.parse::<i64>()
+    };
+
+    parsed.map_err(|_| anyhow::anyhow!(tr!("{}: value too great for base", text)))
+}
+
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Number(n) => n.to_string(),
+        Token::Ident(name) => name.clone(),
+        Token::Op(op) => op.to_string(),
+        Token::LParen => "(".to_string(),
+        Token::RParen => ")".to_string(),
+    }
+}
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    match op {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "|" => Some(3),
+        "^" => Some(4),
+        "&" => Some(5),
+        "==" | "!=" => Some(6),
+        "<" | "<=" | ">" | ">=" => Some(7),
+        "<<" | ">>" => Some(8),
+        "+" | "-" => Some(9),
+        "*" | "/" | "%" => Some(10),
+        "**" => Some(11),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // comma := assignment (',' assignment)*
+    fn parse_comma(&mut self) -> anyhow::Result<Expr> {
+        let mut lhs = self.parse_assignment()?;
+        while self.eat_op(",") {
+            let rhs = self.parse_assignment()?;
+            lhs = Expr::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // assignment := IDENT assign_op assignment | ternary
+    fn parse_assignment(&mut self) -> anyhow::Result<Expr> {
+        if let (Some(Token::Ident(name)), Some(Token::Op(op))) = (self.peek(), self.tokens.get(self.pos + 1))
+            && ASSIGNMENT_OPERATORS.contains(op)
+        {
+            let (name, op) = (name.clone(), *op);
+            self.pos += 2;
+            let value = self.parse_assignment()?;
+            return Ok(Expr::Assign(name, op, Box::new(value)));
+        }
+        self.parse_ternary()
+    }
+
+    // ternary := binary ('?' assignment ':' assignment)?
+    fn parse_ternary(&mut self) -> anyhow::Result<Expr> {
+        let condition = self.parse_binary(1)?;
+        if !self.eat_op("?") {
+            return Ok(condition);
+        }
+        let then_branch = self.parse_assignment()?;
+        if !self.eat_op(":") {
+            anyhow::bail!(tr!("`:' expected for conditional expression"));
+        }
+        let else_branch = self.parse_assignment()?;
+        Ok(Expr::Ternary(Box::new(condition), Box::new(then_branch), Box::new(else_branch)))
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> anyhow::Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = self.peek_op() {
+            let Some(precedence) = binary_precedence(op) else { break };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            // `**` is right-associative, everything else is left-associative
+            let next_precedence = if op == "**" { precedence } else { precedence + 1 };
+            let rhs = self.parse_binary(next_precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> anyhow::Result<Expr> {
+        match self.peek_op() {
+            Some(op @ ("-" | "+" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let delta = if op == "++" { 1 } else { -1 };
+                match self.peek() {
+                    Some(Token::Ident(name)) => {
+                        let name = name.clone();
+                        self.pos += 1;
+                        Ok(Expr::PreIncrement(name, delta))
+                    }
+                    _ => anyhow::bail!(tr!("{}: operand must be a variable", op)),
+                }
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> anyhow::Result<Expr> {
+        let primary = self.parse_primary()?;
+        if let Expr::Var(name) = &primary {
+            match self.peek_op() {
+                Some("++") => {
+                    self.pos += 1;
+                    return Ok(Expr::PostIncrement(name.clone(), 1));
+                }
+                Some("--") => {
+                    self.pos += 1;
+                    return Ok(Expr::PostIncrement(name.clone(), -1));
+                }
+                _ => {}
+            }
+        }
+        Ok(primary)
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<Expr> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(Expr::Var(name))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.parse_comma()?;
+                match self.peek() {
+                    Some(Token::RParen) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    _ => anyhow::bail!(tr!("missing `)'")),
+                }
+            }
+            Some(token) => {
+                anyhow::bail!(tr!("syntax error: operand expected (error token is \"{}\")", token_text(&token)))
+            }
+            None => anyhow::bail!(tr!("syntax error: operand expected")),
+        }
+    }
+}
+
+fn lookup(name: &str, vars: &Variables) -> anyhow::Result<i64> {
+    let value = vars.get(name).unwrap_or_default();
+
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let number = parse_number(digits)
+        .map_err(|_| anyhow::anyhow!(tr!("{}: invalid arithmetic value: {}", name, value)))?;
+    Ok(if negative { number.wrapping_neg() } else { number })
+}
+
+fn apply_binary(op: &str, lhs: i64, rhs: i64) -> anyhow::Result<i64> {
+    Ok(match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => anyhow::bail!(tr!("division by 0")),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => anyhow::bail!(tr!("exponent less than 0")),
+        "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "&" => lhs & rhs,
+        "^" => lhs ^ rhs,
+        "|" => lhs | rhs,
+        "<" => (lhs < rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        _ => anyhow::bail!(tr!("unknown operator {}", op)),
+    })
+}
+
+fn eval(expr: &Expr, vars: &mut Variables) -> anyhow::Result<i64> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Var(name) => lookup(name, vars),
+        Expr::Unary(op, operand) => {
+            let value = eval(operand, vars)?;
+            Ok(match *op {
+                "-" => value.wrapping_neg(),
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                _ => value,
+            })
+        }
+        // Logical operators short-circuit, so side effects on the right may not happen
+        Expr::Binary("&&", lhs, rhs) => {
+            Ok((eval(lhs, vars)? != 0 && eval(rhs, vars)? != 0) as i64)
+        }
+        Expr::Binary("||", lhs, rhs) => {
+            Ok((eval(lhs, vars)? != 0 || eval(rhs, vars)? != 0) as i64)
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, vars)?;
+            let rhs = eval(rhs, vars)?;
+            apply_binary(op, lhs, rhs)
+        }
+        Expr::Ternary(condition, then_branch, else_branch) => {
+            if eval(condition, vars)? != 0 {
+                eval(then_branch, vars)
+            } else {
+                eval(else_branch, vars)
+            }
+        }
+        Expr::Assign(name, op, value) => {
+            let rhs = eval(value, vars)?;
+            let result = match *op {
+                "=" => rhs,
+                compound => apply_binary(&compound[..compound.len() - 1], lookup(name, vars)?, rhs)?,
+            };
+            vars.set(name, &result.to_string());
+            Ok(result)
+        }
+        Expr::PreIncrement(name, delta) => {
+            let result = lookup(name, vars)?.wrapping_add(*delta);
+            vars.set(name, &result.to_string());
+            Ok(result)
+        }
+        Expr::PostIncrement(name, delta) => {
+            let previous = lookup(name, vars)?;
+            vars.set(name, &previous.wrapping_add(*delta).to_string());
+            Ok(previous)
+        }
+        Expr::Comma(lhs, rhs) => {
+            eval(lhs, vars)?;
+            eval(rhs, vars)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arithmetic::{arithmetic_command, check, evaluate};
+    use crate::variables::Variables;
+
+    fn eval_str(expr: &str) -> i64 {
+        evaluate(expr, &mut Variables::new()).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic_command() {
+        assert_eq!(arithmetic_command("((i < 10))"), Some("i < 10"));
+        assert_eq!(arithmetic_command("  (( x++ ))  "), Some(" x++ "));
+        assert_eq!(arithmetic_command("echo ((1))"), None);
+        assert_eq!(arithmetic_command("((1)"), None);
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval_str("1 + 2 * 3"), 7);
+        assert_eq!(eval_str("(1 + 2) * 3"), 9);
+        assert_eq!(eval_str("2 ** 3 ** 2"), 512);
+        assert_eq!(eval_str("-2 ** 2"), 4);
+        assert_eq!(eval_str("10 - 4 - 3"), 3);
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        assert_eq!(eval_str("3 < 10"), 1);
+        assert_eq!(eval_str("3 >= 10"), 0);
+        assert_eq!(eval_str("1 && 0 || 2"), 1);
+        assert_eq!(eval_str("!5"), 0);
+        assert_eq!(eval_str("1 ? 7 : 8"), 7);
+    }
+
+    #[test]
+    fn test_number_bases() {
+        assert_eq!(eval_str("0x1f"), 31);
+        assert_eq!(eval_str("017"), 15);
+    }
+
+    #[test]
+    fn test_assignment_and_increment() {
+        let mut vars = Variables::new();
+        assert_eq!(evaluate("i = 5", &mut vars).unwrap(), 5);
+        assert_eq!(evaluate("i += 2", &mut vars).unwrap(), 7);
+        assert_eq!(evaluate("i++", &mut vars).unwrap(), 7);
+        assert_eq!(vars.get("i").as_deref(), Some("8"));
+        assert_eq!(evaluate("--i", &mut vars).unwrap(), 7);
+        assert_eq!(evaluate("i * 2", &mut vars).unwrap(), 14);
+    }
+
+    #[test]
+    fn test_short_circuit_skips_side_effects() {
+        let mut vars = Variables::new();
+        evaluate("0 && (x = 1)", &mut vars).unwrap();
+        assert_eq!(vars.get("x"), None);
+    }
+
+    #[test]
+    fn test_dynamic_variables() {
+        let mut vars = Variables::new();
+        vars.line_number = 12;
+        assert_eq!(evaluate("LINENO", &mut vars).unwrap(), 12);
+        assert_eq!(evaluate("RANDOM >= 0 && RANDOM <= 32767", &mut vars).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unset_variable_is_zero() {
+        assert_eq!(eval_str("rush_test_unset_variable + 1"), 1);
+    }
+
+    #[test]
+    fn test_check_does_not_evaluate() {
+        assert!(check("x = 1 / 0").is_ok());
+        assert!(check("1 +").is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(evaluate("1 / 0", &mut Variables::new()).is_err());
+        assert!(evaluate("1 +", &mut Variables::new()).is_err());
+        assert!(evaluate("(1", &mut Variables::new()).is_err());
+        assert!(evaluate("2 ** -1", &mut Variables::new()).is_err());
+    }
+}
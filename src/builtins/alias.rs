@@ -0,0 +1,121 @@
+use std::io::Write;
+use crate::alias::{format_alias, is_valid_name, read_alias_file, write_alias_file};
+use crate::builtins::{Execute, Io};
+use crate::dirs::aliases_file;
+use crate::locale::tr;
+
+pub struct AliasBuiltin {
+}
+
+pub struct UnaliasBuiltin {
+}
+
+impl Execute for AliasBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let mut save = false;
+        let mut args = args;
+        while let Some((first, rest)) = args.split_first() {
+            match first.as_str() {
+                "-p" => {}
+                "--save" => save = true,
+                "--" => {
+                    args = rest;
+                    break;
+                }
+                _ => break,
+            }
+            args = rest;
+        }
+
+        // `alias` and `alias -p` list every alias; `alias --save` persists them all
+        if args.is_empty() {
+            for (name, value) in &ctx.aliases {
+                writeln!(io.stdout, "{}", format_alias(name, value))?;
+            }
+            if save {
+                save_aliases(ctx.aliases.keys().cloned().collect(), ctx)?;
+            }
+            return Ok(());
+        }
+
+        let mut defined = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) => {
+                    if !is_valid_name(name) {
+                        anyhow::bail!(tr!("alias: `{}': invalid alias name", name));
+                    }
+                    ctx.aliases.insert(name.to_string(), value.to_string());
+                    defined.push(name.to_string());
+                }
+                None => match ctx.aliases.get(arg) {
+                    Some(value) => {
+                        writeln!(io.stdout, "{}", format_alias(arg, value))?;
+                        defined.push(arg.clone());
+                    }
+                    None => anyhow::bail!(tr!("alias: {}: not found", arg)),
+                },
+            }
+        }
+
+        if save {
+            save_aliases(defined, ctx)?;
+        }
+        Ok(())
+    }
+}
+
+impl Execute for UnaliasBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        let mut save = false;
+        let mut all = false;
+        let mut names = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-a" => all = true,
+                "--save" => save = true,
+                _ => names.push(arg.clone()),
+            }
+        }
+        if !all && names.is_empty() {
+            anyhow::bail!(tr!("unalias: usage: unalias [-a] [--save] name [name ...]"));
+        }
+
+        if all {
+            names = ctx.aliases.keys().cloned().collect();
+            ctx.aliases.clear();
+        } else {
+            for name in &names {
+                if ctx.aliases.remove(name).is_none() {
+                    anyhow::bail!(tr!("unalias: {}: not found", name));
+                }
+            }
+        }
+
+        if save {
+            let path = aliases_file()
+                .ok_or_else(|| anyhow::anyhow!(tr!("{}: cannot locate the aliases file", "unalias")))?;
+            let mut saved = read_alias_file(&path)?;
+            if all {
+                saved.clear();
+            }
+            for name in &names {
+                saved.remove(name);
+            }
+            write_alias_file(&path, &saved)?;
+        }
+        Ok(())
+    }
+}
+
+// Merges the named aliases into the aliases file so that future shells load them
+fn save_aliases(names: Vec<String>, ctx: &crate::builtins::ShellContext) -> anyhow::Result<()> {
+    let path = aliases_file().ok_or_else(|| anyhow::anyhow!(tr!("{}: cannot locate the aliases file", "alias")))?;
+    let mut saved = read_alias_file(&path)?;
+    for name in names {
+        if let Some(value) = ctx.aliases.get(&name) {
+            saved.insert(name, value.clone());
+        }
+    }
+    write_alias_file(&path, &saved)
+}
@@ -1,47 +1,182 @@
-use std::env;
-use std::path::Path;
-use crate::path_utils::{expand_tilde, is_absolute_path};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::builtins::{Io, ShellContext};
+use crate::encoding::{from_os, to_path};
+use crate::error::io_message;
+use crate::locale::tr;
+use crate::path_utils::{home_dir_with, is_absolute_path, normalize_lexically, split_drive, split_path_list};
 
 pub struct CdBuiltin {
 }
 
-impl crate::builtins::Execute for CdBuiltin {
-    fn execute(&self, args: &[String], _ctx: &mut crate::builtins::ShellContext) -> anyhow::Result<()> {
-        let home_dir = &"~".to_string();
+// How many directories `cd -N` can go back to
+const HISTORY_SIZE: usize = 16;
 
-        let target_dir = if args.is_empty() {
-            home_dir
-        } else {
-            &args[0]
-        };
+impl crate::builtins::Execute for CdBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // -P follows symbolic links to the physical directory, -L, the default, keeps the path as given
+        let mut physical = false;
+        let mut args = args;
+        while let Some((first, rest)) = args.split_first() {
+            match first.strip_prefix('-') {
+                Some("-") => {
+                    args = rest;
+                    break;
+                }
+                Some(flags) if !flags.is_empty() && flags.chars().all(|c| c == 'L' || c == 'P') => {
+                    physical = flags.ends_with('P');
+                }
+                _ => break,
+            }
+            args = rest;
+        }
+        if args.len() > 1 {
+            anyhow::bail!(tr!("{}: too many arguments", "cd"));
+        }
 
-        // expand tilde if present
-        let expanded_path = match expand_tilde(target_dir) {
-            Ok(path) => path,
-            Err(e) => anyhow::bail!("cd: {}", e),
+        // `cd -` goes back to OLDPWD and `cd -N` to the Nth directory before this one; both print where they went
+        let back = args.first().and_then(|arg| arg.strip_prefix('-'));
+        let target_dir = &match back {
+            None if args.is_empty() => match home_dir_with(|var| ctx.variables.get(var)) {
+                Some(home) => home,
+                None => anyhow::bail!(tr!("cd: {} not set", "HOME")),
+            },
+            None => args[0].clone(),
+            Some("") => match ctx.variables.get("OLDPWD").filter(|dir| !dir.is_empty()) {
+                Some(dir) => dir,
+                None => anyhow::bail!(tr!("cd: {} not set", "OLDPWD")),
+            },
+            Some(n) => match n.parse::<usize>().ok().and_then(|n| ctx.dir_history.iter().rev().nth(n.checked_sub(1)?)) {
+                Some(dir) => from_os(dir.as_os_str()),
+                None => anyhow::bail!(tr!("cd: {}: no such entry in the directory history", args[0])),
+            },
         };
 
-         // Determine the target path
-        let path = if is_absolute_path(&expanded_path) {
-            // Absolute path
-            Path::new(&expanded_path).to_path_buf()
-        } else {
-            // Relative path - resolve relative to current directory
-            match env::current_dir() {
-                Ok(current) => current.join(&expanded_path),
-                Err(e) => anyhow::bail!("cd: error getting current directory: {}", e),
+        // a relative directory is looked for under each directory CDPATH lists; one found there is printed
+        if back.is_none() {
+            for (dir, listed) in cdpath_candidates(args, ctx) {
+                if let Ok(new) = change_dir("cd", &dir, physical, ctx) {
+                    if listed {
+                        writeln!(io.stdout, "{}", from_os(new.as_os_str()))?;
+                    }
+                    return Ok(());
+                }
             }
-         };
+        }
+        let new = change_dir("cd", target_dir, physical, ctx)?;
+        if back.is_some() {
+            writeln!(io.stdout, "{}", from_os(new.as_os_str()))?;
+        }
+        Ok(())
+    }
+}
 
-        // Check if the path exists and is a directory
-        if path.exists() && path.is_dir() {
-            if let Err(e) = env::set_current_dir(&path) {
-                anyhow::bail!("cd: {}: {}", target_dir, e)
-            }
-        } else {
-            anyhow::bail!("cd: {}: No such file or directory", target_dir)
+// The directories `cd` tries in turn for the directory in `args` when CDPATH is set, each with whether it is
+// under a directory CDPATH lists rather than under an empty entry, which stands for the working directory.
+// Absolute directories and those starting with `.` or `..` aren't looked for.
+fn cdpath_candidates(args: &[String], ctx: &ShellContext) -> Vec<(String, bool)> {
+    let (Some(target), Some(cdpath)) = (args.first(), ctx.variables.get("CDPATH").filter(|cdpath| !cdpath.is_empty()))
+    else {
+        return Vec::new();
+    };
+    let first = target.split(std::path::is_separator).next().unwrap_or_default();
+    let has_drive = split_drive(target).filter(|_| cfg!(windows)).is_some();
+    if is_absolute_path(target) || has_drive || first == "." || first == ".." {
+        return Vec::new();
+    }
+    let candidate = |dir: String| match dir.as_str() {
+        "" => (target.clone(), false),
+        dir => (from_os(to_path(dir).join(to_path(target)).as_os_str()), true),
+    };
+    split_path_list(&cdpath).into_iter().map(candidate).collect()
+}
+
+/// Changes to `target_dir` the way `cd` does, updating PWD, OLDPWD and the directory history, and returns the
+/// new current directory. `name` is the builtin to blame in errors.
+///
+/// A relative `target_dir` is taken from PWD and `..` removes the directory before it, so going back out of a
+/// symbolic link to a directory leads to where the link is. When that directory doesn't exist, or with
+/// `physical`, the path is left to the system, which follows links; PWD is then the directory they lead to.
+pub(crate) fn change_dir(
+    name: &str,
+    target_dir: &str,
+    physical: bool,
+    ctx: &mut ShellContext,
+) -> anyhow::Result<PathBuf> {
+    let current = ctx.system.current_dir();
+    if let Ok(current) = &current {
+        remember_drive_dir(current, ctx);
+    }
+    let logical_current = ctx.logical_dir().or_else(|| current.as_ref().ok().cloned());
+
+    if !physical
+        && let Some(logical_current) = &logical_current
+        && split_drive(target_dir).filter(|_| cfg!(windows)).is_none()
+    {
+        let path = normalize_lexically(&logical_current.join(to_path(target_dir)));
+        if ctx.system.set_current_dir(&path).is_ok() {
+            remember_drive_dir(&path, ctx);
+            record_change(Some(logical_current.clone()), &path, ctx);
+            return Ok(path);
         }
+    }
 
-        Ok(())
+    // Determine the target path
+    let path = if let Some((drive, rest)) = split_drive(target_dir).filter(|_| cfg!(windows)) {
+        // `cd D:` returns to the directory last used on drive D, `cd D:docs` is relative to it
+        let dir = ctx.drive_dirs.get(&drive).cloned().unwrap_or_else(|| PathBuf::from(format!("{}:\\", drive)));
+        if rest.is_empty() { dir } else { dir.join(to_path(rest)) }
+    } else if is_absolute_path(target_dir) {
+        // Absolute path
+        to_path(target_dir)
+    } else {
+        // Relative path - resolve relative to current directory
+        match &current {
+            Ok(current) => current.join(to_path(target_dir)),
+            Err(e) => anyhow::bail!(tr!("{}: error getting current directory: {}", name, e)),
+        }
+    };
+
+    match ctx.system.set_current_dir(&path) {
+        Ok(()) => {
+            remember_drive_dir(&path, ctx);
+            let new = ctx.system.current_dir().unwrap_or(path);
+            record_change(logical_current, &new, ctx);
+            Ok(new)
+        }
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => {
+            anyhow::bail!(tr!("{}: {}: No such file or directory", name, target_dir))
+        }
+        Err(e) => anyhow::bail!("{}: {}: {}", name, target_dir, io_message(&e)),
+    }
+}
+
+// Sets PWD and OLDPWD after a change of directory and moves the directory left to the end of the history,
+// which doesn't hold the current one
+fn record_change(old: Option<PathBuf>, new: &Path, ctx: &mut ShellContext) {
+    let old = old.map(|old| from_os(old.as_os_str())).or_else(|| ctx.variables.get("PWD"));
+    if let Some(old) = old {
+        ctx.variables.set("OLDPWD", &old);
+        let old = PathBuf::from(old);
+        ctx.dir_history.retain(|dir| *dir != old && dir != new);
+        if old != new {
+            ctx.dir_history.push(old);
+        }
+        if ctx.dir_history.len() > HISTORY_SIZE {
+            ctx.dir_history.remove(0);
+        }
+    }
+    ctx.variables.set("PWD", &from_os(new.as_os_str()));
+}
+
+// Records `dir` as the current directory of its drive on Windows
+fn remember_drive_dir(dir: &Path, ctx: &mut ShellContext) {
+    if !cfg!(windows) {
+        return;
+    }
+    if let [drive, b':', ..] = from_os(dir.as_os_str()).as_bytes()
+        && drive.is_ascii_alphabetic()
+    {
+        ctx.drive_dirs.insert(drive.to_ascii_uppercase() as char, dir.to_path_buf());
     }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::io::Write;
+use std::path::PathBuf;
+use crate::builtins::cd::change_dir;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::encoding::from_os;
+use crate::locale::tr;
+use crate::path_utils::{abbreviate_home, home_dir_with};
+
+// The directory stack is the current directory followed by `ShellContext::dir_stack`; entry 0 is the top
+
+pub struct PushdBuiltin {
+}
+
+pub struct PopdBuiltin {
+}
+
+pub struct DirsBuiltin {
+}
+
+impl Execute for PushdBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        if args.len() > 1 {
+            anyhow::bail!(tr!("{}: too many arguments", "pushd"));
+        }
+        let mut stack = full_stack(ctx);
+        // no argument swaps the top two directories, +N and -N rotate entry N to the top
+        let dir = match args.first() {
+            None if stack.len() < 2 => anyhow::bail!(tr!("{}: no other directory", "pushd")),
+            None => {
+                stack.swap(0, 1);
+                None
+            }
+            Some(arg) => match position(arg, stack.len()) {
+                Some(n) => {
+                    stack.rotate_left(n.map_err(|e| anyhow::anyhow!("pushd: {}", e))?);
+                    None
+                }
+                None => Some(arg),
+            },
+        };
+        match dir {
+            Some(dir) => {
+                change_dir("pushd", dir, false, ctx)?;
+                ctx.dir_stack.insert(0, stack.remove(0));
+            }
+            None => {
+                change_dir("pushd", &from_os(stack[0].as_os_str()), false, ctx)?;
+                ctx.dir_stack = stack.split_off(1);
+            }
+        }
+        print_stack(ctx, io, false)
+    }
+}
+
+impl Execute for PopdBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        if args.len() > 1 {
+            anyhow::bail!(tr!("{}: too many arguments", "popd"));
+        }
+        if ctx.dir_stack.is_empty() {
+            anyhow::bail!(tr!("{}: directory stack empty", "popd"));
+        }
+        let stack_len = ctx.dir_stack.len() + 1;
+        let n = match args.first() {
+            Some(arg) => match position(arg, stack_len) {
+                Some(n) => n.map_err(|e| anyhow::anyhow!("popd: {}", e))?,
+                None => anyhow::bail!(tr!("{}: {}: invalid argument", "popd", arg)),
+            },
+            None => 0,
+        };
+        // removing the top changes to the directory below it
+        if n == 0 {
+            change_dir("popd", &from_os(ctx.dir_stack[0].as_os_str()), false, ctx)?;
+        }
+        ctx.dir_stack.remove(n.saturating_sub(1));
+        print_stack(ctx, io, false)
+    }
+}
+
+impl Execute for DirsBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let (mut long, mut lines, mut numbered) = (false, false, false);
+        let mut entry = None;
+        for arg in args {
+            match arg.as_str() {
+                "-c" => ctx.dir_stack.clear(),
+                "-l" => long = true,
+                "-p" => lines = true,
+                "-v" => (lines, numbered) = (true, true),
+                _ => match position(arg, ctx.dir_stack.len() + 1) {
+                    Some(n) => entry = Some(n.map_err(|e| anyhow::anyhow!("dirs: {}", e))?),
+                    None => anyhow::bail!(tr!("{}: {}: invalid argument", "dirs", arg)),
+                },
+            }
+        }
+        if args.iter().any(|arg| arg == "-c") {
+            return Ok(());
+        }
+
+        let names = stack_names(ctx, long);
+        if let Some(n) = entry {
+            writeln!(io.stdout, "{}", names[n])?;
+        } else if numbered {
+            for (n, name) in names.iter().enumerate() {
+                writeln!(io.stdout, "{:2}  {}", n, name)?;
+            }
+        } else if lines {
+            for name in names {
+                writeln!(io.stdout, "{}", name)?;
+            }
+        } else {
+            print_stack(ctx, io, long)?;
+        }
+        Ok(())
+    }
+}
+
+// The current directory followed by the rest of the stack
+fn full_stack(ctx: &ShellContext) -> Vec<PathBuf> {
+    let current = ctx.system.current_dir().unwrap_or_default();
+    std::iter::once(current).chain(ctx.dir_stack.iter().cloned()).collect()
+}
+
+// The stack's entries as `dirs` shows them, with `~` for the home directory unless `long`
+fn stack_names(ctx: &ShellContext, long: bool) -> Vec<String> {
+    let home = if long { None } else { home_dir_with(|name| ctx.variables.get(name)) };
+    full_stack(ctx).iter().map(|dir| abbreviate_home(&from_os(dir.as_os_str()), home.clone())).collect()
+}
+
+// Prints the stack on one line, top first
+fn print_stack(ctx: &ShellContext, io: &mut Io, long: bool) -> anyhow::Result<()> {
+    writeln!(io.stdout, "{}", stack_names(ctx, long).join(" "))?;
+    Ok(())
+}
+
+// The index `+N` (counting from the top) or `-N` (counting from the bottom) names in a stack of `len`
+// entries. None when `arg` is neither, an error when it's out of range.
+fn position(arg: &str, len: usize) -> Option<Result<usize, String>> {
+    let from_top = arg.starts_with('+');
+    let n: usize = arg.strip_prefix(['+', '-'])?.parse().ok()?;
+    let index = if from_top { Some(n) } else { len.checked_sub(n + 1) };
+    Some(index.filter(|index| *index < len).ok_or_else(|| tr!("{}: directory stack index out of range", arg)))
+}
@@ -1,11 +1,99 @@
-use crate::builtins::Execute;
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::encoding::encode;
 
 pub struct EchoBuiltin {
 }
 
 impl Execute for EchoBuiltin {
-    fn execute(&self, args: &[String], _ctx: &mut crate::builtins::ShellContext) -> anyhow::Result<()> {
-        println!("{}", args.join(" "));
+    fn execute(&self, args: &[String], _ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // leading words made only of the flags n, e and E are options; anything else is printed, `--` too
+        let is_option = |arg: &&String| {
+            arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| "neE".contains(c))
+        };
+        let options: Vec<&String> = args.iter().take_while(is_option).collect();
+        let mut newline = true;
+        let mut escapes = false;
+        for c in options.iter().flat_map(|option| option[1..].chars()) {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+
+        let mut output = Vec::new();
+        for (i, arg) in args[options.len()..].iter().enumerate() {
+            if i > 0 {
+                output.push(b' ');
+            }
+            if !escapes {
+                output.extend(encode(arg));
+            } else if unescape(arg, &mut output) {
+                // \c ends the output, newline and all
+                io.stdout.write_all(&output)?;
+                return Ok(());
+            }
+        }
+        if newline {
+            output.push(b'\n');
+        }
+        io.stdout.write_all(&output)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Appends `arg` to `output` with the backslash escapes of `echo -e` replaced: \a \b \e \f \n \r \t \v \\,
+// \0NNN for a byte in octal and \xHH in hex. Returns true at \c, which stops the output.
+fn unescape(arg: &str, output: &mut Vec<u8>) -> bool {
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.extend(encode(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('a') => 0x07,
+            Some('b') => 0x08,
+            Some('c') => return true,
+            Some('e') => 0x1b,
+            Some('f') => 0x0c,
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('v') => 0x0b,
+            Some('\\') => b'\\',
+            Some('0') => number(&mut chars, 8, 3).unwrap_or(0),
+            // without hex digits \x stays as it is
+            Some('x') => match number(&mut chars, 16, 2) {
+                Some(byte) => byte,
+                None => {
+                    output.extend(b"\\x");
+                    continue;
+                }
+            },
+            Some(other) => {
+                output.push(b'\\');
+                output.extend(encode(other.encode_utf8(&mut [0; 4])));
+                continue;
+            }
+            None => b'\\',
+        };
+        output.push(byte);
+    }
+    false
+}
+
+// Reads up to `max_digits` digits in `radix` as a byte, None when there are none
+fn number(chars: &mut std::iter::Peekable<std::str::Chars>, radix: u32, max_digits: usize) -> Option<u8> {
+    let mut value: Option<u32> = None;
+    for _ in 0..max_digits {
+        let Some(digit) = chars.peek().and_then(|c| c.to_digit(radix)) else {
+            break;
+        };
+        chars.next();
+        value = Some(value.unwrap_or(0) * radix + digit);
+    }
+    // \0400 and up wrap around like in bash
+    value.map(|value| value as u8)
+}
@@ -0,0 +1,28 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::error::RushError;
+use crate::locale::tr;
+
+pub struct ExitBuiltin {
+}
+
+impl Execute for ExitBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let status = match args {
+            [] => ctx.last_status,
+            // statuses are a byte, so `exit 256` is 0 and `exit -1` is 255, as the parent would see them
+            [n] => match n.parse::<i64>() {
+                Ok(status) => status.rem_euclid(256) as i32,
+                Err(_) => {
+                    // the shell still exits, with the status of a usage error
+                    writeln!(io.stderr, "{}: {}", ctx.location(), tr!("{}: {}: numeric argument required", "exit", n))?;
+                    2
+                }
+            },
+            // too many arguments is an error that leaves the shell running
+            _ => anyhow::bail!(tr!("{}: too many arguments", "exit")),
+        };
+        // unwinds to whatever is running the shell, which turns it into the exit status
+        Err(RushError::Exit(status).into())
+    }
+}
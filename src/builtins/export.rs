@@ -0,0 +1,43 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::locale::tr;
+use crate::tokenizer::{is_name, quote};
+
+pub struct ExportBuiltin {
+}
+
+impl Execute for ExportBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // `export` or `export -p` lists the exported variables as commands that export them again
+        if args.is_empty() || args.iter().all(|arg| arg == "-p") {
+            for name in ctx.variables.exported() {
+                match ctx.variables.get(name) {
+                    Some(value) => writeln!(io.stdout, "export {}={}", name, quote(&value))?,
+                    None => writeln!(io.stdout, "export {}", name)?,
+                }
+            }
+            return Ok(());
+        }
+
+        let mut invalid = None;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            // like bash, a bad name doesn't stop the others from being exported
+            if !is_name(name) {
+                invalid.get_or_insert(arg);
+                continue;
+            }
+            if let Some(value) = value {
+                ctx.variables.set(name, value);
+            }
+            ctx.variables.set_exported(name, true);
+        }
+        if let Some(arg) = invalid {
+            anyhow::bail!(tr!("export: `{}': not a valid identifier", arg));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::error::RushError;
+use crate::locale::tr;
+
+pub struct HashBuiltin {
+}
+
+impl Execute for HashBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let (mut clear, mut delete, mut print) = (false, false, false);
+        let mut names = args;
+        while let Some((first, rest)) = names.split_first() {
+            match first.as_str() {
+                "-r" => clear = true,
+                "-d" => delete = true,
+                "-t" => print = true,
+                "--" => {
+                    names = rest;
+                    break;
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    anyhow::bail!(tr!("{}: {}: invalid option", "hash", option));
+                }
+                _ => break,
+            }
+            names = rest;
+        }
+        if clear {
+            ctx.command_hash.clear();
+        }
+        if names.is_empty() {
+            if !clear {
+                list(ctx, io)?;
+            }
+            return Ok(());
+        }
+
+        // -d forgets the names, -t shows where they were found, and without either they're looked up
+        let path_dirs = ctx.search_path();
+        let mut status = 0;
+        for name in names {
+            let found = if delete {
+                ctx.command_hash.remove(name)
+            } else if print {
+                match ctx.command_hash.get(name, &path_dirs) {
+                    Some(path) if names.len() > 1 => writeln!(io.stdout, "{}\t{}", name, path.display()).map(|_| true)?,
+                    Some(path) => writeln!(io.stdout, "{}", path.display()).map(|_| true)?,
+                    None => false,
+                }
+            } else {
+                ctx.builtins.contains(name) || ctx.command_hash.add(name, &path_dirs, &*ctx.system).is_some()
+            };
+            if !found {
+                writeln!(io.stderr, "{}: {}", ctx.location(), tr!("{}: {}: not found", "hash", name))?;
+                status = 1;
+            }
+        }
+        if status != 0 {
+            return Err(RushError::Status(status).into());
+        }
+        Ok(())
+    }
+}
+
+// Prints how often each remembered program ran and where it is
+fn list(ctx: &ShellContext, io: &mut Io) -> anyhow::Result<()> {
+    let mut entries = ctx.command_hash.entries().peekable();
+    if entries.peek().is_none() {
+        writeln!(io.stdout, "{}", tr!("{}: hash table empty", "hash"))?;
+        return Ok(());
+    }
+    writeln!(io.stdout, "{}", tr!("hits\tcommand"))?;
+    for (_, path, hits) in entries {
+        writeln!(io.stdout, "{:4}\t{}", hits, path.display())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::locale::{tr, translate};
+
+pub struct HelpBuiltin {
+}
+
+impl Execute for HelpBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        ctx.load_plugins();
+        // `help` lists every builtin, `help name ...` describes the named ones
+        let names: Vec<&str> = if args.is_empty() {
+            ctx.builtins.names()
+        } else {
+            args.iter().map(|s| s.as_str()).collect()
+        };
+
+        let width = names.iter().filter_map(|name| ctx.builtins.get(name)).map(|e| e.synopsis.len()).max().unwrap_or(0);
+        for name in names {
+            match ctx.builtins.get(name) {
+                Some(entry) => writeln!(io.stdout, "{:<width$}  {}", entry.synopsis, translate(entry.summary), width = width)?,
+                None => anyhow::bail!(tr!("help: no help topics match `{}'", name)),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::encoding::encode;
+use crate::locale::tr;
+
+pub struct HistoryBuiltin {
+}
+
+impl Execute for HistoryBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // shells that don't read from a terminal have no history
+        let Some(history) = &ctx.history else {
+            return Ok(());
+        };
+        let count = match args {
+            [] => history.len(),
+            [n] => match n.parse::<usize>() {
+                Ok(count) => count.min(history.len()),
+                Err(_) => anyhow::bail!(tr!("{}: {}: numeric argument required", "history", n)),
+            },
+            _ => anyhow::bail!(tr!("{}: too many arguments", "history")),
+        };
+
+        let mut listing = String::new();
+        for i in history.len() - count..history.len() {
+            listing.push_str(&format!("{:5}  {}\n", i + 1, history.get(i).unwrap_or_default()));
+        }
+        io.stdout.write_all(&encode(&listing))?;
+        Ok(())
+    }
+}
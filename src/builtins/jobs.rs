@@ -0,0 +1,93 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::encoding::encode;
+use crate::error::RushError;
+use crate::jobs::{self, JobState};
+use crate::locale::tr;
+use crate::theme::{self, Role};
+
+pub struct JobsBuiltin {
+}
+
+pub struct FgBuiltin {
+}
+
+pub struct BgBuiltin {
+}
+
+impl Execute for JobsBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        if !args.is_empty() {
+            anyhow::bail!(tr!("{}: too many arguments", "jobs"));
+        }
+        // jobs that have finished are listed one last time
+        let mut lines: Vec<(usize, String, JobState)> = ctx.jobs.take_finished().into_iter()
+            .map(|(marker, job)| (job.id, job.describe(marker), job.state))
+            .collect();
+        lines.extend(ctx.jobs.iter().map(|job| (job.id, job.describe(ctx.jobs.marker(job.id)), job.state)));
+        lines.sort_by_key(|(id, _, _)| *id);
+        for (_, line, state) in lines {
+            let role = match state {
+                JobState::Running => Role::Running,
+                JobState::Stopped => Role::Stopped,
+                JobState::Done(0) => Role::Done,
+                JobState::Done(_) | JobState::Killed(_) => Role::Failure,
+            };
+            let line = theme::paint(&line, role, ctx, io.on_terminal);
+            io.stdout.write_all(&encode(&format!("{}\n", line)))?;
+        }
+        Ok(())
+    }
+}
+
+impl Execute for FgBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let id = find_job("fg", args, ctx)?;
+        let marker = ctx.jobs.marker(id);
+        let job = ctx.jobs.get_mut(id).unwrap();
+        io.stdout.write_all(&encode(&format!("{}\n", job.command)))?;
+        io.stdout.flush()?;
+        let waited = jobs::in_foreground(job.pids()[0], || {
+            // the job gets the terminal before it continues
+            if job.state == JobState::Stopped {
+                jobs::resume(job)?;
+                job.state = JobState::Running;
+            }
+            job.wait_or_stop()
+        })?;
+        // stopped again, it stays a job
+        let Some(status) = waited else {
+            writeln!(io.stderr, "\n{}", job.describe(marker))?;
+            return Err(RushError::Status(128 + jobs::signal_number("TSTP").unwrap_or(20)).into());
+        };
+        ctx.jobs.remove(id);
+        if status != 0 {
+            return Err(RushError::Status(status).into());
+        }
+        Ok(())
+    }
+}
+
+impl Execute for BgBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let id = find_job("bg", args, ctx)?;
+        let marker = ctx.jobs.marker(id);
+        let job = ctx.jobs.get_mut(id).unwrap();
+        if job.state != JobState::Stopped {
+            anyhow::bail!(tr!("bg: job {} already in background", id));
+        }
+        jobs::resume(job)?;
+        job.state = JobState::Running;
+        io.stdout.write_all(&encode(&format!("[{}]{} {} &\n", id, marker, job.command)))?;
+        Ok(())
+    }
+}
+
+// The job named by the optional job spec in `args`, the current job without one
+fn find_job(name: &str, args: &[String], ctx: &ShellContext) -> anyhow::Result<usize> {
+    match args {
+        [] => ctx.jobs.find(None).ok_or_else(|| anyhow::anyhow!(tr!("{}: current: no such job", name))),
+        [spec] => ctx.jobs.find(Some(spec)).ok_or_else(|| anyhow::anyhow!(tr!("{}: {}: no such job", name, spec))),
+        _ => anyhow::bail!(tr!("{}: too many arguments", name)),
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::error::{io_message, RushError};
+use crate::jobs::{self, JobState};
+use crate::locale::tr;
+
+pub struct KillBuiltin {
+}
+
+impl Execute for KillBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let mut signal = jobs::signal_number("TERM").unwrap_or(15);
+        let mut targets = args;
+        match args.first().map(String::as_str) {
+            Some("-l" | "-L") => return list(&args[1..], io),
+            Some(option @ ("-s" | "-n")) => {
+                let [_, spec, rest @ ..] = args else {
+                    anyhow::bail!(tr!("{}: {}: option requires an argument", "kill", option));
+                };
+                signal = parse_signal(spec)?;
+                targets = rest;
+            }
+            Some(option) if option.starts_with('-') && option.len() > 1 && option != "--" => {
+                signal = parse_signal(&option[1..])?;
+                targets = &args[1..];
+            }
+            _ => {}
+        }
+        // `--` ends the options, so that the ID of a process group can follow
+        if targets.first().is_some_and(|first| first == "--") {
+            targets = &targets[1..];
+        }
+        if targets.is_empty() {
+            anyhow::bail!(tr!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ..."));
+        }
+
+        let mut status = 0;
+        for target in targets {
+            if let Err(message) = send(target, signal, ctx) {
+                writeln!(io.stderr, "{}: {}", ctx.location(), message)?;
+                status = 1;
+            }
+        }
+        if status != 0 {
+            return Err(RushError::Status(status).into());
+        }
+        Ok(())
+    }
+}
+
+// The signal a `-s`, `-n` or `-sigspec` option names
+fn parse_signal(spec: &str) -> anyhow::Result<i32> {
+    jobs::signal_number(spec).ok_or_else(|| anyhow::anyhow!(tr!("{}: {}: invalid signal specification", "kill", spec)))
+}
+
+// `kill -l`: the names of all signals, or the name of each signal number given and the number of each name.
+// A number above 128 is taken as the status of a program the signal killed.
+fn list(specs: &[String], io: &mut Io) -> anyhow::Result<()> {
+    if specs.is_empty() {
+        let names: Vec<&str> = jobs::SIGNALS.iter().map(|&(name, _)| name).collect();
+        writeln!(io.stdout, "{}", names.join(" "))?;
+        return Ok(());
+    }
+    for spec in specs {
+        let listed = match spec.parse::<i32>() {
+            Ok(number) => jobs::signal_name(if number > 128 { number - 128 } else { number }).map(String::from),
+            Err(_) => jobs::signal_number(spec).map(|number| number.to_string()),
+        };
+        match listed {
+            Some(listed) => writeln!(io.stdout, "{}", listed)?,
+            None => anyhow::bail!(tr!("{}: {}: invalid signal specification", "kill", spec)),
+        }
+    }
+    Ok(())
+}
+
+// Sends `signal` to a job, named by a job spec, or to a process by ID, a process group for a negative ID.
+// Returns the message to report when that fails.
+fn send(target: &str, signal: i32, ctx: &mut ShellContext) -> Result<(), String> {
+    if target.starts_with('%') {
+        let id = ctx.jobs.find(Some(target)).ok_or_else(|| tr!("{}: {}: no such job", "kill", target))?;
+        let job = ctx.jobs.get_mut(id).unwrap();
+        let pids = job.pids();
+        // the programs of a job share a process group where there are groups
+        let sent = if cfg!(unix) {
+            jobs::kill(pids[0], true, signal)
+        } else {
+            pids.iter().try_for_each(|&pid| jobs::kill(pid, false, signal))
+        };
+        sent.map_err(|e| format!("kill: {}: {}", target, io_message(&e)))?;
+
+        let is = |name| jobs::signal_number(name) == Some(signal);
+        if is("CONT") {
+            job.state = JobState::Running;
+        } else if is("STOP") || is("TSTP") || is("TTIN") || is("TTOU") {
+            job.state = JobState::Stopped;
+        } else if job.state == JobState::Stopped && signal != 0 {
+            // a stopped program only gets the signal once it continues
+            jobs::resume(job).map_err(|e| format!("kill: {}: {}", target, io_message(&e)))?;
+            job.state = JobState::Running;
+        }
+        return Ok(());
+    }
+
+    let pid = target.parse::<i32>().map_err(|_| tr!("{}: {}: arguments must be process or job IDs", "kill", target))?;
+    jobs::kill(pid.unsigned_abs(), pid < 0, signal).map_err(|e| format!("kill: ({}) - {}", target, io_message(&e)))
+}
@@ -0,0 +1,67 @@
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::error::RushError;
+use crate::locale::tr;
+
+pub struct BreakBuiltin {
+}
+
+pub struct ContinueBuiltin {
+}
+
+impl Execute for BreakBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        let count = loop_count("break", args, ctx)?;
+        Err(RushError::Break(count).into())
+    }
+}
+
+impl Execute for ContinueBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        let count = loop_count("continue", args, ctx)?;
+        Err(RushError::Continue(count).into())
+    }
+}
+
+/// How many loops `break`/`continue` leave: a count beyond the loops the command is in means all of them.
+fn loop_count(name: &str, args: &[String], ctx: &ShellContext) -> anyhow::Result<usize> {
+    let count = parse_loop_count(name, args)?;
+    if ctx.loop_depth == 0 {
+        anyhow::bail!(tr!("{}: only meaningful in a `for', `while', or `until' loop", name));
+    }
+    Ok(count.min(ctx.loop_depth))
+}
+
+/// Parses the optional `n` argument of `break`/`continue`, defaulting to 1.
+fn parse_loop_count(name: &str, args: &[String]) -> anyhow::Result<usize> {
+    match args {
+        [] => Ok(1),
+        [n] => match n.parse::<i64>() {
+            Ok(count) if count >= 1 => Ok(count as usize),
+            Ok(_) => anyhow::bail!(tr!("{}: {}: loop count out of range", name, n)),
+            Err(_) => anyhow::bail!(tr!("{}: {}: numeric argument required", name, n)),
+        },
+        _ => anyhow::bail!(tr!("{}: too many arguments", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_loop_count;
+
+    #[test]
+    fn test_default_count() {
+        assert_eq!(parse_loop_count("break", &[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_explicit_count() {
+        assert_eq!(parse_loop_count("break", &["3".to_string()]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_invalid_count() {
+        assert!(parse_loop_count("continue", &["0".to_string()]).is_err());
+        assert!(parse_loop_count("continue", &["abc".to_string()]).is_err());
+        assert!(parse_loop_count("continue", &["1".to_string(), "2".to_string()]).is_err());
+    }
+}
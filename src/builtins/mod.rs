@@ -1,59 +1,393 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, PathBuf};
+use std::rc::Rc;
+use crate::command_hash::CommandHash;
+use crate::completion::CompletionSpecs;
+use crate::error::RushError;
+use crate::history::History;
+use crate::jobs::Jobs;
+use crate::variables::Variables;
+use crate::debugger::Debugger;
+use crate::options::ShellOptions;
+use crate::path_index::PathIndex;
+use crate::encoding::to_path;
+use crate::path_utils::{is_absolute_path, split_path_list};
+use crate::plugin::{CompletionProvider, PromptSegment};
+use crate::profiler::Profiler;
+use crate::prompt::GitCache;
+use crate::system::{RealSystem, System};
+use crate::builtins::alias::{AliasBuiltin, UnaliasBuiltin};
 use crate::builtins::cd::CdBuiltin;
+use crate::builtins::dir_stack::{DirsBuiltin, PopdBuiltin, PushdBuiltin};
 use crate::builtins::echo::EchoBuiltin;
+use crate::builtins::exit::ExitBuiltin;
+use crate::builtins::export::ExportBuiltin;
+use crate::builtins::hash::HashBuiltin;
+use crate::builtins::help::HelpBuiltin;
+use crate::builtins::history::HistoryBuiltin;
+use crate::builtins::jobs::{BgBuiltin, FgBuiltin, JobsBuiltin};
+use crate::builtins::kill::KillBuiltin;
+use crate::builtins::loop_control::{BreakBuiltin, ContinueBuiltin};
 use crate::builtins::pwd::PwdBuiltin;
+use crate::builtins::read::ReadBuiltin;
+use crate::builtins::set::SetBuiltin;
+use crate::builtins::shift::ShiftBuiltin;
+use crate::builtins::source::{DotBuiltin, SourceBuiltin};
+use crate::builtins::trap::TrapBuiltin;
 use crate::builtins::type_builtin::TypeBuiltin;
 
+mod alias;
 mod echo;
+mod exit;
+mod export;
+mod hash;
+mod help;
+mod history;
+mod jobs;
+mod kill;
 mod pwd;
+mod read;
 mod type_builtin;
 mod cd;
+mod dir_stack;
+mod loop_control;
+mod set;
+mod shift;
+mod source;
+mod trap;
 
-pub enum Builtin {
-    Echo(EchoBuiltin),
-    Cd(CdBuiltin),
-    Pwd(PwdBuiltin),
-    Type(TypeBuiltin),
+/// A registered builtin: the command itself plus what `help` shows for it.
+pub struct BuiltinEntry {
+    pub command: Rc<dyn Execute>,
+    pub synopsis: &'static str,
+    pub summary: &'static str,
 }
 
-impl Execute for Builtin {
-    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> anyhow::Result<()> {
-        match self {
-            Builtin::Echo(b) => b.execute(args, ctx),
-            Builtin::Cd(b) => b.execute(args, ctx),
-            Builtin::Pwd(b) => b.execute(args, ctx),
-            Builtin::Type(b) => b.execute(args, ctx),
-        }
-    }
+/// The builtins a shell knows, by name. Everything that lists or looks up builtins (`type`, `help`,
+/// command lookup, pipelines) goes through here.
+#[derive(Default)]
+pub struct BuiltinRegistry {
+    entries: HashMap<&'static str, BuiltinEntry>,
 }
 
-impl Builtin {
-    pub fn from_name(name: &str) -> Option<Self> {
-        match name {
-            "echo" => Some(Builtin::Echo(EchoBuiltin {})),
-            "cd" => Some(Builtin::Cd(CdBuiltin {})),
-            "pwd" => Some(Builtin::Pwd(PwdBuiltin {})),
-            "type" => Some(Builtin::Type(TypeBuiltin {})),
-            _ => None,
-        }
+impl BuiltinRegistry {
+    /// A registry holding all of rush's own builtins.
+    pub fn with_defaults() -> Self {
+        let mut registry = BuiltinRegistry::default();
+        registry.register(".", DotBuiltin {}, ". file [arg ...]", "Run commands from a file in the current shell");
+        registry.register("alias", AliasBuiltin {}, "alias [-p] [--save] [name[=value] ...]", "Define or list aliases");
+        registry.register("bg", BgBuiltin {}, "bg [job]", "Resume a stopped job in the background");
+        registry.register("break", BreakBuiltin {}, "break [n]", "Exit from a loop");
+        registry.register("cd", CdBuiltin {}, "cd [-L | -P] [dir | - | -N]", "Change the current directory");
+        registry.register("continue", ContinueBuiltin {}, "continue [n]", "Start the next iteration of a loop");
+        registry.register("dirs", DirsBuiltin {}, "dirs [-clpv] [+N | -N]", "Display the directory stack");
+        registry.register("echo", EchoBuiltin {}, "echo [-neE] [arg ...]", "Write arguments to standard output");
+        registry.register("exit", ExitBuiltin {}, "exit [n]", "Exit the shell with status n, or that of the last command");
+        registry.register("export", ExportBuiltin {}, "export [-p] [name[=value] ...]", "Export variables to the environment of commands");
+        registry.register("fg", FgBuiltin {}, "fg [job]", "Move a job to the foreground");
+        registry.register("hash", HashBuiltin {}, "hash [-r] [-d | -t] [name ...]", "Remember or display program locations");
+        registry.register("help", HelpBuiltin {}, "help [name ...]", "Describe builtin commands");
+        registry.register("history", HistoryBuiltin {}, "history [n]", "Display the command history");
+        registry.register("jobs", JobsBuiltin {}, "jobs", "Display the status of jobs");
+        registry.register("kill", KillBuiltin {}, "kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]", "Send a signal to jobs or processes");
+        registry.register("popd", PopdBuiltin {}, "popd [+N | -N]", "Remove a directory from the directory stack");
+        registry.register("pwd", PwdBuiltin {}, "pwd [-L | -P]", "Print the current directory");
+        registry.register("pushd", PushdBuiltin {}, "pushd [dir | +N | -N]", "Add a directory to the directory stack");
+        registry.register("read", ReadBuiltin {}, "read [-rs] [-p prompt] [name ...]", "Read a line from standard input into variables");
+        registry.register("set", SetBuiltin {}, "set [-eux] [-o option] [--] [arg ...]", "Set options and positional parameters");
+        registry.register("shift", ShiftBuiltin {}, "shift [n]", "Shift positional parameters");
+        registry.register("source", SourceBuiltin {}, "source file [arg ...]", "Run commands from a file in the current shell");
+        registry.register("trap", TrapBuiltin {}, "trap [-p] [action condition ...]", "Run commands on shell events");
+        registry.register("type", TypeBuiltin {}, "type [-a] name ...", "Describe how command names are resolved");
+        registry.register("unalias", UnaliasBuiltin {}, "unalias [-a] [--save] name ...", "Remove aliases");
+        registry
+    }
+
+    /// Adds a builtin, replacing any earlier one with the same name.
+    pub fn register(&mut self, name: &'static str, command: impl Execute + 'static, synopsis: &'static str, summary: &'static str) {
+        self.entries.insert(name, BuiltinEntry { command: Rc::new(command), synopsis, summary });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BuiltinEntry> {
+        self.entries.get(name)
     }
-}
 
-const BUILTINS: &[&str] = &["exit", "echo", "type", "pwd", "cd"];
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Names of all registered builtins, sorted.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.entries.keys().copied().collect();
+        names.sort();
+        names
+    }
+}
 
 pub struct ShellContext{
+    /// Where programs are looked up until the shell sets PATH itself; see `search_path`
     pub path_dirs: Vec<String>,
-    pub builtin_names: Vec<&'static str>,
+    pub builtins: BuiltinRegistry,
+    pub variables: Variables,
+    pub last_status: i32,
+    pub last_error: Option<RushError>,
+    pub shell_name: String,
+    pub source_name: Option<String>,
+    pub interactive: bool,
+    pub login: bool,
+    pub traps: HashMap<String, String>,
+    pub aliases: BTreeMap<String, String>,
+    pub in_trap: bool,
+    /// Set while the shell runs a condition, such as that of `if`, whose failure is no error
+    pub in_condition: bool,
+    /// How many `for` and `while` loops the running command is in, which `break` and `continue` can leave
+    pub loop_depth: usize,
+    pub options: ShellOptions,
+    pub positional_params: Vec<String>,
+    pub debugger: Option<Debugger>,
+    pub profiler: Option<Profiler>,
+    pub system: Rc<dyn System>,
+    /// Shown in front of the prompt in order, each by name
+    pub prompt_segments: Vec<(String, Rc<dyn PromptSegment>)>,
+    /// Where the prompt's `\g` last found the git repository
+    pub git_cache: GitCache,
+    /// Completion providers by command name
+    pub completions: HashMap<String, Rc<dyn CompletionProvider>>,
+    /// What completes the arguments of commands without a completion provider, by command name
+    pub completion_specs: CompletionSpecs,
+    /// Programs in PATH, for completing command names
+    pub path_index: PathIndex,
+    /// Where programs run so far were found in PATH, which `hash` shows and clears
+    pub command_hash: CommandHash,
+    /// The directory last used on each drive, which `cd D:` returns to on Windows
+    pub drive_dirs: HashMap<char, PathBuf>,
+    /// The directories `cd` left, oldest first, which `cd -N` returns to
+    pub dir_history: Vec<PathBuf>,
+    /// The directory stack of `pushd` and `popd` below the current directory, top first
+    pub dir_stack: Vec<PathBuf>,
+    /// Plugin directory whose plugins haven't been loaded yet; see `load_plugins`
+    pub pending_plugins: Option<PathBuf>,
+    /// The lines entered at the prompt, in interactive shells
+    pub history: Option<History>,
+    /// Pipelines started in the background
+    pub jobs: Jobs,
+    /// The bodies of the here-documents of the line being run that no command has read yet, in order
+    pub here_docs: VecDeque<String>,
 }
 
 impl ShellContext {
     pub fn new(path_dirs: Vec<String>) -> Self {
         ShellContext {
             path_dirs,
-            builtin_names: BUILTINS.to_vec(),
+            builtins: BuiltinRegistry::with_defaults(),
+            variables: Variables::new(),
+            last_status: 0,
+            last_error: None,
+            shell_name: "rush".to_string(),
+            source_name: None,
+            interactive: true,
+            login: false,
+            traps: HashMap::new(),
+            aliases: BTreeMap::new(),
+            in_trap: false,
+            in_condition: false,
+            loop_depth: 0,
+            options: ShellOptions::default(),
+            positional_params: Vec::new(),
+            debugger: None,
+            profiler: None,
+            system: Rc::new(RealSystem),
+            prompt_segments: Vec::new(),
+            git_cache: GitCache::default(),
+            completions: HashMap::new(),
+            completion_specs: CompletionSpecs::with_defaults(),
+            path_index: PathIndex::default(),
+            command_hash: CommandHash::default(),
+            drive_dirs: HashMap::new(),
+            dir_history: Vec::new(),
+            dir_stack: Vec::new(),
+            pending_plugins: None,
+            history: None,
+            jobs: Jobs::default(),
+            here_docs: VecDeque::new(),
+        }
+    }
+
+    /// The directories programs are looked up in: those of the shell's own PATH variable once it has one,
+    /// e.g. after `export PATH=...` or for a command run as `PATH=... command`, and otherwise `path_dirs`, the
+    /// ones the shell started with. The `hash` table starts over whenever they change.
+    pub fn search_path(&self) -> Vec<String> {
+        match self.variables.stored("PATH") {
+            Some(path) => split_path_list(&path),
+            None => self.path_dirs.clone(),
+        }
+    }
+
+    /// The current directory the way the shell got there, through symbolic links as they were given: PWD, which
+    /// `cd` keeps up to date, as long as it is an absolute path without `.` or `..` that leads to the current
+    /// directory. None when only the physical directory is known.
+    pub fn logical_dir(&self) -> Option<PathBuf> {
+        let pwd = self.variables.get("PWD").filter(|pwd| is_absolute_path(pwd))?;
+        let pwd = to_path(&pwd);
+        if pwd.components().any(|component| matches!(component, Component::CurDir | Component::ParentDir)) {
+            return None;
+        }
+        // a PWD inherited from the environment is stale when whoever started the shell changed directory since
+        let physical = self.system.current_dir().ok()?;
+        if pwd != physical && self.system.canonicalize(&pwd).ok()? != self.system.canonicalize(&physical).ok()? {
+            return None;
+        }
+        Some(pwd)
+    }
+
+    /// Routes process spawning, directory changes and environment lookups through `system`.
+    pub fn set_system(&mut self, system: Rc<dyn System>) {
+        self.variables.set_system(Rc::clone(&system));
+        self.system = system;
+    }
+
+    /// Loads the plugins in `pending_plugins`. Startup only notes the directory, so that shells which never
+    /// need a plugin don't pay for loading them; this runs the first time a command isn't one of rush's own
+    /// builtins, or something lists the builtins, and before the first prompt.
+    pub fn load_plugins(&mut self) {
+        let Some(dir) = self.pending_plugins.take() else {
+            return;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::plugin::load_plugins(&dir, self);
+        #[cfg(target_arch = "wasm32")]
+        let _ = dir;
+    }
+
+    /// Leaves the shell with `status`, printing the profile summary first when profiling.
+    pub fn exit(&self, status: i32) -> ! {
+        let _ = self.system.stdout().flush();
+        if let Some(profiler) = &self.profiler {
+            eprint!("{}", profiler.report());
+        }
+        std::process::exit(status)
+    }
+
+    /// Prefix for diagnostics, e.g. `rush` at the prompt, `~/.rushrc: line 3` while reading a file, or
+    /// `rush: line 3` when reading non-interactive input or running in strict mode.
+    pub fn location(&self) -> String {
+        if let Some(source) = &self.source_name {
+            format!("{}: line {}", source, self.variables.line_number)
+        } else if self.interactive && !self.options.strict {
+            self.shell_name.clone()
+        } else {
+            format!("{}: line {}", self.shell_name, self.variables.line_number)
+        }
+    }
+}
+
+/// The standard streams a builtin reads and writes. The shell connects them to the terminal, to a pipe, or
+/// to a buffer, so builtins never touch the process's streams directly.
+pub struct Io<'a> {
+    pub stdin: Box<dyn Read + 'a>,
+    pub stdout: Box<dyn Write + 'a>,
+    pub stderr: Box<dyn Write + 'a>,
+    /// Whether stdout goes to the terminal, where output for people to read may be colored; see [`crate::theme`]
+    pub on_terminal: bool,
+}
+
+impl Io<'static> {
+    /// The shell's own stdin, stdout and stderr.
+    pub fn std() -> Self {
+        Io {
+            stdin: Box::new(io::stdin()),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            on_terminal: io::stdout().is_terminal(),
+        }
+    }
+
+    /// The terminal of `system`, which is the process's own streams unless the shell runs on another backend.
+    pub fn terminal(system: &dyn System) -> Self {
+        Io {
+            stdin: system.stdin(),
+            stdout: system.stdout(),
+            stderr: system.stderr(),
+            on_terminal: system.stdout_is_terminal(),
         }
     }
 }
 
 pub trait Execute {
-    fn execute(&self, args: &[String], ctx: &mut ShellContext) -> anyhow::Result<()>;
-}
\ No newline at end of file
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()>;
+}
+
+/// Runs the builtin `name`, or returns `None` when there is no such builtin.
+pub fn run_builtin(name: &str, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> Option<anyhow::Result<()>> {
+    if !ctx.builtins.contains(name) {
+        ctx.load_plugins();
+    }
+    // the registry lives in the context the builtin may change, so hold our own handle to it
+    let command = Rc::clone(&ctx.builtins.get(name)?.command);
+    Some(command.execute(args, ctx, io))
+}
+#[cfg(test)]
+mod tests {
+    use crate::builtins::{run_builtin, BuiltinRegistry, Execute, Io, ShellContext};
+
+    struct Answer {}
+
+    impl Execute for Answer {
+        fn execute(&self, _args: &[String], ctx: &mut ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+            ctx.variables.set("answer", "42");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_default_registry() {
+        let registry = BuiltinRegistry::with_defaults();
+        assert!(registry.contains("cd") && registry.contains("exit"));
+        let names = registry.names();
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_register_and_run() {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.builtins.register("answer", Answer {}, "answer", "Set the answer");
+        assert!(run_builtin("answer", &[], &mut ctx, &mut Io::std()).unwrap().is_ok());
+        assert_eq!(ctx.variables.get("answer").as_deref(), Some("42"));
+        assert!(run_builtin("rush_no_such_builtin", &[], &mut ctx, &mut Io::std()).is_none());
+    }
+
+    #[test]
+    fn test_captured_output() {
+        let mut ctx = ShellContext::new(Vec::new());
+        let mut out = Vec::new();
+        let mut io = Io {
+            stdin: Box::new(std::io::empty()),
+            stdout: Box::new(&mut out),
+            stderr: Box::new(std::io::sink()),
+            on_terminal: false,
+        };
+        let args = ["hello".to_string(), "world".to_string()];
+        run_builtin("echo", &args, &mut ctx, &mut io).unwrap().unwrap();
+        drop(io);
+        assert_eq!(out, b"hello world\n");
+    }
+
+    #[test]
+    fn test_plugins_load_on_first_unknown_command() {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.pending_plugins = Some(std::env::temp_dir().join("rush-no-such-plugins"));
+        run_builtin("answer", &[], &mut ctx, &mut Io::std());
+        assert!(ctx.pending_plugins.is_none());
+
+        ctx.pending_plugins = Some(std::env::temp_dir().join("rush-no-such-plugins"));
+        let mut io = Io {
+            stdin: Box::new(std::io::empty()),
+            stdout: Box::new(std::io::sink()),
+            stderr: Box::new(std::io::sink()),
+            on_terminal: false,
+        };
+        run_builtin("echo", &[], &mut ctx, &mut io).unwrap().unwrap();
+        assert!(ctx.pending_plugins.is_some());
+    }
+}
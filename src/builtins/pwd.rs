@@ -1,16 +1,35 @@
-use std::env;
+use std::io::Write;
 
-use crate::builtins::Execute;
+use crate::builtins::{Execute, Io};
+use crate::encoding::{encode, from_os};
+use crate::locale::tr;
 
 pub struct PwdBuiltin {
 }
 
 impl Execute for PwdBuiltin {
-    fn execute(&self, _args: &[String], _ctx: &mut crate::builtins::ShellContext) -> anyhow::Result<()> {
-        match env::current_dir() {
-            Ok(path) => println!("{}", path.display()),
-            Err(e) => eprintln!("pwd: error getting current directory: {}", e),
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // -L, the default, prints the directory the way `cd` got there, -P with symbolic links resolved
+        let mut physical = false;
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && flags.chars().all(|c| c == 'L' || c == 'P') => {
+                    physical = flags.ends_with('P');
+                }
+                Some("-") | None => break,
+                Some(_) => anyhow::bail!(tr!("{}: {}: invalid option", "pwd", arg)),
+            }
+        }
+
+        let logical = if physical { None } else { ctx.logical_dir() };
+        let dir = match logical {
+            Some(dir) => Ok(dir),
+            None => ctx.system.current_dir().map(|dir| ctx.system.canonicalize(&dir).unwrap_or(dir)),
+        };
+        match dir {
+            Ok(path) => io.stdout.write_all(&encode(&format!("{}\n", from_os(path.as_os_str()))))?,
+            Err(e) => writeln!(io.stderr, "pwd: error getting current directory: {}", e)?,
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
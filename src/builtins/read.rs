@@ -0,0 +1,169 @@
+use std::io::{self, IsTerminal, Read, Write};
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::encoding;
+use crate::error::RushError;
+use crate::locale::tr;
+use crate::terminal::NoEcho;
+use crate::tokenizer::is_name;
+
+pub struct ReadBuiltin {
+}
+
+impl Execute for ReadBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        let (mut raw, mut silent, mut prompt) = (false, false, None);
+        let mut names = args;
+        while let Some((first, rest)) = names.split_first() {
+            if first == "--" {
+                names = rest;
+                break;
+            }
+            if !first.starts_with('-') || first.len() == 1 {
+                break;
+            }
+            names = rest;
+            for (i, option) in first.char_indices().skip(1) {
+                match option {
+                    'r' => raw = true,
+                    's' => silent = true,
+                    // the prompt is the rest of the argument, or else the next one
+                    'p' if i + 1 < first.len() => prompt = Some(&first[i + 1..]),
+                    'p' => {
+                        let Some((value, rest)) = names.split_first() else {
+                            anyhow::bail!(tr!("{}: {}: option requires an argument", "read", "-p"));
+                        };
+                        prompt = Some(value.as_str());
+                        names = rest;
+                    }
+                    _ => anyhow::bail!(tr!("{}: {}: invalid option", "read", format!("-{}", option))),
+                }
+                if option == 'p' {
+                    break;
+                }
+            }
+        }
+        if let Some(name) = names.iter().find(|name| !is_name(name)) {
+            anyhow::bail!(tr!("{}: `{}': not a valid identifier", "read", name));
+        }
+
+        // like other shells, prompt only when a user is typing the answer
+        let terminal = io::stdin().is_terminal();
+        if let Some(prompt) = prompt.filter(|_| terminal) {
+            write!(io.stderr, "{}", prompt)?;
+            io.stderr.flush()?;
+        }
+        let mut no_echo = if silent { NoEcho::enable() } else { None };
+        let line = read_line(&mut io.stdin, raw);
+        // the newline typed to end the line wasn't echoed either
+        if no_echo.take().is_some() {
+            writeln!(io.stderr)?;
+        }
+        let (line, complete) = line?;
+
+        let ifs = ctx.variables.get("IFS").unwrap_or_else(|| " \t\n".to_string());
+        match names {
+            [] => ctx.variables.set("REPLY", &line.iter().map(|&(c, _)| c).collect::<String>()),
+            names => {
+                for (name, value) in names.iter().zip(split_fields(&line, &ifs, names.len())) {
+                    ctx.variables.set(name, &value);
+                }
+            }
+        }
+        // a line cut short by the end of input is still assigned, but read fails
+        if !complete {
+            return Err(RushError::Status(1).into());
+        }
+        Ok(())
+    }
+}
+
+// Reads a line up to a newline or the end of input, and returns its characters, each with whether it was
+// escaped, along with whether the line had its newline. Unless `raw`, a backslash escapes the character after
+// it and a backslash before the newline continues the line on the next one.
+fn read_line(input: &mut dyn Read, raw: bool) -> io::Result<(Vec<(char, bool)>, bool)> {
+    let mut line = Vec::new();
+    let mut complete = false;
+    // the input is read a byte at a time so that whoever reads it next gets the rest
+    let mut byte = [0];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => {
+                let escapes = line.iter().rev().take_while(|&&byte| byte == b'\\').count();
+                if raw || escapes % 2 == 0 {
+                    complete = true;
+                    break;
+                }
+                line.pop();
+            }
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    let line = encoding::decode(&line);
+    let mut chars = line.chars();
+    let mut result = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !raw => result.extend(chars.next().map(|c| (c, true))),
+            c => result.push((c, false)),
+        }
+    }
+    Ok((result, complete))
+}
+
+// Splits a line into `count` fields at the unescaped characters in IFS, the way the shell splits words:
+// white space in IFS around the fields is left out, and the last field gets the rest of the line
+fn split_fields(line: &[(char, bool)], ifs: &str, count: usize) -> Vec<String> {
+    let is_space = |&(c, escaped): &(char, bool)| !escaped && c.is_whitespace() && ifs.contains(c);
+    let is_delimiter = |&(c, escaped): &(char, bool)| !escaped && !c.is_whitespace() && ifs.contains(c);
+    let trimmed_end = line.len() - line.iter().rev().take_while(|c| is_space(c)).count();
+    let mut rest = &line[..trimmed_end];
+    let mut fields = Vec::new();
+    while fields.len() + 1 < count {
+        rest = &rest[rest.iter().take_while(|c| is_space(c)).count()..];
+        let end = rest.iter().position(|c| is_space(c) || is_delimiter(c)).unwrap_or(rest.len());
+        fields.push(rest[..end].iter().map(|&(c, _)| c).collect());
+        rest = &rest[end..];
+        // a field ends at white space, one other delimiter, or both
+        rest = &rest[rest.iter().take_while(|c| is_space(c)).count()..];
+        if rest.first().is_some_and(is_delimiter) {
+            rest = &rest[1..];
+        }
+    }
+    rest = &rest[rest.iter().take_while(|c| is_space(c)).count()..];
+    fields.push(rest.iter().map(|&(c, _)| c).collect());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_line, split_fields};
+
+    fn fields(line: &str, ifs: &str, count: usize) -> Vec<String> {
+        let (line, _) = read_line(&mut line.as_bytes(), false).unwrap();
+        split_fields(&line, ifs, count)
+    }
+
+    #[test]
+    fn test_split_fields() {
+        assert_eq!(fields("  one two  three  ", " \t\n", 2), ["one", "two  three"]);
+        assert_eq!(fields("one", " \t\n", 3), ["one", "", ""]);
+        assert_eq!(fields("a:b::c", ":", 4), ["a", "b", "", "c"]);
+        assert_eq!(fields("a : b", " :", 2), ["a", "b"]);
+        // escaped delimiters are part of a field
+        assert_eq!(fields("a\\ b c", " \t\n", 2), ["a b", "c"]);
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut input: &[u8] = b"one\\\ntwo\\x\nnext\n";
+        let (line, complete) = read_line(&mut input, false).unwrap();
+        assert_eq!(line.iter().map(|&(c, _)| c).collect::<String>(), "onetwox");
+        assert!(complete);
+        assert_eq!(input, b"next\n");
+        let (line, complete) = read_line(&mut &b"a\\b"[..], true).unwrap();
+        assert_eq!((line.len(), complete), (3, false));
+    }
+}
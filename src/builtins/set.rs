@@ -0,0 +1,63 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::locale::tr;
+use crate::options::{ShellOptions, OPTION_NAMES};
+
+pub struct SetBuiltin {
+}
+
+impl Execute for SetBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // `set` with no arguments lists shell variables
+        if args.is_empty() {
+            for name in ctx.variables.names() {
+                writeln!(io.stdout, "{}={}", name, ctx.variables.get(name).unwrap_or_default())?;
+            }
+            return Ok(());
+        }
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // `--` or the first non-option argument starts the new positional parameters
+            if arg == "--" {
+                ctx.positional_params = args.cloned().collect();
+                break;
+            }
+            let on = arg.starts_with('-');
+            if !on && !arg.starts_with('+') {
+                ctx.positional_params = std::iter::once(arg).chain(args).cloned().collect();
+                break;
+            }
+
+            if &arg[1..] == "o" {
+                match args.next() {
+                    Some(name) => ctx.options.set(name, on).map_err(|e| anyhow::anyhow!("set: {}", e))?,
+                    None => print_options(&ctx.options, on, &mut io.stdout)?,
+                }
+                continue;
+            }
+
+            for flag in arg[1..].chars() {
+                match ShellOptions::from_flag(flag) {
+                    Some(name) => ctx.options.set(name, on)?,
+                    None => anyhow::bail!(tr!("{}: {}: invalid option", "set", format!("{}{}", &arg[..1], flag))),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// `set -o` prints a table, `set +o` prints commands that recreate the current settings
+fn print_options(options: &ShellOptions, table: bool, out: &mut impl Write) -> std::io::Result<()> {
+    for name in OPTION_NAMES {
+        let on = options.get(name).unwrap_or(false);
+        if table {
+            writeln!(out, "{:<15}{}", name, if on { "on" } else { "off" })?;
+        } else {
+            writeln!(out, "set {}o {}", if on { '-' } else { '+' }, name)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::builtins::{Execute, Io};
+use crate::locale::tr;
+
+pub struct ShiftBuiltin {
+}
+
+impl Execute for ShiftBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        let count = match args {
+            [] => 1,
+            [n] => match n.parse::<usize>() {
+                Ok(count) => count,
+                Err(_) => anyhow::bail!(tr!("{}: {}: numeric argument required", "shift", n)),
+            },
+            _ => anyhow::bail!(tr!("{}: too many arguments", "shift")),
+        };
+
+        if count > ctx.positional_params.len() {
+            anyhow::bail!(tr!("shift: {}: shift count out of range", count));
+        }
+        ctx.positional_params.drain(..count);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::path::Path;
+use crate::builtins::{Execute, Io, ShellContext};
+use crate::encoding;
+use crate::error::{io_message, RushError};
+use crate::locale::tr;
+use crate::shell::execute_source;
+
+pub struct SourceBuiltin {
+}
+
+pub struct DotBuiltin {
+}
+
+impl Execute for SourceBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        source("source", args, ctx)
+    }
+}
+
+impl Execute for DotBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut ShellContext, _io: &mut Io) -> anyhow::Result<()> {
+        source(".", args, ctx)
+    }
+}
+
+// Runs the commands of a file in the current shell, so that the variables, aliases and directory it sets stay
+// set. Arguments after the file are the positional parameters while it runs.
+fn source(name: &str, args: &[String], ctx: &mut ShellContext) -> anyhow::Result<()> {
+    let Some((file, params)) = args.split_first() else {
+        anyhow::bail!(tr!("{}: filename argument required", name));
+    };
+    let contents = read_script(file, ctx).map_err(|e| anyhow::anyhow!("{}: {}: {}", name, file, io_message(&e)))?;
+
+    let saved_params = (!params.is_empty()).then(|| std::mem::replace(&mut ctx.positional_params, params.to_vec()));
+    let result = execute_source(file, &encoding::decode(&contents), ctx);
+    if let Some(saved_params) = saved_params {
+        ctx.positional_params = saved_params;
+    }
+    result?;
+    // the status is that of the file's last command
+    if ctx.last_status != 0 {
+        return Err(RushError::Status(ctx.last_status).into());
+    }
+    Ok(())
+}
+
+// Reads the file `source` names: a name without a slash is looked for in PATH, then in the current directory
+fn read_script(file: &str, ctx: &ShellContext) -> io::Result<Vec<u8>> {
+    if !file.contains(['/', std::path::MAIN_SEPARATOR]) {
+        for dir in ctx.search_path() {
+            if let Ok(contents) = ctx.system.read_file(&Path::new(&dir).join(file)) {
+                return Ok(contents);
+            }
+        }
+    }
+    ctx.system.read_file(&encoding::to_path(file))
+}
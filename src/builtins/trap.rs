@@ -0,0 +1,60 @@
+use std::io::Write;
+use crate::builtins::{Execute, Io};
+use crate::interrupt;
+use crate::jobs;
+use crate::locale::tr;
+
+pub struct TrapBuiltin {
+}
+
+// Conditions `trap` can register an action for, in the order `trap -p` lists them
+const TRAP_CONDITIONS: &[&str] = &["EXIT", "INT", "TERM", "DEBUG", "ERR"];
+
+impl Execute for TrapBuiltin {
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // `trap` and `trap -p` print the registered actions in a reusable form
+        if args.is_empty() || (args.len() == 1 && args[0] == "-p") {
+            for condition in TRAP_CONDITIONS {
+                if let Some(action) = ctx.traps.get(*condition) {
+                    writeln!(io.stdout, "trap -- '{}' {}", action.replace('\'', r"'\''"), condition)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let args = if args[0] == "--" { &args[1..] } else { args };
+        let (action, conditions) = match args {
+            [] => return Ok(()),
+            [condition] => ("-", std::slice::from_ref(condition)),
+            [action, conditions @ ..] => (action.as_str(), conditions),
+        };
+
+        for condition in conditions {
+            let Some(name) = condition_name(condition) else {
+                anyhow::bail!(tr!("{}: {}: invalid signal specification", "trap", condition));
+            };
+            // the signal layer catches a trapped signal and the shell runs the action between commands
+            if let Some(signal) = jobs::signal_number(name).filter(|&signal| signal != 0) {
+                interrupt::catch(signal, action != "-");
+            }
+            if action == "-" {
+                ctx.traps.remove(name);
+            } else {
+                ctx.traps.insert(name.to_string(), action.to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The name a condition is registered under: EXIT is also 0, and signals may be given by number or with SIG
+fn condition_name(condition: &str) -> Option<&'static str> {
+    let upper = condition.to_ascii_uppercase();
+    let name = match jobs::signal_number(&upper) {
+        Some(0) => "EXIT",
+        Some(signal) => jobs::signal_name(signal)?,
+        None => &upper,
+    };
+    TRAP_CONDITIONS.iter().find(|&&known| known == name).copied()
+}
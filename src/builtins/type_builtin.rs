@@ -1,27 +1,80 @@
-use crate::path_utils::find_in_path;
+use std::io::Write;
+use std::path::PathBuf;
+use crate::builtins::Io;
+use crate::error::RushError;
+use crate::locale::tr;
+use crate::theme::{self, Role};
 
 pub struct TypeBuiltin {
 }
 
 impl crate::builtins::Execute for TypeBuiltin {
-    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext) -> anyhow::Result<()> {
-        if args.is_empty() {
-            anyhow::bail!("Type args cannot be empty");
+    fn execute(&self, args: &[String], ctx: &mut crate::builtins::ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        // -a lists every way a name resolves, in the order the shell tries them, instead of the one it uses
+        let mut all = false;
+        let mut names = args;
+        while let Some((first, rest)) = names.split_first() {
+            if first == "-a" {
+                all = true;
+            } else if first == "--" {
+                names = rest;
+                break;
+            } else if first.starts_with('-') && first.len() > 1 {
+                anyhow::bail!(tr!("{}: {}: invalid option", "type", first));
+            } else {
+                break;
+            }
+            names = rest;
         }
-
-        // Split args to get just the program name
-        let program_name = args[0].as_str();
-
-        if ctx.builtin_names.contains(&program_name) {
-            println!("{} is a shell builtin", program_name)
+        if names.is_empty() {
+            anyhow::bail!(tr!("Type args cannot be empty"));
         }
-        else {
-            match find_in_path(program_name, &ctx.path_dirs.iter().map(|s| s.as_str()).collect::<Vec<&str>>()) {
-                Some(file_path) => println!("{} is {}", program_name, file_path.display()),
-                None => println!("{}: not found", program_name),
+        ctx.load_plugins();
+
+        let mut status = 0;
+        for name in names {
+            let mut found = false;
+            // the name is colored by what it runs
+            let paint = |role| theme::paint(name, role, ctx, io.on_terminal);
+            if let Some(value) = ctx.aliases.get(name) {
+                writeln!(io.stdout, "{}", tr!("{} is aliased to `{}'", paint(Role::Alias), value))?;
+                found = true;
+            }
+            if (all || !found) && ctx.builtins.contains(name) {
+                writeln!(io.stdout, "{}", tr!("{} is a shell builtin", paint(Role::Builtin)))?;
+                found = true;
+            }
+            if all || !found {
+                for path in programs(name, all, ctx) {
+                    writeln!(io.stdout, "{}", tr!("{} is {}", paint(Role::Program), path.display()))?;
+                    found = true;
+                }
+            }
+            if !found {
+                writeln!(io.stderr, "{}: {}", ctx.location(), tr!("{}: {}: not found", "type", name))?;
+                status = 1;
             }
         }
 
+        if status != 0 {
+            return Err(RushError::Status(status).into());
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Where `name` is found in PATH: the first match, or with `all` every one in PATH order
+fn programs(name: &str, all: bool, ctx: &crate::builtins::ShellContext) -> Vec<PathBuf> {
+    if !all {
+        return ctx.system.find_program(name, &ctx.search_path()).into_iter().collect();
+    }
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for dir in &ctx.search_path() {
+        if let Some(path) = ctx.system.find_program(name, std::slice::from_ref(dir))
+            && !paths.contains(&path)
+        {
+            paths.push(path);
+        }
+    }
+    paths
+}
@@ -0,0 +1,91 @@
+//! The shell's memory of where it found programs, so that running a command again doesn't search PATH.
+//!
+//! Entries are only good for the PATH they were found in: a lookup with other directories than the ones the
+//! table was filled for forgets everything first. Names with a directory part are never remembered.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::system::System;
+
+#[derive(Default)]
+pub struct CommandHash {
+    // the PATH directories the entries were found in
+    path_dirs: Vec<String>,
+    // by command name: where it was found and how often the shell ran it since
+    entries: BTreeMap<String, (PathBuf, usize)>,
+}
+
+impl CommandHash {
+    /// Finds the program `name` to run it, from the table or else in `path_dirs`, and counts a hit.
+    pub fn find(&mut self, name: &str, path_dirs: &[String], system: &dyn System) -> Option<PathBuf> {
+        if name.contains(std::path::is_separator) {
+            return system.find_program(name, path_dirs);
+        }
+        if self.path_dirs != path_dirs {
+            self.clear();
+            self.path_dirs = path_dirs.to_vec();
+        }
+        if let Some((path, hits)) = self.entries.get_mut(name) {
+            *hits += 1;
+            return Some(path.clone());
+        }
+        let path = system.find_program(name, path_dirs)?;
+        self.entries.insert(name.to_string(), (path.clone(), 1));
+        Some(path)
+    }
+
+    /// Looks `name` up in `path_dirs` and remembers where it is without counting a hit, the way `hash name`
+    /// does. None when it isn't found.
+    pub fn add(&mut self, name: &str, path_dirs: &[String], system: &dyn System) -> Option<PathBuf> {
+        let path = self.find(name, path_dirs, system)?;
+        if let Some((_, hits)) = self.entries.get_mut(name) {
+            *hits -= 1;
+        }
+        Some(path)
+    }
+
+    /// Where `name` was found, if the table has it for `path_dirs`.
+    pub fn get(&self, name: &str, path_dirs: &[String]) -> Option<&PathBuf> {
+        self.entries.get(name).filter(|_| self.path_dirs == path_dirs).map(|(path, _)| path)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The entries as (name, path, hits), sorted by name.
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &PathBuf, usize)> {
+        self.entries.iter().map(|(name, (path, hits))| (name, path, *hits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::command_hash::CommandHash;
+    use crate::system::FakeSystem;
+
+    #[test]
+    fn test_hits_and_path_changes() {
+        let system = FakeSystem::new().program("ls", |_, _| (0, Vec::new()));
+        let dirs = vec!["/bin".to_string()];
+        let mut hash = CommandHash::default();
+        assert_eq!(hash.add("ls", &dirs, &system).as_deref(), Some(Path::new("/bin/ls")));
+        hash.find("ls", &dirs, &system);
+        hash.find("ls", &dirs, &system);
+        assert_eq!(hash.find("rush-none", &dirs, &system), None);
+        assert_eq!(hash.entries().map(|(name, _, hits)| (name.as_str(), hits)).collect::<Vec<_>>(), [("ls", 2)]);
+        // names with a directory aren't remembered
+        hash.find("./ls", &dirs, &system);
+        assert_eq!(hash.entries().count(), 1);
+        // another PATH starts over
+        let other = vec!["/usr/bin".to_string()];
+        assert_eq!(hash.get("ls", &other), None);
+        hash.find("ls", &other, &system);
+        assert_eq!(hash.entries().map(|(_, _, hits)| hits).collect::<Vec<_>>(), [1]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Completion of command lines.
+//!
+//! Command names complete from builtins, aliases and PATH. What completes a command's arguments depends on
+//! the command: a plugin's [`CompletionProvider`](crate::plugin::CompletionProvider) when it registered one,
+//! else the command's [`CompletionSpec`], such as directories after `cd` and jobs after `kill`. Command names
+//! and paths match regardless of case when RUSH_COMPLETION_IGNORE_CASE, which the config file's
+//! `completion.ignore_case` sets, is `on`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use crate::builtins::ShellContext;
+use crate::locale;
+use crate::path_utils::{expand_tilde_with, home_dir_with};
+
+/// What the arguments of a command are, which decides what completes them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionSpec {
+    /// Files and directories, for commands without a spec of their own
+    Files,
+    /// Directories only
+    Directories,
+    /// Job specs such as `%1`, and the process IDs of the jobs' programs
+    Jobs,
+}
+
+/// The completion specs of commands, by name.
+#[derive(Default)]
+pub struct CompletionSpecs {
+    specs: HashMap<String, CompletionSpec>,
+}
+
+impl CompletionSpecs {
+    /// The specs of rush's own builtins whose arguments aren't just files.
+    pub fn with_defaults() -> Self {
+        let mut specs = CompletionSpecs::default();
+        for command in ["cd", "pushd"] {
+            specs.set(command, CompletionSpec::Directories);
+        }
+        for command in ["source", "."] {
+            specs.set(command, CompletionSpec::Files);
+        }
+        for command in ["kill", "fg", "bg"] {
+            specs.set(command, CompletionSpec::Jobs);
+        }
+        specs
+    }
+
+    pub fn set(&mut self, command: &str, spec: CompletionSpec) {
+        self.specs.insert(command.to_string(), spec);
+    }
+
+    /// The spec of `command`, files when it has none.
+    pub fn get(&self, command: &str) -> CompletionSpec {
+        self.specs.get(command).copied().unwrap_or(CompletionSpec::Files)
+    }
+}
+
+/// Completes the word before the cursor, given the line up to it: returns where the word starts and what can
+/// replace it. The first word of a command, which starts the line or follows `;`, `|`, `&` or `(`, is a
+/// command name, and the words after it are the command's arguments.
+pub fn complete(line: &str, ctx: &ShellContext) -> (usize, Vec<String>) {
+    let command_start = line.rfind([';', '|', '&', '(']).map_or(0, |i| i + 1);
+    let start = line.rfind([' ', '\t']).map_or(0, |i| i + 1).max(command_start);
+    let mut words: Vec<String> = line[command_start..start].split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        return (start, complete_command(&line[start..], ctx));
+    }
+    words.push(line[start..].to_string());
+    (start, complete_argument(&words, ctx))
+}
+
+/// The commands whose names start with `prefix`: builtins, aliases and the programs in PATH, without
+/// duplicates and sorted for the locale. Programs come from the PATH index, so a scan still in progress
+/// doesn't hold this up.
+pub fn complete_command(prefix: &str, ctx: &ShellContext) -> Vec<String> {
+    let ignore_case = ignore_case(ctx);
+    let mut names: BTreeSet<String> = ctx.builtins.names().into_iter().map(String::from).collect();
+    names.extend(ctx.aliases.keys().cloned());
+    names.retain(|name| starts_with(name, prefix, ignore_case));
+    if ignore_case {
+        names.extend(ctx.path_index.matches_ignoring_case(prefix));
+    } else {
+        names.extend(ctx.path_index.matches(prefix));
+    }
+    let mut names: Vec<String> = names.into_iter().collect();
+    names.sort_by(|a, b| locale::compare(a, b));
+    names
+}
+
+/// Candidates for the last word of `words`, the command line up to the cursor, as an argument of the command
+/// in `words[0]`, sorted for the locale. Directories end in `/`, the way `ls -F` shows them, so completion
+/// can go on into them.
+pub fn complete_argument(words: &[String], ctx: &ShellContext) -> Vec<String> {
+    let (Some(command), Some(word)) = (words.first(), words.last()) else {
+        return Vec::new();
+    };
+    if let Some(provider) = ctx.completions.get(command) {
+        return provider.complete(words);
+    }
+    let mut candidates = match ctx.completion_specs.get(command) {
+        CompletionSpec::Files => complete_path(word, false, ctx),
+        CompletionSpec::Directories => complete_path(word, true, ctx),
+        CompletionSpec::Jobs => complete_job(word, ctx),
+    };
+    candidates.sort_by(|a, b| locale::compare(a, b));
+    candidates
+}
+
+// The files, or only the directories, whose paths start with `word`; hidden ones only when `word` names one
+fn complete_path(word: &str, directories: bool, ctx: &ShellContext) -> Vec<String> {
+    let (dir, name) = word.split_at(word.rfind('/').map_or(0, |i| i + 1));
+    let listed = match dir {
+        "" => ".".to_string(),
+        dir => expand_tilde_with(dir, home_dir_with(|name| ctx.variables.get(name))).unwrap_or(dir.to_string()),
+    };
+    let Ok(entries) = ctx.system.read_dir(Path::new(&listed)) else {
+        return Vec::new();
+    };
+    let ignore_case = ignore_case(ctx);
+    entries
+        .into_iter()
+        .filter(|entry| starts_with(entry, name, ignore_case) && (name.starts_with('.') || !entry.starts_with('.')))
+        .filter_map(|entry| {
+            // what can be listed is a directory
+            let is_dir = ctx.system.read_dir(&Path::new(&listed).join(&entry)).is_ok();
+            (is_dir || !directories).then(|| format!("{}{}{}", dir, entry, if is_dir { "/" } else { "" }))
+        })
+        .collect()
+}
+
+fn ignore_case(ctx: &ShellContext) -> bool {
+    ctx.variables.get("RUSH_COMPLETION_IGNORE_CASE").as_deref() == Some("on")
+}
+
+fn starts_with(name: &str, prefix: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        return name.to_lowercase().starts_with(&prefix.to_lowercase());
+    }
+    name.starts_with(prefix)
+}
+
+// The job specs and process IDs of the shell's jobs that start with `word`
+fn complete_job(word: &str, ctx: &ShellContext) -> Vec<String> {
+    let specs = ctx.jobs.iter().map(|job| format!("%{}", job.id));
+    let pids = ctx.jobs.iter().flat_map(|job| job.pids()).map(|pid| pid.to_string());
+    specs.chain(pids).filter(|candidate| candidate.starts_with(word)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::builtins::ShellContext;
+    use crate::completion::{complete, complete_argument, complete_command, CompletionSpec};
+    use crate::system::{FakeSystem, FinishedProcess, System};
+
+    fn words(line: &str) -> Vec<String> {
+        let mut words: Vec<String> = line.split(' ').map(String::from).collect();
+        if line.ends_with(' ') {
+            words.pop();
+            words.push(String::new());
+        }
+        words
+    }
+
+    #[test]
+    fn test_complete_command() {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.aliases.insert("exa".to_string(), "ls".to_string());
+        assert_eq!(complete_command("ex", &ctx), vec!["exa", "exit", "export"]);
+        assert!(complete_command("rush_test_none", &ctx).is_empty());
+        assert!(complete_command("EX", &ctx).is_empty());
+        ctx.variables.set("RUSH_COMPLETION_IGNORE_CASE", "on");
+        assert_eq!(complete_command("EX", &ctx), vec!["exa", "exit", "export"]);
+        ctx.variables.set("RUSH_COMPLETION_IGNORE_CASE", "off");
+
+        assert_eq!(complete("ex", &ctx), (0, complete_command("ex", &ctx)));
+        assert_eq!(complete("ls | exi", &ctx), (5, vec!["exit".to_string()]));
+        assert_eq!(complete("true&&  exi", &ctx), (8, vec!["exit".to_string()]));
+    }
+
+    #[test]
+    fn test_complete_argument() {
+        let system = FakeSystem::new().dir("/home/ann").dir("/home/ann/src").dir("/home/ann/.config")
+            .dir("/home/ann/src/rush").file("/home/ann/setup.sh").file("/home/ann/src/notes");
+        system.set_current_dir("/home/ann".as_ref()).unwrap();
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(Rc::new(system));
+        ctx.variables.set("HOME", "/home/ann");
+
+        assert_eq!(complete_argument(&words("cd "), &ctx), ["src/"]);
+        assert_eq!(complete_argument(&words("cd ."), &ctx), [".config/"]);
+        assert_eq!(complete_argument(&words("pushd ~/src/"), &ctx), ["~/src/rush/"]);
+        assert_eq!(complete_argument(&words("source "), &ctx), ["setup.sh", "src/"]);
+        assert_eq!(complete_argument(&words("cat src/"), &ctx), ["src/notes", "src/rush/"]);
+        assert_eq!(complete_argument(&words("cat /home/ann/s"), &ctx), ["/home/ann/setup.sh", "/home/ann/src/"]);
+        assert!(complete_argument(&words("cd nowhere/"), &ctx).is_empty());
+        assert!(complete_argument(&words("cat src/N"), &ctx).is_empty());
+        ctx.variables.set("RUSH_COMPLETION_IGNORE_CASE", "on");
+        assert_eq!(complete_argument(&words("cat src/N"), &ctx), ["src/notes"]);
+        assert_eq!(complete_argument(&words("cd S"), &ctx), ["src/"]);
+        ctx.variables.set("RUSH_COMPLETION_IGNORE_CASE", "off");
+        // the word the cursor is in is an argument once a command precedes it
+        assert_eq!(complete("ls; cd  s", &ctx), (8, vec!["src/".to_string()]));
+        assert_eq!(complete("echo | cat src/n", &ctx), (11, vec!["src/notes".to_string()]));
+
+        ctx.jobs.add("sleep 10", vec![Box::new(FinishedProcess { id: 4321, status: 0 })]);
+        ctx.jobs.add("sleep 20", vec![Box::new(FinishedProcess { id: 4400, status: 0 })]);
+        assert_eq!(complete_argument(&words("kill -TERM "), &ctx), ["%1", "%2", "4321", "4400"]);
+        assert_eq!(complete_argument(&words("fg %"), &ctx), ["%1", "%2"]);
+        assert_eq!(complete_argument(&words("kill 43"), &ctx), ["4321"]);
+
+        // commands can be given a spec of their own
+        ctx.completion_specs.set("rmdir", CompletionSpec::Directories);
+        assert_eq!(complete_argument(&words("rmdir "), &ctx), ["src/"]);
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use regex::Regex;
+use crate::arithmetic;
+use crate::encoding::to_path;
+use crate::locale::tr;
+use crate::path_utils::is_executable;
+use crate::variables::Variables;
+
+/// Recognizes a standalone `[[ expr ]]` command and returns the inner expression.
+pub fn conditional_command(input: &str) -> Option<&str> {
+    let inner = input.trim().strip_prefix("[[")?.strip_suffix("]]")?;
+    if inner.starts_with([' ', '\t']) && inner.ends_with([' ', '\t']) {
+        Some(inner)
+    } else {
+        None
+    }
+}
+
+/// Evaluates the words of a `[[ ... ]]` expression. A successful `=~` match stores the whole
+/// match and its capture groups in the RUSH_REMATCH array.
+pub fn evaluate(words: &[String], vars: &mut Variables) -> anyhow::Result<bool> {
+    if words.is_empty() {
+        anyhow::bail!(tr!("[[: expression expected"));
+    }
+
+    let mut parser = Parser { words, pos: 0, vars, skipping: false };
+    let result = parser.parse_or()?;
+    if let Some(word) = parser.peek() {
+        anyhow::bail!(tr!("[[: syntax error near `{}'", word));
+    }
+    Ok(result)
+}
+
+/// Parses the words of a `[[ ... ]]` expression without evaluating comparisons, reporting syntax errors only.
+pub fn check(words: &[String]) -> anyhow::Result<()> {
+    if words.is_empty() {
+        anyhow::bail!(tr!("[[: expression expected"));
+    }
+
+    let mut vars = Variables::new();
+    let mut parser = Parser { words, pos: 0, vars: &mut vars, skipping: true };
+    parser.parse_or()?;
+    if let Some(word) = parser.peek() {
+        anyhow::bail!(tr!("[[: syntax error near `{}'", word));
+    }
+    Ok(())
+}
+
+struct Parser<'a> {
+    words: &'a [String],
+    pos: usize,
+    vars: &'a mut Variables,
+    // set while parsing the right side of a short-circuited && or ||
+    skipping: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.words.get(self.pos).map(|w| w.as_str())
+    }
+
+    fn next_word(&mut self) -> anyhow::Result<&str> {
+        let word = self.words.get(self.pos).map(|w| w.as_str());
+        self.pos += 1;
+        word.ok_or_else(|| anyhow::anyhow!(tr!("[[: unexpected end of expression")))
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        if self.peek() == Some(word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // The right side of && and || is always parsed, but only evaluated when it can change the result
+    fn parse_or(&mut self) -> anyhow::Result<bool> {
+        let mut result = self.parse_and()?;
+        while self.eat("||") {
+            let skipping = self.skipping;
+            self.skipping |= result;
+            let rhs = self.parse_and()?;
+            self.skipping = skipping;
+            result = result || rhs;
+        }
+        Ok(result)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<bool> {
+        let mut result = self.parse_not()?;
+        while self.eat("&&") {
+            let skipping = self.skipping;
+            self.skipping |= !result;
+            let rhs = self.parse_not()?;
+            self.skipping = skipping;
+            result = result && rhs;
+        }
+        Ok(result)
+    }
+
+    fn parse_not(&mut self) -> anyhow::Result<bool> {
+        if self.eat("!") {
+            return Ok(!self.parse_not()?);
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<bool> {
+        if self.eat("(") {
+            let result = self.parse_or()?;
+            if !self.eat(")") {
+                anyhow::bail!(tr!("[[: expected `)'"));
+            }
+            return Ok(result);
+        }
+
+        let first = self.next_word()?.to_string();
+        if is_unary_operator(&first) && self.peek().is_some_and(|w| !is_connective(w)) {
+            let operand = self.next_word()?.to_string();
+            return unary_test(&first, &operand);
+        }
+
+        match self.peek() {
+            Some(op) if is_binary_operator(op) => {
+                let op = op.to_string();
+                self.pos += 1;
+                let rhs = self.next_word()?.to_string();
+                self.binary_test(&first, &op, &rhs)
+            }
+            // a lone word is true when it is non-empty
+            _ => Ok(!first.is_empty()),
+        }
+    }
+
+    fn binary_test(&mut self, lhs: &str, op: &str, rhs: &str) -> anyhow::Result<bool> {
+        if self.skipping {
+            return Ok(false);
+        }
+        Ok(match op {
+            "==" | "=" => lhs == rhs,
+            "!=" => lhs != rhs,
+            "<" => lhs < rhs,
+            ">" => lhs > rhs,
+            "=~" => {
+                let regex = Regex::new(rhs)
+                    .map_err(|_| anyhow::anyhow!(tr!("[[: {}: invalid regular expression", rhs)))?;
+                let groups: Vec<String> = match regex.captures(lhs) {
+                    Some(captures) => captures
+                        .iter()
+                        .map(|group| group.map(|m| m.as_str().to_string()).unwrap_or_default())
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let matched = !groups.is_empty();
+                self.vars.set_array("RUSH_REMATCH", groups);
+                matched
+            }
+            _ => {
+                // -eq and friends compare both sides as arithmetic expressions
+                let lhs = arithmetic::evaluate(lhs, self.vars)?;
+                let rhs = arithmetic::evaluate(rhs, self.vars)?;
+                match op {
+                    "-eq" => lhs == rhs,
+                    "-ne" => lhs != rhs,
+                    "-lt" => lhs < rhs,
+                    "-le" => lhs <= rhs,
+                    "-gt" => lhs > rhs,
+                    _ => lhs >= rhs,
+                }
+            }
+        })
+    }
+}
+
+fn is_connective(word: &str) -> bool {
+    matches!(word, "&&" | "||" | ")")
+}
+
+fn is_unary_operator(word: &str) -> bool {
+    matches!(word, "-z" | "-n" | "-e" | "-f" | "-d" | "-r" | "-w" | "-x" | "-s" | "-L" | "-h")
+}
+
+fn is_binary_operator(word: &str) -> bool {
+    matches!(
+        word,
+        "==" | "=" | "!=" | "<" | ">" | "=~" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge"
+    )
+}
+
+fn unary_test(op: &str, operand: &str) -> anyhow::Result<bool> {
+    let path = &to_path(operand);
+    Ok(match op {
+        "-z" => operand.is_empty(),
+        "-n" => !operand.is_empty(),
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-s" => fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false),
+        "-L" | "-h" => path.is_symlink(),
+        "-r" => fs::File::open(path).is_ok(),
+        "-w" => fs::metadata(path).map(|m| !m.permissions().readonly()).unwrap_or(false),
+        "-x" => is_executable(path),
+        _ => anyhow::bail!(tr!("[[: {}: unary operator expected", op)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conditional::{check, conditional_command, evaluate};
+    use crate::variables::Variables;
+
+    fn holds(expr: &str) -> bool {
+        let words: Vec<String> = expr.split_whitespace().map(|w| w.to_string()).collect();
+        evaluate(&words, &mut Variables::new()).unwrap()
+    }
+
+    #[test]
+    fn test_conditional_command() {
+        assert_eq!(conditional_command("[[ -n x ]]"), Some(" -n x "));
+        assert_eq!(conditional_command("[[-n x]]"), None);
+        assert_eq!(conditional_command("echo [[ x ]]"), None);
+    }
+
+    #[test]
+    fn test_string_tests() {
+        assert!(holds("abc == abc"));
+        assert!(holds("abc != abd"));
+        assert!(holds("-n x"));
+        assert!(evaluate(&["-z".to_string(), String::new()], &mut Variables::new()).unwrap());
+        assert!(holds("a < b"));
+        assert!(holds("word"));
+    }
+
+    #[test]
+    fn test_arithmetic_comparisons() {
+        assert!(holds("10 -gt 9"));
+        assert!(holds("2+2 -eq 4"));
+        assert!(!holds("1 -ge 2"));
+    }
+
+    #[test]
+    fn test_logic() {
+        assert!(holds("! a == b"));
+        assert!(holds("a == b || c == c"));
+        assert!(!holds("a == a && c == d"));
+        assert!(holds("( a == b || a == a ) && x"));
+    }
+
+    #[test]
+    fn test_short_circuit_skips_side_effects() {
+        let mut vars = Variables::new();
+        let words: Vec<String> = ["a", "==", "a", "||", "x=5", "-eq", "5"].iter().map(|w| w.to_string()).collect();
+        assert!(evaluate(&words, &mut vars).unwrap());
+        assert_eq!(vars.get("x"), None);
+    }
+
+    #[test]
+    fn test_file_tests() {
+        assert!(holds("-d /"));
+        assert!(!holds("-f /"));
+        assert!(!holds("-e /rush/does/not/exist"));
+    }
+
+    #[test]
+    fn test_regex_captures() {
+        let mut vars = Variables::new();
+        let words: Vec<String> = ["release-1.42", "=~", r"^release-([0-9]+)\.([0-9]+)$"]
+            .iter().map(|w| w.to_string()).collect();
+        assert!(evaluate(&words, &mut vars).unwrap());
+        assert_eq!(vars.get_array("RUSH_REMATCH").unwrap(), ["release-1.42", "1", "42"]);
+
+        let words: Vec<String> = ["main", "=~", "^release"].iter().map(|w| w.to_string()).collect();
+        assert!(!evaluate(&words, &mut vars).unwrap());
+        assert!(vars.get_array("RUSH_REMATCH").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let words: Vec<String> = ["x", "-eq", "1/0"].iter().map(|w| w.to_string()).collect();
+        assert!(check(&words).is_ok());
+        let words: Vec<String> = ["(", "a", "==", "b"].iter().map(|w| w.to_string()).collect();
+        assert!(check(&words).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let words = vec!["a".to_string(), "=~".to_string(), "(".to_string()];
+        assert!(evaluate(&words, &mut Variables::new()).is_err());
+        assert!(evaluate(&[], &mut Variables::new()).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use crate::builtins::ShellContext;
+use crate::locale::tr;
+use crate::options::OPTION_NAMES;
+use crate::theme::SCHEMES;
+
+/// Settings read from `config.toml` in the config directory before the rc file runs.
+///
+/// Some settings are exported as variables (HISTSIZE, RUSH_PROMPT_THEME, ...) instead of applied directly, so
+/// that the rc file can read or override them. The line editor only has emacs keys, so `keybindings.mode = "vi"`
+/// is reported by [`Config::warnings`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub prompt: PromptConfig,
+    pub history: HistoryConfig,
+    pub completion: CompletionConfig,
+    pub keybindings: KeybindingsConfig,
+    pub colors: ColorsConfig,
+    // `set -o` options, e.g. `pipefail = true`
+    pub options: BTreeMap<String, bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptConfig {
+    // one of the color schemes in `theme::SCHEMES`, for the prompt's colors
+    pub theme: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub size: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompletionConfig {
+    pub ignore_case: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeybindingsConfig {
+    pub mode: Option<EditingMode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditingMode {
+    Emacs,
+    Vi,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsConfig {
+    // one of `theme::SCHEMES`
+    pub scheme: Option<String>,
+}
+
+impl Config {
+    /// Parses the file's contents. Errors quote the offending key or value with its line and column.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let config: Config = toml::from_str(contents)?;
+        for name in config.options.keys() {
+            if !OPTION_NAMES.contains(&name.as_str()) {
+                anyhow::bail!(tr!("{}: invalid option name", format!("options.{}", name)));
+            }
+        }
+        for (key, value) in [("prompt.theme", &config.prompt.theme), ("colors.scheme", &config.colors.scheme)] {
+            if let Some(value) = value.as_deref().filter(|value| !SCHEMES.contains(value)) {
+                anyhow::bail!(tr!("{}: {}: invalid argument", key, value));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Messages about settings that are valid but can't take effect.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.keybindings.mode == Some(EditingMode::Vi) {
+            warnings.push(tr!("{}: {}: not supported, using {}", "keybindings.mode", "vi", "emacs"));
+        }
+        warnings
+    }
+
+    pub fn apply(&self, ctx: &mut ShellContext) -> anyhow::Result<()> {
+        for (name, on) in &self.options {
+            ctx.options.set(name, *on)?;
+        }
+
+        let vars = &mut ctx.variables;
+        if let Some(theme) = &self.prompt.theme {
+            vars.set("RUSH_PROMPT_THEME", theme);
+        }
+        if let Some(size) = self.history.size {
+            vars.set("HISTSIZE", &size.to_string());
+        }
+        if let Some(ignore_case) = self.completion.ignore_case {
+            vars.set("RUSH_COMPLETION_IGNORE_CASE", if ignore_case { "on" } else { "off" });
+        }
+        if let Some(scheme) = &self.colors.scheme {
+            vars.set("RUSH_COLOR_SCHEME", scheme);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builtins::ShellContext;
+    use crate::config::{Config, EditingMode};
+
+    #[test]
+    fn test_parse_and_apply() {
+        let config = Config::parse(
+            "[history]\nsize = 500\n\n[keybindings]\nmode = \"vi\"\n\n[options]\npipefail = true\n",
+        ).unwrap();
+        assert_eq!(config.keybindings.mode, Some(EditingMode::Vi));
+        assert_eq!(config.warnings(), ["keybindings.mode: vi: not supported, using emacs"]);
+
+        let mut ctx = ShellContext::new(Vec::new());
+        config.apply(&mut ctx).unwrap();
+        assert_eq!(ctx.variables.get("HISTSIZE").as_deref(), Some("500"));
+        assert!(ctx.options.pipefail);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        assert!(Config::parse("").unwrap().options.is_empty());
+    }
+
+    #[test]
+    fn test_errors_name_the_key() {
+        let err = Config::parse("[history]\nsise = 5\n").unwrap_err().to_string();
+        assert!(err.contains("line 2") && err.contains("sise"), "{}", err);
+        let err = Config::parse("[keybindings]\nmode = \"emac\"\n").unwrap_err().to_string();
+        assert!(err.contains("emac"), "{}", err);
+        let err = Config::parse("[options]\nbogus = true\n").unwrap_err().to_string();
+        assert_eq!(err, "options.bogus: invalid option name");
+        let err = Config::parse("[colors]\nscheme = \"solarized\"\n").unwrap_err().to_string();
+        assert_eq!(err, "colors.scheme: solarized: invalid argument");
+    }
+}
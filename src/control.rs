@@ -0,0 +1,362 @@
+//! Compound commands: `if` statements and `for` and `while` loops, which are made of the commands of lists and
+//! may span lines.
+//!
+//! A command line is split into lines at unquoted newlines and into the commands of lists as usual. A
+//! reserved word at the start of a command, such as `then` in `then echo yes`, then groups the commands
+//! around it into statements. Everything else stays a command of a list, which the shell runs as before.
+
+use crate::locale::tr;
+use crate::tokenizer::{split_lines, split_list, split_words, Connector, SyntaxError};
+
+/// A command of a list, or a compound command made of them.
+#[derive(Debug, PartialEq)]
+pub enum Statement<'a> {
+    /// A command of a list as written, with what ends it
+    Simple(&'a str, Connector),
+    /// `if list; then list; [elif list; then list;] ... [else list;] fi`, ended by the connector after `fi`
+    If { branches: Vec<Branch<'a>>, otherwise: Option<Vec<Statement<'a>>>, connector: Connector },
+    /// `for name [in words]; do list; done`: `words` as written, None without `in` to loop over the positional
+    /// parameters
+    For { name: &'a str, words: Option<&'a str>, body: Vec<Statement<'a>>, connector: Connector },
+    /// `while list; do list; done`, ended by the connector after `done`
+    While { condition: Vec<Statement<'a>>, body: Vec<Statement<'a>>, connector: Connector },
+}
+
+/// A condition of an `if` statement and the commands that run when it succeeds.
+#[derive(Debug, PartialEq)]
+pub struct Branch<'a> {
+    pub condition: Vec<Statement<'a>>,
+    pub body: Vec<Statement<'a>>,
+}
+
+impl<'a> Statement<'a> {
+    /// What ends the statement, which decides whether the next one runs.
+    pub fn connector(&self) -> Connector {
+        match self {
+            Statement::Simple(_, connector)
+            | Statement::If { connector, .. }
+            | Statement::For { connector, .. }
+            | Statement::While { connector, .. } => *connector,
+        }
+    }
+
+    /// The commands of lists the statement is made of, in the order they are written.
+    pub fn commands(&self) -> Vec<&'a str> {
+        match self {
+            Statement::Simple(command, _) => vec![command],
+            Statement::If { branches, otherwise, .. } => branches
+                .iter()
+                .flat_map(|branch| branch.condition.iter().chain(&branch.body))
+                .chain(otherwise.iter().flatten())
+                .flat_map(Statement::commands)
+                .collect(),
+            Statement::For { body, .. } => body.iter().flat_map(Statement::commands).collect(),
+            Statement::While { condition, body, .. } => {
+                condition.iter().chain(body).flat_map(Statement::commands).collect()
+            }
+        }
+    }
+}
+
+// Words that start or continue a compound command when they are the first word of a command
+const RESERVED_WORDS: &[&str] = &["if", "then", "elif", "else", "fi", "for", "in", "while", "do", "done"];
+
+// A command of a list, or a reserved word split off the start of one
+#[derive(Debug, Clone, Copy)]
+enum Item<'a> {
+    Command(&'a str, Connector),
+    Reserved(&'a str, Connector),
+    // a word after `fi` or `done`, where the command has to end
+    Misplaced(&'a str),
+}
+
+enum ParseError {
+    // the input ended inside a compound command
+    EndOfInput,
+    Syntax(SyntaxError),
+}
+
+/// Parses `input`, which may span lines, into the statements it runs, failing with its first syntax error.
+/// Blank and comment-only commands are left out.
+pub fn parse(input: &str) -> Result<Vec<Statement<'_>>, SyntaxError> {
+    match parse_statements(input) {
+        Ok(statements) => Ok(statements),
+        Err(ParseError::EndOfInput) => {
+            let message = tr!("syntax error: unexpected end of file");
+            Err(SyntaxError { column: input.trim_end().chars().count() + 1, message })
+        }
+        Err(ParseError::Syntax(error)) => Err(error),
+    }
+}
+
+/// Whether `input` opens a compound command that it doesn't close, so that the next line goes on with it.
+pub fn is_incomplete(input: &str) -> bool {
+    matches!(parse_statements(input), Err(ParseError::EndOfInput))
+}
+
+/// The reserved words that start the commands of `input`, in order, such as `then` and `fi` in
+/// `then echo yes; fi`.
+pub fn reserved_words(input: &str) -> Vec<&str> {
+    let words = items(input).into_iter().filter_map(|item| match item {
+        Item::Reserved(word, _) => Some(word),
+        Item::Command(..) | Item::Misplaced(_) => None,
+    });
+    words.collect()
+}
+
+fn parse_statements(input: &str) -> Result<Vec<Statement<'_>>, ParseError> {
+    Parser { input, items: items(input), position: 0 }.list(&[])
+}
+
+// The commands of the lists on each line of `input`, with reserved words split off their starts
+fn items(input: &str) -> Vec<Item<'_>> {
+    let mut items = Vec::new();
+    for line in split_lines(input) {
+        for (mut command, connector) in split_list(line) {
+            loop {
+                let words = split_words(command);
+                let Some(&first) = words.first() else {
+                    break;
+                };
+                if !RESERVED_WORDS.contains(&first) {
+                    items.push(Item::Command(command, connector));
+                    break;
+                }
+                let rest = &command[first.as_ptr() as usize - command.as_ptr() as usize + first.len()..];
+                let next = split_words(rest).first().copied();
+                items.push(Item::Reserved(first, if next.is_none() { connector } else { Connector::Sequence }));
+                if matches!(first, "fi" | "done")
+                    && let Some(next) = next
+                {
+                    items.push(Item::Misplaced(next));
+                    break;
+                }
+                command = rest;
+            }
+        }
+    }
+    items
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    items: Vec<Item<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    // Statements up to one of the reserved words in `end`, which is left for the caller, or to the end of input
+    fn list(&mut self, end: &[&str]) -> Result<Vec<Statement<'a>>, ParseError> {
+        let mut statements = Vec::new();
+        while let Some(&item) = self.items.get(self.position) {
+            match item {
+                Item::Command(command, connector) => {
+                    self.position += 1;
+                    statements.push(Statement::Simple(command, connector));
+                }
+                Item::Reserved("if", _) => {
+                    self.position += 1;
+                    statements.push(self.if_statement()?);
+                }
+                Item::Reserved("for", _) => {
+                    self.position += 1;
+                    statements.push(self.for_statement()?);
+                }
+                Item::Reserved("while", _) => {
+                    self.position += 1;
+                    statements.push(self.while_statement()?);
+                }
+                Item::Reserved(word, _) if end.contains(&word) => return Ok(statements),
+                Item::Reserved(word, _) | Item::Misplaced(word) => return Err(self.unexpected(word)),
+            }
+        }
+        if end.is_empty() { Ok(statements) } else { Err(ParseError::EndOfInput) }
+    }
+
+    // The rest of an `if` statement, after the `if`
+    fn if_statement(&mut self) -> Result<Statement<'a>, ParseError> {
+        let mut branches = Vec::new();
+        loop {
+            let condition = self.nonempty_list(&["then"])?;
+            self.position += 1;
+            let body = self.nonempty_list(&["elif", "else", "fi"])?;
+            branches.push(Branch { condition, body });
+            let Item::Reserved(word, connector) = self.items[self.position] else {
+                unreachable!("lists end at reserved words");
+            };
+            self.position += 1;
+            match word {
+                "elif" => continue,
+                "else" => {
+                    let otherwise = self.nonempty_list(&["fi"])?;
+                    let Item::Reserved(_, connector) = self.items[self.position] else {
+                        unreachable!("lists end at reserved words");
+                    };
+                    self.position += 1;
+                    return Ok(Statement::If { branches, otherwise: Some(otherwise), connector });
+                }
+                _ => return Ok(Statement::If { branches, otherwise: None, connector }),
+            }
+        }
+    }
+
+    // The rest of a `for` loop, after the `for`: the name and words of the loop, then its body
+    fn for_statement(&mut self) -> Result<Statement<'a>, ParseError> {
+        let command = match self.next()? {
+            Item::Command(command, _) => command,
+            Item::Reserved(word, _) | Item::Misplaced(word) => return Err(self.unexpected(word)),
+        };
+        let (name, words) = match split_words(command)[..] {
+            [name] => (name, None),
+            [name, "in", ref words @ ..] => {
+                let offset = |word: &&str| word.as_ptr() as usize - command.as_ptr() as usize;
+                let start = words.first().map_or(command.len(), offset);
+                (name, Some(&command[start..]))
+            }
+            [_, word, ..] => return Err(self.unexpected(word)),
+            [] => unreachable!("commands have words"),
+        };
+        match self.next()? {
+            Item::Reserved("do", _) => {}
+            Item::Command(command, _) => return Err(self.unexpected(split_words(command)[0])),
+            Item::Reserved(word, _) | Item::Misplaced(word) => return Err(self.unexpected(word)),
+        }
+        let body = self.nonempty_list(&["done"])?;
+        let Item::Reserved(_, connector) = self.items[self.position] else {
+            unreachable!("lists end at reserved words");
+        };
+        self.position += 1;
+        Ok(Statement::For { name, words, body, connector })
+    }
+
+    // The rest of a `while` loop, after the `while`: its condition, then its body
+    fn while_statement(&mut self) -> Result<Statement<'a>, ParseError> {
+        let condition = self.nonempty_list(&["do"])?;
+        self.position += 1;
+        let body = self.nonempty_list(&["done"])?;
+        let Item::Reserved(_, connector) = self.items[self.position] else {
+            unreachable!("lists end at reserved words");
+        };
+        self.position += 1;
+        Ok(Statement::While { condition, body, connector })
+    }
+
+    // The next item, which the input must have
+    fn next(&mut self) -> Result<Item<'a>, ParseError> {
+        let item = *self.items.get(self.position).ok_or(ParseError::EndOfInput)?;
+        self.position += 1;
+        Ok(item)
+    }
+
+    // A list that must have at least one command before the reserved word that ends it
+    fn nonempty_list(&mut self, end: &[&str]) -> Result<Vec<Statement<'a>>, ParseError> {
+        let statements = self.list(end)?;
+        if statements.is_empty()
+            && let Item::Reserved(word, _) = self.items[self.position]
+        {
+            return Err(self.unexpected(word));
+        }
+        Ok(statements)
+    }
+
+    // The error for a word where none can be, a slice of the input
+    fn unexpected(&self, word: &str) -> ParseError {
+        let offset = word.as_ptr() as usize - self.input.as_ptr() as usize;
+        let column = self.input[..offset].chars().count() + 1;
+        ParseError::Syntax(SyntaxError { column, message: tr!("syntax error near unexpected token `{}'", word) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control::{is_incomplete, parse, Branch, Statement};
+    use crate::tokenizer::Connector::*;
+
+    #[test]
+    fn test_parse_if() {
+        assert_eq!(parse("ls; echo a && echo b").unwrap(), vec![
+            Statement::Simple("ls", Sequence),
+            Statement::Simple(" echo a ", And),
+            Statement::Simple(" echo b", Sequence),
+        ]);
+        assert_eq!(parse("if a || b; then c; elif d\nthen e; else f\n  fi && g").unwrap(), vec![
+            Statement::If {
+                branches: vec![
+                    Branch {
+                        condition: vec![Statement::Simple(" a ", Or), Statement::Simple(" b", Sequence)],
+                        body: vec![Statement::Simple(" c", Sequence)],
+                    },
+                    Branch {
+                        condition: vec![Statement::Simple(" d", Sequence)],
+                        body: vec![Statement::Simple(" e", Sequence)],
+                    },
+                ],
+                otherwise: Some(vec![Statement::Simple(" f", Sequence)]),
+                connector: And,
+            },
+            Statement::Simple(" g", Sequence),
+        ]);
+        // reserved words only count at the start of a command
+        assert_eq!(parse("echo if then fi").unwrap(), vec![Statement::Simple("echo if then fi", Sequence)]);
+        let nested = parse("if a; then if b; then c; fi; fi").unwrap();
+        assert_eq!(nested[0].commands(), vec![" a", " b", " c"]);
+    }
+
+    #[test]
+    fn test_parse_for() {
+        assert_eq!(parse("for x in a 'b c'; do echo $x; done || echo no").unwrap(), vec![
+            Statement::For {
+                name: "x",
+                words: Some("a 'b c'"),
+                body: vec![Statement::Simple(" echo $x", Sequence)],
+                connector: Or,
+            },
+            Statement::Simple(" echo no", Sequence),
+        ]);
+        let nested = parse("for arg\ndo\n  for x in; do echo $arg$x; done\ndone").unwrap();
+        let Statement::For { name: "arg", words: None, body, .. } = &nested[0] else {
+            panic!("not a loop over the positional parameters: {:?}", nested);
+        };
+        assert!(matches!(body[0], Statement::For { name: "x", words: Some(""), .. }));
+    }
+
+    #[test]
+    fn test_parse_while() {
+        assert_eq!(parse("while ((i < 3)); do echo $i; ((i++)); done && echo end").unwrap(), vec![
+            Statement::While {
+                condition: vec![Statement::Simple(" ((i < 3))", Sequence)],
+                body: vec![Statement::Simple(" echo $i", Sequence), Statement::Simple(" ((i++))", Sequence)],
+                connector: And,
+            },
+            Statement::Simple(" echo end", Sequence),
+        ]);
+        let nested = parse("while a
+do
+  for x in b; do c; done
+done").unwrap();
+        assert_eq!(nested[0].commands(), vec![" a", " c"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| parse(input).unwrap_err();
+        assert_eq!(error("fi").message, "syntax error near unexpected token `fi'");
+        assert_eq!(error("if a; then b; else fi").column, 20);
+        assert_eq!(error("if then b; fi").message, "syntax error near unexpected token `then'");
+        assert_eq!(error("echo; then").message, "syntax error near unexpected token `then'");
+        assert_eq!(error("if a; then b; fi c").message, "syntax error near unexpected token `c'");
+        assert_eq!(error("if a; then b").message, "syntax error: unexpected end of file");
+        assert_eq!(error("for x y; do b; done").message, "syntax error near unexpected token `y'");
+        assert_eq!(error("for x in a; b; done").message, "syntax error near unexpected token `b'");
+        assert_eq!(error("for x in a; do b; done c").column, 24);
+        assert!(is_incomplete("if a\nthen"));
+        assert!(is_incomplete("if a; then if b; then c; fi"));
+        assert!(!is_incomplete("if a; then b; fi"));
+        assert!(!is_incomplete("fi"));
+        assert!(is_incomplete("for x in a b"));
+        assert!(is_incomplete("for x in a b; do echo $x"));
+        assert_eq!(error("while do b; done").message, "syntax error near unexpected token `do'");
+        assert_eq!(error("while a; b; done").message, "syntax error near unexpected token `done'");
+        assert!(is_incomplete("while a; do b"));
+    }
+}
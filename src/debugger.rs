@@ -0,0 +1,101 @@
+use std::io::{self, Read, Write};
+use crate::builtins::ShellContext;
+use crate::expand::lookup;
+use crate::error::{Result, RushError};
+use crate::syntax::display_command;
+use crate::tokenizer::quote;
+
+const HELP: &str = "\
+step, s, next, n   run the command and stop before the next one (also: empty line)
+continue, c        run to the end of the script without stopping
+print, p NAME...   show variables; NAME may also be a special or positional parameter (?, #, 1, ...)
+quit, q            stop the script and exit
+help, h            show this help";
+
+pub struct Debugger {
+    stepping: bool,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger { stepping: true }
+    }
+}
+
+// Stops before `input` runs while stepping, showing the command as it will be executed,
+// and reads debugger commands from stdin until told to go on.
+pub fn pause(input: &str, ctx: &mut ShellContext) -> Result<()> {
+    match &ctx.debugger {
+        Some(debugger) if debugger.stepping => {}
+        _ => return Ok(()),
+    }
+
+    let mut stderr = ctx.system.stderr();
+    let mut stdin = ctx.system.stdin();
+    writeln!(stderr, "{}: {}", ctx.location(), display_command(input))?;
+    loop {
+        write!(stderr, "(rdb) ")?;
+        stderr.flush()?;
+
+        let Some(line) = read_line(&mut *stdin)? else {
+            // no more debugger input: run the rest of the script unattended
+            stop_stepping(ctx);
+            return Ok(());
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["s" | "step" | "n" | "next"] => return Ok(()),
+            ["c" | "continue"] => {
+                stop_stepping(ctx);
+                return Ok(());
+            }
+            ["p" | "print", names @ ..] if !names.is_empty() => {
+                for name in names {
+                    if let Some(values) = ctx.variables.get_array(name) {
+                        let quoted: Vec<String> = values.iter().map(|v| quote(v)).collect();
+                        writeln!(stderr, "{}=({})", name, quoted.join(" "))?;
+                        continue;
+                    }
+                    match lookup(name, ctx) {
+                        Some(value) => writeln!(stderr, "{}={}", name, quote(&value))?,
+                        None => writeln!(stderr, "{}: unset", name)?,
+                    }
+                }
+            }
+            ["q" | "quit"] => return Err(RushError::Exit(ctx.last_status)),
+            ["h" | "help"] => writeln!(stderr, "{}", HELP)?,
+            _ => writeln!(stderr, "unknown debugger command: {} (try help)", line.trim())?,
+        }
+    }
+}
+
+// Reads a debugger command a byte at a time, so that the programs of the script get the rest of the input.
+// None at the end of the input.
+fn read_line(input: &mut dyn Read) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) if line.is_empty() => return Ok(None),
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn stop_stepping(ctx: &mut ShellContext) {
+    if let Some(debugger) = ctx.debugger.as_mut() {
+        debugger.stepping = false;
+    }
+}
@@ -0,0 +1,53 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::path_utils::{expand_tilde, home_dir};
+
+/// Directory holding rush's configuration: `$XDG_CONFIG_HOME/rush`, or `~/.config/rush` when it is unset.
+pub fn config_dir() -> Option<PathBuf> {
+    let home = home_dir();
+    xdg_dir(env::var("XDG_CONFIG_HOME").ok(), home.as_deref(), ".config")
+}
+
+/// Picks the startup file `name` from the config directory. When that doesn't exist but the legacy dotfile
+/// does, the dotfile is used instead so existing setups keep working.
+pub fn config_file(name: &str, legacy: &str) -> Option<String> {
+    let preferred = config_dir().map(|dir| dir.join(name));
+    if let Some(path) = &preferred
+        && path.exists()
+    {
+        return Some(path.to_string_lossy().into_owned());
+    }
+
+    match expand_tilde(legacy) {
+        Ok(path) if Path::new(&path).exists() => Some(path),
+        _ => preferred.map(|path| path.to_string_lossy().into_owned()),
+    }
+}
+
+/// File holding the aliases saved with `alias --save`, loaded by interactive shells at startup.
+pub fn aliases_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("aliases"))
+}
+
+// Resolves an XDG base directory. The spec says relative values are invalid and must be ignored.
+fn xdg_dir(value: Option<String>, home: Option<&str>, default: &str) -> Option<PathBuf> {
+    match value {
+        Some(dir) if Path::new(&dir).is_absolute() => Some(Path::new(&dir).join("rush")),
+        _ => home.map(|home| Path::new(home).join(default).join("rush")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::dirs::xdg_dir;
+
+    #[test]
+    fn test_xdg_dir() {
+        assert_eq!(xdg_dir(Some("/xdg".to_string()), Some("/home/u"), ".config"), Some(PathBuf::from("/xdg/rush")));
+        assert_eq!(xdg_dir(None, Some("/home/u"), ".config"), Some(PathBuf::from("/home/u/.config/rush")));
+        // relative values are ignored
+        assert_eq!(xdg_dir(Some("xdg".to_string()), Some("/home/u"), ".config"), Some(PathBuf::from("/home/u/.config/rush")));
+        assert_eq!(xdg_dir(None, None, ".config"), None);
+    }
+}
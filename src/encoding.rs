@@ -0,0 +1,121 @@
+//! Conversions between the shell's text and the bytes the OS hands out.
+//!
+//! Unix file names, arguments and environment values are arbitrary bytes, but the shell works on `String`s.
+//! Bytes that aren't valid UTF-8 are carried through as the private-use characters U+EF80..U+EFFF (one per
+//! byte, the same mapping mksh uses) and turned back into the original bytes wherever text leaves the shell:
+//! when spawning a command, opening a file or writing output. Text that is valid UTF-8 is left untouched.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const RAW_BASE: u32 = 0xEF00;
+
+fn raw_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        code @ 0xEF80..=0xEFFF => Some((code - RAW_BASE) as u8),
+        _ => None,
+    }
+}
+
+fn push_raw(text: &mut String, bytes: &[u8]) {
+    text.extend(bytes.iter().map(|&b| char::from_u32(RAW_BASE + b as u32).unwrap()));
+}
+
+/// Decodes bytes into shell text, keeping invalid UTF-8 as raw-byte characters.
+pub fn decode(mut bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    while !bytes.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, 0),
+            Err(e) => (
+                std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+                e.error_len().unwrap_or(bytes.len() - e.valid_up_to()),
+            ),
+        };
+        for c in valid.chars() {
+            // a character that is itself in the raw-byte range is stored byte by byte so it survives encoding
+            if raw_byte(c).is_some() {
+                push_raw(&mut text, c.encode_utf8(&mut [0; 4]).as_bytes());
+            } else {
+                text.push(c);
+            }
+        }
+        let end = valid.len() + invalid_len;
+        push_raw(&mut text, &bytes[valid.len()..end]);
+        bytes = &bytes[end..];
+    }
+    text
+}
+
+/// Turns shell text back into the bytes it was decoded from.
+pub fn encode(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match raw_byte(c) {
+            Some(b) => bytes.push(b),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    bytes
+}
+
+/// Shell text for an OS string. Outside Unix, where OS strings aren't bytes, invalid data is replaced.
+pub fn from_os(os: &OsStr) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        decode(os.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        os.to_string_lossy().into_owned()
+    }
+}
+
+/// The OS string to pass to the system for shell text: a command name, argument or environment value.
+pub fn to_os(text: &str) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(encode(text))
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(text)
+    }
+}
+
+pub fn to_path(text: &str) -> PathBuf {
+    PathBuf::from(to_os(text))
+}
+
+/// Reads a whole file as shell text.
+pub fn read_file(path: impl AsRef<Path>) -> io::Result<String> {
+    std::fs::read(path).map(|bytes| decode(&bytes))
+}
+
+/// An environment variable as shell text, whether or not it is valid UTF-8.
+pub fn env_var(name: &str) -> Option<String> {
+    std::env::var_os(name).map(|value| from_os(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::{decode, encode};
+
+    #[test]
+    fn test_utf8_unchanged() {
+        assert_eq!(decode("héllo wörld".as_bytes()), "héllo wörld");
+        assert_eq!(encode("héllo wörld"), "héllo wörld".as_bytes());
+    }
+
+    #[test]
+    fn test_invalid_bytes_round_trip() {
+        for bytes in [&b"caf\xe9.txt"[..], b"\xff\xfe", b"ok\xc3", b"\xe2\x82 x", "\u{ef80}\u{efff}".as_bytes()] {
+            let text = decode(bytes);
+            assert_eq!(encode(&text), bytes, "{:?}", text);
+        }
+        assert_eq!(decode(b"caf\xe9").chars().count(), 4);
+    }
+}
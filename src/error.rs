@@ -0,0 +1,183 @@
+use std::fmt;
+use std::io;
+use crate::locale::tr;
+use crate::tokenizer::SyntaxError;
+
+/// Why a command failed, with the exit status it maps to.
+///
+/// Errors that don't stop the shell are reported where they happen; the most recent one stays available
+/// through `Shell::last_error` so embedding programs don't have to parse diagnostics.
+#[derive(Debug)]
+pub enum RushError {
+    /// The line couldn't be parsed; `column` is 1-based within the line when known.
+    Syntax { column: Option<usize>, message: String },
+    CommandNotFound(String),
+    /// The command was found but couldn't be started.
+    NotExecutable { command: String, source: io::Error },
+    /// A file output is redirected to couldn't be opened; the command doesn't run.
+    Redirection { path: String, source: io::Error },
+    /// A builtin rejected its arguments or failed; the message starts with the builtin's name.
+    BuiltinUsage(String),
+    /// A `(( ))` expression couldn't be evaluated.
+    Arithmetic { expr: String, message: String },
+    /// A pattern matched no files with failglob set.
+    NoMatch(String),
+    /// A parameter that isn't set was expanded with nounset set.
+    Unbound(String),
+    /// A `!` reference in a line entered at the prompt matched no history entry; the line doesn't run.
+    EventNotFound(String),
+    Io(io::Error),
+    Interrupted,
+    /// A builtin failed with this status and has already printed whatever it had to say.
+    Status(i32),
+    /// Raised by `exit`, `set -e` and the debugger's `quit` to unwind out of whatever the shell is running.
+    Exit(i32),
+    /// Raised by `break n` to leave the `n` innermost loops.
+    Break(usize),
+    /// Raised by `continue n` to go on with the next round of the `n`th innermost loop.
+    Continue(usize),
+}
+
+pub type Result<T> = std::result::Result<T, RushError>;
+
+impl RushError {
+    /// The value `$?` takes when a command fails this way.
+    pub fn status(&self) -> i32 {
+        match self {
+            RushError::Syntax { .. } => 2,
+            RushError::CommandNotFound(_) => 127,
+            RushError::NotExecutable { .. } => 126,
+            RushError::Redirection { .. }
+            | RushError::BuiltinUsage(_)
+            | RushError::Arithmetic { .. }
+            | RushError::NoMatch(_)
+            | RushError::Unbound(_)
+            | RushError::EventNotFound(_)
+            | RushError::Io(_) => 1,
+            // 128 + SIGINT
+            RushError::Interrupted => 130,
+            RushError::Status(status) | RushError::Exit(status) => *status,
+            RushError::Break(_) | RushError::Continue(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for RushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RushError::Syntax { message, .. } => write!(f, "{}", message),
+            RushError::CommandNotFound(command) => write!(f, "{}", tr!("{}: command not found", command)),
+            RushError::NotExecutable { command, source } => {
+                write!(f, "{}", tr!("failed to execute {}: {}", command, io_message(source)))
+            }
+            RushError::Redirection { path, source } => write!(f, "{}: {}", path, io_message(source)),
+            RushError::BuiltinUsage(message) => write!(f, "{}", message),
+            RushError::Arithmetic { expr, message } => write!(f, "((: {}: {}", expr, message),
+            RushError::NoMatch(pattern) => write!(f, "{}", tr!("no match: {}", pattern)),
+            RushError::Unbound(name) => write!(f, "{}", tr!("{}: unbound variable", name)),
+            RushError::EventNotFound(reference) => write!(f, "{}", tr!("{}: event not found", reference)),
+            RushError::Io(e) => write!(f, "{}", io_message(e)),
+            RushError::Interrupted => write!(f, "{}", tr!("interrupted")),
+            RushError::Status(status) => write!(f, "{}", tr!("exit status {}", status)),
+            RushError::Exit(status) => write!(f, "exit {}", status),
+            RushError::Break(count) => write!(f, "break {}", count),
+            RushError::Continue(count) => write!(f, "continue {}", count),
+        }
+    }
+}
+
+/// What an I/O error says to the user, the way shells put it: "No such file or directory", without the
+/// "(os error 2)" the standard library adds. An error made with a message of its own says that.
+pub fn io_message(error: &io::Error) -> String {
+    if error.get_ref().is_some() {
+        return error.to_string();
+    }
+    match error.kind() {
+        io::ErrorKind::NotFound => tr!("No such file or directory"),
+        io::ErrorKind::PermissionDenied => tr!("Permission denied"),
+        io::ErrorKind::AlreadyExists => tr!("File exists"),
+        io::ErrorKind::IsADirectory => tr!("Is a directory"),
+        io::ErrorKind::NotADirectory => tr!("Not a directory"),
+        io::ErrorKind::DirectoryNotEmpty => tr!("Directory not empty"),
+        _ => {
+            let message = error.to_string();
+            match error.raw_os_error() {
+                Some(code) => message.strip_suffix(&format!(" (os error {})", code)).unwrap_or(&message).to_string(),
+                None => message,
+            }
+        }
+    }
+}
+
+impl std::error::Error for RushError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RushError::NotExecutable { source, .. } | RushError::Redirection { source, .. } => Some(source),
+            RushError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RushError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::Interrupted {
+            RushError::Interrupted
+        } else {
+            RushError::Io(e)
+        }
+    }
+}
+
+impl From<SyntaxError> for RushError {
+    fn from(e: SyntaxError) -> Self {
+        RushError::Syntax { column: Some(e.column), message: e.message }
+    }
+}
+
+impl From<anyhow::Error> for RushError {
+    /// Builtins report failures through anyhow; a `RushError` they pass along (such as `exit`'s) is kept as is.
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<RushError>() {
+            Ok(e) => e,
+            Err(e) => RushError::BuiltinUsage(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use crate::error::{io_message, RushError};
+    use crate::tokenizer::tokenize;
+
+    #[test]
+    fn test_statuses() {
+        assert_eq!(RushError::CommandNotFound("x".to_string()).status(), 127);
+        assert_eq!(RushError::from(tokenize("echo 'open").unwrap_err()).status(), 2);
+        assert_eq!(RushError::from(io::Error::from(io::ErrorKind::Interrupted)).status(), 130);
+        assert_eq!(RushError::Exit(3).status(), 3);
+    }
+
+    #[test]
+    fn test_io_messages() {
+        assert_eq!(io_message(&io::Error::from(io::ErrorKind::NotFound)), "No such file or directory");
+        assert_eq!(io_message(&io::Error::other("disk on fire")), "disk on fire");
+        let source = io::ErrorKind::PermissionDenied.into();
+        let error = RushError::NotExecutable { command: "x".to_string(), source };
+        assert_eq!(error.to_string(), "failed to execute x: Permission denied");
+        // other errors of the system keep its message, without the error number
+        #[cfg(unix)]
+        assert_eq!(io_message(&io::Error::from_raw_os_error(libc::ESRCH)), "No such process");
+    }
+
+    #[test]
+    fn test_from_builtin_error() {
+        let e = RushError::from(anyhow::anyhow!("cd: nowhere: No such file or directory"));
+        assert!(matches!(e, RushError::BuiltinUsage(_)));
+        assert_eq!(e.to_string(), "cd: nowhere: No such file or directory");
+
+        let e = RushError::from(anyhow::Error::from(RushError::Exit(4)));
+        assert!(matches!(e, RushError::Exit(4)));
+    }
+}
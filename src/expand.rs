@@ -0,0 +1,505 @@
+use crate::arithmetic;
+use crate::builtins::ShellContext;
+use crate::error::{Result, RushError};
+use crate::glob;
+use crate::path_utils::{home_dir_with, user_home_dir};
+use crate::tokenizer::{arithmetic_expansion, is_name, tokenize_segments, Quoting};
+
+/// Expands `$NAME`, `${NAME}` and the special parameters `$?`, `$$`, `$!`, `$#`, `$@`, `$*` and `$0`..`$9`
+/// in `text`, and the elements of arrays: `${NAME[i]}`, counting from the end when `i` is negative, and
+/// `${NAME[@]}` and `${NAME[*]}` for all of them, which `$@` and `$*` are to the positional parameters. Unset
+/// parameters expand to nothing; a `$` that does not start a parameter is kept as is. `$((expression))` expands
+/// to the value of the arithmetic expression, evaluated as the `((` command does after its parameters are
+/// expanded; one that fails to evaluate ends the expansion.
+pub fn expand_parameters(text: &str, ctx: &mut ShellContext) -> String {
+    let mut result = String::new();
+    // unset parameters are no error here, and a failed arithmetic expansion leaves what came before it
+    let _ = expand_pieces(text, ctx, false, |piece| match piece {
+        Piece::Literal(text) | Piece::Value(text) => result.push_str(text),
+        Piece::Params(params) => result.push_str(&params.join(" ")),
+    });
+    tracing::debug!(text, expanded = %result, "expand");
+    result
+}
+
+/// Expands the parameters in the body of a here-document, where quotes are plain text and a backslash only
+/// escapes `$`, `` ` ``, `\` and a newline, which it removes along with itself.
+pub fn expand_here_document(body: &str, ctx: &mut ShellContext) -> String {
+    let mut result = String::new();
+    let mut text = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('$' | '`' | '\\')) => {
+                result.push_str(&expand_parameters(&std::mem::take(&mut text), ctx));
+                result.extend(chars.next());
+            }
+            ('\\', Some('\n')) => {
+                chars.next();
+            }
+            _ => text.push(c),
+        }
+    }
+    result.push_str(&expand_parameters(&text, ctx));
+    result
+}
+
+/// Expands the parameters in the words of `text` where its quoting allows, POSIX style: not in single
+/// quotes or after a backslash, and without splitting in double quotes. A word starting with an unquoted `~`
+/// gets the directory it stands for first, see `expand_tilde_prefix`. With `split_fields`, the values
+/// of unquoted parameters are split into separate words on the characters in IFS, a word that was only an
+/// unquoted parameter expanding to nothing disappears, and a word with unquoted wildcards expands to the
+/// files it matches. A pattern that matches nothing is kept as is, or is an error with failglob. `"$@"` makes a
+/// word of each positional parameter when words are split, and none at all when there are none. With nounset,
+/// a parameter that isn't set is an error, as is an arithmetic expansion that fails to evaluate.
+pub fn expand_words(text: &str, ctx: &mut ShellContext, split_fields: bool) -> Result<Vec<String>> {
+    let ifs = ctx.variables.get("IFS").unwrap_or_else(|| " \t\n".to_string());
+    let nounset = ctx.options.nounset;
+    let mut fields = Fields::default();
+    for mut word in tokenize_segments(text)? {
+        expand_tilde_prefix(&mut word, ctx);
+        if !split_fields {
+            fields.current = Some(Field::default());
+        }
+        for (quoting, segment) in word {
+            match quoting {
+                Quoting::Literal => fields.push(&segment, true),
+                Quoting::Double => {
+                    if makes_no_word(&segment, ctx) {
+                        continue;
+                    }
+                    expand_pieces(&segment, ctx, nounset, |piece| match piece {
+                        Piece::Literal(text) | Piece::Value(text) => fields.push(text, true),
+                        Piece::Params(params) => {
+                            fields.push_params(params, split_fields, |fields, param| fields.push(param, true));
+                        }
+                    })?
+                }
+                Quoting::Unquoted => expand_pieces(&segment, ctx, nounset, |piece| match piece {
+                    Piece::Value(value) if split_fields => fields.split(value, &ifs),
+                    Piece::Literal(text) | Piece::Value(text) => {
+                        if !text.is_empty() {
+                            fields.push(text, false);
+                        }
+                    }
+                    Piece::Params(params) => fields.push_params(params, split_fields, |fields, param| {
+                        if split_fields {
+                            fields.split(param, &ifs);
+                        } else if !param.is_empty() {
+                            fields.push(param, false);
+                        }
+                    }),
+                })?,
+            }
+        }
+        fields.end_word();
+    }
+
+    let mut words = Vec::new();
+    for field in fields.words {
+        if split_fields && field.wildcards && glob::has_wildcards(&field.pattern) {
+            let matches = glob::expand(&field.pattern, &*ctx.system);
+            if !matches.is_empty() {
+                words.extend(matches);
+                continue;
+            }
+            if ctx.options.failglob {
+                return Err(RushError::NoMatch(field.text));
+            }
+        }
+        words.push(field.text);
+    }
+    tracing::debug!(text, ?words, "expand");
+    Ok(words)
+}
+
+// Replaces the tilde-prefix of a word, the unquoted text from a leading `~` up to the first slash, with the
+// directory it stands for: `~` the home directory, `~user` that user's, `~+` PWD and `~-` OLDPWD. The
+// directory is taken as quoted, so it is neither split nor matched as a pattern. A prefix that stands for
+// nothing is left as it is.
+fn expand_tilde_prefix(word: &mut Vec<(Quoting, String)>, ctx: &ShellContext) {
+    let Some((Quoting::Unquoted, first)) = word.first() else {
+        return;
+    };
+    if !first.starts_with('~') {
+        return;
+    }
+    // quoted characters before the slash make it no prefix at all
+    let end = match first.find('/') {
+        Some(end) => end,
+        None if word.len() == 1 => first.len(),
+        None => return,
+    };
+    let dir = match &first[1..end] {
+        "" => home_dir_with(|name| ctx.variables.get(name)),
+        "+" => ctx.variables.get("PWD"),
+        "-" => ctx.variables.get("OLDPWD"),
+        user => user_home_dir(user),
+    };
+    let Some(dir) = dir else {
+        return;
+    };
+    let rest = first[end..].to_string();
+    word[0] = (Quoting::Literal, dir);
+    if !rest.is_empty() {
+        word.insert(1, (Quoting::Unquoted, rest));
+    }
+}
+
+// A piece of text with parameters in it
+enum Piece<'a> {
+    Literal(&'a str),
+    /// The value of a parameter
+    Value(&'a str),
+    /// The positional parameters `$@` stands for, which can make separate words
+    Params(&'a [String]),
+}
+
+// Calls `f` with each piece of `text` in turn. With `nounset`, a parameter that isn't set stops the expansion;
+// so does an arithmetic expansion that fails to evaluate.
+fn expand_pieces(text: &str, ctx: &mut ShellContext, nounset: bool, mut f: impl FnMut(Piece)) -> Result<()> {
+    let mut chars = text.char_indices().peekable();
+    let mut literal = String::new();
+    let value = |literal: &mut String, name: &str, ctx: &ShellContext, f: &mut dyn FnMut(Piece)| {
+        f(Piece::Literal(&std::mem::take(literal)));
+        match (name, split_subscript(name)) {
+            ("@", _) => f(Piece::Params(&ctx.positional_params)),
+            (_, Some((array, "@"))) => f(Piece::Params(&elements(array, ctx))),
+            _ => match lookup(name, ctx) {
+                Some(value) => f(Piece::Value(&value)),
+                None if nounset => return Err(RushError::Unbound(name.to_string())),
+                None => f(Piece::Value("")),
+            },
+        }
+        Ok(())
+    };
+
+    while let Some((i, c)) = chars.next() {
+        if c != '$' {
+            literal.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some((_, '(')) if arithmetic_expansion(&text[i..]).is_some() => {
+                let end = i + arithmetic_expansion(&text[i..]).unwrap_or(0);
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+                let expr = expand_parameters(&text[i + "$((".len()..end - "))".len()], ctx);
+                let value = arithmetic::evaluate(&expr, &mut ctx.variables).map_err(|e| {
+                    RushError::Arithmetic { expr: expr.trim().to_string(), message: e.to_string() }
+                })?;
+                f(Piece::Literal(&std::mem::take(&mut literal)));
+                f(Piece::Value(&value.to_string()));
+            }
+            Some((_, '{')) => {
+                chars.next();
+                let mut name = String::new();
+                let mut closed = false;
+                for (_, ch) in chars.by_ref() {
+                    if ch == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(ch);
+                }
+                if closed {
+                    value(&mut literal, &name, ctx, &mut f)?;
+                } else {
+                    literal.push_str("${");
+                    literal.push_str(&name);
+                }
+            }
+            Some(&(_, ch)) if ch.is_ascii_alphabetic() || ch == '_' => {
+                let mut name = String::new();
+                while let Some(&(_, ch)) = chars.peek() {
+                    if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                        break;
+                    }
+                    name.push(ch);
+                    chars.next();
+                }
+                value(&mut literal, &name, ctx, &mut f)?;
+            }
+            Some(&(_, ch)) if ch.is_ascii_digit() || matches!(ch, '?' | '$' | '!' | '#' | '@' | '*') => {
+                chars.next();
+                value(&mut literal, &ch.to_string(), ctx, &mut f)?;
+            }
+            _ => literal.push('$'),
+        }
+    }
+    f(Piece::Literal(&literal));
+    Ok(())
+}
+
+// The words made so far, and the one being made if it has begun
+#[derive(Default)]
+struct Fields {
+    words: Vec<Field>,
+    current: Option<Field>,
+    // the last word ended at IFS white space, which a following IFS delimiter belongs to
+    after_space: bool,
+}
+
+// A word, along with the pattern it is for filename expansion, which has the quoted characters escaped
+#[derive(Default)]
+struct Field {
+    text: String,
+    pattern: String,
+    // an unquoted wildcard character went into the word
+    wildcards: bool,
+}
+
+impl Fields {
+    // Adds text to the current word, starting one if needed
+    fn push(&mut self, text: &str, quoted: bool) {
+        let field = self.current.get_or_insert_default();
+        field.text.push_str(text);
+        for c in text.chars() {
+            if quoted && matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                field.pattern.push('\\');
+            }
+            field.pattern.push(c);
+            field.wildcards |= !quoted && matches!(c, '*' | '?' | '[');
+        }
+    }
+
+    // Adds the value of an unquoted parameter: IFS white space separates words, any other IFS character
+    // ends one, even an empty one
+    fn split(&mut self, value: &str, ifs: &str) {
+        for c in value.chars() {
+            if !ifs.contains(c) {
+                self.push(c.encode_utf8(&mut [0; 4]), false);
+                self.after_space = false;
+            } else if c.is_whitespace() {
+                self.words.extend(self.current.take());
+                self.after_space = true;
+            } else if !std::mem::take(&mut self.after_space) || self.current.is_some() {
+                self.words.push(self.current.take().unwrap_or_default());
+            }
+        }
+    }
+
+    // Adds the positional parameters with `push`: each starts a word of its own when words are split, and is
+    // separated from the one before by a space otherwise
+    fn push_params(&mut self, params: &[String], split_fields: bool, mut push: impl FnMut(&mut Self, &str)) {
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 && split_fields {
+                self.words.extend(self.current.take());
+            } else if i > 0 {
+                self.push(" ", true);
+            }
+            push(self, param);
+        }
+    }
+
+    fn end_word(&mut self) {
+        self.words.extend(self.current.take());
+        self.after_space = false;
+    }
+}
+
+/// Looks up a shell parameter: special parameters, positional parameters, then variables and the elements of
+/// arrays.
+pub fn lookup(name: &str, ctx: &ShellContext) -> Option<String> {
+    match name {
+        "?" => Some(ctx.last_status.to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "!" => ctx.jobs.last_pid().map(|pid| pid.to_string()),
+        "#" => Some(ctx.positional_params.len().to_string()),
+        "@" => Some(ctx.positional_params.join(" ")),
+        "*" => Some(join_with_ifs(&ctx.positional_params, ctx)),
+        "0" => Some(ctx.shell_name.clone()),
+        _ => match (name.parse::<usize>(), split_subscript(name)) {
+            (Ok(index), _) => index.checked_sub(1).and_then(|i| ctx.positional_params.get(i)).cloned(),
+            (_, Some((array, "@"))) => Some(elements(array, ctx).join(" ")),
+            (_, Some((array, "*"))) => Some(join_with_ifs(&elements(array, ctx), ctx)),
+            (_, Some((array, index))) => element(&elements(array, ctx), index, ctx),
+            _ => ctx.variables.get(name),
+        },
+    }
+}
+
+// Joins values by the first character of IFS, or by nothing when IFS is empty
+fn join_with_ifs(values: &[String], ctx: &ShellContext) -> String {
+    let ifs = ctx.variables.get("IFS").unwrap_or_else(|| " ".to_string());
+    values.join(&ifs.chars().next().map(String::from).unwrap_or_default())
+}
+
+// Splits `NAME[subscript]` into the array's name and the subscript
+fn split_subscript(name: &str) -> Option<(&str, &str)> {
+    let (array, subscript) = name.strip_suffix(']')?.split_once('[')?;
+    is_name(array).then_some((array, subscript))
+}
+
+// The elements of an array; a variable that isn't one is an array of its value, as in bash
+fn elements(array: &str, ctx: &ShellContext) -> Vec<String> {
+    match ctx.variables.get_array(array) {
+        Some(elements) => elements.to_vec(),
+        None => ctx.variables.get(array).into_iter().collect(),
+    }
+}
+
+// The element at `index`, a number or a variable holding one, counting from the end when it's negative
+fn element(elements: &[String], index: &str, ctx: &ShellContext) -> Option<String> {
+    let index = index.trim();
+    let index = match index.parse::<i64>() {
+        Ok(index) => index,
+        Err(_) => ctx.variables.get(index.strip_prefix('$').unwrap_or(index))?.trim().parse().ok()?,
+    };
+    let index = if index < 0 { elements.len().checked_sub(index.unsigned_abs() as usize)? } else { index as usize };
+    elements.get(index).cloned()
+}
+
+// Whether a double-quoted `segment` makes no word at all: `"$@"` without positional parameters, and
+// `"${NAME[@]}"` for an empty array
+fn makes_no_word(segment: &str, ctx: &ShellContext) -> bool {
+    match segment.strip_prefix("${").and_then(|name| name.strip_suffix('}')) {
+        Some("@") => ctx.positional_params.is_empty(),
+        Some(name) => split_subscript(name).is_some_and(|(array, subscript)| {
+            subscript == "@" && elements(array, ctx).is_empty()
+        }),
+        None => segment == "$@" && ctx.positional_params.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::builtins::ShellContext;
+    use crate::expand::{expand_parameters, expand_words};
+    use crate::system::FakeSystem;
+
+    fn context() -> ShellContext {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.variables.set("name", "rush");
+        ctx.positional_params = (1..=10).map(|i| format!("arg{}", i)).collect();
+        ctx.last_status = 3;
+        ctx
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut ctx = context();
+        assert_eq!(expand_parameters("hello $name!", &mut ctx), "hello rush!");
+        assert_eq!(expand_parameters("${name}shell", &mut ctx), "rushshell");
+        assert_eq!(expand_parameters("[$rush_test_unset_variable]", &mut ctx), "[]");
+    }
+
+    #[test]
+    fn test_special_parameters() {
+        let mut ctx = context();
+        ctx.variables.line_number = 4;
+        assert_eq!(expand_parameters("$? $# $1 ${10}", &mut ctx), "3 10 arg1 arg10");
+        ctx.positional_params.truncate(3);
+        assert_eq!(expand_parameters("[$@] [${*}]", &mut ctx), "[arg1 arg2 arg3] [arg1 arg2 arg3]");
+        assert_eq!(expand_parameters("+ ${LINENO}: ", &mut ctx), "+ 4: ");
+        // no job has been started in the background
+        assert_eq!(expand_parameters("[$!]", &mut ctx), "[]");
+    }
+
+    #[test]
+    fn test_literal_dollar() {
+        let mut ctx = context();
+        assert_eq!(expand_parameters("cost: 5$ $", &mut ctx), "cost: 5$ $");
+        assert_eq!(expand_parameters("${unclosed", &mut ctx), "${unclosed");
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        let mut ctx = context();
+        ctx.variables.set("i", "4");
+        assert_eq!(expand_parameters("$((1+2*3)) $(( i * (i + 1) ))", &mut ctx), "7 20");
+        // parameters expand before the expression is evaluated, and assignments stay made
+        assert_eq!(expand_parameters("$(($# - i++)) $i", &mut ctx), "6 5");
+        // the expression is one word whatever spaces it has, and quotes keep its value from being split
+        ctx.variables.set("IFS", "6");
+        assert_eq!(expand_words("x$(( 3 << 1 ))y \"$(( 16 ))\"", &mut ctx, true).unwrap(), ["x", "y", "16"]);
+        assert_eq!(expand_parameters("$((1+2)", &mut ctx), "$((1+2)");
+        let error = expand_words("$((1/0))", &mut ctx, true).unwrap_err();
+        assert_eq!(error.to_string(), "((: 1/0: division by 0");
+    }
+
+    #[test]
+    fn test_expand_words_by_quoting() {
+        let mut ctx = context();
+        ctx.variables.set("pair", " a  b ");
+        let words = |text: &str, ctx: &mut ShellContext| expand_words(text, ctx, true).unwrap();
+        assert_eq!(words("echo $name \"$name\" '$name' \\$name \"\\$name\"", &mut ctx), ["echo", "rush", "rush", "$name", "$name", "$name"]);
+        // unquoted values are split into words, quoted ones aren't
+        assert_eq!(words("x$pair\"y\" \"$pair\"", &mut ctx), ["x", "a", "b", "y", " a  b "]);
+        // an unquoted parameter that is unset or empty leaves no word, a quoted one leaves an empty word
+        assert_eq!(words("[ $unset \"$unset\" ]", &mut ctx), ["[", "", "]"]);
+        assert_eq!(expand_words("-n $unset", &mut ctx, false).unwrap(), ["-n", ""]);
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let mut ctx = context();
+        ctx.positional_params = vec!["a b".to_string(), String::new()];
+        let words = |text: &str, ctx: &mut ShellContext| expand_words(text, ctx, true).unwrap();
+        // "$@" keeps each parameter a word of its own, "$*" joins them with the first character of IFS
+        assert_eq!(words("x\"$@\"y \"$*\" $@", &mut ctx), ["xa b", "y", "a b ", "a", "b"]);
+        assert_eq!(expand_words("\"$@\"", &mut ctx, false).unwrap(), ["a b "]);
+        ctx.variables.set("IFS", ":");
+        assert_eq!(words("\"$*\"", &mut ctx), ["a b:"]);
+        ctx.positional_params.clear();
+        assert_eq!(words("[ \"$@\" \"${@}\" \"$*\" ]", &mut ctx), ["[", "", "]"]);
+    }
+
+    #[test]
+    fn test_array_elements() {
+        let mut ctx = context();
+        ctx.variables.set_array("parts", vec!["a b".to_string(), "c".to_string(), String::new()]);
+        ctx.variables.set_array("none", Vec::new());
+        ctx.variables.set("i", "1");
+        assert_eq!(expand_parameters("${parts[0]}-${parts[$i]}-${parts[-2]}-[${parts[3]}]", &mut ctx), "a b-c-c-[]");
+        // a variable that isn't an array is one of its value
+        assert_eq!(expand_parameters("${name[0]} [${name[1]}] ${name[@]}", &mut ctx), "rush [] rush");
+        let words = |text: &str, ctx: &mut ShellContext| expand_words(text, ctx, true).unwrap();
+        // "${NAME[@]}" keeps each element a word of its own, like "$@"
+        let expected = ["[", "a b", "c", "", "a", "b", "c", "]"];
+        assert_eq!(words("[ \"${parts[@]}\" ${parts[*]} \"${none[@]}\" ]", &mut ctx), expected);
+        ctx.options.nounset = true;
+        assert_eq!(expand_words("${parts[1]}", &mut ctx, true).unwrap(), ["c"]);
+        assert!(expand_words("${parts[5]}", &mut ctx, true).is_err());
+    }
+
+    #[test]
+    fn test_filename_expansion() {
+        let mut ctx = context();
+        ctx.system = Rc::new(FakeSystem::new().file("/a.rs").file("/b.rs").file("/[x].rs"));
+        ctx.variables.set("pattern", "*.rs");
+        let words = |text: &str, ctx: &mut ShellContext| expand_words(text, ctx, true).unwrap();
+        assert_eq!(words("ls *.rs", &mut ctx), ["ls", "[x].rs", "a.rs", "b.rs"]);
+        assert_eq!(words("echo [ab].rs ?.rs", &mut ctx), ["echo", "a.rs", "b.rs", "a.rs", "b.rs"]);
+        // quoted wildcards are ordinary characters, those in unquoted values aren't
+        assert_eq!(words("echo '*.rs' \\*.rs \"[x]\".rs $pattern", &mut ctx), ["echo", "*.rs", "*.rs", "[x].rs", "[x].rs", "a.rs", "b.rs"]);
+        // a pattern that matches nothing stays, unless that's an error
+        assert_eq!(words("echo *.txt", &mut ctx), ["echo", "*.txt"]);
+        assert_eq!(expand_words("[[ *.rs ]]", &mut ctx, false).unwrap(), ["[[", "*.rs", "]]"]);
+        ctx.options.failglob = true;
+        assert_eq!(expand_words("echo *.txt", &mut ctx, true).unwrap_err().to_string(), "no match: *.txt");
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let mut ctx = context();
+        ctx.set_system(Rc::new(FakeSystem::new().dir("/home").dir("/home/me*").file("/home/me*/a.rs")));
+        ctx.variables.set("HOME", "/home/me*");
+        ctx.variables.set("PWD", "/cwd");
+        let words = |text: &str, ctx: &mut ShellContext| expand_words(text, ctx, true).unwrap();
+        assert_eq!(words("echo ~ ~/*.rs ~+/x ~- a~ '~' ~'/x' \\~ ~rush-no-such-user/x", &mut ctx), [
+            "echo", "/home/me*", "/home/me*/a.rs", "/cwd/x", "~-", "a~", "~", "~/x", "~", "~rush-no-such-user/x",
+        ]);
+        assert_eq!(expand_words("~/x", &mut ctx, false).unwrap(), ["/home/me*/x"]);
+    }
+
+    #[test]
+    fn test_ifs() {
+        let mut ctx = context();
+        ctx.variables.set("IFS", " :");
+        ctx.variables.set("path", "/bin::/usr/bin : /sbin");
+        assert_eq!(expand_words("$path", &mut ctx, true).unwrap(), ["/bin", "", "/usr/bin", "/sbin"]);
+        ctx.variables.set("IFS", "");
+        assert_eq!(expand_words("$path", &mut ctx, true).unwrap(), ["/bin::/usr/bin : /sbin"]);
+    }
+}
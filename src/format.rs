@@ -0,0 +1,283 @@
+//! `rush fmt`: rewrites scripts in one consistent style without changing what they run.
+//!
+//! Every line is parsed and printed back: the bodies of `if` statements and `for` and `while` loops are indented
+//! by four spaces a level and other lines not at all, words are separated by one space, pipeline
+//! stages by ` | ` and the commands of a list by `; `, ` && `, ` || ` or ` & `, a comment follows its code
+//! after one space, and runs of blank lines collapse into one. Quoting is normalized only where that can't
+//! change the meaning, so words containing `$` or backquotes keep theirs, as does the command word, where
+//! quoting suppresses alias expansion, and so do redirections. The bodies of here-documents are kept exactly
+//! as written. A line continued with a backslash is joined with the next, and long lines aren't wrapped.
+
+use std::fmt::Write;
+use crate::control::reserved_words;
+use crate::shell::read_commands;
+use crate::syntax::{check_input, parse_line, Command, Line};
+use crate::tokenizer::{quote, split_lines, tokenize, Connector, SyntaxError};
+
+// One level of indentation in the body of a compound command
+const INDENT: &str = "    ";
+
+/// Formats a script, or returns the syntax errors that keep it from being formatted with their line numbers.
+pub fn format_script(contents: &str) -> Result<String, Vec<(usize, SyntaxError)>> {
+    // each line with whether it is part of a here-document, which is kept exactly as written
+    let mut lines = Vec::new();
+    let mut errors = Vec::new();
+    for command in read_commands(contents) {
+        if command.line == 1 && command.input.starts_with("#!") {
+            lines.push((command.input.trim_end().to_string(), false));
+            continue;
+        }
+        let found = check_input(&command.input);
+        if found.is_empty() {
+            let mut depth = 0;
+            for line in split_lines(&command.input) {
+                let Ok(parsed) = parse_line(line) else {
+                    continue;
+                };
+                // a line goes as deep as its shallowest part: `else` lines up with its `if`
+                let mut indent = depth;
+                for word in reserved_words(line) {
+                    if matches!(word, "elif" | "else" | "fi" | "done") {
+                        depth -= 1;
+                        indent = indent.min(depth);
+                    }
+                    if matches!(word, "then" | "else" | "do") {
+                        depth += 1;
+                    }
+                }
+                let formatted = format_line(&parsed);
+                if formatted.is_empty() {
+                    lines.push((formatted, false));
+                } else {
+                    lines.push((format!("{}{}", INDENT.repeat(indent), formatted), false));
+                }
+            }
+        }
+        errors.extend(found.into_iter().map(|(lines, e)| (command.line + lines, e)));
+        lines.extend(command.here_docs.into_iter().map(|line| (line, true)));
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // blank lines only separate code: none at either end and never two in a row
+    let mut formatted = String::new();
+    let mut blank = false;
+    for (line, here_doc) in lines {
+        if line.is_empty() && !here_doc {
+            blank = !formatted.is_empty();
+            continue;
+        }
+        if blank {
+            formatted.push('\n');
+            blank = false;
+        }
+        formatted.push_str(&line);
+        formatted.push('\n');
+    }
+    Ok(formatted)
+}
+
+/// Prints a parsed line in the standard style.
+pub fn format_line(line: &Line) -> String {
+    let code = format_command(&line.command);
+    match line.comment.map(str::trim_end) {
+        None => code,
+        Some(comment) if code.is_empty() => comment.to_string(),
+        Some(comment) => format!("{} {}", code, comment),
+    }
+}
+
+fn format_command(command: &Command) -> String {
+    match command {
+        Command::Empty => String::new(),
+        Command::Arithmetic(expr) => format!("(({}))", expr),
+        Command::Conditional(words) => {
+            format!("[[ {} ]]", words.iter().map(|word| format_word(word)).collect::<Vec<_>>().join(" "))
+        }
+        Command::Pipeline(stages) => stages
+            .iter()
+            .map(|words| {
+                let mut formatted = vec![words[0].to_string()];
+                for (previous, word) in words.iter().zip(&words[1..]) {
+                    // a redirection and its word keep their quoting, which decides whether a here-document
+                    // is expanded
+                    let redirection = previous.contains(['<', '>']) && previous.ends_with(['<', '>', '&', '-']);
+                    if redirection || word.contains(['<', '>']) {
+                        formatted.push(word.to_string());
+                    } else {
+                        formatted.push(format_word(word));
+                    }
+                }
+                formatted.join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" | "),
+        // `;` directly after its command, the other operators between spaces; none at the end of the line
+        Command::List(commands) => {
+            let mut formatted = String::new();
+            for (i, (command, connector)) in commands.iter().enumerate() {
+                formatted.push_str(&format_command(command));
+                match connector {
+                    Connector::Sequence if i + 1 == commands.len() => {}
+                    Connector::Sequence => formatted.push_str("; "),
+                    Connector::Background if i + 1 == commands.len() => formatted.push_str(" &"),
+                    connector => formatted.push_str(&format!(" {} ", connector.as_str())),
+                }
+            }
+            formatted
+        }
+    }
+}
+
+// Re-quotes a word as written in the source: not at all when it doesn't need it, in single quotes
+// otherwise, or in double quotes when it contains a single quote
+fn format_word(word: &str) -> String {
+    if !word.contains(['\'', '"', '\\']) || word.contains(['$', '`']) {
+        return word.to_string();
+    }
+    // the value of an assignment-like word is quoted on its own: name='a b'
+    if let Some((name, value)) = word.split_once('=')
+        && !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return format!("{}={}", name, format_word(value));
+    }
+    match tokenize(word).as_deref() {
+        Ok([value]) if value.contains('\'') && !value.contains(['"', '\\']) => format!("\"{}\"", value),
+        Ok([value]) => quote(value),
+        _ => word.to_string(),
+    }
+}
+
+/// A unified diff without context lines that turns `old` into `new`, or an empty string when they're
+/// equal.
+pub fn diff(name: &str, old: &str, new: &str) -> String {
+    // a line missing its newline differs from the same line with one
+    let lines = |text: &str| -> Vec<(String, bool)> {
+        text.split_inclusive('\n')
+            .map(|line| match line.strip_suffix('\n') {
+                Some(line) => (line.to_string(), true),
+                None => (line.to_string(), false),
+            })
+            .collect()
+    };
+    let (a, b) = (lines(old), lines(new));
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut out = String::new();
+    let print_line = |out: &mut String, sign: char, (line, newline): &(String, bool)| {
+        let _ = writeln!(out, "{}{}", sign, line);
+        if !newline {
+            out.push_str("\\ No newline at end of file\n");
+        }
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (start_a, start_b) = (i, j);
+        while (i < a.len() || j < b.len()) && !(i < a.len() && j < b.len() && a[i] == b[j]) {
+            if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        if out.is_empty() {
+            let _ = writeln!(out, "--- {}\n+++ {} (formatted)", name, name);
+        }
+        // an empty range starts at the line before it, as in diff -u
+        let range = |start: usize, len: usize| format!("{},{}", if len == 0 { start } else { start + 1 }, len);
+        let _ = writeln!(out, "@@ -{} +{} @@", range(start_a, i - start_a), range(start_b, j - start_b));
+        a[start_a..i].iter().for_each(|line| print_line(&mut out, '-', line));
+        b[start_b..j].iter().for_each(|line| print_line(&mut out, '+', line));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::{diff, format_script};
+
+    #[test]
+    fn test_format_script() {
+        let script = "#!/usr/bin/env rush  \n\n\n   echo   \"hello\"  'a b'|wc   -l   # count  \n\n\n\n((  x = 1  ))\n[[  -n \"x\"  ]]\n\n";
+        let formatted = "#!/usr/bin/env rush\n\necho hello 'a b' | wc -l # count\n\n((x = 1))\n[[ -n x ]]\n";
+        assert_eq!(format_script(script).unwrap(), formatted);
+        assert_eq!(format_script(formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_quoting_that_matters_is_kept() {
+        let format = |line: &str| format_script(line).unwrap();
+        assert_eq!(format("'ls' \"-l\"\n"), "'ls' -l\n");
+        assert_eq!(format("echo \"$HOME\" \"`pwd`\" \\$x\n"), "echo \"$HOME\" \"`pwd`\" \\$x\n");
+        assert_eq!(format("echo \"it's\" \"a\\\"b\" x=\"1 2\" '~' *.rs\n"), "echo \"it's\" 'a\"b' x='1 2' '~' *.rs\n");
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(format_script("make&&  ./run ||echo failed ;echo done &\n").unwrap(), "make && ./run || echo failed; echo done &\n");
+        assert_eq!(format_script("a & b;\n").unwrap(), "a & b\n");
+    }
+
+    #[test]
+    fn test_here_documents() {
+        let script = "cat <<'EOF' >\"out 1\"\n  $x  'q'\n\n\nEOF\ncat <<- \"E\" 2>'e'\n\t echo   'x'\n\tE\n";
+        assert_eq!(format_script(script).unwrap(), script);
+        let errors = format_script("cat <<E\necho 'open\nE\necho 'open\n").unwrap_err();
+        assert_eq!(errors.iter().map(|(line, e)| (*line, e.column)).collect::<Vec<_>>(), vec![(4, 6)]);
+    }
+
+    #[test]
+    fn test_continued_lines() {
+        assert_eq!(format_script("echo  \"a\n  b\"  c\n").unwrap(), "echo 'a\n  b' c\n");
+        assert_eq!(format_script("echo a \\\n   b\n").unwrap(), "echo a b\n");
+        let errors = format_script("echo \"a\nb\" 'c\n").unwrap_err();
+        assert_eq!(errors.iter().map(|(line, e)| (*line, e.column)).collect::<Vec<_>>(), vec![(2, 4)]);
+    }
+
+    #[test]
+    fn test_compound_commands() {
+        let script = concat!(
+            "for f in *.rs;do\nif [[ -n $f ]]\n  then  echo \"$f\"\n# other\n",
+            "elif x;then\n:\nelse  echo;fi\ndone\n",
+        );
+        let formatted = concat!(
+            "for f in *.rs; do\n    if [[ -n $f ]]\n    then echo \"$f\"\n        # other\n",
+            "    elif x; then\n        :\n    else echo; fi\ndone\n",
+        );
+        assert_eq!(format_script(script).unwrap(), formatted);
+        assert_eq!(format_script(formatted).unwrap(), formatted);
+        assert_eq!(format_script("if true; then echo yes; fi\n").unwrap(), "if true; then echo yes; fi\n");
+        let formatted = "while ((i < 3))\ndo\n    echo $(( i * 2 ))\n    ((i++))\ndone\n";
+        assert_eq!(format_script("while ((i < 3))\ndo\necho $(( i * 2 ))\n  ((i++))\ndone\n").unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let errors = format_script("echo ok\necho 'open\n").unwrap_err();
+        assert_eq!(errors.iter().map(|(line, e)| (*line, e.column)).collect::<Vec<_>>(), vec![(2, 6)]);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("s", "a\nb\n", "a\nb\n"), "");
+        assert_eq!(diff("s", "a\n b\nc\n", "a\nb\nc\n"), "--- s\n+++ s (formatted)\n@@ -2,1 +2,1 @@\n- b\n+b\n");
+        assert_eq!(diff("s", "a\n\n", "a\n"), "--- s\n+++ s (formatted)\n@@ -2,1 +1,0 @@\n-\n");
+        assert_eq!(diff("s", "a", "a\n"), "--- s\n+++ s (formatted)\n@@ -1,1 +1,1 @@\n-a\n\\ No newline at end of file\n+a\n");
+    }
+}
@@ -0,0 +1,167 @@
+//! Filename expansion: a word with unquoted `*`, `?` or `[...]` in it is a pattern that expands to the names
+//! of the files it matches.
+//!
+//! Patterns come with the characters that were quoted escaped by a backslash, so they match only
+//! themselves. A pattern matches one path component at a time: no wildcard matches a `/`, nor the `.` that
+//! starts the name of a hidden file unless the pattern starts with one too. Matches are sorted like `ls`
+//! sorts names.
+
+use std::path::Path;
+use crate::locale;
+use crate::system::System;
+
+/// Whether `pattern` has any wildcards outside escapes. A `[` without a closing `]` is no wildcard.
+pub fn has_wildcards(pattern: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            '\\' => i += 1,
+            '*' | '?' => return true,
+            '[' if bracket(&pattern[i + 1..], '\0').is_some() => return true,
+            _ => {}
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Whether `name` matches all of `pattern`.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            (0..=name.len()).any(|i| matches_from(rest, &name[i..]))
+        }
+        Some('?') => !name.is_empty() && matches_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            match bracket(&pattern[1..], c) {
+                Some((matched, len)) => matched && matches_from(&pattern[1 + len..], &name[1..]),
+                // an ordinary `[`
+                None => c == '[' && matches_from(&pattern[1..], &name[1..]),
+            }
+        }
+        Some('\\') if pattern.len() > 1 => name.first() == Some(&pattern[1]) && matches_from(&pattern[2..], &name[1..]),
+        Some(c) => name.first() == Some(c) && matches_from(&pattern[1..], &name[1..]),
+    }
+}
+
+// Matches `c` against the bracket expression that starts `pattern`, just after its `[`: characters and
+// ranges like `a-z`, all but them after a leading `!` or `^`, with a `]` first in the list taken literally.
+// Returns whether `c` matched and how long the expression is up to its `]`, or None without a `]`.
+fn bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let start = i;
+    let mut matched = false;
+    // an escaped character stands for itself
+    let next = |i: &mut usize| -> Option<char> {
+        if pattern.get(*i) == Some(&'\\') {
+            *i += 1;
+        }
+        let c = *pattern.get(*i)?;
+        *i += 1;
+        Some(c)
+    };
+    loop {
+        if pattern.get(i) == Some(&']') && i > start {
+            return Some((matched != negated, i + 1));
+        }
+        let low = next(&mut i)?;
+        let mut high = low;
+        if pattern.get(i) == Some(&'-') && pattern.get(i + 1).is_some_and(|&c| c != ']') {
+            i += 1;
+            high = next(&mut i)?;
+        }
+        matched |= low <= c && c <= high;
+    }
+}
+
+/// The paths `pattern` matches, sorted; none when nothing does. A relative pattern matches relative paths.
+pub fn expand(pattern: &str, system: &dyn System) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+    for component in rest.split('/') {
+        let mut next = Vec::new();
+        for path in &paths {
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let Ok(names) = system.read_dir(Path::new(dir)) else {
+                continue;
+            };
+            if component.is_empty() {
+                // a trailing `/` matches directories only, which could be listed
+                next.push(format!("{}/", path.trim_end_matches('/')));
+                continue;
+            }
+            let prefix = if path.is_empty() || path.ends_with('/') { path.clone() } else { format!("{}/", path) };
+            let hidden = component.starts_with('.') || component.starts_with("\\.");
+            for name in names {
+                if (hidden || !name.starts_with('.')) && matches(component, &name) {
+                    next.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+        paths = next;
+    }
+    paths.sort_by(|a, b| locale::compare(a, b));
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glob::{expand, has_wildcards, matches};
+    use crate::system::FakeSystem;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("?a*", "bar"));
+        assert!(!matches("?", ""));
+        assert!(matches("[a-c]x", "bx") && !matches("[a-c]x", "dx"));
+        assert!(matches("[!a-c]x", "dx") && matches("[^a]x", "bx") && !matches("[!a-c]x", "ax"));
+        assert!(matches("[]]", "]") && matches("[a-]", "-"));
+        assert!(matches("\\*", "*") && !matches("\\*", "a"));
+        // without its `]` a `[` is an ordinary character
+        assert!(matches("[a", "[a"));
+    }
+
+    #[test]
+    fn test_has_wildcards() {
+        assert!(has_wildcards("*.rs") && has_wildcards("a?") && has_wildcards("[ab]"));
+        assert!(!has_wildcards("\\*.rs") && !has_wildcards("[a") && !has_wildcards("plain"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let system = FakeSystem::new()
+            .dir("/src")
+            .dir("/src/builtins")
+            .file("/src/main.rs")
+            .file("/src/lib.rs")
+            .file("/src/.hidden.rs")
+            .file("/src/builtins/cd.rs")
+            .file("/README.md");
+        assert_eq!(expand("src/*.rs", &system), ["src/lib.rs", "src/main.rs"]);
+        assert_eq!(expand("/src/.*", &system), ["/src/.hidden.rs"]);
+        assert_eq!(expand("*/*/*.rs", &system), ["src/builtins/cd.rs"]);
+        assert_eq!(expand("*/", &system), ["src/"]);
+        assert_eq!(expand("*.txt", &system), Vec::<String>::new());
+    }
+}
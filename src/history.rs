@@ -0,0 +1,509 @@
+//! The history file: the lines entered at the prompt, oldest first.
+//!
+//! Entries are stored as length-prefixed records after a short header, so that a history of 100k entries
+//! opens without being read: the file is memory-mapped, the record offsets are indexed on first use, and
+//! searches compare bytes in the mapping without copying entries out. Writing only ever appends whole
+//! records under a lock on the file, so shells sharing the file don't overwrite each other's entries, and the
+//! file is never truncated or replaced while they use it: the oldest entries past those the shell keeps are
+//! only left out in memory. A record that a shell died while writing is filled in with zeros by the next
+//! append, which marks it as one to leave out. A plain-text history with one entry per line is converted once,
+//! the first time it's opened.
+//!
+//! [`expand`] replaces the `!` references to entries in a line entered at the prompt.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use memchr::memmem;
+
+const HEADER: &[u8] = b"rush history 1\n";
+
+// The file contents: mapped where the platform can, read otherwise
+#[cfg(not(target_arch = "wasm32"))]
+type Contents = memmap2::Mmap;
+#[cfg(target_arch = "wasm32")]
+type Contents = Vec<u8>;
+
+pub struct History {
+    path: PathBuf,
+    contents: Option<Contents>,
+    // the file `contents` was read from, to tell whether it has been replaced since
+    metadata: Option<Metadata>,
+    index: OnceCell<Index>,
+    // entries added since the file was opened
+    added: Vec<String>,
+    // how many of the oldest entries are left out
+    skipped: usize,
+    file: Option<File>,
+    // where the records in `file` are known to end, 0 until that's been found
+    end: u64,
+    // set once the file couldn't be opened for appending
+    unwritable: bool,
+}
+
+// The records in the file's contents
+struct Index {
+    // the start and length of every complete record, leaving out those filled in for a torn one
+    records: Vec<(usize, usize)>,
+    // where the last complete record ends
+    end: usize,
+}
+
+impl History {
+    /// Opens the history file at `path`, converting it first if it's plain text. A missing file is an empty
+    /// history; it's created by the first `add`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(History::new(path, None, None)),
+            Err(e) => return Err(e),
+        };
+        let contents = read_contents(&file)?;
+        if !contents.is_empty() && !contents.starts_with(HEADER) {
+            drop(contents);
+            convert(path)?;
+            return History::open(path);
+        }
+        Ok(History::new(path, Some(contents), Some(file.metadata()?)))
+    }
+
+    fn new(path: &Path, contents: Option<Contents>, metadata: Option<Metadata>) -> Self {
+        History {
+            path: path.to_path_buf(),
+            contents,
+            metadata,
+            index: OnceCell::new(),
+            added: Vec::new(),
+            skipped: 0,
+            file: None,
+            end: 0,
+            unwritable: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records().len() + self.added.len() - self.skipped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entry at `index`, counting from the oldest.
+    pub fn get(&self, index: usize) -> Option<Cow<'_, str>> {
+        let index = index + self.skipped;
+        let records = self.records();
+        match records.get(index) {
+            Some(&(start, len)) => Some(String::from_utf8_lossy(&self.bytes()[start..start + len])),
+            None => self.added.get(index - records.len()).map(|entry| Cow::Borrowed(entry.as_str())),
+        }
+    }
+
+    /// The index of the newest entry before `before` that contains `query`, for searching back through the
+    /// history one match at a time.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let finder = memmem::Finder::new(query.as_bytes());
+        let records = self.records();
+        let (skipped, before) = (self.skipped, before.min(self.len()) + self.skipped);
+        let matches = |entry: &[u8]| finder.find(entry).is_some();
+        let added = records.len().max(skipped)..before;
+        if let Some(i) = added.rev().find(|&i| matches(self.added[i - records.len()].as_bytes())) {
+            return Some(i - skipped);
+        }
+        let bytes = self.bytes();
+        let start = skipped.min(records.len());
+        records[start..before.min(records.len())]
+            .iter()
+            .rposition(|&(start, len)| matches(&bytes[start..start + len]))
+            .map(|i| i + start - skipped)
+    }
+
+    /// Appends an entry to the history and its file. The entry is remembered even when writing it fails;
+    /// once the file can't be opened, entries are only kept in memory without further errors.
+    pub fn add(&mut self, entry: &str) -> io::Result<()> {
+        self.added.push(entry.to_string());
+        if self.unwritable {
+            return Ok(());
+        }
+        // a file replaced since it was opened for appending would take the entries nowhere anyone reads
+        if let Some(file) = &self.file
+            && !is_file_at(file, &self.path)
+        {
+            self.file = None;
+        }
+        if self.file.is_none() {
+            let file = self.open_for_append().inspect_err(|_| self.unwritable = true)?;
+            self.file = Some(file);
+        }
+        let mut record = Vec::with_capacity(4 + entry.len());
+        push_record(&mut record, entry);
+        // the lock keeps other shells from appending between finding where the records end and writing
+        let file = self.file.as_ref().unwrap();
+        let locked = file.lock().is_ok();
+        let result = append(file, &mut self.end, &record);
+        if locked {
+            let _ = file.unlock();
+        }
+        result
+    }
+
+    /// Leaves out all but the newest `keep` entries. The file keeps them, for other shells sharing it.
+    pub fn keep_newest(&mut self, keep: usize) {
+        self.skipped += self.len().saturating_sub(keep);
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.contents.as_deref().unwrap_or_default()
+    }
+
+    fn records(&self) -> &[(usize, usize)] {
+        &self.index.get_or_init(|| index(self.bytes())).records
+    }
+
+    fn open_for_append(&mut self) -> io::Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+        // appending goes on after the records already read, unless the file has been replaced since
+        self.end = match &self.metadata {
+            Some(metadata) if same_file(metadata, &file.metadata()?) => {
+                self.index.get_or_init(|| index(self.bytes())).end as u64
+            }
+            _ => 0,
+        };
+        Ok(file)
+    }
+}
+
+// Appends a record to `file` after the records in it, which end at `end` or, when that's 0, are yet to be
+// found, and moves `end` past it. The caller holds the file's lock.
+fn append(mut file: &File, end: &mut u64, record: &[u8]) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        file.write_all(HEADER)?;
+        *end = HEADER.len() as u64;
+    } else if *end == 0 || *end > len {
+        *end = HEADER.len() as u64;
+    }
+    if *end < len {
+        // what other shells appended since, which may end with a record one of them died while writing
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(*end))?;
+        file.read_to_end(&mut tail)?;
+        let torn = &tail[walk(&tail, 0).end..];
+        let filler = filler(torn);
+        file.write_all(&filler)?;
+        *end += (tail.len() + filler.len()) as u64;
+    }
+    // one write per record keeps records whole should a shell that doesn't lock the file append at once
+    file.write_all(record)?;
+    *end += record.len() as u64;
+    Ok(())
+}
+
+// The zeros that complete a record a shell died while writing, so that the next one starts where a record
+// would; the zeros mark the record as one to leave out
+fn filler(torn: &[u8]) -> Vec<u8> {
+    if torn.is_empty() {
+        return Vec::new();
+    }
+    let mut prefix = [0; 4];
+    let known = torn.len().min(4);
+    prefix[..known].copy_from_slice(&torn[..known]);
+    let len = u32::from_le_bytes(prefix) as usize;
+    vec![0; 4 - known + len - (torn.len() - known)]
+}
+
+// Whether `file` is still the one at `path`
+fn is_file_at(file: &File, path: &Path) -> bool {
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => same_file(&open, &current),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+// other platforms don't replace a file that's open
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
+
+/// Replaces the references to history entries in a line entered at the prompt: `!!` is the last entry, `!n`
+/// entry `n` as `history` numbers them, `!-n` the `n`th entry back and `!prefix` the newest entry starting with
+/// `prefix`. A `!` that is quoted with single quotes or escaped, that comes before a blank, `=` or `(`, or
+/// that is part of `$!` stays as it is. Returns None when the line has no references, and the first reference
+/// that matches no entry as the error.
+pub fn expand(line: &str, history: &History) -> Result<Option<String>, String> {
+    let mut expanded = String::with_capacity(line.len());
+    let mut found = false;
+    let (mut in_single, mut in_double, mut escaped) = (false, false, false);
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if escaped {
+            escaped = false;
+        } else if in_single {
+            in_single = c != '\'';
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '\'' && !in_double {
+            in_single = true;
+        } else if c == '"' {
+            in_double = !in_double;
+        } else if c == '!'
+            && !expanded.ends_with('$')
+            && !expanded.ends_with("${")
+            && let Some(reference) = reference(&line[i + 1..])
+        {
+            let entry = find_entry(reference, history).ok_or_else(|| format!("!{}", reference))?;
+            expanded.push_str(&entry);
+            found = true;
+            // skips the rest of the reference
+            chars.nth(reference.chars().count() - 1);
+            continue;
+        }
+        expanded.push(c);
+    }
+    Ok(found.then_some(expanded))
+}
+
+// The reference a `!` starts, which `rest` follows: `!`, or the word up to a blank or an operator
+fn reference(rest: &str) -> Option<&str> {
+    if rest.starts_with('!') {
+        return Some(&rest[..1]);
+    }
+    let len = rest.find(|c: char| c.is_whitespace() || ";&|<>()'\"".contains(c)).unwrap_or(rest.len());
+    (len > 0 && !rest.starts_with('=')).then(|| &rest[..len])
+}
+
+// The entry a reference without its `!` names
+fn find_entry<'a>(reference: &str, history: &'a History) -> Option<Cow<'a, str>> {
+    let len = history.len();
+    let index = if reference == "!" {
+        len.checked_sub(1)
+    } else if let Ok(n) = reference.parse::<usize>() {
+        n.checked_sub(1)
+    } else if let Some(Ok(n)) = reference.strip_prefix('-').map(str::parse::<usize>) {
+        len.checked_sub(n).filter(|_| n > 0)
+    } else {
+        (0..len).rev().find(|&i| history.get(i).is_some_and(|entry| entry.starts_with(reference)))
+    };
+    index.and_then(|i| history.get(i))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_contents(file: &File) -> io::Result<Contents> {
+    // Safety: rush only ever appends to the file, so the mapped bytes don't change
+    unsafe { memmap2::Mmap::map(file) }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_contents(mut file: &File) -> io::Result<Contents> {
+    let mut contents = Vec::new();
+    io::Read::read_to_end(&mut file, &mut contents)?;
+    Ok(contents)
+}
+
+// Finds the records in the file's contents
+fn index(bytes: &[u8]) -> Index {
+    let index = walk(bytes, HEADER.len());
+    tracing::debug!(entries = index.records.len(), "history index");
+    index
+}
+
+// Finds the records in `bytes` from `pos` on, up to a torn one at the end. Records filled in for a torn one
+// hold zeros, and are left out along with empty ones, which no entry makes.
+fn walk(bytes: &[u8], mut pos: usize) -> Index {
+    let mut records = Vec::new();
+    while let Some(prefix) = bytes.get(pos..pos + 4) {
+        let len = u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+        if pos + 4 + len > bytes.len() {
+            break;
+        }
+        let entry = &bytes[pos + 4..pos + 4 + len];
+        if !entry.is_empty() && !entry.contains(&0) {
+            records.push((pos + 4, len));
+        }
+        pos += 4 + len;
+    }
+    Index { records, end: pos.min(bytes.len()) }
+}
+
+fn push_record(buffer: &mut Vec<u8>, entry: &str) {
+    buffer.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(entry.as_bytes());
+}
+
+// Replaces the file at `path` with one holding `entries`, all at once
+fn write_records<'a>(path: &Path, entries: impl Iterator<Item = &'a str>) -> io::Result<()> {
+    let mut contents = HEADER.to_vec();
+    entries.for_each(|entry| push_record(&mut contents, entry));
+    let tmp = path.with_extension("rewriting");
+    fs::write(&tmp, &contents)?;
+    fs::rename(&tmp, path)
+}
+
+// Rewrites a plain-text history file, one entry per line, as records
+fn convert(path: &Path) -> io::Result<()> {
+    let text = fs::read(path)?;
+    let text = String::from_utf8_lossy(&text);
+    write_records(path, text.lines().filter(|line| !line.trim().is_empty()))?;
+    tracing::info!(path = %path.display(), "converted plain-text history");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use crate::history::{expand, History};
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rush-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_add_and_reopen() {
+        let path = temp_file("reopen");
+        fs::write(&path, "").unwrap();
+        let mut history = History::open(&path).unwrap();
+        assert!(history.is_empty());
+        history.add("ls -l").unwrap();
+        history.add("echo 'a\nb'").unwrap();
+        assert_eq!(history.get(1).as_deref(), Some("echo 'a\nb'"));
+
+        let mut history = History::open(&path).unwrap();
+        history.add("git status").unwrap();
+        let entries: Vec<String> = (0..history.len()).map(|i| history.get(i).unwrap().into_owned()).collect();
+        assert_eq!(entries, vec!["ls -l", "echo 'a\nb'", "git status"]);
+        assert_eq!(history.get(3), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_search() {
+        let path = temp_file("search");
+        let mut history = History::open(&path).unwrap();
+        for entry in ["git log", "ls", "git status"] {
+            history.add(entry).unwrap();
+        }
+        let mut history = History::open(&path).unwrap();
+        history.add("echo git").unwrap();
+        assert_eq!(history.search("git", history.len()), Some(3));
+        assert_eq!(history.search("git", 3), Some(2));
+        assert_eq!(history.search("git", 2), Some(0));
+        assert_eq!(history.search("git", 0), None);
+        assert_eq!(history.search("svn", 4), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_plain_text_is_converted() {
+        let path = temp_file("plain");
+        fs::write(&path, "ls\n\ncd /tmp\n").unwrap();
+        let mut history = History::open(&path).unwrap();
+        history.add("pwd").unwrap();
+        let history = History::open(&path).unwrap();
+        assert_eq!((0..3).map(|i| history.get(i).unwrap().into_owned()).collect::<Vec<_>>(), vec!["ls", "cd /tmp", "pwd"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_keep_newest() {
+        let path = temp_file("keep");
+        let mut history = History::open(&path).unwrap();
+        for entry in ["one", "two", "three"] {
+            history.add(entry).unwrap();
+        }
+        history.keep_newest(5);
+        assert_eq!(history.len(), 3);
+        history.keep_newest(2);
+        history.add("four").unwrap();
+        let entries =
+            |history: &History| (0..history.len()).map(|i| history.get(i).unwrap().into_owned()).collect::<Vec<_>>();
+        assert_eq!(entries(&history), vec!["two", "three", "four"]);
+        assert_eq!((history.search("o", 3), history.search("o", 1)), (Some(2), Some(0)));
+        assert_eq!(history.search("one", 3), None);
+        // the file keeps every entry
+        assert_eq!(entries(&History::open(&path).unwrap()), vec!["one", "two", "three", "four"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shared_file() {
+        let path = temp_file("shared");
+        let mut first = History::open(&path).unwrap();
+        first.add("a1").unwrap();
+        let mut second = History::open(&path).unwrap();
+        second.keep_newest(1);
+        second.add("b1").unwrap();
+        first.add("a2").unwrap();
+        let history = History::open(&path).unwrap();
+        let entries: Vec<_> = (0..history.len()).map(|i| history.get(i).unwrap().into_owned()).collect();
+        assert_eq!(entries, vec!["a1", "b1", "a2"]);
+
+        // a file replaced under a shell gets its entries from then on
+        let replaced = temp_file("replaced");
+        History::open(&replaced).unwrap().add("c1").unwrap();
+        fs::rename(&replaced, &path).unwrap();
+        first.add("a3").unwrap();
+        let history = History::open(&path).unwrap();
+        assert_eq!((history.len(), history.get(1).as_deref()), (2, Some("a3")));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_torn_record_is_dropped() {
+        let path = temp_file("torn");
+        History::open(&path).unwrap().add("ls").unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        contents.extend_from_slice(&[9, 0, 0, 0, b'e']);
+        fs::write(&path, contents).unwrap();
+
+        let mut history = History::open(&path).unwrap();
+        assert_eq!(history.len(), 1);
+        history.add("pwd").unwrap();
+        // the torn record is filled in rather than cut off, which other shells may be appending after
+        assert_eq!(fs::metadata(&path).unwrap().len(), 15 + 6 + 13 + 7);
+        let mut history = History::open(&path).unwrap();
+        assert_eq!((history.len(), history.get(1).as_deref()), (2, Some("pwd")));
+
+        // a record torn inside its length too
+        let mut contents = fs::read(&path).unwrap();
+        contents.extend_from_slice(&[2, 0]);
+        fs::write(&path, contents).unwrap();
+        history.add("cd").unwrap();
+        let history = History::open(&path).unwrap();
+        assert_eq!((history.len(), history.get(2).as_deref()), (3, Some("cd")));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expand() {
+        let path = temp_file("expand");
+        let mut history = History::open(&path).unwrap();
+        for entry in ["git log", "ls -l", "git status", "echo hi"] {
+            history.add(entry).unwrap();
+        }
+        assert_eq!(expand("!! | wc", &history), Ok(Some("echo hi | wc".to_string())));
+        assert_eq!(expand("!1; !-3", &history), Ok(Some("git log; ls -l".to_string())));
+        assert_eq!(expand("sudo !git", &history), Ok(Some("sudo git status".to_string())));
+        for line in ["echo '!!' \\!! ! x", "[[ a != b ]]", "echo $! ${!name}", "echo hi!"] {
+            assert_eq!(expand(line, &history), Ok(None), "{}", line);
+        }
+        assert_eq!(expand("echo \"!!\"", &history), Ok(Some("echo \"echo hi\"".to_string())));
+        assert_eq!(expand("!svn", &history), Err("!svn".to_string()));
+        assert_eq!(expand("!9", &history), Err("!9".to_string()));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Ctrl-C in an interactive shell: it interrupts what runs in the foreground, never the shell itself.
+//!
+//! At the prompt it drops the line being typed; while a program runs it stops that program and the shell
+//! goes on with the next command. On Unix the programs an interactive shell waits for run in a process group
+//! of their own that the terminal sends SIGINT to; the shell catches SIGINT sent to it instead of dying from
+//! it and passes it on to them. On Windows a console control handler takes care of this:
+//! the programs the shell starts run in a job object, which Ctrl-C terminates along with anything they
+//! started in turn.
+//!
+//! Signals with a trap set are caught in any shell: their arrival is only noted here, and the shell runs the
+//! traps between commands.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// the signals that have a trap, and those of them that arrived since their traps last ran, a bit per number
+static TRAPPED: AtomicU64 = AtomicU64::new(0);
+static CAUGHT: AtomicU64 = AtomicU64::new(0);
+// the signals a handler is registered for; handlers stay once registered
+#[cfg(unix)]
+static HANDLED: AtomicU64 = AtomicU64::new(0);
+// whether `install` set up Ctrl-C handling, which keeps SIGINT from ending the shell without a trap
+#[cfg(unix)]
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+// the process group of the programs in the foreground, which gets the interrupts the shell does; 0 for none
+#[cfg(unix)]
+static FOREGROUND: AtomicI32 = AtomicI32::new(0);
+
+/// Whether Ctrl-C was pressed since the last call.
+pub fn take() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(windows)]
+pub use console::{install, track};
+
+/// Sets up Ctrl-C handling for an interactive shell.
+#[cfg(unix)]
+pub fn install() {
+    INSTALLED.store(true, Ordering::SeqCst);
+    // a handler, unlike ignoring the signal, is reset for the programs the shell starts. Safety: it only
+    // uses atomics and kill, which are async-signal-safe
+    let handler = || {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        let group = FOREGROUND.load(Ordering::SeqCst);
+        if group > 0 {
+            // a negative ID signals the whole process group
+            unsafe { libc::kill(-group, libc::SIGINT) };
+        }
+    };
+    if let Err(e) = unsafe { signal_hook::low_level::register(signal_hook::consts::SIGINT, handler) } {
+        tracing::debug!(error = %e, "SIGINT handler");
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
+
+/// Starts or stops catching `signal` for its trap. While trapped, the signal no longer has its usual effect,
+/// like ending the shell, but is noted for `take_caught`.
+pub fn catch(signal: i32, trapped: bool) {
+    let bit = 1 << signal;
+    if !trapped {
+        TRAPPED.fetch_and(!bit, Ordering::SeqCst);
+        return;
+    }
+    #[cfg(unix)]
+    if HANDLED.fetch_or(bit, Ordering::SeqCst) & bit == 0 {
+        // Safety: it only uses atomics and signal-hook's default emulation, which are async-signal-safe
+        let handler = move || {
+            if TRAPPED.load(Ordering::SeqCst) & bit != 0 {
+                CAUGHT.fetch_or(bit, Ordering::SeqCst);
+            } else if !(signal == libc::SIGINT && INSTALLED.load(Ordering::SeqCst)) {
+                // the trap is gone, so the signal does what it would without one
+                let _ = signal_hook::low_level::emulate_default_handler(signal);
+            }
+        };
+        if let Err(e) = unsafe { signal_hook::low_level::register(signal, handler) } {
+            tracing::debug!(signal, error = %e, "trap handler");
+        }
+    }
+    // Ctrl-C is the only signal a Windows console delivers, through the console control handler
+    #[cfg(windows)]
+    if signal == 2 {
+        install();
+    }
+    TRAPPED.fetch_or(bit, Ordering::SeqCst);
+}
+
+/// The signals caught for their traps since the last call, by number.
+pub fn take_caught() -> Vec<i32> {
+    let caught = CAUGHT.swap(0, Ordering::SeqCst);
+    (1..64).filter(|signal| caught & (1 << signal) != 0).collect()
+}
+
+/// Passes the interrupts the shell gets on to a process group, or to nobody with None.
+#[cfg(unix)]
+pub fn forward_to(group: Option<u32>) {
+    FOREGROUND.store(group.map_or(0, |group| group as i32), Ordering::SeqCst);
+    // an interrupt that came while the group's programs were starting is theirs too
+    if let Some(group) = group
+        && INTERRUPTED.load(Ordering::SeqCst)
+    {
+        unsafe { libc::kill(-(group as i32), libc::SIGINT) };
+    }
+}
+
+/// Makes a program the shell started one that Ctrl-C interrupts. On Unix the terminal already does.
+#[cfg(not(windows))]
+pub fn track(_child: &std::process::Child) {}
+
+#[cfg(windows)]
+mod console {
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::OnceLock;
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT};
+    use windows_sys::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject};
+    use crate::interrupt::{CAUGHT, INTERRUPTED, TRAPPED};
+
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    // the job holding the programs started in the foreground, as an address so threads can share it; 0 when
+    // it couldn't be created
+    static JOB: OnceLock<usize> = OnceLock::new();
+
+    // Runs on a thread of its own when a console event arrives; returning true keeps the shell alive
+    unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+        match ctrl_type {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT => {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+                // SIGINT's number on Unix, which names Ctrl-C for `trap` everywhere
+                if TRAPPED.load(Ordering::SeqCst) & (1 << 2) != 0 {
+                    CAUGHT.fetch_or(1 << 2, Ordering::SeqCst);
+                }
+                if let Some(&job) = JOB.get()
+                    && job != 0
+                {
+                    // programs that ignore Ctrl-C end too, with the status an interrupted program has on Unix
+                    unsafe { TerminateJobObject(job as HANDLE, 130) };
+                }
+                1
+            }
+            // closing the console or logging off ends the shell as usual
+            _ => 0,
+        }
+    }
+
+    pub fn install() {
+        if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } != 0 {
+            INSTALLED.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn track(child: &Child) {
+        if !INSTALLED.load(Ordering::SeqCst) {
+            return;
+        }
+        let job = *JOB.get_or_init(|| unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) } as usize);
+        if job != 0 {
+            unsafe { AssignProcessToJobObject(job as HANDLE, child.as_raw_handle() as HANDLE) };
+        }
+    }
+}
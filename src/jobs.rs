@@ -0,0 +1,437 @@
+//! Jobs: pipelines started in the background with `&`, which the shell keeps track of until they finish.
+//!
+//! A job's programs run in a process group of their own, so Ctrl-C at the terminal doesn't reach them, with
+//! stdin from /dev/null, so they don't compete with the prompt for input. Jobs are numbered from 1 in the
+//! order they start; the most recent one is the current job that `fg` and `bg` act on by default.
+//!
+//! An interactive shell on Unix leads a process group of its own too. Ctrl+Z stops the programs it waits
+//! for, which then become a stopped job, while the shell itself carries on at the prompt.
+
+use std::io;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+#[cfg(unix)]
+use crate::interrupt;
+use crate::system::Process;
+
+// the terminal's foreground process group before the shell took a group of its own, 0 if it didn't
+#[cfg(unix)]
+static PREVIOUS_FOREGROUND: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    /// Exited with the status of the job's last program
+    Done(i32),
+    /// The job's last program was killed by the signal with this number
+    Killed(i32),
+}
+
+pub struct Job {
+    pub id: usize,
+    /// The command line as typed, without the `&`
+    pub command: String,
+    pub state: JobState,
+    // the job's programs in pipeline order, with their statuses once they've exited
+    processes: Vec<(Box<dyn Process>, Option<i32>)>,
+}
+
+impl Job {
+    /// The process IDs of the job's programs; the first one leads its process group.
+    pub fn pids(&self) -> Vec<u32> {
+        self.processes.iter().map(|(process, _)| process.id()).collect()
+    }
+
+    /// Waits for all the job's programs to exit and returns the job's status.
+    pub fn wait(&mut self) -> io::Result<i32> {
+        for (process, status) in &mut self.processes {
+            if status.is_none() {
+                *status = Some(process.wait()?);
+            }
+        }
+        Ok(self.finish())
+    }
+
+    /// Waits for all the job's programs to exit, or for the job to stop, as Ctrl+Z stops it. Returns the job's
+    /// status, or None when it stopped.
+    pub fn wait_or_stop(&mut self) -> io::Result<Option<i32>> {
+        for (process, status) in &mut self.processes {
+            if status.is_none() {
+                match process.wait_or_stop()? {
+                    Some(exited) => *status = Some(exited),
+                    None => {
+                        self.state = JobState::Stopped;
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        Ok(Some(self.finish()))
+    }
+
+    // Checks without waiting whether the job's programs have exited
+    fn poll(&mut self) -> io::Result<()> {
+        for (process, status) in &mut self.processes {
+            if status.is_none() {
+                *status = process.try_wait()?;
+            }
+        }
+        if self.processes.iter().all(|(_, status)| status.is_some()) {
+            self.finish();
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> i32 {
+        let last = self.processes.last();
+        let status = last.and_then(|(_, status)| *status).unwrap_or(0);
+        self.state = match last.and_then(|(process, _)| process.signal()) {
+            Some(signal) => JobState::Killed(signal),
+            None => JobState::Done(status),
+        };
+        status
+    }
+
+    /// The job as `jobs` lists it, e.g. `[2]+  Running                 sleep 10 &`. `marker` is `+` for the
+    /// current job, `-` for the previous one and a space for the others.
+    pub fn describe(&self, marker: char) -> String {
+        let (state, suffix) = match self.state {
+            JobState::Running => ("Running".to_string(), " &"),
+            JobState::Stopped => ("Stopped".to_string(), ""),
+            JobState::Done(0) => ("Done".to_string(), ""),
+            JobState::Done(status) => (format!("Exit {}", status), ""),
+            JobState::Killed(signal) => (signal_description(signal), ""),
+        };
+        format!("[{}]{}  {:<24}{}{}", self.id, marker, state, self.command, suffix)
+    }
+}
+
+/// The shell's jobs table.
+#[derive(Default)]
+pub struct Jobs {
+    // oldest first, so the last job is the current one
+    jobs: Vec<Job>,
+    last_pid: Option<u32>,
+}
+
+impl Jobs {
+    /// Adds a job running `processes` and returns it. Its number is one more than the highest in use.
+    pub fn add(&mut self, command: &str, processes: Vec<Box<dyn Process>>) -> &Job {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.last_pid = processes.last().map(|process| process.id());
+        let processes = processes.into_iter().map(|process| (process, None)).collect();
+        self.jobs.push(Job { id, command: command.to_string(), state: JobState::Running, processes });
+        self.jobs.last().unwrap()
+    }
+
+    /// The process ID of the last program started in the background, for `$!`.
+    pub fn last_pid(&self) -> Option<u32> {
+        self.last_pid
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    /// `+` for the current job, `-` for the previous one, a space otherwise.
+    pub fn marker(&self, id: usize) -> char {
+        let mut ids = self.jobs.iter().rev().map(|job| job.id);
+        match (ids.next(), ids.next()) {
+            (Some(current), _) if current == id => '+',
+            (_, Some(previous)) if previous == id => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Finds the job a job spec names: `%n` by number, `%%` or `%+` for the current job, `%-` for the previous
+    /// one and `%string` for the latest job whose command starts with `string`. No spec is the current job.
+    pub fn find(&self, spec: Option<&str>) -> Option<usize> {
+        let spec = spec.map(|spec| spec.strip_prefix('%').unwrap_or(spec));
+        let mut latest = self.jobs.iter().rev();
+        match spec {
+            None | Some("" | "%" | "+") => latest.next(),
+            Some("-") => latest.nth(1),
+            Some(spec) => match spec.parse::<usize>() {
+                Ok(id) => self.jobs.iter().find(|job| job.id == id),
+                Err(_) => latest.find(|job| job.command.starts_with(spec)),
+            },
+        }
+        .map(|job| job.id)
+    }
+
+    /// Checks which jobs have finished, without waiting, and takes them out of the table.
+    pub fn take_finished(&mut self) -> Vec<(char, Job)> {
+        for job in &mut self.jobs {
+            if let Err(e) = job.poll() {
+                tracing::debug!(job = job.id, error = %e, "poll");
+            }
+        }
+        let finished: Vec<usize> = self.jobs.iter()
+            .filter(|job| matches!(job.state, JobState::Done(_) | JobState::Killed(_)))
+            .map(|job| job.id)
+            .collect();
+        finished.into_iter().map(|id| (self.marker(id), self.remove(id).unwrap())).collect()
+    }
+}
+
+/// Continues a stopped job's programs.
+#[cfg(unix)]
+pub fn resume(job: &Job) -> io::Result<()> {
+    let Some(&leader) = job.pids().first() else {
+        return Ok(());
+    };
+    // a negative ID signals the whole process group
+    if unsafe { libc::kill(-(leader as i32), libc::SIGCONT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Continues a stopped job's programs. Jobs are never stopped where there are no signals.
+#[cfg(not(unix))]
+pub fn resume(_job: &Job) -> io::Result<()> {
+    Ok(())
+}
+
+/// The signals `kill` knows by name, without the `SIG` prefix, and their numbers.
+#[cfg(unix)]
+pub const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ABRT", libc::SIGABRT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+/// The signals `kill` knows by name. Without signals any of them terminates the program; the numbers are the
+/// usual Unix ones, so scripts written for Unix work unchanged.
+#[cfg(not(unix))]
+pub const SIGNALS: &[(&str, i32)] = &[("HUP", 1), ("INT", 2), ("QUIT", 3), ("KILL", 9), ("TERM", 15)];
+
+/// The number of a signal given by name, with or without the `SIG` prefix and in any case, or by number.
+pub fn signal_number(spec: &str) -> Option<i32> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return (number == 0 || SIGNALS.iter().any(|&(_, n)| n == number)).then_some(number);
+    }
+    let name = spec.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS.iter().find(|&&(n, _)| n == name).map(|&(_, number)| number)
+}
+
+/// The name of a signal, without the `SIG` prefix.
+pub fn signal_name(number: i32) -> Option<&'static str> {
+    SIGNALS.iter().find(|&&(_, n)| n == number).map(|&(name, _)| name)
+}
+
+/// How a job killed by a signal is listed, as bash lists it: `Terminated` for TERM, `Killed` for KILL and so on.
+pub fn signal_description(number: i32) -> String {
+    let description = match signal_name(number) {
+        Some("HUP") => "Hangup",
+        Some("INT") => "Interrupt",
+        Some("QUIT") => "Quit",
+        Some("ABRT") => "Aborted",
+        Some("KILL") => "Killed",
+        Some("USR1") => "User defined signal 1",
+        Some("SEGV") => "Segmentation fault",
+        Some("USR2") => "User defined signal 2",
+        Some("PIPE") => "Broken pipe",
+        Some("ALRM") => "Alarm clock",
+        Some("TERM") => "Terminated",
+        _ => return format!("Signal {}", number),
+    };
+    description.to_string()
+}
+
+/// Sends `signal` to a process, or to the process group it leads with `group`. Signal 0 only checks that the
+/// process exists.
+#[cfg(unix)]
+pub fn kill(pid: u32, group: bool, signal: i32) -> io::Result<()> {
+    // a negative ID signals the whole process group
+    let target = if group { -(pid as i32) } else { pid as i32 };
+    if unsafe { libc::kill(target, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Terminates a process, with the status a program killed by `signal` has on Unix. Signal 0 only checks that
+/// the process exists. Windows has no process groups, so `group` has no effect.
+#[cfg(windows)]
+pub fn kill(pid: u32, _group: bool, signal: i32) -> io::Result<()> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    let process = unsafe { OpenProcess(PROCESS_TERMINATE, 0, pid) };
+    if process.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let terminated = signal == 0 || unsafe { TerminateProcess(process, 128 + signal as u32) } != 0;
+    let error = io::Error::last_os_error();
+    unsafe { CloseHandle(process) };
+    if !terminated {
+        return Err(error);
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn kill(_pid: u32, _group: bool, _signal: i32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// Makes process group `group` the terminal's foreground group while `f` waits for its programs, so that Ctrl-C
+/// and keys typed go to them rather than the shell, then takes the terminal back. An interrupt the shell
+/// receives meanwhile is passed on to the group. Without the terminal `f` simply runs.
+#[cfg(unix)]
+pub fn in_foreground<T>(group: u32, f: impl FnOnce() -> T) -> T {
+    let shell = unsafe { libc::getpgrp() };
+    let owner = unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) };
+    // a program started in the foreground has taken the terminal already
+    let terminal = owner == shell || owner == group as libc::pid_t;
+    if terminal {
+        set_foreground(group as libc::pid_t);
+    }
+    interrupt::forward_to(Some(group));
+    let result = f();
+    interrupt::forward_to(None);
+    if terminal {
+        set_foreground(shell);
+    }
+    result
+}
+
+#[cfg(not(unix))]
+pub fn in_foreground<T>(_group: u32, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+/// Puts an interactive shell in a process group of its own and gives that group the terminal, so that Ctrl+Z
+/// and Ctrl-C reach only the programs the shell hands the terminal to. Ctrl+Z at the prompt is ignored rather
+/// than stopping the shell. Does nothing unless the shell is in the terminal's foreground.
+#[cfg(unix)]
+pub fn take_terminal() {
+    let group = unsafe { libc::getpgrp() };
+    if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } != group {
+        return;
+    }
+    // a handler, unlike ignoring the signal, is reset for the programs the shell starts
+    if let Err(e) = unsafe { signal_hook::low_level::register(libc::SIGTSTP, || {}) } {
+        tracing::debug!(error = %e, "SIGTSTP handler");
+    }
+    let pid = unsafe { libc::getpid() };
+    if group != pid && unsafe { libc::setpgid(0, 0) } == 0 {
+        PREVIOUS_FOREGROUND.store(group, Ordering::SeqCst);
+        set_foreground(pid);
+    }
+}
+
+/// Gives the terminal back to the process group that had it before `take_terminal`.
+#[cfg(unix)]
+pub fn release_terminal() {
+    let group = PREVIOUS_FOREGROUND.swap(0, Ordering::SeqCst);
+    if group != 0 {
+        set_foreground(group);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn take_terminal() {}
+
+#[cfg(not(unix))]
+pub fn release_terminal() {}
+
+/// Hands the terminal to a process group. A process that isn't in the foreground when it does would be stopped
+/// with SIGTTOU unless that's blocked meanwhile. Only makes async-signal-safe calls, so a program about to
+/// start can call it.
+#[cfg(unix)]
+pub fn set_foreground(group: libc::pid_t) {
+    unsafe {
+        let mut block: libc::sigset_t = std::mem::zeroed();
+        let mut saved: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut block);
+        libc::sigaddset(&mut block, libc::SIGTTOU);
+        libc::sigprocmask(libc::SIG_BLOCK, &block, &mut saved);
+        libc::tcsetpgrp(libc::STDIN_FILENO, group);
+        libc::sigprocmask(libc::SIG_SETMASK, &saved, std::ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jobs::{signal_description, signal_name, signal_number, JobState, Jobs};
+    use crate::system::{FinishedProcess, Process};
+
+    fn finished(id: u32, status: i32) -> Vec<Box<dyn Process>> {
+        vec![Box::new(FinishedProcess { id, status })]
+    }
+
+    #[test]
+    fn test_job_numbers_and_specs() {
+        let mut jobs = Jobs::default();
+        assert_eq!(jobs.add("sleep 10", finished(100, 0)).id, 1);
+        assert_eq!(jobs.add("make all", finished(101, 0)).id, 2);
+        assert_eq!(jobs.add("sleep 20", finished(102, 0)).id, 3);
+        assert_eq!(jobs.last_pid(), Some(102));
+        assert_eq!((jobs.marker(3), jobs.marker(2), jobs.marker(1)), ('+', '-', ' '));
+        assert_eq!(jobs.find(None), Some(3));
+        assert_eq!(jobs.find(Some("%%")), Some(3));
+        assert_eq!(jobs.find(Some("%-")), Some(2));
+        assert_eq!(jobs.find(Some("%1")), Some(1));
+        assert_eq!(jobs.find(Some("2")), Some(2));
+        assert_eq!(jobs.find(Some("%sleep")), Some(3));
+        assert_eq!(jobs.find(Some("%ma")), Some(2));
+        assert_eq!(jobs.find(Some("%4")), None);
+        // numbers continue from the highest in use
+        jobs.remove(2);
+        assert_eq!(jobs.add("ls", finished(103, 0)).id, 4);
+    }
+
+    #[test]
+    fn test_take_finished() {
+        let mut jobs = Jobs::default();
+        jobs.add("false | true", vec![Box::new(FinishedProcess { id: 1, status: 1 }), Box::new(FinishedProcess { id: 2, status: 0 })]);
+        jobs.add("exit 3", finished(3, 3));
+        let finished = jobs.take_finished();
+        assert_eq!(finished.iter().map(|(marker, job)| (*marker, job.state)).collect::<Vec<_>>(), vec![('-', JobState::Done(0)), ('+', JobState::Done(3))]);
+        assert_eq!(finished[0].1.describe('-'), "[1]-  Done                    false | true");
+        assert_eq!(finished[1].1.describe('+'), "[2]+  Exit 3                  exit 3");
+        assert_eq!(jobs.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_signal_names() {
+        assert_eq!(signal_number("TERM"), Some(15));
+        assert_eq!(signal_number("sigkill"), Some(9));
+        assert_eq!(signal_number("9"), Some(9));
+        assert_eq!(signal_number("0"), Some(0));
+        assert_eq!(signal_number("NOPE"), None);
+        assert_eq!(signal_number("1000"), None);
+        assert_eq!(signal_name(2), Some("INT"));
+        assert_eq!(signal_description(15), "Terminated");
+        assert_eq!(signal_description(9), "Killed");
+        assert_eq!(signal_description(1000), "Signal 1000");
+    }
+}
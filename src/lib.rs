@@ -0,0 +1,49 @@
+//! rush, a small Unix-style shell.
+//!
+//! The `rush` binary is a thin wrapper around [`Shell`], which other programs can embed to run shell
+//! commands with their own configuration. [`Shell::eval`] runs a script's worth of lines and
+//! [`Shell::execute_line`] one command line; the state they share, variables, aliases, options and the like,
+//! is the [`ShellContext`] behind [`Shell::context`]. [`Shell::register_builtin`] adds a command of the
+//! embedder's own, an [`Execute`] implementation. [`tokenize`] splits a line into words without running it.
+
+pub mod alias;
+pub mod arithmetic;
+pub mod builtins;
+pub mod command_hash;
+pub mod completion;
+pub mod conditional;
+pub mod config;
+pub mod control;
+pub mod debugger;
+pub mod dirs;
+pub mod encoding;
+pub mod error;
+pub mod expand;
+pub mod format;
+pub mod glob;
+pub mod history;
+pub mod interrupt;
+pub mod jobs;
+pub mod line_editor;
+pub mod locale;
+pub mod options;
+pub mod path_index;
+pub mod path_utils;
+pub mod plugin;
+pub mod profiler;
+pub mod prompt;
+pub mod shell;
+pub mod syntax;
+pub mod system;
+pub mod terminal;
+pub mod theme;
+pub mod tokenizer;
+pub mod variables;
+pub mod vfs;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use builtins::{Execute, Io, ShellContext};
+pub use error::RushError;
+pub use shell::{ExitStatus, Shell, ShellConfig};
+pub use tokenizer::tokenize;
@@ -0,0 +1,661 @@
+//! Reading a line at the prompt, with the editing keys of readline.
+//!
+//! While a line is typed the terminal is in raw mode: the shell sees every key as it's pressed, arrow keys
+//! included, and echoes the line itself. Left/Right (Ctrl-B/Ctrl-F) move the cursor, Home/End (Ctrl-A/Ctrl-E)
+//! jump to either end of the line, Backspace and Delete remove the character before or under the cursor and
+//! anything typed is inserted at the cursor. Up/Down (Ctrl-P/Ctrl-N) step through the history, and back
+//! down past the newest entry to the line that was being typed. Ctrl-R searches back through the history
+//! while the search string is typed: the newest entry holding it shows, Ctrl-R again finds the one before,
+//! Enter runs it and Esc or Ctrl-G brings back the line as it was. Tab completes the word before the cursor:
+//! one candidate replaces it, several are completed as far as they agree, and listed when that adds nothing.
+//! When stdin isn't a terminal, lines are read as they come.
+
+use std::io::{self, BufRead, Read, Write};
+use crate::encoding;
+use crate::history::History;
+use crate::interrupt;
+
+/// What reading at the prompt came back with.
+#[derive(Debug, PartialEq)]
+pub enum ReadLine {
+    Line(String),
+    /// Ctrl-C dropped the line being typed.
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of stdin.
+    EndOfInput,
+}
+
+/// A key pressed at the prompt.
+#[derive(Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Backspace,
+    Delete,
+    Tab,
+    Enter,
+    /// Ctrl-C
+    Interrupt,
+    /// Ctrl-D
+    EndOfInput,
+    /// Ctrl-R
+    Search,
+    /// Esc on its own, or Ctrl-G
+    Cancel,
+    /// A key without a binding, or an escape sequence that isn't recognized
+    Ignored,
+}
+
+/// What completes the word before the cursor: given the line up to the cursor, returns where the word starts
+/// and the candidates to replace it with.
+pub type Complete<'a> = &'a dyn Fn(&str) -> (usize, Vec<String>);
+
+/// The line being edited and the cursor position in it, as a byte offset.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    text: String,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replaces the line, leaving the cursor at its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = text.len();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn move_left(&mut self) -> bool {
+        match self.text[..self.cursor].chars().next_back() {
+            Some(c) => {
+                self.cursor -= c.len_utf8();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn move_right(&mut self) -> bool {
+        match self.text[self.cursor..].chars().next() {
+            Some(c) => {
+                self.cursor += c.len_utf8();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Replaces the text from `start` up to the cursor with `text`, leaving the cursor after it.
+    pub fn replace_before(&mut self, start: usize, text: &str) {
+        self.text.replace_range(start..self.cursor, text);
+        self.cursor = start + text.len();
+    }
+
+    /// Removes the character before the cursor.
+    pub fn delete_back(&mut self) -> bool {
+        self.move_left() && self.delete_forward()
+    }
+
+    /// Removes the character under the cursor.
+    pub fn delete_forward(&mut self) -> bool {
+        if self.cursor == self.text.len() {
+            return false;
+        }
+        self.text.remove(self.cursor);
+        true
+    }
+}
+
+/// Shows `prompt` and reads a line, letting the user edit it, recall one from `history` or `complete` words.
+pub fn read_line(prompt: &str, history: Option<&History>, complete: Option<Complete>) -> io::Result<ReadLine> {
+    // raw mode goes on before the prompt shows, so keys typed ahead aren't echoed twice
+    let raw_mode = RawMode::enable();
+    let mut out = io::stdout().lock();
+    write!(out, "{}", prompt)?;
+    out.flush()?;
+    if raw_mode.is_none() {
+        return read_cooked_line();
+    }
+    let read = {
+        let _raw_mode = raw_mode;
+        // a multi-line prompt only has its last line redrawn
+        edit_line(prompt.rsplit('\n').next().unwrap_or_default(), history, complete, &mut out)
+    };
+    // the terminal is back to normal before the line ends, so keys typed for the command are handled as usual
+    match read {
+        Ok(ReadLine::Interrupted) => writeln!(out, "^C")?,
+        _ => writeln!(out)?,
+    }
+    out.flush()?;
+    read
+}
+
+// A search back through the history that Ctrl-R started
+struct Search {
+    query: String,
+    // the newest entry found holding `query`, and whether the last attempt to find one failed
+    found: Option<usize>,
+    failed: bool,
+    // the line as it was before the search, which cancelling it brings back
+    original: String,
+}
+
+impl Search {
+    // Finds the newest entry before `before` that holds the query, keeping the one found last when none does
+    fn find(&mut self, history: &History, before: usize) {
+        match history.search(&self.query, before) {
+            Some(found) => {
+                self.found = Some(found);
+                self.failed = false;
+            }
+            None => self.failed = true,
+        }
+    }
+
+    // What the line shows instead of the prompt while searching
+    fn prompt(&self) -> String {
+        format!("({}reverse-i-search)`{}': ", if self.failed { "failed " } else { "" }, self.query)
+    }
+}
+
+// Reads keys and edits the line until it's entered, echoing it as it goes
+fn edit_line(
+    prompt: &str,
+    history: Option<&History>,
+    complete: Option<Complete>,
+    out: &mut impl Write,
+) -> io::Result<ReadLine> {
+    let mut input = TerminalKeys(io::stdin().lock());
+    let mut line = LineBuffer::default();
+    // the history entry shown, or the history's length for the line being typed, which `draft` keeps while
+    // an entry is shown
+    let history_len = history.map_or(0, History::len);
+    let mut shown = history_len;
+    let mut draft = String::new();
+    let mut search: Option<Search> = None;
+    loop {
+        let Some(key) = read_key(&mut input)? else {
+            // the terminal went away
+            return Ok(ReadLine::EndOfInput);
+        };
+        if let (Some(active), Some(history)) = (&mut search, history) {
+            match key {
+                Key::Char(c) => {
+                    active.query.push(c);
+                    // the entry found stays while it still holds the longer query
+                    active.find(history, active.found.map_or(history_len, |found| found + 1));
+                }
+                Key::Backspace => {
+                    active.query.pop();
+                    active.found = None;
+                    active.failed = false;
+                    if !active.query.is_empty() {
+                        active.find(history, history_len);
+                    }
+                }
+                Key::Search if !active.query.is_empty() => active.find(history, active.found.unwrap_or(history_len)),
+                Key::Search => {}
+                Key::Cancel => {
+                    line.set(&active.original);
+                    search = None;
+                    redraw(out, prompt, &line)?;
+                    out.flush()?;
+                    continue;
+                }
+                Key::Enter | Key::Interrupt => {}
+                _ => {
+                    // any other key takes the entry found to edit, and then does what it always does
+                    if let Some(found) = active.found {
+                        if shown == history_len {
+                            draft = active.original.clone();
+                        }
+                        shown = found;
+                    }
+                    search = None;
+                    redraw(out, prompt, &line)?;
+                }
+            }
+            if let Some(active) = &search {
+                let found = active.found.and_then(|found| history.get(found));
+                line.set(found.as_deref().unwrap_or(&active.original));
+                if matches!(key, Key::Enter | Key::Interrupt) {
+                    redraw(out, prompt, &line)?;
+                } else {
+                    redraw(out, &active.prompt(), &line)?;
+                    out.flush()?;
+                    continue;
+                }
+            }
+        }
+        match key {
+            Key::Char(c) if line.cursor() == line.text().len() => {
+                // typing at the end of the line is just echoed
+                line.insert(c);
+                write!(out, "{}", c)?;
+            }
+            Key::Char(c) => {
+                line.insert(c);
+                redraw(out, prompt, &line)?;
+            }
+            Key::Left => {
+                if line.move_left() {
+                    write!(out, "\x1b[D")?;
+                }
+            }
+            Key::Right => {
+                if line.move_right() {
+                    write!(out, "\x1b[C")?;
+                }
+            }
+            Key::Up | Key::Down => {
+                let Some(history) = history else { continue };
+                let next = match key {
+                    Key::Up if shown > 0 => shown - 1,
+                    Key::Down if shown < history_len => shown + 1,
+                    _ => continue,
+                };
+                if shown == history_len {
+                    draft = line.text().to_string();
+                }
+                shown = next;
+                match history.get(shown) {
+                    Some(entry) => line.set(&entry),
+                    None => line.set(&draft),
+                }
+                redraw(out, prompt, &line)?;
+            }
+            Key::Home => {
+                line.move_home();
+                redraw(out, prompt, &line)?;
+            }
+            Key::End => {
+                line.move_end();
+                redraw(out, prompt, &line)?;
+            }
+            Key::Backspace => {
+                if line.delete_back() {
+                    redraw(out, prompt, &line)?;
+                }
+            }
+            Key::Delete => {
+                if line.delete_forward() {
+                    redraw(out, prompt, &line)?;
+                }
+            }
+            Key::Tab => {
+                let Some(complete) = complete else { continue };
+                let (start, candidates) = complete(&line.text()[..line.cursor()]);
+                if complete_word(&mut line, start, &candidates) {
+                    writeln!(out)?;
+                    writeln!(out, "{}", candidates.join("  "))?;
+                }
+                redraw(out, prompt, &line)?;
+            }
+            Key::EndOfInput if line.text().is_empty() => return Ok(ReadLine::EndOfInput),
+            // like Delete, on a line that isn't empty
+            Key::EndOfInput => {
+                if line.delete_forward() {
+                    redraw(out, prompt, &line)?;
+                }
+            }
+            Key::Search if history.is_some() => {
+                let active = Search { query: String::new(), found: None, failed: false, original: line.text.clone() };
+                redraw(out, &active.prompt(), &line)?;
+                search = Some(active);
+            }
+            Key::Enter => return Ok(ReadLine::Line(line.text)),
+            Key::Interrupt => return Ok(ReadLine::Interrupted),
+            Key::Search | Key::Cancel | Key::Ignored => {}
+        }
+        out.flush()?;
+    }
+}
+
+// Completes the word before the cursor, which starts at `start`, from `candidates`: a single one replaces the
+// word and is followed by a space, unless it's a directory to go on into, and several replace it with what they
+// start with in common. Returns whether the candidates should be listed because that added nothing.
+fn complete_word(line: &mut LineBuffer, start: usize, candidates: &[String]) -> bool {
+    let Some((first, rest)) = candidates.split_first() else {
+        return false;
+    };
+    if rest.is_empty() {
+        let space = if first.ends_with('/') { "" } else { " " };
+        line.replace_before(start, &format!("{}{}", first, space));
+        return false;
+    }
+    let common = rest.iter().fold(first.as_str(), |common, candidate| common_prefix(common, candidate));
+    if common.len() > line.cursor() - start {
+        line.replace_before(start, common);
+        return false;
+    }
+    true
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let differs = a.char_indices().zip(b.chars()).find(|((_, x), y)| x != y);
+    &a[..differs.map_or(a.len().min(b.len()), |((i, _), _)| i)]
+}
+
+// Reads a line from stdin as it is, for input that isn't a terminal
+fn read_cooked_line() -> io::Result<ReadLine> {
+    let mut buffer = Vec::new();
+    if io::stdin().lock().read_until(b'\n', &mut buffer)? == 0 {
+        // on Windows, Ctrl-C ends the read as if input had ended; it only drops the line
+        if interrupt::take() {
+            println!();
+            return Ok(ReadLine::Interrupted);
+        }
+        return Ok(ReadLine::EndOfInput);
+    }
+    // trailing blanks may be quoted, so only the line ending goes
+    Ok(ReadLine::Line(encoding::decode(&buffer).trim_end_matches(['\n', '\r']).to_owned()))
+}
+
+// Writes the prompt and the line over what's on the terminal and puts the cursor back in its place
+fn redraw(out: &mut impl Write, prompt: &str, line: &LineBuffer) -> io::Result<()> {
+    write!(out, "\r{}{}\x1b[K", prompt, line.text())?;
+    let after_cursor = line.text()[line.cursor()..].chars().count();
+    if after_cursor > 0 {
+        write!(out, "\x1b[{}D", after_cursor)?;
+    }
+    Ok(())
+}
+
+/// Where keys are read from.
+pub trait KeyInput: Read {
+    /// Reads the byte after an ESC, or returns None when none follows right away, as when Esc is pressed on
+    /// its own rather than starting the sequence another key sends.
+    fn read_following(&mut self) -> io::Result<Option<u8>> {
+        read_byte(self)
+    }
+}
+
+impl KeyInput for &[u8] {}
+
+// The keys typed at the terminal while it's in raw mode
+struct TerminalKeys<'a>(io::StdinLock<'a>);
+
+impl Read for TerminalKeys<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl KeyInput for TerminalKeys<'_> {
+    // a key's sequence arrives all at once, so a read that gives up after a tenth of a second tells Esc apart
+    #[cfg(unix)]
+    fn read_following(&mut self) -> io::Result<Option<u8>> {
+        // Safety: termios is plain data, filled in by tcgetattr before it's used
+        let mut raw: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut raw) } != 0 {
+            return read_byte(self);
+        }
+        let mut timed = raw;
+        timed.c_cc[libc::VMIN] = 0;
+        timed.c_cc[libc::VTIME] = 1;
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &timed) };
+        let byte = read_byte(self);
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) };
+        byte
+    }
+}
+
+/// Reads the next key from a terminal in raw mode, decoding the escape sequences for arrow keys and the like.
+/// None at the end of input.
+pub fn read_key(input: &mut impl KeyInput) -> io::Result<Option<Key>> {
+    let key = match read_byte(input)? {
+        None => return Ok(None),
+        Some(0x04) => Key::EndOfInput,
+        Some(0x01) => Key::Home,
+        Some(0x02) => Key::Left,
+        Some(0x03) => Key::Interrupt,
+        Some(0x05) => Key::End,
+        Some(0x06) => Key::Right,
+        Some(0x07) => Key::Cancel,
+        Some(0x08 | 0x7f) => Key::Backspace,
+        Some(0x0e) => Key::Down,
+        Some(0x10) => Key::Up,
+        Some(0x12) => Key::Search,
+        Some(b'\t') => Key::Tab,
+        Some(b'\r' | b'\n') => Key::Enter,
+        Some(0x1b) => read_escape_sequence(input)?,
+        Some(byte) if byte < 0x20 => Key::Ignored,
+        Some(byte) => read_char(input, byte)?,
+    };
+    Ok(Some(key))
+}
+
+// Decodes what follows ESC: CSI sequences like `ESC [ 3 ~` and SS3 ones like `ESC O H`, or nothing for Esc
+fn read_escape_sequence(input: &mut impl KeyInput) -> io::Result<Key> {
+    match input.read_following()? {
+        None => return Ok(Key::Cancel),
+        Some(b'[' | b'O') => {}
+        Some(_) => return Ok(Key::Ignored),
+    }
+    let mut parameter = Vec::new();
+    let last = loop {
+        match read_byte(input)? {
+            Some(byte @ 0x40..=0x7e) => break byte,
+            Some(byte) => parameter.push(byte),
+            None => return Ok(Key::Ignored),
+        }
+    };
+    let key = match (last, parameter.as_slice()) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) | (b'~', b"1" | b"7") => Key::Home,
+        (b'F', _) | (b'~', b"4" | b"8") => Key::End,
+        (b'~', b"3") => Key::Delete,
+        _ => Key::Ignored,
+    };
+    Ok(key)
+}
+
+// Reads the rest of a UTF-8 character that starts with `first`
+fn read_char(input: &mut impl Read, first: u8) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).map_or(Key::Ignored, Key::Char))
+}
+
+fn read_byte(input: &mut (impl Read + ?Sized)) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// The terminal in raw mode for as long as this lives: keys arrive one at a time and aren't echoed
+#[cfg(unix)]
+struct RawMode(libc::termios);
+
+#[cfg(unix)]
+impl RawMode {
+    // None when stdin isn't a terminal
+    fn enable() -> Option<Self> {
+        // Safety: termios is plain data, filled in by tcgetattr before it's used
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return None;
+        }
+        let mut raw = original;
+        // Ctrl-C and Ctrl-D are read as keys; output processing stays on, so "\n" still starts a new line
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } != 0 {
+            return None;
+        }
+        Some(RawMode(original))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.0) };
+    }
+}
+
+#[cfg(windows)]
+struct RawMode(u32);
+
+#[cfg(windows)]
+impl RawMode {
+    // None when stdin isn't a console
+    fn enable() -> Option<Self> {
+        use windows_sys::Win32::System::Console::{
+            GetConsoleMode, GetStdHandle, SetConsoleMode, ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT,
+            ENABLE_PROCESSED_INPUT, ENABLE_VIRTUAL_TERMINAL_INPUT, STD_INPUT_HANDLE,
+        };
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            let mut original = 0;
+            if GetConsoleMode(handle, &mut original) == 0 {
+                return None;
+            }
+            // arrow keys arrive as the same escape sequences as on Unix, and Ctrl-C as a key
+            let raw = (original & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT))
+                | ENABLE_VIRTUAL_TERMINAL_INPUT;
+            if SetConsoleMode(handle, raw) == 0 {
+                return None;
+            }
+            Some(RawMode(original))
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Console::{GetStdHandle, SetConsoleMode, STD_INPUT_HANDLE};
+        unsafe { SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), self.0) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct RawMode;
+
+#[cfg(not(any(unix, windows)))]
+impl RawMode {
+    fn enable() -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_editor::{complete_word, read_key, Key, LineBuffer};
+
+    fn keys(mut input: &[u8]) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while !input.is_empty() {
+            keys.push(read_key(&mut input).unwrap().unwrap());
+        }
+        keys
+    }
+
+    #[test]
+    fn test_read_key() {
+        assert_eq!(keys(b"a\x7f\r"), vec![Key::Char('a'), Key::Backspace, Key::Enter]);
+        assert_eq!(keys(b"\x1b[D\x1b[C\x1b[H\x1b[F"), vec![Key::Left, Key::Right, Key::Home, Key::End]);
+        assert_eq!(keys(b"\x1b[A\x1bOB\x10\x0e"), vec![Key::Up, Key::Down, Key::Up, Key::Down]);
+        assert_eq!(keys(b"\x1bOH\x1b[1~\x1b[4~\x1b[3~"), vec![Key::Home, Key::Home, Key::End, Key::Delete]);
+        assert_eq!(keys(b"\x01\x05\x02\x06\x03\x04"), vec![Key::Home, Key::End, Key::Left, Key::Right, Key::Interrupt, Key::EndOfInput]);
+        // unknown sequences, including with modifiers, don't leave bytes behind to be typed
+        assert_eq!(keys(b"\x1b[1;5Qx\x1b[15~"), vec![Key::Ignored, Key::Char('x'), Key::Ignored]);
+        assert_eq!(keys("ü€".as_bytes()), vec![Key::Char('ü'), Key::Char('€')]);
+        assert_eq!(keys(b"\x12\x07\t\x1b"), vec![Key::Search, Key::Cancel, Key::Tab, Key::Cancel]);
+    }
+
+    #[test]
+    fn test_line_buffer() {
+        let mut line = LineBuffer::default();
+        "ech hi".chars().for_each(|c| line.insert(c));
+        (0..3).for_each(|_| assert!(line.move_left()));
+        line.insert('o');
+        assert_eq!((line.text(), line.cursor()), ("echo hi", 4));
+        line.move_home();
+        assert!(!line.move_left() && !line.delete_back());
+        assert!(line.delete_forward());
+        assert_eq!(line.text(), "cho hi");
+        line.move_end();
+        assert!(!line.move_right() && !line.delete_forward());
+        assert!(line.delete_back());
+        line.insert('ü');
+        assert!(line.move_left() && line.move_right());
+        assert_eq!((line.text(), line.cursor()), ("cho hü", 7));
+        line.set("ls");
+        assert_eq!((line.text(), line.cursor()), ("ls", 2));
+    }
+
+    #[test]
+    fn test_complete_word() {
+        let candidates = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let mut line = LineBuffer::default();
+        line.set("cd sr");
+        assert!(!complete_word(&mut line, 3, &candidates(&["src/"])));
+        assert_eq!((line.text(), line.cursor()), ("cd src/", 7));
+        line.set("ec");
+        assert!(!complete_word(&mut line, 0, &candidates(&["echo"])));
+        assert_eq!(line.text(), "echo ");
+
+        // several candidates complete as far as they agree, and are listed once they don't add anything
+        line.set("e");
+        assert!(!complete_word(&mut line, 0, &candidates(&["exit", "export", "expr"])));
+        assert_eq!(line.text(), "ex");
+        assert!(complete_word(&mut line, 0, &candidates(&["exit", "export", "expr"])));
+        assert_eq!(line.text(), "ex");
+        assert!(!complete_word(&mut line, 0, &[]));
+
+        // the rest of the line stays after the cursor
+        line.set("ls ü | w");
+        (0..4).for_each(|_| assert!(line.move_left()));
+        assert!(!complete_word(&mut line, 3, &candidates(&["üben/", "übel/"])));
+        assert_eq!((line.text(), line.cursor()), ("ls übe | w", 7));
+    }
+}
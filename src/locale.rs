@@ -0,0 +1,245 @@
+//! Translated messages and locale-aware ordering.
+//!
+//! Messages are looked up by their English text, gettext style, in the catalog for the language of
+//! LC_ALL, LC_MESSAGES or LANG, whichever is set first; anything without a translation stays English.
+//! Translations keep the `{}` placeholders of the English text, in the same order. Lists shown to the user
+//! are sorted by LC_ALL, LC_COLLATE or LANG: byte order in the C locale, case-insensitively otherwise.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::sync::OnceLock;
+use crate::encoding::env_var;
+
+type Catalog = &'static [(&'static str, &'static str)];
+
+struct Locale {
+    catalog: Catalog,
+    fold_case: bool,
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+// The process's locale, read from the environment the first time it's needed
+fn locale() -> &'static Locale {
+    LOCALE.get_or_init(|| Locale {
+        catalog: setting("LC_MESSAGES").map_or(&[], |locale| catalog(&locale)),
+        fold_case: setting("LC_COLLATE").is_some_and(|locale| !is_c_locale(&locale)),
+    })
+}
+
+// The locale for a category, as POSIX resolves it
+fn setting(category: &str) -> Option<String> {
+    ["LC_ALL", category, "LANG"].into_iter().filter_map(env_var).find(|value| !value.is_empty())
+}
+
+fn is_c_locale(locale: &str) -> bool {
+    matches!(locale, "C" | "POSIX") || locale.starts_with("C.")
+}
+
+// The catalog for a locale name like de_DE.UTF-8
+fn catalog(locale: &str) -> Catalog {
+    let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+    CATALOGS.iter().find(|(name, _)| *name == language).map_or(&[], |(_, catalog)| catalog)
+}
+
+fn lookup(catalog: Catalog, msgid: &str) -> &str {
+    catalog.iter().find(|(english, _)| *english == msgid).map_or(msgid, |(_, translated)| translated)
+}
+
+/// The translation of `msgid`, or `msgid` itself when there's none.
+pub fn translate(msgid: &str) -> &str {
+    lookup(locale().catalog, msgid)
+}
+
+/// Fills the `{}` placeholders of a message with `args`, in order.
+pub fn format_message(message: &str, args: &[&dyn Display]) -> String {
+    let mut parts = message.split("{}");
+    let mut formatted = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            formatted.push_str(&arg.to_string());
+        }
+        formatted.push_str(part);
+    }
+    formatted
+}
+
+/// Translates a message and fills in its placeholders: `tr!("{}: command not found", name)`.
+macro_rules! tr {
+    ($msgid:literal $(, $arg:expr)* $(,)?) => {
+        $crate::locale::format_message($crate::locale::translate($msgid), &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+pub(crate) use tr;
+
+/// Orders two strings for display in the user's locale.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    compare_with(locale().fold_case, a, b)
+}
+
+fn compare_with(fold_case: bool, a: &str, b: &str) -> Ordering {
+    if !fold_case {
+        return a.cmp(b);
+    }
+    let folded = |s: &str| s.chars().flat_map(char::to_lowercase).collect::<Vec<char>>();
+    folded(a).cmp(&folded(b)).then_with(|| a.cmp(b))
+}
+
+static CATALOGS: &[(&str, Catalog)] = &[("de", DE)];
+
+static DE: Catalog = &[
+    // errors
+    ("{}: command not found", "{}: Befehl nicht gefunden"),
+    ("{}: unbound variable", "{}: Ungebundene Variable"),
+    ("{}: event not found", "{}: Ereignis nicht gefunden"),
+    ("Bad file descriptor", "Ungültiger Dateideskriptor"),
+    ("No such file or directory", "Datei oder Verzeichnis nicht gefunden"),
+    ("Permission denied", "Keine Berechtigung"),
+    ("File exists", "Die Datei existiert bereits"),
+    ("Is a directory", "Ist ein Verzeichnis"),
+    ("Not a directory", "Ist kein Verzeichnis"),
+    ("Directory not empty", "Verzeichnis nicht leer"),
+    ("no match: {}", "kein Treffer: {}"),
+    ("failed to execute {}: {}", "{} konnte nicht ausgeführt werden: {}"),
+    ("interrupted", "unterbrochen"),
+    ("exit status {}", "Exit-Status {}"),
+    ("Unterminated single quote", "Nicht abgeschlossenes einfaches Anführungszeichen"),
+    ("Unterminated double quote", "Nicht abgeschlossenes doppeltes Anführungszeichen"),
+    ("Trailing backslash", "Backslash am Zeilenende"),
+    ("{}: No such file or directory", "{}: Datei oder Verzeichnis nicht gefunden"),
+    ("{}: {}: exited with status {}", "{}: {}: mit Status {} beendet"),
+    ("syntax error near unexpected token `{}'", "Syntaxfehler beim unerwarteten Symbol `{}'"),
+    ("syntax error: unexpected end of file", "Syntaxfehler: Unerwartetes Dateiende"),
+    ("HOME environment variable not set", "Die Umgebungsvariable HOME ist nicht gesetzt"),
+    ("{}: invalid option name", "{}: Ungültiger Optionsname"),
+    ("{}: {}: not supported, using {}", "{}: {}: Nicht unterstützt, {} wird verwendet"),
+    ("plugin ABI version {} is not supported (expected {})", "Plugin-ABI-Version {} wird nicht unterstützt ({} erwartet)"),
+    ("plugin initialization failed with status {}", "Plugin-Initialisierung mit Status {} fehlgeschlagen"),
+    // conditional and arithmetic expressions
+    ("[[: expression expected", "[[: Ausdruck erwartet"),
+    ("[[: syntax error near `{}'", "[[: Syntaxfehler bei `{}'"),
+    ("[[: unexpected end of expression", "[[: Unerwartetes Ende des Ausdrucks"),
+    ("[[: expected `)'", "[[: `)' erwartet"),
+    ("[[: {}: invalid regular expression", "[[: {}: Ungültiger regulärer Ausdruck"),
+    ("[[: {}: unary operator expected", "[[: {}: Unärer Operator erwartet"),
+    ("syntax error in expression (error token is \"{}\")", "Syntaxfehler im Ausdruck (Fehlerhaftes Zeichen ist \"{}\")"),
+    ("syntax error: operand expected (error token is \"{}\")", "Syntaxfehler: Operand erwartet (Fehlerhaftes Zeichen ist \"{}\")"),
+    ("syntax error: operand expected", "Syntaxfehler: Operand erwartet"),
+    ("{}: value too great for base", "{}: Wert zu groß für die Basis"),
+    ("`:' expected for conditional expression", "`:' für bedingten Ausdruck erwartet"),
+    ("{}: operand must be a variable", "{}: Operand muss eine Variable sein"),
+    ("missing `)'", "Fehlende `)'"),
+    ("{}: invalid arithmetic value: {}", "{}: Ungültiger arithmetischer Wert: {}"),
+    ("division by 0", "Division durch 0"),
+    ("exponent less than 0", "Exponent kleiner als 0"),
+    ("unknown operator {}", "Unbekannter Operator {}"),
+    ("warning: here-document at line {} delimited by end-of-file (wanted `{}')", "Warnung: Here-Dokument in Zeile {} durch Dateiende begrenzt (`{}' erwartet)"),
+    // builtins
+    ("alias: `{}': invalid alias name", "alias: `{}': Ungültiger Aliasname"),
+    ("alias: {}: not found", "alias: {}: Nicht gefunden"),
+    ("unalias: {}: not found", "unalias: {}: Nicht gefunden"),
+    ("{}: {}: No such file or directory", "{}: {}: Datei oder Verzeichnis nicht gefunden"),
+    ("{}: error getting current directory: {}", "{}: Fehler beim Ermitteln des aktuellen Verzeichnisses: {}"),
+    ("cd: {} not set", "cd: {} ist nicht gesetzt"),
+    ("cd: {}: no such entry in the directory history", "cd: {}: Kein solcher Eintrag im Verzeichnisverlauf"),
+    ("{}: no other directory", "{}: Kein anderes Verzeichnis"),
+    ("{}: directory stack empty", "{}: Verzeichnisstapel leer"),
+    ("{}: directory stack index out of range", "{}: Index des Verzeichnisstapels außerhalb des gültigen Bereichs"),
+    ("{}: {}: invalid argument", "{}: {}: Ungültiges Argument"),
+    ("help: no help topics match `{}'", "help: Keine Hilfethemen passen zu `{}'"),
+    ("{}: {}: numeric argument required", "{}: {}: Numerisches Argument erforderlich"),
+    ("{}: too many arguments", "{}: Zu viele Argumente"),
+    ("{}: {}: loop count out of range", "{}: {}: Schleifenanzahl außerhalb des gültigen Bereichs"),
+    ("{}: only meaningful in a `for', `while', or `until' loop", "{}: Nur in einer `for'-, `while'- oder `until'-Schleife sinnvoll"),
+    ("shift: {}: shift count out of range", "shift: {}: Verschiebeanzahl außerhalb des gültigen Bereichs"),
+    ("{} is aliased to `{}'", "{} ist ein Alias für `{}'"),
+    ("{} is a shell builtin", "{} ist ein eingebauter Shell-Befehl"),
+    ("{} is {}", "{} ist {}"),
+    ("{}: {}: not found", "{}: {}: Nicht gefunden"),
+    ("{}: {}: invalid option", "{}: {}: Ungültige Option"),
+    ("{}: current: no such job", "{}: aktuell: Kein solcher Job"),
+    ("{}: {}: no such job", "{}: {}: Kein solcher Job"),
+    ("bg: job {} already in background", "bg: Job {} läuft bereits im Hintergrund"),
+    ("export: `{}': not a valid identifier", "export: `{}': Kein gültiger Bezeichner"),
+    ("`{}': not a valid identifier", "`{}': Kein gültiger Bezeichner"),
+    ("{}: `{}': not a valid identifier", "{}: `{}': Kein gültiger Bezeichner"),
+    ("{}: hash table empty", "{}: Hash-Tabelle leer"),
+    ("hits\tcommand", "Treffer\tBefehl"),
+    ("{}: {}: option requires an argument", "{}: {}: Option erfordert ein Argument"),
+    ("{}: {}: invalid signal specification", "{}: {}: Ungültige Signalangabe"),
+    ("{}: {}: arguments must be process or job IDs", "{}: {}: Argumente müssen Prozess- oder Job-IDs sein"),
+    ("{}: filename argument required", "{}: Dateiname als Argument erforderlich"),
+    ("Type args cannot be empty", "type benötigt mindestens ein Argument"),
+    ("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ...", "kill: Aufruf: kill [-s Signal | -n Signalnummer | -Signal] PID | Jobangabe ..."),
+    ("unalias: usage: unalias [-a] [--save] name [name ...]", "unalias: Aufruf: unalias [-a] [--save] Name [Name ...]"),
+    ("{}: cannot locate the aliases file", "{}: Die Aliasdatei wurde nicht gefunden"),
+    // help summaries
+    ("Define or list aliases", "Aliase definieren oder auflisten"),
+    ("Resume a stopped job in the background", "Einen angehaltenen Job im Hintergrund fortsetzen"),
+    ("Exit from a loop", "Eine Schleife verlassen"),
+    ("Change the current directory", "Das aktuelle Verzeichnis wechseln"),
+    ("Display the directory stack", "Den Verzeichnisstapel anzeigen"),
+    ("Start the next iteration of a loop", "Den nächsten Schleifendurchlauf beginnen"),
+    ("Write arguments to standard output", "Argumente auf die Standardausgabe schreiben"),
+    ("Exit the shell with status n, or that of the last command", "Die Shell mit Status n oder dem des letzten Befehls beenden"),
+    ("Export variables to the environment of commands", "Variablen in die Umgebung von Befehlen exportieren"),
+    ("Remember or display program locations", "Orte von Programmen merken oder anzeigen"),
+    ("Send a signal to jobs or processes", "Ein Signal an Jobs oder Prozesse senden"),
+    ("Describe builtin commands", "Eingebaute Befehle beschreiben"),
+    ("Move a job to the foreground", "Einen Job in den Vordergrund holen"),
+    ("Display the command history", "Den Befehlsverlauf anzeigen"),
+    ("Display the status of jobs", "Den Status von Jobs anzeigen"),
+    ("Remove a directory from the directory stack", "Ein Verzeichnis vom Verzeichnisstapel entfernen"),
+    ("Print the current directory", "Das aktuelle Verzeichnis ausgeben"),
+    ("Read a line from standard input into variables", "Eine Zeile von der Standardeingabe in Variablen lesen"),
+    ("Add a directory to the directory stack", "Ein Verzeichnis auf den Verzeichnisstapel legen"),
+    ("Set options and positional parameters", "Optionen und Positionsparameter setzen"),
+    ("Shift positional parameters", "Positionsparameter verschieben"),
+    ("Run commands from a file in the current shell", "Befehle aus einer Datei in der aktuellen Shell ausführen"),
+    ("Run commands on shell events", "Befehle bei Shell-Ereignissen ausführen"),
+    ("Describe how command names are resolved", "Beschreiben, wie Befehlsnamen aufgelöst werden"),
+    ("Remove aliases", "Aliase entfernen"),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use crate::locale::{catalog, compare_with, format_message, is_c_locale, lookup, CATALOGS};
+
+    #[test]
+    fn test_catalog_selection() {
+        let de = catalog("de_DE.UTF-8");
+        assert_eq!(lookup(de, "{}: command not found"), "{}: Befehl nicht gefunden");
+        assert_eq!(lookup(catalog("de"), "interrupted"), "unterbrochen");
+        // no translation, or no catalog for the language
+        assert_eq!(lookup(de, "no such message"), "no such message");
+        assert_eq!(lookup(catalog("en_US.UTF-8"), "interrupted"), "interrupted");
+        assert_eq!(lookup(catalog("C"), "interrupted"), "interrupted");
+    }
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        for (_, catalog) in CATALOGS {
+            for (english, translated) in catalog.iter() {
+                assert_eq!(english.matches("{}").count(), translated.matches("{}").count(), "{}", english);
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_message() {
+        assert_eq!(format_message("{}: {}: exited with status {}", &[&"rush", &"ls", &2]), "rush: ls: exited with status 2");
+        assert_eq!(format_message("interrupted", &[]), "interrupted");
+    }
+
+    #[test]
+    fn test_compare() {
+        assert!(is_c_locale("C") && is_c_locale("C.UTF-8") && is_c_locale("POSIX") && !is_c_locale("en_US.UTF-8"));
+        let mut names = vec!["b", "Zed", "a", "B"];
+        names.sort_by(|a, b| compare_with(false, a, b));
+        assert_eq!(names, vec!["B", "Zed", "a", "b"]);
+        names.sort_by(|a, b| compare_with(true, a, b));
+        assert_eq!(names, vec!["a", "B", "b", "Zed"]);
+        assert_eq!(compare_with(true, "a", "A"), Ordering::Greater);
+    }
+}
@@ -1,72 +1,258 @@
-mod tokenizer;
-mod builtins;
-mod path_utils;
-
 use std::env;
-use std::io::{self, Write};
-use std::process::Command;
-use tokenizer::tokenize;
-use crate::builtins::{Builtin, Execute};
-use crate::path_utils::find_in_path;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Read, Write};
+use clap::{Parser, Subcommand};
+use rush::tokenizer::{self, SyntaxError};
+use rush::{dirs, encoding, format, shell, syntax, Shell, ShellConfig};
+
+/// rush, a small Unix-style shell
+#[derive(Parser)]
+#[command(name = "rush", version, args_conflicts_with_subcommands = true, disable_help_subcommand = true)]
+struct Cli {
+    #[command(subcommand)]
+    action: Option<Action>,
+    /// Run COMMAND; the first operand becomes $0 and the rest the positional parameters
+    #[arg(short = 'c', value_name = "COMMAND")]
+    command: Option<String>,
+    /// Read commands from standard input; all operands become positional parameters
+    #[arg(short = 's', conflicts_with = "command")]
+    stdin: bool,
+    /// Run interactively even when standard input isn't a terminal
+    #[arg(short = 'i')]
+    interactive: bool,
+    /// Act as a login shell
+    #[arg(short = 'l', long = "login")]
+    login: bool,
+    /// Check syntax without running anything
+    #[arg(short = 'n')]
+    no_exec: bool,
+    /// Print the tokens and syntax tree of every line without running anything
+    #[arg(long, conflicts_with_all = ["no_exec", "explain"])]
+    dump_ast: bool,
+    /// Like --dump-ast, plus the words each command runs with after expansion and where it is found
+    #[arg(long, conflicts_with = "no_exec")]
+    explain: bool,
+    /// Don't read the rc file
+    #[arg(long)]
+    norc: bool,
+    /// Read FILE instead of the default rc file
+    #[arg(long, value_name = "FILE")]
+    rcfile: Option<String>,
+    /// Turn on strict mode (set -o strict)
+    #[arg(long)]
+    strict: bool,
+    /// Turn on POSIX mode (set -o posix)
+    #[arg(long)]
+    posix: bool,
+    /// Run the script under the step debugger
+    #[arg(long)]
+    debug: bool,
+    /// Print per-command timings when the script exits
+    #[arg(long)]
+    profile: bool,
+    /// Print how long each startup phase took (config, rc file, plugins, ...) once the shell is ready
+    #[arg(long)]
+    profile_startup: bool,
+    /// The script to run followed by its arguments; everything after the script is passed to it as is
+    #[arg(value_name = "SCRIPT", trailing_var_arg = true)]
+    operands: Vec<OsString>,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Rewrite scripts in the standard style; without files, format stdin to stdout
+    Fmt {
+        /// Change nothing; print a diff and exit with status 1 if any file isn't formatted
+        #[arg(long)]
+        check: bool,
+        /// Scripts to format in place
+        files: Vec<OsString>,
+    },
+}
 
 fn main() -> anyhow::Result<()> {
-    let path = env::var("PATH").unwrap_or_default();
-    let path_dirs = path.split(if cfg!(windows) { ';' } else { ':' }).map(|s| s.to_string()).collect();
-    let mut ctx = builtins::ShellContext::new(path_dirs);
+    // RUSH_TRACE=debug (or any tracing filter, e.g. `rush::shell=debug`) logs timestamped events to stderr
+    if let Ok(filter) = env::var("RUSH_TRACE") {
+        tracing_subscriber::fmt()
+            .with_env_filter(tracing_subscriber::EnvFilter::new(filter))
+            .with_writer(io::stderr)
+            .init();
+    }
 
-    loop {
-        print!("$ ");
-        io::stdout().flush()?;
+    // usage errors exit with status 2, --help and --version with 0
+    let cli = Cli::parse();
+    if let Some(Action::Fmt { check, files }) = &cli.action {
+        std::process::exit(format_files(*check, files)?);
+    }
+    // arguments may be file names that aren't valid UTF-8
+    let mut operands = cli.operands.iter().map(|arg| encoding::from_os(arg));
 
-        // wait for command input
-        let mut buffer = String::new();
-        io::stdin().read_line(&mut buffer)?;
-        let input = buffer.trim_end().to_owned();
+    let mut config = ShellConfig {
+        interactive: cli.interactive || (cli.command.is_none() && io::stdin().is_terminal()),
+        // login(1) and friends start the login shell with a leading dash in argv[0]
+        login: cli.login || env::args_os().next().is_some_and(|arg0| encoding::from_os(&arg0).starts_with('-')),
+        rc_file: if cli.norc { None } else { cli.rcfile.or_else(|| dirs::config_file("rushrc", "~/.rushrc")) },
+        debug: cli.debug,
+        profile: cli.profile,
+        profile_startup: cli.profile_startup,
+        ..ShellConfig::default()
+    };
+    if cli.strict {
+        config.options.set("strict", true)?;
+    }
+    if cli.posix {
+        config.options.set("posix", true)?;
+    }
 
-        // evaluate
-        let tokens = match tokenize(&input) {
-            Ok(t) => t,
-            Err(e) => {
-                eprintln!("rush: {}", e);
-                continue;
+    // with -c the first operand names the shell, with -s there's no script, otherwise it is the script
+    let name = if cli.command.is_some() || !cli.stdin { operands.next() } else { None };
+    config.positional_params = operands.collect();
+    let script = name.clone().filter(|_| cli.command.is_none());
+
+    // -n, --dump-ast and --explain read the command, the script or stdin without running it
+    if cli.no_exec || cli.dump_ast || cli.explain {
+        let (name, contents) = match (&cli.command, &script) {
+            (Some(command), _) => ("-c", command.clone()),
+            (None, Some(path)) => (path.as_str(), read_script(path)),
+            (None, None) => {
+                let mut bytes = Vec::new();
+                io::stdin().read_to_end(&mut bytes)?;
+                ("stdin", encoding::decode(&bytes))
             }
         };
-        if tokens.is_empty() {
-            continue;
+        if cli.explain {
+            // expansion depends on the aliases and options set up by the startup files
+            let mut shell = Shell::new(config);
+            shell.context().shell_name = name.to_string();
+            let status = shell.explain(&contents);
+            shell.exit(status);
         }
-        let (command, args) = (tokens[0].as_str(), tokens[1..].to_vec());
+        std::process::exit(if cli.dump_ast { dump_script(name, &contents) } else { check_script(name, &contents) });
+    }
+
+    if script.is_none() && (config.debug || config.profile) {
+        eprintln!("rush: --debug and --profile require a script");
+        std::process::exit(2);
+    }
 
-        // if exit, break
-        if command == "exit" {
-            break Ok(());
+    let mut shell = Shell::new(config);
+    let status = match (cli.command, script) {
+        (Some(command), _) => {
+            if let Some(name) = name {
+                shell.context().shell_name = name;
+            }
+            shell.run_command(&command)
         }
+        (None, Some(path)) => shell.run_script(&path),
+        (None, None) => shell.run_interactive(),
+    };
+    shell.exit(status)
+}
 
-        match Builtin::from_name(command){
-            Some(builtin) => {
-                if let Err(e) = builtin.execute(&args, &mut ctx) {
-                    eprintln!("rush: {}", e);
-                }
-            },
-            None => {
-                // Try to execute as an external program
-                let path_dirs_ref: Vec<&str> = ctx.path_dirs.iter().map(|s| s.as_str()).collect();
-                if find_in_path(command, &path_dirs_ref).is_some() {
-                    let program_args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-                    match Command::new(command).args(&program_args).output() {
-                        Ok(output) => {
-                            io::stdout().write_all(&output.stdout)?;
-                            io::stderr().write_all(&output.stderr)?;
-                        }
-                        Err(e) => {
-                            eprintln!("rush: failed to execute {}: {}", command, e);
-                        }
-                    }
-                } else {
-                    eprintln!("{}: command not found", command);
-                }
-            },
+fn read_script(path: &str) -> String {
+    match encoding::read_file(encoding::to_path(path)) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("rush: {}: {}", path, e);
+            std::process::exit(127);
         }
+    }
+}
 
-        io::stdout().flush()?;
+// Reports every syntax error as `name:line:column: message` and returns the exit status for -n
+fn check_script(name: &str, contents: &str) -> i32 {
+    let mut status = 0;
+    for command in shell::read_commands(contents) {
+        for (lines, error) in syntax::check_input(&command.input) {
+            print_syntax_error(name, command.line + lines, &error);
+            status = 2;
+        }
+    }
+    status
+}
+
+// Prints the tokens and syntax tree of every command line for --dump-ast, leaving out the bodies of
+// here-documents; returns 2 if any line has syntax errors
+fn dump_script(name: &str, contents: &str) -> i32 {
+    let mut status = 0;
+    for command in shell::read_commands(contents) {
+        let errors = syntax::check_input(&command.input);
+        for (lines, error) in &errors {
+            print_syntax_error(name, command.line + lines, error);
+            status = 2;
+        }
+        if !errors.is_empty() {
+            continue;
+        }
+        // a command spanning lines is dumped one line at a time
+        let mut line = command.line;
+        for input in tokenizer::split_lines(&command.input) {
+            if let Ok(parsed) = syntax::parse_line(input)
+                && parsed.command != syntax::Command::Empty
+            {
+                print!("{}:{}: {}\n{}", name, line, input.trim(), syntax::dump_line(&parsed));
+            }
+            line += input.matches('\n').count() + 1;
+        }
+    }
+    status
+}
+
+fn print_syntax_error(name: &str, line: usize, error: &SyntaxError) {
+    eprintln!("{}:{}:{}: {}", name, line, error.column, error.message);
+}
+
+// `rush fmt`: rewrites each file in place, or with --check prints what would change. Returns 1 when a file
+// can't be read or written or --check found one that isn't formatted, and 2 when one has syntax errors.
+fn format_files(check: bool, files: &[OsString]) -> anyhow::Result<i32> {
+    if files.is_empty() {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        let contents = encoding::decode(&bytes);
+        return Ok(match format::format_script(&contents) {
+            Err(errors) => {
+                errors.iter().for_each(|(line, error)| print_syntax_error("stdin", *line, error));
+                2
+            }
+            Ok(formatted) if check => {
+                io::stdout().write_all(&encoding::encode(&format::diff("stdin", &contents, &formatted)))?;
+                (formatted != contents) as i32
+            }
+            Ok(formatted) => {
+                io::stdout().write_all(&encoding::encode(&formatted))?;
+                0
+            }
+        });
+    }
+
+    let mut status = 0;
+    for file in files {
+        let path = encoding::from_os(file);
+        let contents = match encoding::read_file(encoding::to_path(&path)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("rush: {}: {}", path, e);
+                status = status.max(1);
+                continue;
+            }
+        };
+        match format::format_script(&contents) {
+            Err(errors) => {
+                errors.iter().for_each(|(line, error)| print_syntax_error(&path, *line, error));
+                status = 2;
+            }
+            Ok(formatted) if formatted == contents => {}
+            Ok(formatted) if check => {
+                io::stdout().write_all(&encoding::encode(&format::diff(&path, &contents, &formatted)))?;
+                status = status.max(1);
+            }
+            Ok(formatted) => {
+                if let Err(e) = std::fs::write(encoding::to_path(&path), encoding::encode(&formatted)) {
+                    eprintln!("rush: {}: {}", path, e);
+                    status = status.max(1);
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+    Ok(status)
+}
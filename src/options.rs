@@ -0,0 +1,84 @@
+use crate::locale::tr;
+
+// Names accepted by `set -o` / `set +o`, in listing order
+pub const OPTION_NAMES: &[&str] = &["errexit", "failglob", "nounset", "pipefail", "posix", "strict", "xtrace"];
+
+#[derive(Default)]
+pub struct ShellOptions {
+    pub errexit: bool,
+    // a pattern that matches no files is an error instead of staying as it is
+    pub failglob: bool,
+    pub nounset: bool,
+    pub pipefail: bool,
+    // POSIX sh behavior: no [[ ]], no (( )), no aliases in scripts, $ENV instead of the rc file
+    pub posix: bool,
+    // errexit + nounset + pipefail, with line info in every diagnostic
+    pub strict: bool,
+    pub xtrace: bool,
+}
+
+impl ShellOptions {
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "errexit" => Some(self.errexit),
+            "failglob" => Some(self.failglob),
+            "nounset" => Some(self.nounset),
+            "pipefail" => Some(self.pipefail),
+            "posix" => Some(self.posix),
+            "strict" => Some(self.strict),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, on: bool) -> anyhow::Result<()> {
+        match name {
+            "errexit" => self.errexit = on,
+            "failglob" => self.failglob = on,
+            "nounset" => self.nounset = on,
+            "pipefail" => self.pipefail = on,
+            "posix" => self.posix = on,
+            "xtrace" => self.xtrace = on,
+            "strict" => {
+                self.strict = on;
+                self.errexit = on;
+                self.nounset = on;
+                self.pipefail = on;
+            }
+            _ => anyhow::bail!(tr!("{}: invalid option name", name)),
+        }
+        Ok(())
+    }
+
+    // Maps single-letter flags (`set -e`) to their long option names
+    pub fn from_flag(flag: char) -> Option<&'static str> {
+        match flag {
+            'e' => Some("errexit"),
+            'u' => Some("nounset"),
+            'x' => Some("xtrace"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::ShellOptions;
+
+    #[test]
+    fn test_strict_enables_all() {
+        let mut options = ShellOptions::default();
+        options.set("strict", true).unwrap();
+        assert!(options.errexit && options.nounset && options.pipefail);
+
+        options.set("strict", false).unwrap();
+        assert!(!options.errexit && !options.nounset && !options.pipefail);
+    }
+
+    #[test]
+    fn test_invalid_option() {
+        let mut options = ShellOptions::default();
+        assert!(options.set("bogus", true).is_err());
+        assert_eq!(options.get("bogus"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! The names of the programs in PATH, for command completion.
+//!
+//! Scanning hundreds of PATH directories can take a noticeable moment, so the index is built on a
+//! background thread: the shell checks for changes each time it has printed the prompt, and
+//! lookups answer from whatever has been indexed so far instead of waiting.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+use crate::encoding::{from_os, to_path};
+use crate::path_utils::is_executable;
+
+#[derive(Default)]
+struct Index {
+    // the directories that were scanned, with their modification times at the time
+    dirs: Vec<(String, Option<SystemTime>)>,
+    names: BTreeSet<String>,
+}
+
+#[derive(Default)]
+pub struct PathIndex {
+    index: Arc<Mutex<Index>>,
+    scan: RefCell<Option<JoinHandle<()>>>,
+}
+
+impl PathIndex {
+    /// Starts bringing the index up to date with `path_dirs` in the background, unless that is already
+    /// happening. The directories are rescanned when they aren't the ones indexed or one of them was
+    /// modified since, i.e. a program was added or removed.
+    pub fn refresh(&self, path_dirs: &[String]) {
+        let index = Arc::clone(&self.index);
+        let path_dirs = path_dirs.to_vec();
+        // there are no threads to scan on in the browser, and nothing in the real PATH to find anyway
+        #[cfg(target_arch = "wasm32")]
+        update(&index, path_dirs);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut scan = self.scan.borrow_mut();
+            if scan.as_ref().is_some_and(|handle| !handle.is_finished()) {
+                return;
+            }
+            *scan = Some(std::thread::spawn(move || update(&index, path_dirs)));
+        }
+    }
+
+    /// Waits for a scan in progress to finish.
+    pub fn wait(&self) {
+        if let Some(handle) = self.scan.borrow_mut().take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// The indexed program names starting with `prefix`, sorted. Answers from the index as it is, even while
+    /// a scan is running.
+    pub fn matches(&self, prefix: &str) -> Vec<String> {
+        let index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.names.range(prefix.to_string()..).take_while(|name| name.starts_with(prefix)).cloned().collect()
+    }
+
+    /// Like [`matches`](Self::matches), regardless of case, which takes going through all the names.
+    pub fn matches_ignoring_case(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        index.names.iter().filter(|name| name.to_lowercase().starts_with(&prefix)).cloned().collect()
+    }
+}
+
+fn update(index: &Mutex<Index>, path_dirs: Vec<String>) {
+    let dirs: Vec<(String, Option<SystemTime>)> = path_dirs
+        .into_iter()
+        .map(|dir| {
+            let modified = fs::metadata(to_path(&dir)).and_then(|metadata| metadata.modified()).ok();
+            (dir, modified)
+        })
+        .collect();
+    if index.lock().unwrap_or_else(PoisonError::into_inner).dirs == dirs {
+        return;
+    }
+
+    // scan without holding the lock, so lookups keep answering from the old index meanwhile
+    let mut names = BTreeSet::new();
+    for (dir, _) in &dirs {
+        let Ok(entries) = fs::read_dir(to_path(dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if is_executable(&entry.path()) {
+                names.insert(from_os(&entry.file_name()));
+            }
+        }
+    }
+    tracing::debug!(dirs = dirs.len(), programs = names.len(), "path index");
+    *index.lock().unwrap_or_else(PoisonError::into_inner) = Index { dirs, names };
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use crate::path_index::PathIndex;
+
+    fn program(path: &Path) {
+        fs::write(path, "").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_refresh() {
+        let root = std::env::temp_dir().join(format!("rush-path-index-{}", std::process::id()));
+        let (bin, sbin) = (root.join("bin"), root.join("sbin"));
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&sbin).unwrap();
+        program(&bin.join("rush-a"));
+        fs::write(bin.join("rush-data"), "").unwrap();
+        program(&sbin.join("rush-b"));
+        let bin_dir = bin.to_string_lossy().to_string();
+
+        let index = PathIndex::default();
+        assert!(index.matches("rush-").is_empty());
+        index.refresh(std::slice::from_ref(&bin_dir));
+        index.wait();
+        assert_eq!(index.matches("rush-"), vec!["rush-a"]);
+
+        // adding a program changes the directory's modification time
+        program(&bin.join("rush-c"));
+        index.refresh(std::slice::from_ref(&bin_dir));
+        index.wait();
+        assert_eq!(index.matches("rush-"), vec!["rush-a", "rush-c"]);
+
+        index.refresh(&[bin_dir, sbin.to_string_lossy().to_string()]);
+        index.wait();
+        assert_eq!(index.matches("rush-"), vec!["rush-a", "rush-b", "rush-c"]);
+        assert_eq!(index.matches("rush-b"), vec!["rush-b"]);
+        assert_eq!(index.matches_ignoring_case("RUSH-B"), vec!["rush-b"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,75 +1,289 @@
-use std::env;
 use anyhow::Context;
+use crate::encoding::{env_var, to_path};
+use crate::locale::tr;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
-#[cfg(windows)]
-const WINDOWS_EXECUTABLES: &[&str] = &["exe", "bat", "cmd", "com", "ps1"];
+// What Windows uses when PATHEXT isn't set
+const DEFAULT_PATHEXT: &str = ".COM;.EXE;.BAT;.CMD";
 
 pub fn is_executable(file_path: &std::path::Path) -> bool {
     #[cfg(unix)]
     {
-        if file_path.exists() {
-            if let Ok(metadata) = std::fs::metadata(file_path) {
+        if file_path.exists()
+            && let Ok(metadata) = std::fs::metadata(file_path) {
                 let permissions = metadata.permissions();
                 return permissions.mode() & 0o111 != 0;
             }
-        }
         false
     }
 
     #[cfg(windows)]
     {
-        if file_path.exists() {
-            if let Some(ext) = file_path.extension() {
-                let ext = ext.to_str().unwrap_or("").to_lowercase();
-                return WINDOWS_EXECUTABLES.contains(&ext.as_str());
-            }
-        }
-        false
+        let name = crate::encoding::from_os(file_path.as_os_str()).to_lowercase();
+        file_path.is_file()
+            && executable_extensions(env_var("PATHEXT").as_deref()).iter().any(|ext| name.ends_with(ext.as_str()))
+    }
+
+    // no permission bits to check elsewhere (e.g. WASM)
+    #[cfg(not(any(unix, windows)))]
+    {
+        file_path.is_file()
+    }
+}
+
+/// The extensions PATHEXT marks as executable on Windows, lowercased with their dots, e.g. `.exe`.
+pub fn executable_extensions(pathext: Option<&str>) -> Vec<String> {
+    let pathext = pathext.filter(|value| !value.trim().is_empty()).unwrap_or(DEFAULT_PATHEXT);
+    pathext
+        .split(';')
+        .map(|ext| ext.trim().to_lowercase())
+        .filter(|ext| ext.len() > 1 && ext.starts_with('.'))
+        .collect()
+}
+
+/// The file names a command can refer to on Windows: the name itself when it already ends in one of
+/// `extensions`, otherwise the name with each of them appended, in PATHEXT order.
+pub fn candidate_names(name: &str, extensions: &[String]) -> Vec<String> {
+    let lower = name.to_lowercase();
+    if extensions.iter().any(|ext| lower.ends_with(ext.as_str())) {
+        vec![name.to_string()]
+    } else {
+        extensions.iter().map(|ext| format!("{}{}", name, ext)).collect()
     }
 }
 
+/// The directories of a PATH-style list, which separates them with `;` on Windows and `:` elsewhere.
+pub fn split_path_list(path: &str) -> Vec<String> {
+    path.split(if cfg!(windows) { ';' } else { ':' }).map(|dir| dir.to_string()).collect()
+}
+
+/// Finds the file to run for `program_name`: the name itself when it has a directory in it, else the first
+/// executable file of that name in `path_dirs`. A file that is found but can't run is still returned when
+/// there's nothing better, so that starting it fails with the reason rather than the command not being found.
 pub fn find_in_path(program_name: &str, path_dirs: &[&str]) -> Option<std::path::PathBuf> {
+    // `python` means `python.exe` (or .bat, .cmd, ...) on Windows
+    let names = if cfg!(windows) {
+        candidate_names(program_name, &executable_extensions(env_var("PATHEXT").as_deref()))
+    } else {
+        vec![program_name.to_string()]
+    };
+    // a name with a directory in it is a path, relative to the working directory rather than to PATH's
+    if program_name.contains(std::path::is_separator) {
+        let found = names.iter().map(|name| to_path(name)).find(|path| path.is_file() && is_executable(path));
+        let found = found.or_else(|| Some(to_path(program_name)).filter(|path| path.exists()));
+        tracing::debug!(program = program_name, found = found.is_some(), "resolve path");
+        return found;
+    }
+    let mut unusable = None;
     for dir in path_dirs {
-        let file_path = std::path::Path::new(dir).join(program_name);
-        if is_executable(&file_path) {
-            return Some(file_path);
+        for name in &names {
+            let file_path = to_path(dir).join(to_path(name));
+            if !file_path.is_file() {
+                continue;
+            }
+            if is_executable(&file_path) {
+                tracing::debug!(program = program_name, path = %file_path.display(), "resolve");
+                return Some(file_path);
+            }
+            unusable.get_or_insert(file_path);
         }
     }
-    None
+    tracing::debug!(program = program_name, dirs = path_dirs.len(), unusable = ?unusable, "resolve: not found");
+    unusable
 }
 
-pub fn is_absolute_path(path: &str) -> bool {
-    // Check for Unix absolute path (starts with /)
-    if path.starts_with('/') {
-        return true;
+/// Removes the `.` components of a path and each `..` along with the component before it, without looking at
+/// the filesystem. `..` at the root stays there.
+pub fn normalize_lexically(path: &std::path::Path) -> std::path::PathBuf {
+    let mut normalized = std::path::PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
     }
+    normalized
+}
 
-    // Check for Windows absolute path (e.g., C:\, D:\)
-    #[cfg(windows)]
+pub fn is_absolute_path(path: &str) -> bool {
+    // `/x`, or on Windows also `\x` (the root of the current drive), `C:\x`, `C:/x` and `\\server\share`
+    path.starts_with(std::path::is_separator) || to_path(path).is_absolute()
+}
+
+/// Splits a drive-relative Windows path, `D:` or `D:docs`, into the uppercase drive letter and the rest.
+/// `D:\docs` has a root and isn't drive-relative.
+pub fn split_drive(path: &str) -> Option<(char, &str)> {
+    let drive = path.chars().next().filter(char::is_ascii_alphabetic)?;
+    let rest = path[1..].strip_prefix(':')?;
+    (!rest.starts_with(['/', '\\'])).then_some((drive.to_ascii_uppercase(), rest))
+}
+
+/// Drops the `\\?\` prefix of a long Windows path where the path means the same without it:
+/// `\\?\C:\x` becomes `C:\x` and `\\?\UNC\server\share` becomes `\\server\share`. Such paths can't go
+/// through `..`, so they are unwieldy to show and to resolve relative paths against.
+pub fn strip_verbatim(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\")
+        && let [drive, b':', ..] = rest.as_bytes()
+        && drive.is_ascii_alphabetic()
     {
-        if path.len() >= 3 {
-            let chars: Vec<char> = path.chars().collect();
-            if chars[0].is_alphabetic() && chars[1] == ':' && (chars[2] == '\\' || chars[2] == '/') {
-                return true;
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// The directory `~` stands for: HOME, or on Windows, where HOME usually isn't set, the user's profile.
+pub fn home_dir() -> Option<String> {
+    home_dir_with(env_var)
+}
+
+/// Like `home_dir`, looking variables up with `var`.
+pub fn home_dir_with(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let home = var("HOME").filter(|home| !home.is_empty());
+    if cfg!(windows) { home.or_else(|| windows_home(&var)) } else { home }
+}
+
+// USERPROFILE, or else HOMEDRIVE followed by HOMEPATH, which is how Windows finds the user's home
+fn windows_home(var: &impl Fn(&str) -> Option<String>) -> Option<String> {
+    let non_empty = |name: &str| var(name).filter(|value| !value.is_empty());
+    non_empty("USERPROFILE").or_else(|| Some(non_empty("HOMEDRIVE")? + &non_empty("HOMEPATH")?))
+}
+
+/// The home directory of the user `user`, from the passwd database on Unix. Windows keeps profiles side by
+/// side, so it is the directory next to USERPROFILE named for the user, if there is one.
+#[cfg(unix)]
+pub fn user_home_dir(user: &str) -> Option<String> {
+    let name = std::ffi::CString::new(user).ok()?;
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        let status =
+            unsafe { libc::getpwnam_r(name.as_ptr(), &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut found) };
+        match status {
+            // the entry didn't fit
+            libc::ERANGE if buffer.len() < 1 << 20 => buffer.resize(buffer.len() * 2, 0),
+            0 if !found.is_null() && !passwd.pw_dir.is_null() => {
+                let dir = unsafe { std::ffi::CStr::from_ptr(passwd.pw_dir) };
+                return Some(dir.to_string_lossy().into_owned());
             }
+            _ => return None,
         }
     }
+}
 
-    false
+#[cfg(windows)]
+pub fn user_home_dir(user: &str) -> Option<String> {
+    let profile = env_var("USERPROFILE").filter(|profile| !profile.is_empty())?;
+    if env_var("USERNAME").is_some_and(|name| name.eq_ignore_ascii_case(user)) {
+        return Some(profile);
+    }
+    let dir = to_path(&profile).parent()?.join(to_path(user));
+    dir.is_dir().then(|| crate::encoding::from_os(dir.as_os_str()))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn user_home_dir(_user: &str) -> Option<String> {
+    None
 }
 
 pub fn expand_tilde(path: &str) -> anyhow::Result<String> {
-    if path == "~" {
-        // Just ~, return home directory
-        env::var("HOME").context("HOME environment variable not set")
-    } else if path.starts_with("~/") {
-        // ~/something, replace ~ with home directory
-        let home = env::var("HOME").context("HOME environment variable not set")?;
-        Ok(path.replacen("~", &home, 1))
+    expand_tilde_with(path, home_dir())
+}
+
+/// Like `expand_tilde`, with the home directory supplied by the caller. `~user` is the home directory of
+/// `user`, see `user_home_dir`.
+pub fn expand_tilde_with(path: &str, home: Option<String>) -> anyhow::Result<String> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Ok(path.to_string());
+    };
+    // ~, ~/something (or ~\something on Windows) and ~user/something
+    let (user, rest) = rest.split_at(rest.find(std::path::is_separator).unwrap_or(rest.len()));
+    if user.is_empty() {
+        let home = home.with_context(|| tr!("HOME environment variable not set"))?;
+        Ok(format!("{}{}", home, rest))
     } else {
-        // No tilde, return as-is
-        Ok(path.to_string())
+        // an unknown user's ~name is just a name
+        Ok(user_home_dir(user).map_or_else(|| path.to_string(), |home| format!("{}{}", home, rest)))
+    }
+}
+
+/// The reverse of `expand_tilde_with`: `path` with `~` for `home` when it is the home directory or in it.
+pub fn abbreviate_home(path: &str, home: Option<String>) -> String {
+    match home {
+        Some(home) if path == home => "~".to_string(),
+        Some(home) if path.strip_prefix(&home).is_some_and(|rest| rest.starts_with(std::path::is_separator)) => {
+            format!("~{}", &path[home.len()..])
+        }
+        _ => path.to_string(),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path_utils::{
+        candidate_names, executable_extensions, expand_tilde_with, split_drive, strip_verbatim, windows_home,
+    };
+
+    #[test]
+    fn test_executable_extensions() {
+        assert_eq!(executable_extensions(None), vec![".com", ".exe", ".bat", ".cmd"]);
+        assert_eq!(executable_extensions(Some(".EXE; .Py;;bad")), vec![".exe", ".py"]);
+        assert_eq!(executable_extensions(Some("")), executable_extensions(None));
+    }
+
+    #[test]
+    fn test_candidate_names() {
+        let extensions = executable_extensions(None);
+        assert_eq!(candidate_names("python", &extensions), vec!["python.com", "python.exe", "python.bat", "python.cmd"]);
+        assert_eq!(candidate_names("build.CMD", &extensions), vec!["build.CMD"]);
+        assert_eq!(candidate_names("v1.2", &extensions)[1], "v1.2.exe");
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = || Some("/home/me".to_string());
+        assert_eq!(expand_tilde_with("~", home()).unwrap(), "/home/me");
+        assert_eq!(expand_tilde_with("~/src", home()).unwrap(), "/home/me/src");
+        assert_eq!(expand_tilde_with("a~", home()).unwrap(), "a~");
+        assert!(expand_tilde_with("~/src", None).is_err());
+        assert_eq!(expand_tilde_with("~rush-no-such-user/x", home()).unwrap(), "~rush-no-such-user/x");
+        #[cfg(unix)]
+        assert!(!expand_tilde_with("~root/x", None).unwrap().starts_with('~'));
+    }
+
+    #[test]
+    fn test_split_drive() {
+        assert_eq!(split_drive("d:"), Some(('D', "")));
+        assert_eq!(split_drive("D:docs\\notes"), Some(('D', "docs\\notes")));
+        assert_eq!(split_drive("D:\\docs"), None);
+        assert_eq!(split_drive("D:/docs"), None);
+        assert_eq!(split_drive("docs"), None);
+        assert_eq!(split_drive("1:"), None);
+    }
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(strip_verbatim(r"\\?\C:\Users\me"), r"C:\Users\me");
+        assert_eq!(strip_verbatim(r"\\?\UNC\server\share\dir"), r"\\server\share\dir");
+        assert_eq!(strip_verbatim(r"\\?\Volume{0b1c}\dir"), r"\\?\Volume{0b1c}\dir");
+        assert_eq!(strip_verbatim(r"\\server\share"), r"\\server\share");
+        assert_eq!(strip_verbatim("/home/me"), "/home/me");
+    }
+
+    #[test]
+    fn test_windows_home() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+        };
+        assert_eq!(windows_home(&vars(&[("USERPROFILE", "C:\\Users\\me"), ("HOMEDRIVE", "D:")])), Some("C:\\Users\\me".to_string()));
+        assert_eq!(windows_home(&vars(&[("HOMEDRIVE", "D:"), ("HOMEPATH", "\\me")])), Some("D:\\me".to_string()));
+        assert_eq!(windows_home(&vars(&[("USERPROFILE", ""), ("HOMEDRIVE", "D:")])), None);
+    }
+}
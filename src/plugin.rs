@@ -0,0 +1,228 @@
+//! Plugins: builtins, prompt segments and completion providers that live outside rush.
+//!
+//! Rust code embedding rush registers them directly: builtins on `ShellContext::builtins`, and
+//! [`PromptSegment`]s and [`CompletionProvider`]s on the context. Other plugins are dynamic libraries in the
+//! `plugins` directory of the config directory, loaded at startup through a small C ABI (see
+//! `include/rush_plugin.h`), so they can be written in any language and built with any compiler version.
+//!
+//! A plugin library exports two functions:
+//!
+//! - `uint32_t rush_plugin_abi_version(void)`, returning [`RUSH_PLUGIN_ABI_VERSION`];
+//! - `int rush_plugin_init(const RushPluginHost *host)`, which registers everything through the host's
+//!   callbacks and returns 0 on success.
+
+use std::ffi::{c_char, c_int, c_void};
+
+/// Version of the plugin ABI; a plugin built for another version is rejected.
+pub const RUSH_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Text a [`PromptSegment`] shows in front of the prompt, e.g. the current kubectl context.
+pub trait PromptSegment {
+    /// The segment's text, or an empty string to hide it.
+    fn render(&self) -> String;
+}
+
+/// Completions for the arguments of one command.
+pub trait CompletionProvider {
+    /// Candidates for the last word of `words`, which holds the command line up to the cursor.
+    fn complete(&self, words: &[String]) -> Vec<String>;
+}
+
+/// Receives output from a plugin: `sink` is the host's opaque pointer passed alongside.
+pub type RushWriteFn = unsafe extern "C" fn(sink: *mut c_void, data: *const u8, len: usize);
+/// Runs a builtin with `argc` NUL-terminated arguments, writing its output through `write`; returns the
+/// exit status.
+pub type RushBuiltinFn =
+    unsafe extern "C" fn(argc: usize, argv: *const *const c_char, sink: *mut c_void, write: RushWriteFn) -> c_int;
+/// Writes a prompt segment's text through `write`.
+pub type RushPromptSegmentFn = unsafe extern "C" fn(sink: *mut c_void, write: RushWriteFn);
+/// Writes completion candidates for `argv`, one per line, through `write`.
+pub type RushCompletionFn =
+    unsafe extern "C" fn(argc: usize, argv: *const *const c_char, sink: *mut c_void, write: RushWriteFn);
+
+/// What `rush_plugin_init` receives. Strings passed to the callbacks are copied; they only need to live
+/// for the duration of the call.
+#[repr(C)]
+pub struct RushPluginHost {
+    pub abi_version: u32,
+    pub host: *mut c_void,
+    pub register_builtin: unsafe extern "C" fn(
+        host: *mut c_void,
+        name: *const c_char,
+        synopsis: *const c_char,
+        summary: *const c_char,
+        run: RushBuiltinFn,
+    ),
+    pub register_prompt_segment: unsafe extern "C" fn(host: *mut c_void, name: *const c_char, render: RushPromptSegmentFn),
+    pub register_completion: unsafe extern "C" fn(host: *mut c_void, command: *const c_char, complete: RushCompletionFn),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use dylib::load_plugins;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod dylib {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+    use libloading::Library;
+    use crate::builtins::{Execute, Io, ShellContext};
+    use crate::encoding;
+    use crate::error::RushError;
+    use crate::locale::tr;
+    use crate::plugin::{
+        CompletionProvider, PromptSegment, RushBuiltinFn, RushCompletionFn, RushPluginHost, RushPromptSegmentFn,
+        RUSH_PLUGIN_ABI_VERSION,
+    };
+
+    // What a plugin registered during `rush_plugin_init`
+    #[derive(Default)]
+    struct Registrations {
+        builtins: Vec<(String, String, String, RushBuiltinFn)>,
+        prompt_segments: Vec<(String, RushPromptSegmentFn)>,
+        completions: Vec<(String, RushCompletionFn)>,
+    }
+
+    unsafe fn string(s: *const c_char) -> String {
+        if s.is_null() {
+            return String::new();
+        }
+        encoding::decode(unsafe { CStr::from_ptr(s) }.to_bytes())
+    }
+
+    unsafe extern "C" fn register_builtin(
+        host: *mut c_void,
+        name: *const c_char,
+        synopsis: *const c_char,
+        summary: *const c_char,
+        run: RushBuiltinFn,
+    ) {
+        let registrations = unsafe { &mut *(host as *mut Registrations) };
+        registrations.builtins.push(unsafe { (string(name), string(synopsis), string(summary), run) });
+    }
+
+    unsafe extern "C" fn register_prompt_segment(host: *mut c_void, name: *const c_char, render: RushPromptSegmentFn) {
+        let registrations = unsafe { &mut *(host as *mut Registrations) };
+        registrations.prompt_segments.push((unsafe { string(name) }, render));
+    }
+
+    unsafe extern "C" fn register_completion(host: *mut c_void, command: *const c_char, complete: RushCompletionFn) {
+        let registrations = unsafe { &mut *(host as *mut Registrations) };
+        registrations.completions.push((unsafe { string(command) }, complete));
+    }
+
+    unsafe extern "C" fn write_to_vec(sink: *mut c_void, data: *const u8, len: usize) {
+        if !data.is_null() {
+            let out = unsafe { &mut *(sink as *mut Vec<u8>) };
+            out.extend_from_slice(unsafe { std::slice::from_raw_parts(data, len) });
+        }
+    }
+
+    // Calls into the plugin with the arguments as C strings and returns the result plus what it wrote
+    fn call<T>(args: &[String], f: impl FnOnce(usize, *const *const c_char, *mut c_void) -> T) -> (T, Vec<u8>) {
+        // arguments come from the OS or the tokenizer, so they can't contain NUL bytes
+        let args: Vec<CString> = args.iter().map(|a| CString::new(encoding::encode(a)).unwrap_or_default()).collect();
+        let argv: Vec<*const c_char> = args.iter().map(|a| a.as_ptr()).collect();
+        let mut out: Vec<u8> = Vec::new();
+        let result = f(argv.len(), argv.as_ptr(), &mut out as *mut Vec<u8> as *mut c_void);
+        (result, out)
+    }
+
+    // Each registered function keeps its library loaded for as long as the shell can call it
+    struct PluginBuiltin {
+        run: RushBuiltinFn,
+        _library: Rc<Library>,
+    }
+
+    impl Execute for PluginBuiltin {
+        fn execute(&self, args: &[String], _ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+            let (status, out) = call(args, |argc, argv, sink| unsafe { (self.run)(argc, argv, sink, write_to_vec) });
+            io.stdout.write_all(&out)?;
+            match status {
+                0 => Ok(()),
+                status => Err(RushError::Status(status).into()),
+            }
+        }
+    }
+
+    struct PluginPromptSegment {
+        render: RushPromptSegmentFn,
+        _library: Rc<Library>,
+    }
+
+    impl PromptSegment for PluginPromptSegment {
+        fn render(&self) -> String {
+            let ((), out) = call(&[], |_, _, sink| unsafe { (self.render)(sink, write_to_vec) });
+            encoding::decode(&out)
+        }
+    }
+
+    struct PluginCompletion {
+        complete: RushCompletionFn,
+        _library: Rc<Library>,
+    }
+
+    impl CompletionProvider for PluginCompletion {
+        fn complete(&self, words: &[String]) -> Vec<String> {
+            let ((), out) = call(words, |argc, argv, sink| unsafe { (self.complete)(argc, argv, sink, write_to_vec) });
+            encoding::decode(&out).lines().map(|line| line.to_string()).collect()
+        }
+    }
+
+    /// Loads every dynamic library in `dir` in name order. A plugin that fails to load is reported and skipped.
+    pub fn load_plugins(dir: &Path, ctx: &mut ShellContext) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+            .collect();
+        paths.sort();
+        for path in paths {
+            if let Err(e) = load_plugin(&path, ctx) {
+                let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn load_plugin(path: &Path, ctx: &mut ShellContext) -> anyhow::Result<()> {
+        // loading runs the library's initializers; plugins in the user's own config directory are trusted
+        let library = Rc::new(unsafe { Library::new(path)? });
+        let mut registrations = Registrations::default();
+        unsafe {
+            let version = library.get::<unsafe extern "C" fn() -> u32>(b"rush_plugin_abi_version")?;
+            if version() != RUSH_PLUGIN_ABI_VERSION {
+                let (version, expected) = (version(), RUSH_PLUGIN_ABI_VERSION);
+                anyhow::bail!(tr!("plugin ABI version {} is not supported (expected {})", version, expected));
+            }
+            let init = library.get::<unsafe extern "C" fn(*const RushPluginHost) -> c_int>(b"rush_plugin_init")?;
+            let host = RushPluginHost {
+                abi_version: RUSH_PLUGIN_ABI_VERSION,
+                host: &mut registrations as *mut Registrations as *mut c_void,
+                register_builtin,
+                register_prompt_segment,
+                register_completion,
+            };
+            let status = init(&host);
+            if status != 0 {
+                anyhow::bail!(tr!("plugin initialization failed with status {}", status));
+            }
+        }
+
+        for (name, synopsis, summary, run) in registrations.builtins {
+            // registry entries are 'static; plugins stay loaded until the shell exits anyway
+            let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+            let builtin = PluginBuiltin { run, _library: Rc::clone(&library) };
+            ctx.builtins.register(leak(name), builtin, leak(synopsis), leak(summary));
+        }
+        for (name, render) in registrations.prompt_segments {
+            ctx.prompt_segments.push((name, Rc::new(PluginPromptSegment { render, _library: Rc::clone(&library) })));
+        }
+        for (command, complete) in registrations.completions {
+            ctx.completions.insert(command, Rc::new(PluginCompletion { complete, _library: Rc::clone(&library) }));
+        }
+        Ok(())
+    }
+}
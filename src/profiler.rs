@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+#[derive(Default)]
+struct CommandStats {
+    calls: usize,
+    total: Duration,
+}
+
+// Wall-clock time per executed command, keyed by line and command text
+pub struct Profiler {
+    running: Option<(usize, String, Instant)>,
+    stats: HashMap<(usize, String), CommandStats>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler { running: None, stats: HashMap::new() }
+    }
+
+    pub fn start(&mut self, line: usize, command: String) {
+        self.running = Some((line, command, Instant::now()));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((line, command, started)) = self.running.take() {
+            let stats = self.stats.entry((line, command)).or_default();
+            stats.calls += 1;
+            stats.total += started.elapsed();
+        }
+    }
+
+    /// Formats the summary table, slowest commands first.
+    pub fn report(&self) -> String {
+        let mut rows: Vec<_> = self.stats.iter().collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+
+        let mut report = format!("{:>12}  {:>6}  {:>5}  command\n", "total ms", "calls", "line");
+        let mut total = Duration::ZERO;
+        for ((line, command), stats) in rows {
+            total += stats.total;
+            let _ = writeln!(report, "{:>12.3}  {:>6}  {:>5}  {}", millis(stats.total), stats.calls, line, command);
+        }
+        let _ = writeln!(report, "{:>12.3}  total", millis(total));
+        report
+    }
+}
+
+// Wall-clock time per startup phase, from the shell's creation until it's ready for input
+pub struct StartupProfiler {
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Default for StartupProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartupProfiler {
+    pub fn new() -> Self {
+        StartupProfiler { started: Instant::now(), phases: Vec::new() }
+    }
+
+    /// Runs `f` as the phase `name`.
+    pub fn phase<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.phases.push((name, started.elapsed()));
+        result
+    }
+
+    /// Formats the phases in the order they ran, and the time from the start until now.
+    pub fn report(&self) -> String {
+        let mut report = format!("{:>12}  phase\n", "startup ms");
+        for (name, duration) in &self.phases {
+            let _ = writeln!(report, "{:>12.3}  {}", millis(*duration), name);
+        }
+        let _ = writeln!(report, "{:>12.3}  ready", millis(self.started.elapsed()));
+        report
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::profiler::{Profiler, StartupProfiler};
+
+    #[test]
+    fn test_report_sorted_by_total_time() {
+        let mut profiler = Profiler::new();
+        profiler.start(1, "fast".to_string());
+        profiler.stop();
+        profiler.start(2, "slow".to_string());
+        std::thread::sleep(Duration::from_millis(20));
+        profiler.stop();
+        profiler.start(1, "fast".to_string());
+        profiler.stop();
+
+        let report = profiler.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[1].ends_with("slow"));
+        assert!(lines[2].ends_with("fast"));
+        assert!(lines[2].contains("     2  "));
+        assert!(lines[3].ends_with("total"));
+    }
+
+    #[test]
+    fn test_stop_without_start() {
+        let mut profiler = Profiler::new();
+        profiler.stop();
+        assert_eq!(profiler.report().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_startup_report_in_phase_order() {
+        let mut profiler = StartupProfiler::new();
+        assert_eq!(profiler.phase("config", || 42), 42);
+        profiler.phase("rc file", || std::thread::sleep(Duration::from_millis(5)));
+
+        let report = profiler.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].ends_with("config"));
+        assert!(lines[2].ends_with("rc file"));
+        assert!(lines[3].ends_with("ready"));
+    }
+}
@@ -0,0 +1,232 @@
+//! The prompt an interactive shell shows, rendered from the template in PS1.
+//!
+//! The template may use bash's escapes: `\u` the user name, `\h` the host name up to the first dot and `\H`
+//! all of it, `\w` the current directory with the home directory shortened to `~` and `\W` its last
+//! component, `\$` `#` for root and `$` for everyone else, `\s` and `\v` the shell's name and version, `\n`,
+//! `\e`, `\a` and `\\`. `\[` and `\]`, which bash needs around escape sequences, are accepted and dropped.
+//! rush adds `\?`, the status of the last command, `\g`, the git branch checked out in the current directory
+//! in parentheses after a space, or nothing outside a repository, and `\c`, which colors what follows green
+//! after a command that succeeded and red after one that failed, up to `\C`, in the colors of the prompt
+//! theme, and not at all where [`crate::theme`] turns colors off. Other backslashes are kept as they are.
+//! Without PS1 the prompt is `\w\$ `.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use crate::builtins::ShellContext;
+use crate::path_utils::{abbreviate_home, home_dir_with};
+use crate::theme::{self, Role};
+
+const DEFAULT_PS1: &str = r"\w\$ ";
+
+/// Where the repository of the directory the prompt was last shown in keeps its HEAD, so `\g` only looks
+/// through the directories above the current one again after `cd`.
+#[derive(Default)]
+pub struct GitCache(RefCell<Option<(PathBuf, Option<PathBuf>)>>);
+
+/// The prompt for the shell's current state, without the segments plugins add in front of it.
+pub fn render(ctx: &ShellContext) -> String {
+    let template = ctx.variables.get("PS1").unwrap_or_else(|| DEFAULT_PS1.to_string());
+    // the prompt is only shown on the terminal
+    let colors = theme::prompt_enabled(ctx);
+    let mut prompt = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => prompt.push_str(&user(ctx)),
+            Some('h') => prompt.push_str(host(ctx).split('.').next().unwrap_or_default()),
+            Some('H') => prompt.push_str(&host(ctx)),
+            Some('w') => prompt.push_str(&working_dir(ctx)),
+            Some('W') => {
+                let dir = working_dir(ctx);
+                let name = dir.rsplit(std::path::is_separator).find(|name| !name.is_empty());
+                prompt.push_str(name.unwrap_or(&dir));
+            }
+            Some('$') => prompt.push(if is_root() { '#' } else { '$' }),
+            Some('s') => prompt.push_str("rush"),
+            Some('v') => prompt.push_str(concat!(env!("CARGO_PKG_VERSION_MAJOR"), ".", env!("CARGO_PKG_VERSION_MINOR"))),
+            Some('?') => prompt.push_str(&ctx.last_status.to_string()),
+            Some('g') => {
+                if let Some(branch) = git_branch(ctx) {
+                    prompt.push_str(&format!(" ({})", branch));
+                }
+            }
+            Some('c') if colors => {
+                let role = if ctx.last_status == 0 { Role::Success } else { Role::Failure };
+                prompt.push_str(theme::prompt_color(role, ctx));
+            }
+            Some('C') if colors => prompt.push_str(theme::RESET),
+            Some('c' | 'C' | '[' | ']') => {}
+            Some('n') => prompt.push('\n'),
+            Some('e') => prompt.push('\x1b'),
+            Some('a') => prompt.push('\x07'),
+            Some('\\') => prompt.push('\\'),
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+fn user(ctx: &ShellContext) -> String {
+    ["USER", "LOGNAME", "USERNAME"].iter().find_map(|name| ctx.variables.get(name)).unwrap_or_default()
+}
+
+// HOSTNAME when the shell has it, else what the system calls itself
+fn host(ctx: &ShellContext) -> String {
+    ctx.variables.get("HOSTNAME").or_else(|| ctx.variables.get("COMPUTERNAME")).unwrap_or_else(system_host_name)
+}
+
+#[cfg(unix)]
+fn system_host_name() -> String {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn system_host_name() -> String {
+    String::new()
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_root() -> bool {
+    false
+}
+
+// The current directory, with `~` for the home directory it is in
+fn working_dir(ctx: &ShellContext) -> String {
+    let Ok(dir) = ctx.system.current_dir() else {
+        return String::new();
+    };
+    abbreviate_home(&dir.to_string_lossy(), home_dir_with(|name| ctx.variables.get(name)))
+}
+
+// The branch checked out in the repository of the current directory, or the commit when none is
+fn git_branch(ctx: &ShellContext) -> Option<String> {
+    let dir = ctx.system.current_dir().ok()?;
+    let mut cache = ctx.git_cache.0.borrow_mut();
+    let cached = cache.as_ref().filter(|(cached, _)| *cached == dir).map(|(_, head)| head.clone());
+    let head = cached.unwrap_or_else(|| find_head(ctx, &dir));
+    // HEAD itself is read every time, as checking out another branch changes it
+    let contents = head.as_ref().and_then(|head| ctx.system.read_file(head).ok());
+    // a repository that went away is looked for again next time
+    *cache = if head.is_some() && contents.is_none() { None } else { Some((dir, head)) };
+    let contents = String::from_utf8(contents?).ok()?;
+    let contents = contents.trim_end();
+    Some(match contents.strip_prefix("ref: ") {
+        Some(reference) => reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string(),
+        // a detached HEAD is shown as its commit, shortened the way git does
+        None => contents.chars().take(7).collect(),
+    })
+}
+
+// The HEAD of the repository in `dir` or the nearest directory above it
+fn find_head(ctx: &ShellContext, dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find_map(|dir| {
+        let git = dir.join(".git");
+        match ctx.system.read_file(&git) {
+            // worktrees and submodules have a file naming the repository's directory instead
+            Ok(contents) => {
+                let contents = String::from_utf8(contents).ok()?;
+                Some(dir.join(contents.trim().strip_prefix("gitdir: ")?).join("HEAD"))
+            }
+            Err(_) => {
+                let head = git.join("HEAD");
+                ctx.system.read_file(&head).is_ok().then_some(head)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::rc::Rc;
+    use crate::builtins::ShellContext;
+    use crate::prompt::{is_root, render};
+    use crate::system::{FakeSystem, System};
+
+    fn write(system: &FakeSystem, path: &str, contents: &str) {
+        system.open_output(path.as_ref(), false).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn context(cwd: &str) -> ShellContext {
+        let system = FakeSystem::new().dir("/home/ann").dir("/home/ann/src").dir("/home/annex");
+        system.set_current_dir(cwd.as_ref()).unwrap();
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(Rc::new(system));
+        ctx.variables.set("HOME", "/home/ann");
+        ctx.variables.set("USER", "ann");
+        ctx.variables.set("HOSTNAME", "box.example.com");
+        // the tests' own environment may turn colors off
+        ctx.variables.set("NO_COLOR", "");
+        ctx
+    }
+
+    #[test]
+    fn test_escapes() {
+        let mut ctx = context("/home/ann/src");
+        assert_eq!(render(&ctx), format!("~/src{} ", if is_root() { '#' } else { '$' }));
+        ctx.variables.set("PS1", r"\u@\h:\w \W\n\H \s\\\x \?> ");
+        assert_eq!(render(&ctx), "ann@box:~/src src\nbox.example.com rush\\\\x 0> ");
+        // the home directory only shortens whole path components
+        let mut ctx = context("/home/annex");
+        ctx.variables.set("PS1", r"\w \W ");
+        assert_eq!(render(&ctx), "/home/annex annex ");
+        let mut ctx = context("/home/ann");
+        ctx.variables.set("PS1", r"\w \W ");
+        assert_eq!(render(&ctx), "~ ~ ");
+    }
+
+    #[test]
+    fn test_status_color() {
+        let mut ctx = context("/");
+        ctx.variables.set("PS1", r"\[\c\]>\[\C\] ");
+        assert_eq!(render(&ctx), "\x1b[32m>\x1b[0m ");
+        ctx.last_status = 1;
+        assert_eq!(render(&ctx), "\x1b[31m>\x1b[0m ");
+        ctx.variables.set("RUSH_PROMPT_THEME", "bright");
+        assert_eq!(render(&ctx), "\x1b[1;91m>\x1b[0m ");
+        ctx.variables.set("TERM", "dumb");
+        assert_eq!(render(&ctx), "> ");
+        ctx.variables.set("TERM", "xterm");
+        ctx.variables.set("NO_COLOR", "1");
+        assert_eq!(render(&ctx), "> ");
+    }
+
+    #[test]
+    fn test_git_branch() {
+        let system = Rc::new(FakeSystem::new().dir("/repo/.git").dir("/repo/src").dir("/elsewhere").dir("/tree")
+            .dir("/repo/.git/worktrees/tree"));
+        write(&system, "/repo/.git/HEAD", "ref: refs/heads/main\n");
+        write(&system, "/tree/.git", "gitdir: /repo/.git/worktrees/tree\n");
+        system.set_current_dir("/repo/src".as_ref()).unwrap();
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(system.clone());
+        ctx.variables.set("PS1", r"\W\g> ");
+        assert_eq!(render(&ctx), "src (main)> ");
+        // HEAD is read again for each prompt, the repository is only looked for after changing directory
+        write(&system, "/repo/.git/HEAD", "0123456789abcdef\n");
+        assert_eq!(render(&ctx), "src (0123456)> ");
+        system.set_current_dir("/elsewhere".as_ref()).unwrap();
+        assert_eq!(render(&ctx), "elsewhere> ");
+        system.set_current_dir("/tree".as_ref()).unwrap();
+        write(&system, "/repo/.git/worktrees/tree/HEAD", "ref: refs/heads/topic\n");
+        assert_eq!(render(&ctx), "tree (topic)> ");
+    }
+}
@@ -0,0 +1,2203 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::alias;
+use crate::arithmetic::{self, arithmetic_command};
+use crate::builtins::{run_builtin, Execute, Io, ShellContext};
+use crate::completion;
+use crate::conditional::{self, conditional_command};
+use crate::config::Config;
+use crate::control::{self, Branch, Statement};
+use crate::debugger::{self, Debugger};
+use crate::dirs;
+use crate::encoding;
+use crate::error::{Result, RushError};
+use crate::expand::{expand_here_document, expand_parameters, expand_words};
+use crate::history::{self, History};
+use crate::interrupt;
+use crate::jobs::{self, JobState};
+use crate::line_editor::{self, ReadLine};
+use crate::locale::tr;
+use crate::options::ShellOptions;
+use crate::path_utils::{expand_tilde, split_path_list};
+use crate::profiler::{Profiler, StartupProfiler};
+use crate::prompt;
+use crate::syntax::{check_line, display_command, dump_line, parse_line, Command};
+use crate::system::{Process, ProcessFd, ProcessGroup, ProcessInput, ProcessSink, RealSystem, System};
+use crate::terminal;
+use crate::theme::{self, Role};
+use crate::tokenizer::{
+    continuation, quote, split_assignments, split_comment, split_here_inputs, split_lines, split_list,
+    split_pipeline, split_redirections, split_words, is_name, Connector, Continuation, HereInput,
+};
+
+const SYSTEM_PROFILE: &str = "/etc/rush/profile";
+
+/// The exit status of a command, script, or shell session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl ExitStatus {
+    pub fn code(self) -> i32 {
+        self.0
+    }
+
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// How a `Shell` is set up: what it reads at startup and what state it starts with.
+pub struct ShellConfig {
+    pub path_dirs: Vec<String>,
+    /// Prompt for input, expand aliases per interactive rules and read the rc file
+    pub interactive: bool,
+    /// Read the login profiles at startup and the logout file on exit
+    pub login: bool,
+    /// rc file read by interactive shells; `None` skips it
+    pub rc_file: Option<String>,
+    pub options: ShellOptions,
+    pub positional_params: Vec<String>,
+    pub debug: bool,
+    pub profile: bool,
+    /// Print how long each startup phase took once the shell is ready for input
+    pub profile_startup: bool,
+    /// What the shell runs programs on; the real OS unless replaced, e.g. with a `FakeSystem` in tests
+    pub system: Rc<dyn System>,
+}
+
+impl Default for ShellConfig {
+    /// A non-interactive shell that searches the current PATH and reads no startup files.
+    fn default() -> Self {
+        let path = encoding::env_var("PATH").unwrap_or_default();
+        ShellConfig {
+            path_dirs: split_path_list(&path),
+            interactive: false,
+            login: false,
+            rc_file: None,
+            options: ShellOptions::default(),
+            positional_params: Vec::new(),
+            debug: false,
+            profile: false,
+            profile_startup: false,
+            system: Rc::new(RealSystem),
+        }
+    }
+}
+
+/// An embeddable rush shell.
+///
+/// ```no_run
+/// use rush::{Shell, ShellConfig};
+///
+/// let mut shell = Shell::new(ShellConfig::default());
+/// let status = shell.eval("set -o pipefail\nls | wc -l");
+/// assert!(status.success());
+/// ```
+pub struct Shell {
+    ctx: ShellContext,
+    rc_file: Option<String>,
+    startup_profiler: Option<StartupProfiler>,
+}
+
+impl Shell {
+    pub fn new(config: ShellConfig) -> Self {
+        let startup_profiler = config.profile_startup.then(StartupProfiler::new);
+        let mut ctx = ShellContext::new(config.path_dirs);
+        ctx.set_system(config.system);
+        ctx.interactive = config.interactive;
+        ctx.login = config.login;
+        ctx.options = config.options;
+        ctx.positional_params = config.positional_params;
+        if config.debug {
+            ctx.debugger = Some(Debugger::new());
+        }
+        if config.profile {
+            ctx.profiler = Some(Profiler::new());
+        }
+        Shell { ctx, rc_file: config.rc_file, startup_profiler }
+    }
+
+    /// The shell's state: variables, options, aliases, traps and so on.
+    pub fn context(&mut self) -> &mut ShellContext {
+        &mut self.ctx
+    }
+
+    /// Adds a builtin command to this shell, replacing any earlier one with the same name. `type`, `help` and
+    /// completion list it like rush's own builtins.
+    pub fn register_builtin(
+        &mut self,
+        name: &'static str,
+        command: impl Execute + 'static,
+        synopsis: &'static str,
+        summary: &'static str,
+    ) {
+        self.ctx.builtins.register(name, command, synopsis, summary);
+    }
+
+    /// Runs `input` line by line in this shell and returns the status of the last command.
+    /// `exit` stops evaluation with its status.
+    pub fn eval(&mut self, input: &str) -> ExitStatus {
+        self.ctx.last_error = None;
+        let result = run_lines(input.lines(), &mut self.ctx, execute_line);
+        self.finish(result)
+    }
+
+    /// Runs `line` as one command line and returns its status, the way the shell runs a line typed at the
+    /// prompt. Unlike `eval` it doesn't split `line` at newlines, so a newline in it has to be inside quotes,
+    /// and here-documents in it are empty.
+    pub fn execute_line(&mut self, line: &str) -> ExitStatus {
+        self.ctx.last_error = None;
+        self.ctx.variables.line_number += 1;
+        let result = execute_line(line, &mut self.ctx);
+        self.finish(result)
+    }
+
+    /// Reads the startup files, then runs the script at `path` with `$0` set to it.
+    pub fn run_script(&mut self, path: &str) -> ExitStatus {
+        let contents = match encoding::read_file(encoding::to_path(path)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                let _ = writeln!(self.ctx.system.stderr(), "rush: {}: {}", path, e);
+                return ExitStatus(127);
+            }
+        };
+
+        self.ctx.interactive = false;
+        let result = self.source_startup_files().and_then(|_| {
+            self.ctx.shell_name = path.to_string();
+            execute_source(path, &contents, &mut self.ctx)
+        });
+        let status = self.finish(result);
+        self.run_exit_trap(status)
+    }
+
+    /// Reads the startup files, then runs `command` line by line the way `rush -c` does.
+    pub fn run_command(&mut self, command: &str) -> ExitStatus {
+        let result = self.source_startup_files().and_then(|_| run_lines(command.lines(), &mut self.ctx, execute_line));
+        let status = self.finish(result);
+        self.run_exit_trap(status)
+    }
+
+    /// Reads the startup files, then prints how each line of `input` would run without running it: its
+    /// tokens, its syntax tree and the words every command of a pipeline runs with after expansion.
+    pub fn explain(&mut self, input: &str) -> ExitStatus {
+        let result = self.source_startup_files().and_then(|_| run_lines(input.lines(), &mut self.ctx, explain_line));
+        self.finish(result)
+    }
+
+    /// Reads the startup files, then reads and runs commands from stdin until `exit` or end of input,
+    /// prompting for each one when interactive.
+    pub fn run_interactive(&mut self) -> ExitStatus {
+        interrupt::install();
+        if self.ctx.interactive {
+            jobs::take_terminal();
+        }
+        terminal::enable_virtual_terminal();
+        let result = self.source_startup_files().and_then(|_| self.read_eval_loop());
+        let status = self.finish(result);
+        let status = self.run_exit_trap(status);
+        jobs::release_terminal();
+        status
+    }
+
+    /// Leaves the process with `status`, printing the profile summary first when profiling.
+    pub fn exit(&self, status: ExitStatus) -> ! {
+        self.ctx.exit(status.code())
+    }
+
+    fn read_eval_loop(&mut self) -> Result<()> {
+        // plugins may add prompt segments
+        self.phase("plugins", |ctx| ctx.load_plugins());
+        self.phase("history", open_history);
+        self.report_startup();
+        let ctx = &mut self.ctx;
+        loop {
+            run_signal_traps(ctx)?;
+            report_finished_jobs(ctx);
+            // picks up programs installed since the last prompt, in the background
+            ctx.path_index.refresh(&ctx.search_path());
+            // an interrupt of the previous command is dealt with
+            interrupt::take();
+
+            // wait for command input; commands piped in aren't prompted for
+            let prompt = if ctx.interactive { prompt(ctx) } else { String::new() };
+            let complete = |line: &str| completion::complete(line, ctx);
+            let line = match line_editor::read_line(&prompt, ctx.history.as_ref(), Some(&complete))? {
+                ReadLine::Line(line) => line,
+                ReadLine::Interrupted => continue,
+                // end of input behaves like exit
+                ReadLine::EndOfInput => return exit_shell(ctx.last_status, ctx),
+            };
+            ctx.variables.line_number += 1;
+            // a command that isn't complete yet goes on in the lines read at the continuation prompt
+            let continuation = continuation_prompt(ctx);
+            let mut next_line = || line_editor::read_line(&continuation, None, None);
+            let Some((input, joined)) = join_continued(&line, &mut next_line)? else {
+                continue;
+            };
+            let Some(input) = expand_history(input, ctx) else {
+                continue;
+            };
+            add_to_history(&input, ctx);
+
+            let Some(body_lines) = read_here_docs(&input, &mut next_line, ctx)? else {
+                continue;
+            };
+            execute_line(&input, ctx)?;
+            ctx.variables.line_number += joined + body_lines;
+
+            ctx.system.stdout().flush()?;
+        }
+    }
+
+    fn source_startup_files(&mut self) -> Result<()> {
+        // plugins load when first needed, see `ShellContext::load_plugins`
+        self.ctx.pending_plugins = dirs::config_dir().map(|dir| dir.join("plugins"));
+        if self.ctx.login {
+            self.phase("login profiles", source_login_profiles)?;
+        }
+
+        if !self.ctx.interactive {
+            self.phase("env file", source_env_file)?;
+            self.report_startup();
+            return Ok(());
+        }
+
+        self.phase("config", load_config)?;
+        if let Some(aliases) = dirs::aliases_file() {
+            self.phase("aliases", |ctx| source_file(&aliases.to_string_lossy(), ctx))?;
+        }
+        if self.ctx.options.posix {
+            self.phase("env file", source_posix_env_file)?;
+        } else if let Some(rc_file) = self.rc_file.clone() {
+            self.phase("rc file", |ctx| source_file(&rc_file, ctx))?;
+        }
+        Ok(())
+    }
+
+    // Runs one step of startup, timing it for --profile-startup
+    fn phase<T>(&mut self, name: &'static str, f: impl FnOnce(&mut ShellContext) -> T) -> T {
+        match &mut self.startup_profiler {
+            Some(profiler) => profiler.phase(name, || f(&mut self.ctx)),
+            None => f(&mut self.ctx),
+        }
+    }
+
+    // Prints the startup phases' timings once the shell is ready for input
+    fn report_startup(&mut self) {
+        if let Some(profiler) = self.startup_profiler.take() {
+            let _ = write!(self.ctx.system.stderr(), "{}", profiler.report());
+        }
+    }
+
+    /// The error from the most recent command that failed, if any command has failed since this shell last
+    /// started evaluating input.
+    pub fn last_error(&self) -> Option<&RushError> {
+        self.ctx.last_error.as_ref()
+    }
+
+    /// The prompt printed before each line read interactively.
+    pub fn prompt(&self) -> String {
+        prompt(&self.ctx)
+    }
+
+    // Runs the EXIT trap as the shell ends with `status`, with `$?` set to it; an `exit` in the trap changes
+    // the status. The trap runs only once.
+    fn run_exit_trap(&mut self, status: ExitStatus) -> ExitStatus {
+        if !self.ctx.traps.contains_key("EXIT") {
+            return status;
+        }
+        self.ctx.last_status = status.code();
+        let result = run_trap("EXIT", &mut self.ctx);
+        self.ctx.traps.remove("EXIT");
+        let _ = self.ctx.system.stdout().flush();
+        match result {
+            Ok(()) => status,
+            Err(RushError::Exit(code)) => ExitStatus(code),
+            Err(e) => {
+                report(e, &mut self.ctx);
+                status
+            }
+        }
+    }
+
+    // Turns the outcome of running commands into a status, reporting errors that stopped the shell
+    fn finish(&mut self, result: Result<()>) -> ExitStatus {
+        let _ = self.ctx.system.stdout().flush();
+        match result {
+            Ok(()) => ExitStatus(self.ctx.last_status),
+            Err(RushError::Exit(status)) => ExitStatus(status),
+            Err(e) => {
+                report(e, &mut self.ctx);
+                ExitStatus(self.ctx.last_status)
+            }
+        }
+    }
+}
+
+// Applies config.toml from the config directory. A file that fails to validate is reported and skipped.
+fn load_config(ctx: &mut ShellContext) -> Result<()> {
+    let Some(path) = dirs::config_dir().map(|dir| dir.join("config.toml")) else {
+        return Ok(());
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path.display(), e);
+            return Ok(());
+        }
+    };
+    match Config::parse(&contents) {
+        Ok(config) => {
+            for warning in config.warnings() {
+                let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path.display(), warning);
+            }
+            Ok(config.apply(ctx)?)
+        }
+        Err(e) => {
+            let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path.display(), e);
+            Ok(())
+        }
+    }
+}
+
+// Leaves the shell with `status`, sourcing the logout file first when an interactive login shell exits
+fn exit_shell(status: i32, ctx: &mut ShellContext) -> Result<()> {
+    if ctx.login
+        && ctx.interactive
+        && let Some(logout) = dirs::config_file("logout", "~/.rush_logout")
+    {
+        // an `exit` in the logout file must not read it again
+        ctx.login = false;
+        if let Err(e) = source_file(&logout, ctx)
+            && !matches!(e, RushError::Exit(_))
+        {
+            let _ = writeln!(ctx.system.stderr(), "{}: {}", ctx.location(), e);
+        }
+    }
+    Err(RushError::Exit(status))
+}
+
+// Tells the user about background jobs that finished since the last prompt
+fn report_finished_jobs(ctx: &mut ShellContext) {
+    for (marker, job) in ctx.jobs.take_finished() {
+        let _ = writeln!(ctx.system.stderr(), "{}", job.describe(marker));
+    }
+}
+
+// Opens the history file named by HISTFILE, ~/.rush_history by default, keeping the newest HISTSIZE entries
+fn open_history(ctx: &mut ShellContext) {
+    let path = match ctx.variables.get("HISTFILE").filter(|file| !file.is_empty()) {
+        Some(file) => file,
+        None => match expand_tilde("~/.rush_history") {
+            Ok(file) => file,
+            Err(_) => return,
+        },
+    };
+    let size = history_size(ctx);
+    match History::open(Path::new(&path)) {
+        Ok(mut history) => {
+            history.keep_newest(size);
+            ctx.history = Some(history);
+        }
+        Err(e) => {
+            let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path, e);
+        }
+    }
+}
+
+// How many entries the history keeps: HISTSIZE, 500 when it's unset or not a number
+fn history_size(ctx: &ShellContext) -> usize {
+    ctx.variables.get("HISTSIZE").and_then(|size| size.parse().ok()).unwrap_or(500)
+}
+
+// Records a line entered at the prompt; with HISTSIZE=0 nothing is recorded
+fn add_to_history(line: &str, ctx: &mut ShellContext) {
+    if line.trim().is_empty() || history_size(ctx) == 0 {
+        return;
+    }
+    if let Some(history) = &mut ctx.history
+        && let Err(e) = history.add(line)
+    {
+        // the line is still recalled in this session; only the file is missing it
+        let _ = writeln!(ctx.system.stderr(), "rush: history: {}", e);
+    }
+}
+
+// Replaces the `!` references to history entries in a line entered at the prompt, showing the line when they
+// changed it. None when a reference matched no entry, which is reported instead.
+fn expand_history(line: String, ctx: &mut ShellContext) -> Option<String> {
+    let expanded = match &ctx.history {
+        Some(history) if ctx.interactive => history::expand(&line, history),
+        _ => return Some(line),
+    };
+    match expanded {
+        Ok(None) => Some(line),
+        Ok(Some(expanded)) => {
+            let _ = writeln!(ctx.system.stderr(), "{}", expanded);
+            Some(expanded)
+        }
+        Err(reference) => {
+            report(RushError::EventNotFound(reference), ctx);
+            None
+        }
+    }
+}
+
+// Prompt segments from plugins, then `$ `
+fn prompt(ctx: &ShellContext) -> String {
+    let mut prompt: String = ctx.prompt_segments.iter().map(|(_, segment)| segment.render()).collect();
+    prompt.push_str(&prompt::render(ctx));
+    prompt
+}
+
+// Sources the file named by RUSH_ENV, after parameter expansion, in non-interactive shells
+fn source_env_file(ctx: &mut ShellContext) -> Result<()> {
+    let Some(value) = ctx.variables.get("RUSH_ENV").filter(|v| !v.is_empty()) else {
+        return Ok(());
+    };
+    let path = expand_parameters(&value, ctx);
+    if !source_file(&path, ctx)? {
+        let _ = writeln!(ctx.system.stderr(), "rush: {}", tr!("{}: No such file or directory", path));
+    }
+    Ok(())
+}
+
+// Interactive POSIX shells source the file named by $ENV, after parameter expansion, instead of the rc file
+fn source_posix_env_file(ctx: &mut ShellContext) -> Result<()> {
+    if let Some(value) = ctx.variables.get("ENV").filter(|v| !v.is_empty()) {
+        let path = expand_parameters(&value, ctx);
+        source_file(&path, ctx)?;
+    }
+    Ok(())
+}
+
+// Sources the system profile, then the user's rush profile, falling back to the generic ~/.profile
+fn source_login_profiles(ctx: &mut ShellContext) -> Result<()> {
+    source_file(SYSTEM_PROFILE, ctx)?;
+    let sourced = match dirs::config_file("profile", "~/.rush_profile") {
+        Some(profile) => source_file(&profile, ctx)?,
+        None => false,
+    };
+    if !sourced {
+        source_file("~/.profile", ctx)?;
+    }
+    Ok(())
+}
+
+// Sources a startup file, returning whether it existed. Unreadable files are reported but don't stop startup.
+fn source_file(path: &str, ctx: &mut ShellContext) -> Result<bool> {
+    let Ok(path) = expand_tilde(path) else {
+        return Ok(false);
+    };
+    match encoding::read_file(encoding::to_path(&path)) {
+        Ok(contents) => execute_source(&path, &contents, ctx).map(|_| true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => {
+            let _ = writeln!(ctx.system.stderr(), "rush: {}: {}", path, e);
+            Ok(true)
+        }
+    }
+}
+
+// Runs each line of `contents` in the current shell, with diagnostics reported against `name`
+pub(crate) fn execute_source(name: &str, contents: &str, ctx: &mut ShellContext) -> Result<()> {
+    let saved_source = ctx.source_name.replace(name.to_string());
+    let saved_line = std::mem::take(&mut ctx.variables.line_number);
+
+    let result = run_lines(contents.lines(), ctx, execute_line);
+
+    ctx.source_name = saved_source;
+    ctx.variables.line_number = saved_line;
+    result
+}
+
+// Runs `lines` one command line at a time with `run`, each after reading the bodies of the here-documents it
+// starts from the lines that follow it
+fn run_lines<'a>(
+    mut lines: impl Iterator<Item = &'a str>,
+    ctx: &mut ShellContext,
+    run: fn(&str, &mut ShellContext) -> Result<()>,
+) -> Result<()> {
+    while let Some(line) = lines.next() {
+        ctx.variables.line_number += 1;
+        let mut next_line = || Ok(lines.next().map_or(ReadLine::EndOfInput, |line| ReadLine::Line(line.to_string())));
+        let (input, joined) = join_continued(line, &mut next_line)?.unwrap_or_default();
+        let body_lines = read_here_docs(&input, &mut next_line, ctx)?.unwrap_or_default();
+        let result = run(&input, ctx);
+        ctx.variables.line_number += joined + body_lines;
+        result?;
+    }
+    Ok(())
+}
+
+// Joins the lines `next_line` reads to `line` for as long as it ends inside quotes, with a backslash or inside
+// a compound command like `if`, and returns the command with how many lines were joined to it. At the end of
+// input the command is left as it is, for running it to report. None when Ctrl-C cancelled the command.
+fn join_continued(
+    line: &str,
+    mut next_line: impl FnMut() -> io::Result<ReadLine>,
+) -> io::Result<Option<(String, usize)>> {
+    let mut command = line.trim_end_matches(['\n', '\r']).to_string();
+    let mut joined = 0;
+    let compound = |command: &str| control::is_incomplete(command).then_some(Continuation::Compound);
+    while let Some(kind) = continuation(&command).or_else(|| compound(&command)) {
+        let next = match next_line()? {
+            ReadLine::Line(next) => next,
+            ReadLine::Interrupted => return Ok(None),
+            ReadLine::EndOfInput => break,
+        };
+        match kind {
+            Continuation::Quote | Continuation::Compound => command.push('\n'),
+            Continuation::Backslash => {
+                command.pop();
+            }
+        }
+        command.push_str(next.trim_end_matches(['\n', '\r']));
+        joined += 1;
+    }
+    Ok(Some((command, joined)))
+}
+
+// Reads the bodies of the here-documents the command line `line` starts into `ctx.here_docs`, each from the
+// lines `next_line` reads up to its delimiter, and returns how many lines they took. None when Ctrl-C
+// cancelled the command.
+fn read_here_docs(
+    line: &str,
+    mut next_line: impl FnMut() -> io::Result<ReadLine>,
+    ctx: &mut ShellContext,
+) -> Result<Option<usize>> {
+    ctx.here_docs.clear();
+    let mut count = 0;
+    for (delimiter, strip_tabs) in here_documents(line) {
+        let mut body = String::new();
+        loop {
+            let text = match next_line()? {
+                ReadLine::Line(text) => text,
+                ReadLine::Interrupted => {
+                    ctx.here_docs.clear();
+                    return Ok(None);
+                }
+                ReadLine::EndOfInput => {
+                    let start = ctx.variables.line_number;
+                    ctx.variables.line_number += count;
+                    let message = tr!("warning: here-document at line {} delimited by end-of-file (wanted `{}')", start, delimiter);
+                    let _ = writeln!(ctx.system.stderr(), "{}: {}", ctx.location(), message);
+                    ctx.variables.line_number = start;
+                    break;
+                }
+            };
+            count += 1;
+            let text = text.trim_end_matches(['\n', '\r']);
+            let text = if strip_tabs { text.trim_start_matches('\t') } else { text };
+            if text == delimiter {
+                break;
+            }
+            body.push_str(text);
+            body.push('\n');
+        }
+        ctx.here_docs.push_back(body);
+    }
+    Ok(Some(count))
+}
+
+// The here-documents the commands of `input` start, in order: each one's delimiter and whether it strips tabs
+fn here_documents(input: &str) -> Vec<(String, bool)> {
+    let mut documents = Vec::new();
+    for (command, _) in split_lines(input).into_iter().flat_map(split_list) {
+        if arithmetic_command(command).is_some() || conditional_command(command).is_some() {
+            continue;
+        }
+        for stage in split_pipeline(command) {
+            for here in split_here_inputs(stage).map(|(inputs, _)| inputs).unwrap_or_default() {
+                if let HereInput::Document { word, strip_tabs } = here {
+                    documents.push((HereInput::delimiter(word).0, strip_tabs));
+                }
+            }
+        }
+    }
+    documents
+}
+
+/// A command line of a script as the shell reads it, for checking or formatting the script without running it.
+#[derive(Debug, PartialEq)]
+pub struct ScriptCommand {
+    /// The number of the line the command starts on
+    pub line: usize,
+    /// The command, with the lines continuing it joined to it as for running it
+    pub input: String,
+    /// The lines of the here-documents the command starts, as written, delimiters included
+    pub here_docs: Vec<String>,
+}
+
+/// Splits a script into its command lines the way running it would: lines that continue a command are joined
+/// to it, and the bodies of here-documents are never taken for commands.
+pub fn read_commands(contents: &str) -> Vec<ScriptCommand> {
+    let mut lines = contents.lines();
+    let mut number = 0;
+    let mut commands = Vec::new();
+    while let Some(first) = lines.next() {
+        number += 1;
+        let line = number;
+        let next_line = || Ok(lines.next().map_or(ReadLine::EndOfInput, |line| ReadLine::Line(line.to_string())));
+        // reading lines from a string can neither fail nor be interrupted
+        let (input, joined) = join_continued(first, next_line).ok().flatten().unwrap_or_default();
+        number += joined;
+        let mut here_docs = Vec::new();
+        for (delimiter, strip_tabs) in here_documents(&input) {
+            for text in lines.by_ref() {
+                number += 1;
+                here_docs.push(text.to_string());
+                let text = text.trim_end_matches('\r');
+                let text = if strip_tabs { text.trim_start_matches('\t') } else { text };
+                if text == delimiter {
+                    break;
+                }
+            }
+        }
+        commands.push(ScriptCommand { line, input, here_docs });
+    }
+    commands
+}
+
+// The prompt for the lines after the first of a command: PS2, `> ` by default, in interactive shells
+fn continuation_prompt(ctx: &ShellContext) -> String {
+    if ctx.interactive { ctx.variables.get("PS2").unwrap_or_else(|| "> ".to_string()) } else { String::new() }
+}
+
+fn execute_line(input: &str, ctx: &mut ShellContext) -> Result<()> {
+    let _span = tracing::debug_span!("line", number = ctx.variables.line_number, in_trap = ctx.in_trap).entered();
+    // a list runs only once all of it parses
+    let lines = split_lines(input);
+    let errors = lines.iter().filter(|line| split_list(line).len() > 1).flat_map(|line| check_line(line));
+    let parsed = errors.map(Err).next().unwrap_or_else(|| control::parse(input));
+    match parsed {
+        Ok(statements) => execute_statements(&statements, ctx),
+        Err(error) => {
+            report(error.into(), ctx);
+            Ok(())
+        }
+    }
+}
+
+// Runs statements the way a list runs its commands, each depending on how the one before it ended
+fn execute_statements(statements: &[Statement], ctx: &mut ShellContext) -> Result<()> {
+    let mut run = true;
+    for statement in statements {
+        // the bodies after this statement's own belong to the statements after it, whether or not it reads them
+        let own_docs: usize = statement.commands().into_iter().map(|command| here_documents(command).len()).sum();
+        let later_docs = ctx.here_docs.len().saturating_sub(own_docs);
+        let connector = statement.connector();
+        if run {
+            match statement {
+                Statement::Simple(command, connector) => execute_list_command(command, *connector, ctx)?,
+                Statement::If { branches, otherwise, connector } => execute_if(branches, otherwise, *connector, ctx)?,
+                Statement::For { name, words, body, connector } => execute_for(name, *words, body, *connector, ctx)?,
+                Statement::While { condition, body, connector } => execute_while(condition, body, *connector, ctx)?,
+            }
+            // Ctrl-C stops the rest of the line along with the program it interrupted
+            if ctx.interactive && ctx.last_status == 130 {
+                break;
+            }
+        }
+        // a command skipped by `&&` or `||` leaves `$?` as it was for the next operator to test
+        run = match connector {
+            Connector::And => ctx.last_status == 0,
+            Connector::Or => ctx.last_status != 0,
+            Connector::Sequence | Connector::Background => true,
+        };
+        let read = ctx.here_docs.len().saturating_sub(later_docs);
+        ctx.here_docs.drain(..read);
+    }
+    Ok(())
+}
+
+// Runs the body of the first branch whose condition succeeds, or else the `else` part if there is one. The
+// status is that of the last command run in the body, 0 when none ran.
+fn execute_if(
+    branches: &[Branch],
+    otherwise: &Option<Vec<Statement>>,
+    connector: Connector,
+    ctx: &mut ShellContext,
+) -> Result<()> {
+    // like a command before `&&` or `||`, the statement failing is no error when that decides what runs next
+    as_condition(matches!(connector, Connector::And | Connector::Or), ctx, |ctx| {
+        for branch in branches {
+            as_condition(true, ctx, |ctx| execute_statements(&branch.condition, ctx))?;
+            if ctx.last_status == 0 {
+                return execute_statements(&branch.body, ctx);
+            }
+        }
+        ctx.last_status = 0;
+        match otherwise {
+            Some(otherwise) => execute_statements(otherwise, ctx),
+            None => Ok(()),
+        }
+    })
+}
+
+// Runs the body of a `for` loop for each of its words, expanded like those of a command, with the variable
+// `name` set to the word; without words it loops over the positional parameters. The status is that of the
+// last command run in the body, 0 when none ran.
+fn execute_for(
+    name: &str,
+    words: Option<&str>,
+    body: &[Statement],
+    connector: Connector,
+    ctx: &mut ShellContext,
+) -> Result<()> {
+    if !is_name(name) {
+        report(RushError::BuiltinUsage(tr!("`{}': not a valid identifier", name)), ctx);
+        return Ok(());
+    }
+    let words = match words {
+        Some(words) => match expand_words(words, ctx, true) {
+            Ok(words) => words,
+            Err(e) => return expansion_failed(e, ctx),
+        },
+        None => ctx.positional_params.clone(),
+    };
+    // each round reads the here-documents of the body again
+    let here_docs = ctx.here_docs.clone();
+    ctx.last_status = 0;
+    ctx.loop_depth += 1;
+    let result = as_condition(matches!(connector, Connector::And | Connector::Or), ctx, |ctx| {
+        for word in words {
+            ctx.here_docs = here_docs.clone();
+            ctx.variables.set(name, &word);
+            let round = execute_statements(body, ctx);
+            if end_of_round(round, ctx)? {
+                break;
+            }
+        }
+        Ok(())
+    });
+    ctx.loop_depth -= 1;
+    result
+}
+
+// Runs the body of a `while` loop for as long as its condition succeeds. The status is that of the last command
+// run in the body, 0 when none ran.
+fn execute_while(
+    condition: &[Statement],
+    body: &[Statement],
+    connector: Connector,
+    ctx: &mut ShellContext,
+) -> Result<()> {
+    // each round reads the here-documents of the condition and the body again
+    let here_docs = ctx.here_docs.clone();
+    ctx.loop_depth += 1;
+    let result = as_condition(matches!(connector, Connector::And | Connector::Or), ctx, |ctx| {
+        let mut status = 0;
+        loop {
+            ctx.here_docs = here_docs.clone();
+            as_condition(true, ctx, |ctx| execute_statements(condition, ctx))?;
+            if ctx.last_status != 0 {
+                break;
+            }
+            let round = execute_statements(body, ctx);
+            let done = end_of_round(round, ctx)?;
+            status = ctx.last_status;
+            if done {
+                break;
+            }
+        }
+        ctx.last_status = status;
+        Ok(())
+    });
+    ctx.loop_depth -= 1;
+    result
+}
+
+// Whether a loop is over after a round of its body ended with `result`. `break` and `continue` succeed; with a
+// count they go on in the loops around this one.
+fn end_of_round(result: Result<()>, ctx: &mut ShellContext) -> Result<bool> {
+    match result {
+        Ok(()) => Ok(ctx.interactive && ctx.last_status == 130),
+        Err(RushError::Break(count)) => {
+            ctx.last_status = 0;
+            if count > 1 { Err(RushError::Break(count - 1)) } else { Ok(true) }
+        }
+        Err(RushError::Continue(count)) => {
+            ctx.last_status = 0;
+            if count > 1 { Err(RushError::Continue(count - 1)) } else { Ok(false) }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Runs `f` as a condition when `condition` is set: the commands in it failing don't trigger the ERR trap or
+// errexit
+fn as_condition<T>(
+    condition: bool,
+    ctx: &mut ShellContext,
+    f: impl FnOnce(&mut ShellContext) -> Result<T>,
+) -> Result<T> {
+    let in_condition = ctx.in_condition;
+    ctx.in_condition |= condition;
+    let result = f(ctx);
+    ctx.in_condition = in_condition;
+    result
+}
+
+// Runs one command of a list, where `connector` is what follows it. A failure that decides whether the next
+// command runs doesn't trigger the ERR trap or errexit.
+fn execute_list_command(input: &str, connector: Connector, ctx: &mut ShellContext) -> Result<()> {
+    let input = input.trim();
+    // arithmetic command: exit status reflects the truth value of the expression
+    // (( )) and [[ ]] are rush extensions; POSIX mode runs them as ordinary commands
+    let extended = !ctx.options.posix;
+    if let Some(expr) = arithmetic_command(input).filter(|_| extended) {
+        before_command(input, || display_command(input), ctx)?;
+        match arithmetic::evaluate(expr, &mut ctx.variables) {
+            Ok(value) => ctx.last_status = if value != 0 { 0 } else { 1 },
+            Err(e) => report(RushError::Arithmetic { expr: expr.trim().to_string(), message: e.to_string() }, ctx),
+        }
+        ctx.variables.set_array("PIPESTATUS", vec![ctx.last_status.to_string()]);
+    } else if let Some(expr) = conditional_command(input).filter(|_| extended) {
+        // conditional command: 0 when the expression holds, 1 when it does not, 2 on error
+        let words = match expand_words(expr, ctx, false) {
+            Ok(words) => words,
+            Err(e) => return expansion_failed(e, ctx),
+        };
+        before_command(input, || format!("[[ {} ]]", quote_words(&words)), ctx)?;
+        match conditional::evaluate(&words, &mut ctx.variables) {
+            Ok(holds) => ctx.last_status = if holds { 0 } else { 1 },
+            Err(e) => report(RushError::Syntax { column: None, message: e.to_string() }, ctx),
+        }
+        ctx.variables.set_array("PIPESTATUS", vec![ctx.last_status.to_string()]);
+    } else {
+        // evaluate
+        let mut stages = Vec::new();
+        for stage in split_pipeline(input) {
+            match prepare_stage(stage, ctx) {
+                Ok(stage) => stages.push(stage),
+                Err(e) => return expansion_failed(e, ctx),
+            }
+        }
+        if let [stage] = stages.as_slice()
+            && stage.words.is_empty()
+        {
+            if stage.assignments.is_empty() {
+                return Ok(());
+            }
+            // only assignments: they set shell variables
+            before_command(input, || traced_command(&stages), ctx)?;
+            for (name, value) in &stages[0].assignments {
+                ctx.variables.set(name, value);
+            }
+            ctx.last_status = 0;
+            ctx.variables.set_array("PIPESTATUS", vec!["0".to_string()]);
+            return finish_list_command(input, connector, ctx);
+        }
+        if stages.iter().any(|stage| stage.words.is_empty() && stage.assignments.is_empty()) {
+            let message = "syntax error near unexpected token `|'".to_string();
+            report(RushError::Syntax { column: None, message }, ctx);
+            return Ok(());
+        }
+
+        before_command(input, || traced_command(&stages), ctx)?;
+
+        // a command on its own runs with its assignments in place; the commands of a pipeline or a job each get
+        // their own as they start
+        let assignments = match stages.as_slice() {
+            [stage] if connector != Connector::Background => stage.assignments.clone(),
+            _ => Vec::new(),
+        };
+        let statuses = with_assignments(&assignments, ctx, |ctx| {
+            if connector == Connector::Background {
+                start_job(&stages, input, ctx)?;
+                Ok(vec![ctx.last_status])
+            } else if let [stage] = stages.as_mut_slice() {
+                execute_command(input, stage, ctx)?;
+                Ok(vec![ctx.last_status])
+            } else {
+                let statuses = execute_pipeline(input, &stages, ctx)?;
+                // with pipefail the rightmost failing stage decides the status, otherwise the last stage does
+                ctx.last_status = match statuses.iter().rev().find(|status| **status != 0) {
+                    Some(status) if ctx.options.pipefail => *status,
+                    _ => *statuses.last().unwrap_or(&0),
+                };
+                Ok(statuses)
+            }
+        })?;
+        ctx.variables.set_array("PIPESTATUS", statuses.iter().map(|s| s.to_string()).collect());
+    }
+
+    finish_list_command(input, connector, ctx)
+}
+
+// What follows every command of a list: the profiler's timing, the traps of signals that arrived, then the
+// ERR trap and errexit on failure outside conditions
+fn finish_list_command(input: &str, connector: Connector, ctx: &mut ShellContext) -> Result<()> {
+    if !ctx.in_trap
+        && let Some(profiler) = ctx.profiler.as_mut()
+    {
+        profiler.stop();
+    }
+    run_signal_traps(ctx)?;
+
+    if ctx.last_status != 0 && !ctx.in_condition && !matches!(connector, Connector::And | Connector::Or) {
+        run_trap("ERR", ctx)?;
+
+        if ctx.options.errexit && !ctx.in_trap {
+            if ctx.options.strict {
+                let message = tr!("{}: {}: exited with status {}", ctx.location(), input, ctx.last_status);
+                let _ = writeln!(ctx.system.stderr(), "{}", message);
+            }
+            return Err(RushError::Exit(ctx.last_status));
+        }
+    }
+
+    Ok(())
+}
+
+// Runs `f` with the variables of `NAME=value` assignments before a command set and exported, then puts back
+// what they were
+fn with_assignments<T>(
+    assignments: &[(String, String)],
+    ctx: &mut ShellContext,
+    f: impl FnOnce(&mut ShellContext) -> Result<T>,
+) -> Result<T> {
+    let saved: Vec<(Option<String>, bool)> = assignments.iter()
+        .map(|(name, _)| (ctx.variables.stored(name), ctx.variables.is_exported(name)))
+        .collect();
+    for (name, value) in assignments {
+        ctx.variables.set(name, value);
+        ctx.variables.set_exported(name, true);
+    }
+    let result = f(ctx);
+    // in reverse, so that a name assigned twice gets its value from before the first assignment
+    for ((name, _), (value, exported)) in assignments.iter().zip(saved).rev() {
+        match value {
+            Some(value) => ctx.variables.set(name, &value),
+            None => ctx.variables.unset(name),
+        }
+        ctx.variables.set_exported(name, exported);
+    }
+    result
+}
+
+// A pipeline stage ready to run: the variable assignments before its command, its words, what it reads instead
+// of the previous stage's output or the terminal, where its output and error output go, and where its file
+// descriptors above 2 lead
+struct Stage {
+    assignments: Vec<(String, String)>,
+    words: Vec<String>,
+    input: Option<Input>,
+    stdout: Target,
+    stderr: Target,
+    fds: Vec<(u32, Target)>,
+}
+
+// What a stage reads instead of the previous stage's output or the terminal
+enum Input {
+    // the text of a here-document or here-string
+    Text(Vec<u8>),
+    // a file redirected with `<`
+    File(PathBuf),
+}
+
+// Where a file descriptor of a stage leads after its redirections
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    // where output goes unless redirected: the terminal, or the pipe to the next stage
+    Stdout,
+    // the shell's stderr
+    Stderr,
+    // a file, with whether it is appended to
+    File(PathBuf, bool),
+    // where input comes from unless redirected: the terminal, or the pipe from the previous stage
+    Stdin,
+    // a file read from
+    Input(PathBuf),
+}
+
+// Expands a pipeline stage, taking the bodies of its here-documents from `ctx.here_docs`. With several
+// redirections the last one is the input, but every here-document still takes its body. Output redirections
+// work the same way: every file is created, or emptied, before anything runs, but only the last one of each
+// file descriptor gets what it writes. `N>&M` and `N<&M` give N wherever M leads at that point, so `>file 2>&1`
+// sends both outputs to the file while `2>&1 >file` sends only the output, and `3>&1 1>&2 2>&3` swaps them.
+// A file read with `<` replaces any here-document as the input. The input can't be written to, nor the outputs
+// read from.
+fn prepare_stage(source: &str, ctx: &mut ShellContext) -> Result<Stage> {
+    let (words, source) = split_assignments(source);
+    let mut assignments = Vec::new();
+    for (name, value) in words {
+        assignments.push((name.to_string(), expand_words(value, ctx, false)?.join(" ")));
+    }
+    let (inputs, rest) = split_here_inputs(source)?;
+    let mut input = None;
+    for here in inputs {
+        input = Some(match here {
+            HereInput::Document { word, .. } => {
+                let body = ctx.here_docs.pop_front().unwrap_or_default();
+                // a quoted delimiter keeps the body as it is
+                if HereInput::delimiter(word).1 { body } else { expand_here_document(&body, ctx) }
+            }
+            HereInput::String(word) => format!("{}\n", expand_words(word, ctx, false)?.join(" ")),
+        });
+    }
+    let (redirections, rest) = split_redirections(&rest)?;
+    let bad_fd = |fd: String| RushError::Redirection { path: fd, source: io::Error::other(tr!("Bad file descriptor")) };
+    let mut fds = HashMap::from([(0, Target::Stdin), (1, Target::Stdout), (2, Target::Stderr)]);
+    for redirection in redirections {
+        let word = expand_words(redirection.word, ctx, false)?.join(" ");
+        let target = if redirection.duplicate {
+            match word.parse().ok().and_then(|fd: u32| fds.get(&fd)) {
+                Some(target) => target.clone(),
+                None => return Err(bad_fd(word)),
+            }
+        } else if redirection.input {
+            let path = encoding::to_path(&word);
+            ctx.system.open_input(&path).map_err(|source| RushError::Redirection { path: word, source })?;
+            Target::Input(path)
+        } else {
+            let path = encoding::to_path(&word);
+            open_output(&path, redirection.append, ctx)?;
+            Target::File(path, redirection.append)
+        };
+        fds.insert(redirection.fd, target);
+    }
+    let input = match fds.remove(&0) {
+        Some(Target::Input(path)) => Some(Input::File(path)),
+        Some(Target::Stdin) => input.map(|text| Input::Text(encoding::encode(&text))),
+        _ => return Err(bad_fd("0".to_string())),
+    };
+    let mut output = |fd: u32| match fds.remove(&fd) {
+        Some(Target::Stdin | Target::Input(_)) | None => Err(bad_fd(fd.to_string())),
+        Some(target) => Ok(target),
+    };
+    let (stdout, stderr) = (output(1)?, output(2)?);
+    let mut fds: Vec<(u32, Target)> = fds.into_iter().collect();
+    fds.sort_by_key(|&(fd, _)| fd);
+    Ok(Stage { assignments, words: expand_stage(&rest, ctx)?, input, stdout, stderr, fds })
+}
+
+// Opens what a stage reads instead of the previous stage's output or the terminal, if anything
+fn stage_input(stage: &Stage, ctx: &ShellContext) -> Result<Option<ProcessInput>> {
+    Ok(match &stage.input {
+        None => None,
+        Some(Input::Text(text)) => Some(ProcessInput::Bytes(text.clone())),
+        Some(Input::File(path)) => {
+            let error = |source| RushError::Redirection { path: path.display().to_string(), source };
+            Some(ctx.system.open_input(path).map_err(error)?)
+        }
+    })
+}
+
+// What a builtin reads its input from
+fn input_reader(input: ProcessInput, system: &dyn System) -> Box<dyn Read> {
+    match input {
+        ProcessInput::Inherit => system.stdin(),
+        ProcessInput::Null => Box::new(io::empty()),
+        ProcessInput::Pipe(pipe) => Box::new(pipe),
+        ProcessInput::Bytes(bytes) => Box::new(io::Cursor::new(bytes)),
+        ProcessInput::File(file) => Box::new(file),
+    }
+}
+
+// Opens the file output is redirected to
+fn open_output(path: &Path, append: bool, ctx: &ShellContext) -> Result<Box<dyn Write>> {
+    let error = |source| RushError::Redirection { path: path.display().to_string(), source };
+    ctx.system.open_output(path, append).map_err(error)
+}
+
+// Where a program run as a stage writes its output and error output, and where its file descriptors above 2
+// lead. Output that isn't redirected goes to the terminal from the `last` stage and to the pipe from the others.
+fn program_sinks(stage: &Stage, last: bool) -> (ProcessSink, ProcessSink, Vec<(u32, ProcessFd)>) {
+    let fd = |target: &Target| match target {
+        Target::Stdout if last => ProcessFd::Output(ProcessSink::Stdout),
+        Target::Stdout => ProcessFd::Output(ProcessSink::Pipe),
+        Target::Stderr => ProcessFd::Output(ProcessSink::Stderr),
+        Target::File(path, append) => ProcessFd::Output(ProcessSink::File { path: path.clone(), append: *append }),
+        Target::Stdin => ProcessFd::Stdin,
+        Target::Input(path) => ProcessFd::Input(path.clone()),
+    };
+    let sink = |target: &Target| match fd(target) {
+        ProcessFd::Output(sink) => sink,
+        ProcessFd::Stdin | ProcessFd::Input(_) => unreachable!("outputs lead to outputs"),
+    };
+    let fds = stage.fds.iter().map(|(number, target)| (*number, fd(target))).collect();
+    (sink(&stage.stdout), sink(&stage.stderr), fds)
+}
+
+// The writers a builtin run as a stage gets for its output and error output, given `stdout`, where output goes
+// unless redirected. When both go to the same place they share a writer, which keeps them in order.
+fn stage_output<'a>(
+    stage: &Stage,
+    stdout: Box<dyn Write + 'a>,
+    ctx: &ShellContext,
+) -> Result<(Box<dyn Write + 'a>, Box<dyn Write + 'a>)> {
+    let mut stdout = Some(stdout);
+    let mut open = |target: &Target| -> Result<Box<dyn Write + 'a>> {
+        Ok(match target {
+            Target::Stdout => stdout.take().unwrap_or_else(|| Box::new(io::sink())),
+            Target::Stderr => ctx.system.stderr(),
+            Target::File(path, append) => open_output(path, *append, ctx)?,
+            Target::Stdin | Target::Input(_) => unreachable!("outputs lead to outputs"),
+        })
+    };
+    if stage.stdout == stage.stderr {
+        let shared = SharedWriter(Rc::new(RefCell::new(open(&stage.stdout)?)));
+        return Ok((Box::new(shared.clone()), Box::new(shared)));
+    }
+    Ok((open(&stage.stdout)?, open(&stage.stderr)?))
+}
+
+// Whether a stage's output to `target` ends up on the terminal; only the last stage's stdout does
+fn on_terminal(target: &Target, last: bool, ctx: &ShellContext) -> bool {
+    match target {
+        Target::Stdout => last && ctx.system.stdout_is_terminal(),
+        Target::Stderr => ctx.system.stderr_is_terminal(),
+        Target::File(..) | Target::Stdin | Target::Input(_) => false,
+    }
+}
+
+// A writer both outputs of a builtin share when they go to the same place
+#[derive(Clone)]
+struct SharedWriter<'a>(Rc<RefCell<Box<dyn Write + 'a>>>);
+
+impl Write for SharedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+// Reports an error of a stage's own, such as a command that wasn't found, where its error output goes.
+// `stdout` is where its output goes unless redirected, which is the terminal only for the `last` stage.
+fn report_in_stage(
+    error: RushError,
+    stage: &Stage,
+    stdout: Box<dyn Write + '_>,
+    last: bool,
+    ctx: &mut ShellContext,
+) {
+    match stage_output(stage, stdout, ctx) {
+        Ok((_, mut stderr)) => {
+            let terminal = on_terminal(&stage.stderr, last, ctx);
+            report_to(error, &mut stderr, terminal, ctx);
+        }
+        Err(_) => report(error, ctx),
+    }
+}
+
+// The words a pipeline stage runs with, after alias and parameter expansion
+fn expand_stage(stage: &str, ctx: &mut ShellContext) -> Result<Vec<String>> {
+    // aliases replace the command word as written, so their values are expanded along with the rest
+    let mut words: Vec<String> = split_words(stage).into_iter().map(String::from).collect();
+    // POSIX shells expand aliases only at the prompt
+    if ctx.interactive || !ctx.options.posix {
+        alias::expand_alias(stage, &mut words, &ctx.aliases);
+    }
+    expand_words(&words.join(" "), ctx, true)
+}
+
+// Prints a line's tokens and syntax tree and, for a pipeline, what each stage would run and where the
+// command would be found, without running anything
+fn explain_line(input: &str, ctx: &mut ShellContext) -> Result<()> {
+    let line = match parse_line(input) {
+        Ok(line) => line,
+        Err(e) => {
+            report(e.into(), ctx);
+            return Ok(());
+        }
+    };
+    if line.command == Command::Empty {
+        return Ok(());
+    }
+
+    let mut out = ctx.system.stdout();
+    write!(out, "{}: {}\n{}", ctx.location(), input.trim(), dump_line(&line))?;
+    let commands = split_list(split_comment(input).0).into_iter().map(|(command, _)| command);
+    let is_pipeline = |command: &&str| arithmetic_command(command).is_none() && conditional_command(command).is_none();
+    for pipeline in commands.filter(is_pipeline) {
+        for stage in split_pipeline(pipeline) {
+            let words = expand_stage(&split_redirections(&split_here_inputs(stage)?.1)?.1, ctx)?;
+            // an alias can expand to nothing
+            let Some(command) = words.first().map(|word| word.as_str()) else {
+                continue;
+            };
+            ctx.load_plugins();
+            let kind = if ctx.builtins.contains(command) {
+                "builtin".to_string()
+            } else {
+                match ctx.system.find_program(command, &ctx.search_path()) {
+                    Some(path) => path.display().to_string(),
+                    None => "not found".to_string(),
+                }
+            };
+            let words: Vec<String> = words.iter().map(|word| quote(word)).collect();
+            writeln!(out, "  runs: {} ({})", words.join(" "), kind)?;
+        }
+    }
+    Ok(())
+}
+
+// Runs a pipeline of one stage, reading the stage's input instead of the terminal when there is one and
+// writing where its redirections send its outputs. `line` is the command as typed, which names it as a job when
+// it stops.
+fn execute_command(line: &str, stage: &mut Stage, ctx: &mut ShellContext) -> Result<()> {
+    let (command, args) = (stage.words[0].as_str(), &stage.words[1..]);
+    tracing::debug!(command, ?args, "execute");
+    let mut io = Io::terminal(&*ctx.system);
+    match stage_input(stage, ctx) {
+        Ok(Some(input)) => io.stdin = input_reader(input, &*ctx.system),
+        Ok(None) => {}
+        Err(e) => {
+            report(e, ctx);
+            return Ok(());
+        }
+    }
+    (io.stdout, io.stderr) = match stage_output(stage, ctx.system.stdout(), ctx) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            report(e, ctx);
+            return Ok(());
+        }
+    };
+    io.on_terminal = on_terminal(&stage.stdout, true, ctx);
+    match run_builtin(command, args, ctx, &mut io) {
+        Some(Ok(())) => ctx.last_status = 0,
+        Some(Err(e)) => match RushError::from(e) {
+            RushError::Exit(status) => return exit_shell(status, ctx),
+            e @ (RushError::Break(_) | RushError::Continue(_)) => return Err(e),
+            e => report_to(e, &mut io.stderr, on_terminal(&stage.stderr, true, ctx), ctx),
+        },
+        None => {
+            drop(io);
+            // Try to execute as an external program
+            let path_dirs = ctx.search_path();
+            if let Some(path) = ctx.command_hash.find(command, &path_dirs, &*ctx.system) {
+                // the program shares the terminal, after whatever the shell printed before it
+                ctx.system.stdout().flush()?;
+                let program = program_to_start(command, &path);
+                let env = ctx.variables.environment();
+                let group = foreground_group(ctx);
+                let stdin = match stage_input(stage, ctx) {
+                    Ok(input) => input.unwrap_or(ProcessInput::Inherit),
+                    Err(e) => {
+                        report(e, ctx);
+                        return Ok(());
+                    }
+                };
+                let (stdout, stderr, fds) = program_sinks(stage, true);
+                let started = ctx.system.spawn(&program, args, &env, stdin, stdout, stderr, &fds, group);
+                let mut statuses = [0];
+                let waited = started.and_then(|spawned| {
+                    wait_for_children(line, vec![(0, spawned.process)], &mut statuses, ctx)
+                });
+                match waited {
+                    Ok(()) => {
+                        ctx.last_status = statuses[0];
+                        tracing::debug!(command, status = statuses[0], "exited");
+                    }
+                    Err(source) => {
+                        report_in_stage(start_failed(command, source), stage, ctx.system.stdout(), true, ctx);
+                    }
+                }
+            } else {
+                let error = RushError::CommandNotFound(command.to_string());
+                report_in_stage(error, stage, ctx.system.stdout(), true, ctx);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// The error for a program found in PATH that couldn't be started. One that isn't there after all, or whose
+// interpreter isn't, is not found, as other shells have it; anything else keeps it from running.
+fn start_failed(command: &str, source: io::Error) -> RushError {
+    if source.kind() == io::ErrorKind::NotFound {
+        RushError::CommandNotFound(command.to_string())
+    } else {
+        RushError::NotExecutable { command: command.to_string(), source }
+    }
+}
+
+// What to start for `command` found at `path`. CreateProcess only tries `.exe` on its own, so on Windows it
+// is the file PATHEXT resolution found; std runs `.bat` and `.cmd` files through cmd.exe.
+fn program_to_start(command: &str, path: &Path) -> String {
+    if cfg!(windows) { encoding::from_os(path.as_os_str()) } else { command.to_string() }
+}
+
+// Runs the stages of a pipeline connected stdout-to-stdin and returns each stage's exit status.
+// Builtins run inside the shell: their output is collected and fed to the next stage.
+fn execute_pipeline(line: &str, stages: &[Stage], ctx: &mut ShellContext) -> Result<Vec<i32>> {
+    let (mut statuses, children) = start_pipeline(stages, false, ctx)?;
+    wait_for_children(line, children, &mut statuses, ctx)?;
+    Ok(statuses)
+}
+
+// Waits for the programs of a pipeline in the foreground and records their statuses by stage. In an interactive
+// shell they can stop instead, as Ctrl+Z stops them: then they become a stopped job named `line`, which `fg`
+// continues, and the stages still running get the status of a program stopped by SIGTSTP.
+fn wait_for_children(
+    line: &str,
+    mut children: Children,
+    statuses: &mut [i32],
+    ctx: &mut ShellContext,
+) -> io::Result<()> {
+    let Some(leader) = children.first().map(|(_, process)| process.id()) else {
+        return Ok(());
+    };
+    let interactive = ctx.interactive;
+    let stopped = wait_in_foreground(leader, ctx, || {
+        for (index, (i, process)) in children.iter_mut().enumerate() {
+            let status = if interactive { process.wait_or_stop()? } else { Some(process.wait()?) };
+            let Some(status) = status else {
+                return Ok(Some(index));
+            };
+            statuses[*i] = status;
+            tracing::debug!(stage = *i, status, "exited");
+        }
+        Ok::<_, io::Error>(None)
+    })?;
+
+    if let Some(index) = stopped {
+        let status = 128 + jobs::signal_number("TSTP").unwrap_or(20);
+        for (i, _) in &children[index..] {
+            statuses[*i] = status;
+        }
+        let job = ctx.jobs.add(line, children.into_iter().map(|(_, process)| process).collect());
+        let id = job.id;
+        tracing::debug!(job = id, pids = ?job.pids(), "stopped");
+        let job = ctx.jobs.get_mut(id).unwrap();
+        job.state = JobState::Stopped;
+        // the terminal has echoed ^Z; the report goes on the next line
+        let message = job.describe('+');
+        let _ = writeln!(ctx.system.stderr(), "\n{}", message);
+    }
+    Ok(())
+}
+
+// The process group of the programs the shell waits for. An interactive shell gives them one of their own, which
+// Ctrl-C at the terminal interrupts without reaching the shell.
+fn foreground_group(ctx: &ShellContext) -> ProcessGroup {
+    if ctx.interactive { ProcessGroup::Foreground(None) } else { ProcessGroup::Shell }
+}
+
+// Runs `f`, which waits for the programs in the process group `leader` leads, with them in the foreground
+fn wait_in_foreground<T>(leader: u32, ctx: &ShellContext, f: impl FnOnce() -> T) -> T {
+    if ctx.interactive { jobs::in_foreground(leader, f) } else { f() }
+}
+
+// Starts a pipeline in the background as a new job, announcing its number and last process ID at the prompt.
+// Its builtins run right away, in the shell; `$?` is 0 as the job's programs have only started.
+fn start_job(stages: &[Stage], command: &str, ctx: &mut ShellContext) -> Result<()> {
+    let (_, children) = start_pipeline(stages, true, ctx)?;
+    ctx.last_status = 0;
+    if children.is_empty() {
+        return Ok(());
+    }
+    let job = ctx.jobs.add(command, children.into_iter().map(|(_, process)| process).collect());
+    tracing::debug!(job = job.id, pids = ?job.pids(), "background");
+    if ctx.interactive {
+        let message = format!("[{}] {}", job.id, job.pids().last().unwrap_or(&0));
+        let _ = writeln!(ctx.system.stderr(), "{}", message);
+    }
+    Ok(())
+}
+
+// The programs of a pipeline that are still running, by stage
+type Children = Vec<(usize, Box<dyn Process>)>;
+
+// Starts the stages of a pipeline, returning the statuses of the stages that have finished, i.e. builtins and
+// programs that couldn't start, and the programs still running by stage. A background pipeline's programs
+// read from /dev/null instead of the terminal and go in a process group of their own.
+fn start_pipeline(
+    stages: &[Stage],
+    background: bool,
+    ctx: &mut ShellContext,
+) -> Result<(Vec<i32>, Children)> {
+    let system = Rc::clone(&ctx.system);
+    // programs write straight to the terminal, after whatever the shell printed before them
+    system.stdout().flush()?;
+    let mut statuses = vec![0; stages.len()];
+    let mut children: Children = Vec::new();
+    let mut input = if background { ProcessInput::Null } else { ProcessInput::Inherit };
+    let mut group = if background { ProcessGroup::Background(None) } else { foreground_group(ctx) };
+
+    for (i, stage) in stages.iter().enumerate() {
+        let last = i + 1 == stages.len();
+        let mut stdin = std::mem::replace(&mut input, ProcessInput::Null);
+        // a stage of only assignments sets them for nothing, and outputs nothing
+        let Some(command) = stage.words.first().map(String::as_str) else {
+            continue;
+        };
+        let words = &stage.words;
+        // a here-document, here-string or file redirected with `<` replaces the previous stage's output
+        match stage_input(stage, ctx) {
+            Ok(Some(input)) => stdin = input,
+            Ok(None) => {}
+            Err(e) => {
+                report(e, ctx);
+                statuses[i] = ctx.last_status;
+                continue;
+            }
+        }
+
+        if !ctx.builtins.contains(command) {
+            ctx.load_plugins();
+        }
+        if ctx.builtins.contains(command) {
+            let mut output = Vec::new();
+            let stdout: Box<dyn Write> = if last { system.stdout() } else { Box::new(&mut output) };
+            let (stdout, stderr) = match stage_output(stage, stdout, ctx) {
+                Ok(outputs) => outputs,
+                Err(e) => {
+                    report(e, ctx);
+                    statuses[i] = ctx.last_status;
+                    continue;
+                }
+            };
+            let mut io = Io {
+                stdin: input_reader(stdin, &*system),
+                stdout,
+                stderr,
+                on_terminal: on_terminal(&stage.stdout, last, ctx),
+            };
+            let result = with_assignments(&stage.assignments, ctx, |ctx| {
+                Ok(run_builtin(command, &words[1..], ctx, &mut io).unwrap_or(Ok(())))
+            })?;
+            statuses[i] = match result.map_err(RushError::from) {
+                Ok(()) => 0,
+                // like a subshell, `exit` only ends its own stage and `break` and `continue` leave no loop
+                Err(RushError::Exit(status)) => status,
+                Err(RushError::Break(_) | RushError::Continue(_)) => 0,
+                Err(e) => {
+                    report_to(e, &mut io.stderr, on_terminal(&stage.stderr, last, ctx), ctx);
+                    ctx.last_status
+                }
+            };
+            drop(io);
+            if !last {
+                input = ProcessInput::Bytes(output);
+            }
+            continue;
+        }
+        let (path_dirs, env) = with_assignments(&stage.assignments, ctx, |ctx| {
+            Ok((ctx.search_path(), ctx.variables.environment()))
+        })?;
+        let started = match ctx.command_hash.find(command, &path_dirs, &*system) {
+            Some(path) => {
+                let (stdout, stderr, fds) = program_sinks(stage, last);
+                let program = program_to_start(command, &path);
+                system.spawn(&program, &words[1..], &env, stdin, stdout, stderr, &fds, group)
+                    .map_err(|source| start_failed(command, source))
+            }
+            None => Err(RushError::CommandNotFound(command.to_string())),
+        };
+        match started {
+            Ok(spawned) => {
+                tracing::debug!(stage = i, command, pid = spawned.process.id(), "spawn");
+                // the first program leads the group the others join
+                group = match group {
+                    ProcessGroup::Foreground(None) => ProcessGroup::Foreground(Some(spawned.process.id())),
+                    ProcessGroup::Background(None) => ProcessGroup::Background(Some(spawned.process.id())),
+                    group => group,
+                };
+                if let Some(pipe) = spawned.stdout {
+                    input = pipe;
+                }
+                children.push((i, spawned.process));
+            }
+            Err(error) => {
+                // what it reports can go down the pipe like any other error output
+                let mut output = Vec::new();
+                let stdout: Box<dyn Write> = if last { system.stdout() } else { Box::new(&mut output) };
+                report_in_stage(error, stage, stdout, last, ctx);
+                statuses[i] = ctx.last_status;
+                if !last {
+                    input = ProcessInput::Bytes(output);
+                }
+            }
+        }
+    }
+
+    Ok((statuses, children))
+}
+
+// Reports a command whose words couldn't be expanded, which then doesn't run. A parameter that isn't set
+// under `set -u` ends a non-interactive shell.
+fn expansion_failed(error: RushError, ctx: &mut ShellContext) -> Result<()> {
+    let unbound = matches!(error, RushError::Unbound(_));
+    report(error, ctx);
+    if unbound && !ctx.interactive {
+        return Err(RushError::Exit(ctx.last_status));
+    }
+    Ok(())
+}
+
+// Reports a failed command and records it as the last error; `$?` becomes the error's status
+fn report(error: RushError, ctx: &mut ShellContext) {
+    let mut stderr = ctx.system.stderr();
+    let terminal = ctx.system.stderr_is_terminal();
+    report_to(error, &mut stderr, terminal, ctx);
+}
+
+// Like `report`, writing the message to `stderr`, where the failed command's error output goes, in the
+// theme's color for errors when that is the terminal
+fn report_to(error: RushError, stderr: &mut dyn Write, terminal: bool, ctx: &mut ShellContext) {
+    tracing::debug!(?error, "command failed");
+    if !matches!(error, RushError::Status(_)) {
+        let message = format!("{}: {}", ctx.location(), error);
+        let _ = writeln!(stderr, "{}", theme::paint(&message, Role::Error, ctx, terminal));
+    }
+    ctx.last_status = error.status();
+    ctx.last_error = Some(error);
+}
+
+// Hook point before every simple command: records it in RUSH_COMMAND, runs the DEBUG trap,
+// gives the step debugger a chance to stop, and prints the xtrace line, which `trace` makes.
+fn before_command(input: &str, trace: impl FnOnce() -> String, ctx: &mut ShellContext) -> Result<()> {
+    if !ctx.in_trap {
+        ctx.variables.set("RUSH_COMMAND", input);
+        run_trap("DEBUG", ctx)?;
+        debugger::pause(input, ctx)?;
+    }
+
+    if ctx.options.xtrace {
+        // PS4 is expanded for every command; its first character is repeated once per nesting level
+        let ps4 = ctx.variables.get("PS4").unwrap_or_else(|| "+ ".to_string());
+        let mut prefix = expand_parameters(&ps4, ctx);
+        if ctx.in_trap
+            && let Some(first) = prefix.chars().next()
+        {
+            prefix.insert(0, first);
+        }
+        let _ = writeln!(ctx.system.stderr(), "{}{}", prefix, trace());
+    }
+
+    // timing starts last so the debugger and tracing are not counted
+    if !ctx.in_trap
+        && let Some(profiler) = ctx.profiler.as_mut()
+    {
+        profiler.start(ctx.variables.line_number, display_command(input));
+    }
+
+    Ok(())
+}
+
+// How `set -x` shows a command: the assignments before it and the words of its stages after expansion,
+// quoted so that they can be run again
+fn traced_command(stages: &[Stage]) -> String {
+    let stage = |stage: &Stage| {
+        let assignments = stage.assignments.iter().map(|(name, value)| format!("{}={}", name, quote(value)));
+        let words = Some(quote_words(&stage.words)).filter(|words| !words.is_empty());
+        assignments.chain(words).collect::<Vec<_>>().join(" ")
+    };
+    stages.iter().map(stage).collect::<Vec<_>>().join(" | ")
+}
+
+fn quote_words(words: &[String]) -> String {
+    words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")
+}
+
+// Runs the action registered for `condition`, if any.
+// Traps are not re-entered for the commands they run themselves, and they do not clobber `$?`.
+fn run_trap(condition: &str, ctx: &mut ShellContext) -> Result<()> {
+    if ctx.in_trap {
+        return Ok(());
+    }
+    let Some(action) = ctx.traps.get(condition).cloned() else {
+        return Ok(());
+    };
+
+    let saved_status = ctx.last_status;
+    let here_docs = std::mem::take(&mut ctx.here_docs);
+    ctx.in_trap = true;
+    let result = execute_line(&action, ctx);
+    ctx.in_trap = false;
+    ctx.here_docs = here_docs;
+    ctx.last_status = saved_status;
+    result
+}
+
+// Runs the traps of the signals caught since their traps last ran. Signals that arrive while a trap runs wait
+// for it to finish.
+fn run_signal_traps(ctx: &mut ShellContext) -> Result<()> {
+    if ctx.in_trap {
+        return Ok(());
+    }
+    for signal in interrupt::take_caught() {
+        if let Some(name) = jobs::signal_name(signal) {
+            run_trap(name, ctx)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::rc::Rc;
+    use crate::error::RushError;
+    use crate::shell::{ExitStatus, Shell, ShellConfig};
+    use crate::system::{FakeSystem, System};
+
+    fn fake_shell(system: FakeSystem) -> (Shell, Rc<FakeSystem>) {
+        let system = Rc::new(system);
+        let shell = Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() });
+        (shell, system)
+    }
+
+    #[test]
+    fn test_eval_status() {
+        let mut shell = Shell::new(ShellConfig::default());
+        assert_eq!(shell.eval("((0))"), ExitStatus(1));
+        assert!(shell.eval("[[ a == a ]]").success());
+        assert_eq!(shell.eval("rush_test_no_such_command"), ExitStatus(127));
+    }
+
+    #[test]
+    fn test_regex_match_groups() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        // the match and its groups are the elements of RUSH_REMATCH, which a failed match empties
+        shell.eval("v=release-1.42; [[ $v =~ ([0-9]+)\\.([0-9]+) ]]");
+        shell.eval("echo \"$RUSH_REMATCH ${RUSH_REMATCH[1]} ${RUSH_REMATCH[2]}\"");
+        shell.eval("[[ x =~ y ]]; echo \"[${RUSH_REMATCH[@]}]\"");
+        assert_eq!(system.output(), b"1.42 1 42\n[]\n");
+    }
+
+    #[test]
+    fn test_exit_stops_eval() {
+        let mut shell = Shell::new(ShellConfig::default());
+        assert_eq!(shell.eval("((0))\nexit\n((1))"), ExitStatus(1));
+        assert_eq!(shell.eval("set -e\n((0))\n((1))"), ExitStatus(1));
+    }
+
+    #[test]
+    fn test_exit_argument() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        assert_eq!(shell.eval("exit 3\n((1))"), ExitStatus(3));
+        assert_eq!(shell.eval("exit 256"), ExitStatus(0));
+        assert_eq!(shell.eval("exit -1"), ExitStatus(255));
+        assert_eq!(shell.eval("exit abc"), ExitStatus(2));
+        assert_eq!(system.errors(), b"rush: line 4: exit: abc: numeric argument required\n");
+        // too many arguments doesn't exit
+        assert_eq!(shell.eval("exit 1 2\n((1))"), ExitStatus(0));
+    }
+
+    #[test]
+    fn test_run_command() {
+        let mut shell = Shell::new(ShellConfig::default());
+        assert_eq!(shell.run_command("((1))\n((0))"), ExitStatus(1));
+        assert_eq!(shell.context().variables.line_number, 2);
+        assert_eq!(shell.run_command("((0))\nexit\n((1))"), ExitStatus(1));
+    }
+
+    #[test]
+    fn test_trap() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        // the EXIT trap runs once as the shell ends, with `$?` the status it ends with
+        assert_eq!(shell.run_command("trap 'echo bye $?' 0\necho hi\n((0))"), ExitStatus(1));
+        assert_eq!(shell.run_command("((1))"), ExitStatus(0));
+        assert_eq!(system.output(), b"hi\nbye 1\n");
+        assert_eq!(shell.run_command("trap 'exit 3' EXIT\nexit 2"), ExitStatus(3));
+
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        shell.eval("trap 'echo it'\\''s over' exit\ntrap -- 'echo stopped' SIGTERM 15\ntrap 'echo err' ERR\ntrap -p");
+        assert_eq!(system.output(), b"trap -- 'echo it'\\''s over' EXIT\ntrap -- 'echo stopped' TERM\ntrap -- 'echo err' ERR\n");
+        shell.eval("trap - EXIT TERM\ntrap ERR\ntrap");
+        assert_eq!(shell.eval("trap 'echo x' STOP"), ExitStatus(1));
+        assert_eq!(system.errors(), b"rush: line 8: trap: STOP: invalid signal specification\n");
+        assert_eq!(system.output(), b"trap -- 'echo it'\\''s over' EXIT\ntrap -- 'echo stopped' TERM\ntrap -- 'echo err' ERR\n");
+    }
+
+    #[test]
+    fn test_source() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().dir("/home"));
+        // what the file sets stays set; arguments are its positional parameters while it runs
+        shell.eval("echo greeting=hello > /setup.sh\necho cd /home >> /setup.sh");
+        shell.eval("echo 'echo sourced $1 $#' >> /setup.sh");
+        shell.eval("source /setup.sh one two\necho $greeting $#\npwd");
+        assert_eq!(system.output(), b"sourced one 2\nhello 0\n/home\n");
+
+        // a name without a slash is found in the current directory; the status is that of the last command
+        shell.eval("cd /\necho rush-no-such-command > fail.sh\necho '((0))' >> fail.sh\n. fail.sh\necho $?");
+        assert_eq!(system.output(), b"sourced one 2\nhello 0\n/home\n1\n");
+        assert_eq!(system.errors(), b"fail.sh: line 1: rush-no-such-command: command not found\n");
+        assert_eq!(shell.eval("source /missing.sh"), ExitStatus(1));
+        assert_eq!(shell.eval("source"), ExitStatus(1));
+        assert_eq!(
+            system.errors(),
+            b"fail.sh: line 1: rush-no-such-command: command not found\n\
+              rush: line 12: source: /missing.sh: No such file or directory\n\
+              rush: line 13: source: filename argument required\n",
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        shell.eval("x=2\nif ((x == 1)); then echo one; elif ((x == 2)) && [[ $x ]]; then echo two; else echo no; fi");
+        shell.eval("if ((0)); then echo yes; fi; echo $?\nif ((0)); then echo yes; else ((0)); fi || echo failed");
+        // a statement can span lines, with any comments in it
+        shell.eval("if ((1))\nthen # always\n  if ((0)); then echo inner; fi\n  echo outer\nfi");
+        assert_eq!(system.output(), b"two\n0\nfailed\nouter\n");
+
+        // a failing condition is no error
+        shell.eval("set -e\ntrap 'echo trapped' ERR\nif ((0)); then echo yes; fi\necho alive\nset +e");
+        assert_eq!(system.output(), b"two\n0\nfailed\nouter\nalive\n");
+        assert_eq!(shell.eval("if ((1)); then echo yes; fi fi"), ExitStatus(2));
+        assert_eq!(shell.eval("if ((1)); then echo yes"), ExitStatus(2));
+        assert_eq!(
+            system.errors(),
+            b"rush: line 15: syntax error near unexpected token `fi'\n\
+              rush: line 16: syntax error: unexpected end of file\n",
+        );
+    }
+
+    #[test]
+    fn test_for() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().dir("/notes").file("/notes/a.txt").file("/notes/b.txt"));
+        shell.eval("for x in one 'two three' notes/*.txt; do echo $x; done; echo $x");
+        shell.eval("set -- p q\nfor arg\ndo\n  echo $arg\ndone");
+        shell.eval("for x in; do echo never; done; echo $?");
+        assert_eq!(system.output(), b"one\ntwo three\nnotes/a.txt\nnotes/b.txt\nnotes/b.txt\np\nq\n0\n");
+    }
+
+    #[test]
+    fn test_while() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        shell.eval("i=0; while ((i < 3)); do echo $i; i=$((i + 1)); done; echo $?");
+        shell.eval("while ((i++ < 10)); do ((i == 5)) && break; ((i % 2)) && continue; echo $i; done; echo $i");
+        shell.eval("while false; do echo never; done || echo failed");
+        assert_eq!(system.output(), b"0\n1\n2\n0\n4\n5\n");
+    }
+
+    #[test]
+    fn test_break_continue() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        shell.eval("for x in 1 2 3 4; do ((x == 2)) && continue; ((x == 4)) && break; echo $x; done; echo $?");
+        // with a count, they go on in the loops around
+        shell.eval("for x in a b c; do for y in 1 2; do\n\
+                    [[ $x == b ]] && continue 2; [[ $x == c ]] && break 5\n\
+                    echo $x$y\n\
+                    done; done");
+        assert_eq!(system.output(), b"1\n3\n0\na1\na2\n");
+
+        assert_eq!(shell.eval("break"), ExitStatus(1));
+        assert_eq!(shell.eval("for 1x in a; do echo $x; done"), ExitStatus(1));
+        assert_eq!(
+            system.errors(),
+            b"rush: line 6: break: only meaningful in a `for', `while', or `until' loop\n\
+              rush: line 7: `1x': not a valid identifier\n",
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        shell.eval("IFS=: read user shell <<EOF\nroot:/bin/sh:x\nEOF\necho \"$user $shell\"");
+        shell.eval("echo ' a\\ b ' | read; echo \"[$REPLY]\"\necho 'one two' | read -r first; echo $first");
+        // the end of input makes read fail, with whatever came before it assigned
+        shell.eval("read line; echo $? \"[$line]\"");
+        assert_eq!(system.output(), b"root /bin/sh:x\n[ a b ]\none two\n1 []\n");
+    }
+
+    #[test]
+    fn test_debugger_output() {
+        let system = Rc::new(FakeSystem::new());
+        let mut shell = Shell::new(ShellConfig { system: system.clone(), debug: true, ..ShellConfig::default() });
+        // the debugger stops before the first command, and without commands to read it lets the rest run
+        shell.eval("x=1\necho $x");
+        assert_eq!(system.output(), b"1\n");
+        assert_eq!(system.errors(), b"rush: line 1: x=1\n(rdb) ");
+    }
+
+    #[test]
+    fn test_xtrace_and_nounset() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        // commands are traced with their words expanded
+        shell.eval("set -x\nx='a b'\necho $x \"$x\"\nset +x");
+        assert_eq!(system.errors(), b"+ x='a b'\n+ echo a b 'a b'\n+ set +x\n");
+        // an unset parameter is an error that ends the script, except for $@ and $*
+        assert_eq!(shell.eval("set -u\necho \"$@\" $# $*\necho $nosuch; echo same"), ExitStatus(1));
+        assert_eq!(system.output(), b"a b a b\n0\n");
+        assert!(system.errors().ends_with(b"rush: line 7: nosuch: unbound variable\n"));
+    }
+
+    #[test]
+    fn test_state_persists_between_evals() {
+        let mut shell = Shell::new(ShellConfig::default());
+        shell.eval("((x = 41))");
+        assert!(shell.eval("((x + 1 == 42))").success());
+        assert_eq!(shell.context().variables.get("x").as_deref(), Some("41"));
+    }
+
+    #[test]
+    fn test_last_error() {
+        let mut shell = Shell::new(ShellConfig::default());
+        assert_eq!(shell.eval("rush_test_no_such_command"), ExitStatus(127));
+        assert!(matches!(shell.last_error(), Some(RushError::CommandNotFound(name)) if name == "rush_test_no_such_command"));
+        assert_eq!(shell.eval("echo 'open"), ExitStatus(2));
+        assert!(matches!(shell.last_error(), Some(RushError::Syntax { column: Some(6), .. })));
+        shell.eval("((1))");
+        assert!(shell.last_error().is_none());
+    }
+
+    #[test]
+    fn test_pipeline_on_fake_system() {
+        let (mut shell, system) = fake_shell(
+            FakeSystem::new()
+                .program("greet", |args, _| (0, format!("hello {}\n", args.join(" ")).into_bytes()))
+                .program("upper", |_, input| (0, input.to_ascii_uppercase()))
+                .program("fail", |_, _| (3, Vec::new())),
+        );
+        assert!(shell.eval("greet big world | upper").success());
+        assert!(shell.eval("echo from echo | upper").success());
+        assert_eq!(system.output(), b"HELLO BIG WORLD\nFROM ECHO\n");
+        assert_eq!(system.spawned()[0], ("greet".to_string(), vec!["big".to_string(), "world".to_string()]));
+
+        assert!(shell.eval("fail | upper").success());
+        assert_eq!(shell.eval("set -o pipefail\nfail | upper"), ExitStatus(3));
+        assert_eq!(shell.eval("fail"), ExitStatus(3));
+
+        // PIPESTATUS holds the status of each stage, which set -u knows to be set
+        shell.eval("set -u; greet | fail | upper; echo ${PIPESTATUS[1]} ${PIPESTATUS[@]}");
+        assert!(system.output().ends_with(b"\n3 0 3 0\n"));
+    }
+
+    #[test]
+    fn test_status_parameter() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("fail", |_, _| (3, Vec::new())));
+        shell.eval("fail\necho $?\necho \"status ${?}\"\necho $?");
+        shell.eval("cd /missing\necho $?");
+        shell.eval("rush_test_no_such_command\necho $?");
+        assert_eq!(system.output(), b"3\nstatus 0\n0\n1\n127\n");
+        // a bare exit leaves with the last command's status
+        assert_eq!(shell.eval("fail\nexit"), ExitStatus(3));
+    }
+
+    #[test]
+    fn test_command_lists() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("fail", |_, _| (3, Vec::new())));
+        assert_eq!(shell.eval("fail && echo ran || echo failed; echo done"), ExitStatus(0));
+        assert_eq!(shell.eval("echo a && fail && echo b"), ExitStatus(3));
+        assert_eq!(shell.eval("fail || fail || echo c; echo $?"), ExitStatus(0));
+        assert_eq!(shell.eval("((1)) && [[ -n x && -z '' ]] && echo d;"), ExitStatus(0));
+        assert_eq!(system.output(), b"failed\ndone\na\nc\n0\nd\n");
+        // nothing runs when part of the line doesn't parse
+        assert_eq!(shell.eval("echo e; && echo f"), ExitStatus(2));
+        assert_eq!(system.output(), b"failed\ndone\na\nc\n0\nd\n");
+        assert_eq!(system.errors(), b"rush: line 5: syntax error near unexpected token `&&'\n");
+    }
+
+    #[test]
+    fn test_errexit_in_lists() {
+        let system = Rc::new(FakeSystem::new().program("fail", |_, _| (3, Vec::new())));
+        let mut config = ShellConfig { system: system.clone(), ..ShellConfig::default() };
+        config.options.set("errexit", true).unwrap();
+        let mut shell = Shell::new(config);
+        // a failure that && or || tests doesn't end the shell, the last command of the list does
+        assert_eq!(shell.eval("fail && echo a\nfail || echo b\n((1)) && fail\necho c"), ExitStatus(3));
+        assert_eq!(system.output(), b"b\n");
+    }
+
+    #[test]
+    fn test_assignments_and_export() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("prog", |_, _| (0, Vec::new())));
+        assert_eq!(shell.eval("x=1 y='a b'; echo $x $y; prog"), ExitStatus(0));
+        // assignments before a command last only for it, and go into its environment
+        assert_eq!(shell.eval("x=2 z=3 prog; echo $x [$z]"), ExitStatus(0));
+        assert_eq!(shell.eval("export y w=4; prog; export -p"), ExitStatus(0));
+        assert_eq!(shell.eval("export 1x v=5"), ExitStatus(1));
+        assert_eq!(system.output(), b"1 a b\n1 []\nexport w=4\nexport y='a b'\n");
+        assert_eq!(system.errors(), b"rush: line 4: export: `1x': not a valid identifier\n");
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(system.environments(), [
+            vec![],
+            vec![pair("x", "2"), pair("z", "3")],
+            vec![pair("w", "4"), pair("y", "a b")],
+        ]);
+        assert_eq!(shell.eval("echo $v"), ExitStatus(0));
+        assert_eq!(system.output(), b"1 a b\n1 []\nexport w=4\nexport y='a b'\n5\n");
+    }
+
+    #[test]
+    fn test_assignments_in_pipelines() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("prog", |_, input| (0, input.to_vec())));
+        // each command of a pipeline or a job gets the assignments before it
+        assert_eq!(shell.eval("x=1 prog | y=2 prog; z=3 prog & wait; echo [$x$y$z]"), ExitStatus(0));
+        assert_eq!(system.output(), b"[]\n");
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(system.environments(), [vec![pair("x", "1")], vec![pair("y", "2")], vec![pair("z", "3")]]);
+    }
+
+    #[test]
+    fn test_background_jobs() {
+        let programs = FakeSystem::new().program("fail", |_, _| (3, Vec::new())).program("true", |_, _| (0, Vec::new()));
+        let (mut shell, system) = fake_shell(programs);
+        assert_eq!(shell.eval("fail &"), ExitStatus(0));
+        assert_eq!(shell.eval("echo $!"), ExitStatus(0));
+        assert_eq!(shell.eval("fg"), ExitStatus(3));
+        assert_eq!(system.output(), b"1000\nfail\n");
+        assert_eq!(shell.eval("fg"), ExitStatus(1));
+        assert_eq!(system.errors(), b"rush: line 4: fg: current: no such job\n");
+        shell.eval("true | fail & # both\ntrue&\njobs\nbg %1");
+        assert_eq!(system.output(), b"1000\nfail\n[1]-  Exit 3                  true | fail\n[2]+  Done                    true\n");
+        assert_eq!(system.errors(), b"rush: line 4: fg: current: no such job\nrush: line 8: bg: %1: no such job\n");
+    }
+
+    #[test]
+    fn test_explain_runs_nothing() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("ls", |_, _| (0, b"file\n".to_vec())));
+        shell.eval("alias ll='ls -l'");
+        assert!(shell.explain("ll | pwd").success());
+        let output = String::from_utf8(system.output()).unwrap();
+        assert!(output.contains("  runs: ls -l (/bin/ls)\n  runs: pwd (builtin)\n"), "{}", output);
+        assert!(system.spawned().is_empty());
+    }
+
+    #[test]
+    fn test_cd_and_env_on_fake_system() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().dir("/work").env("RUSH_TEST_GREETING", "hi"));
+        assert!(shell.eval("cd /work").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/work"));
+        assert_eq!(shell.eval("cd /missing"), ExitStatus(1));
+        assert_eq!(shell.context().variables.get("RUSH_TEST_GREETING").as_deref(), Some("hi"));
+        assert_eq!(shell.eval("ls"), ExitStatus(127));
+    }
+
+    #[test]
+    fn test_cd_back() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().dir("/a").dir("/b").dir("/c"));
+        assert_eq!(shell.eval("cd -"), ExitStatus(1));
+        assert!(shell.eval("cd /a; cd /b; cd /c; echo $OLDPWD $PWD").success());
+        assert!(shell.eval("cd -; cd -").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/c"));
+        // the history holds the other directories once each, most recent last
+        assert!(shell.eval("cd -3").success());
+        assert_eq!(shell.eval("cd -4"), ExitStatus(1));
+        assert_eq!(system.output(), b"/b /c\n/b\n/c\n/\n");
+        assert_eq!(system.errors(), b"rush: line 1: cd: OLDPWD not set\nrush: line 5: cd: -4: no such entry in the directory history\n");
+    }
+
+    #[test]
+    fn test_cd_arguments() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().dir("/a").dir("/a/my dir").dir("/b"));
+        assert!(shell.eval("cd '/a/my dir'; cd -L ./../../b/.; echo $PWD; cd -P -- ../a").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/a"));
+        assert_eq!(shell.eval("cd /a /b"), ExitStatus(1));
+        assert_eq!(system.output(), b"/b\n");
+        assert_eq!(system.errors(), b"rush: line 2: cd: too many arguments\n");
+    }
+
+    #[test]
+    fn test_cdpath() {
+        let system = FakeSystem::new().dir("/src").dir("/src/rush").dir("/src/lib").dir("/work").dir("/work/lib");
+        let (mut shell, system) = fake_shell(system);
+        // directories under the ones CDPATH lists are printed; an empty entry stands for the working directory
+        assert!(shell.eval("cd /work; CDPATH=/src:; cd rush; cd lib; cd /work; CDPATH=:/src; cd lib").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/work/lib"));
+        assert!(shell.eval("cd rush; CDPATH=/nowhere; cd /work; cd lib").success());
+        assert_eq!(shell.eval("cd ./rush"), ExitStatus(1));
+        assert_eq!(system.output(), b"/src/rush\n/src/lib\n/src/rush\n");
+        assert_eq!(system.errors(), b"rush: line 3: cd: ./rush: No such file or directory\n");
+    }
+
+    #[test]
+    fn test_here_documents() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("cat", |_, input| (0, input.to_vec())));
+        assert!(shell.eval("x=world; cat <<EOF\nhello $x \\$x\n\t'quoted' \"too\"\nEOF").success());
+        assert!(shell.eval("cat <<-'END' | cat\n\tliteral $x\n\tEND").success());
+        // the documents of a line follow it in order, also for commands that don't run
+        shell.eval("cat <<<\"$x  wide\"; cat <<A && cat <<B; ((0)) && cat <<C; cat <<D\na\nA\nb\nB\nc\nC\nd\nD");
+        assert!(shell.eval("cat <<EOF\nunterminated").success());
+        assert_eq!(String::from_utf8(system.output()).unwrap(), [
+            "hello world $x", "\t'quoted' \"too\"", "literal $x", "world  wide", "a", "b", "d", "unterminated",
+        ].map(|line| format!("{}\n", line)).concat());
+        assert_eq!(
+            String::from_utf8(system.errors()).unwrap(),
+            "rush: line 18: warning: here-document at line 17 delimited by end-of-file (wanted `EOF')\n",
+        );
+        assert_eq!(shell.eval("cat <<"), ExitStatus(2));
+    }
+
+    #[test]
+    fn test_output_redirection() {
+        let system = FakeSystem::new().dir("/tmp").program("upper", |_, input| (0, input.to_ascii_uppercase()));
+        let (mut shell, system) = fake_shell(system);
+        assert!(shell.eval("echo one > /tmp/out; echo two >>/tmp/out\ncd /tmp; echo a b | upper >>out").success());
+        assert_eq!(system.file_contents("/tmp/out").unwrap(), b"one\ntwo\nA B\n");
+        // every file is emptied, but only the last gets the output
+        assert!(shell.eval("echo three >out >last | upper").success());
+        assert_eq!(system.file_contents("out").unwrap(), b"");
+        assert_eq!(system.file_contents("last").unwrap(), b"three\n");
+        assert_eq!(shell.eval("echo lost > /nowhere/file"), ExitStatus(1));
+        assert!(system.output().is_empty());
+        assert_eq!(system.errors(), b"rush: line 4: /nowhere/file: No such file or directory\n");
+    }
+
+    #[test]
+    fn test_colors() {
+        let system = FakeSystem::new().dir("/tmp").terminal().program("ls", |_, _| (0, Vec::new()));
+        let (mut shell, system) = fake_shell(system);
+        shell.eval("nosuch");
+        assert_eq!(system.errors(), b"\x1b[31mrush: line 1: nosuch: command not found\x1b[0m\n");
+        shell.eval("alias ll=ls; type ll cd ls");
+        assert_eq!(
+            String::from_utf8(system.output()).unwrap(),
+            "\x1b[36mll\x1b[0m is aliased to `ls'\n\x1b[33mcd\x1b[0m is a shell builtin\n\x1b[32mls\x1b[0m is /bin/ls\n"
+        );
+
+        // output that doesn't reach the terminal stays plain
+        let upper = |_: &[String], input: &[u8]| (0, input.to_ascii_uppercase());
+        let system = FakeSystem::new().dir("/tmp").terminal().program("upper", upper);
+        let (mut shell, system) = fake_shell(system);
+        shell.eval("nosuch 2>/tmp/err; type cd >/tmp/out; type cd | upper");
+        assert_eq!(system.file_contents("/tmp/err").unwrap(), b"rush: line 1: nosuch: command not found\n");
+        assert_eq!(system.file_contents("/tmp/out").unwrap(), b"cd is a shell builtin\n");
+        assert_eq!(system.output(), b"CD IS A SHELL BUILTIN\n");
+        shell.eval("RUSH_COLOR_SCHEME=none; nosuch");
+        assert_eq!(system.errors(), b"rush: line 2: nosuch: command not found\n");
+    }
+
+    #[test]
+    fn test_error_redirection() {
+        let system = FakeSystem::new().dir("/tmp").program("upper", |_, input| (0, input.to_ascii_uppercase()));
+        let (mut shell, system) = fake_shell(system);
+        assert_eq!(shell.eval("cd /nowhere 2>/tmp/err"), ExitStatus(1));
+        assert!(system.errors().is_empty());
+        let error = b"rush: line 1: cd: /nowhere: No such file or directory\n";
+        assert_eq!(system.file_contents("/tmp/err").unwrap(), error);
+        // the error output follows the output into the pipe, or into the file, but only when duplicated after it
+        let status = shell.eval("nosuch 2>&1 | upper; echo one >/tmp/both 2>&1; cd /nowhere >>/tmp/both 2>&1");
+        assert_eq!(status, ExitStatus(1));
+        assert!(shell.eval("cd /nowhere 2>&1 >/tmp/out; echo two &>>/tmp/both; echo three >&2").success());
+        let line3 = "rush: line 3: cd: /nowhere: No such file or directory\n";
+        assert_eq!(system.output(), format!("RUSH: LINE 2: NOSUCH: COMMAND NOT FOUND\n{}", line3).as_bytes());
+        assert_eq!(
+            system.file_contents("/tmp/both").unwrap(),
+            b"one\nrush: line 2: cd: /nowhere: No such file or directory\ntwo\n"
+        );
+        assert_eq!(system.file_contents("/tmp/out").unwrap(), b"");
+        assert_eq!(system.errors(), b"three\n");
+        assert_eq!(shell.eval("echo lost >&3"), ExitStatus(1));
+        assert_eq!(system.errors(), b"three\nrush: line 4: 3: Bad file descriptor\n");
+        // any file descriptor can be redirected, and swapping the outputs through a third one works
+        assert!(shell.eval("echo four 3>/tmp/three >&3").success());
+        assert_eq!(system.file_contents("/tmp/three").unwrap(), b"four\n");
+        assert_eq!(shell.eval("nosuch 3>&1 1>&2 2>&3 | upper; echo five 3>&1 1>&2 2>&3"), ExitStatus(0));
+        let line6 = "RUSH: LINE 6: NOSUCH: COMMAND NOT FOUND\n";
+        assert_eq!(system.output(), format!("RUSH: LINE 2: NOSUCH: COMMAND NOT FOUND\n{}{}", line3, line6).as_bytes());
+        assert_eq!(system.errors(), b"three\nrush: line 4: 3: Bad file descriptor\nfive\n");
+    }
+
+    #[test]
+    fn test_input_redirection() {
+        let system = FakeSystem::new().dir("/tmp").program("upper", |_, input| (0, input.to_ascii_uppercase()));
+        let (mut shell, system) = fake_shell(system);
+        assert!(shell.eval("echo text >/tmp/in; upper </tmp/in; upper 0< /tmp/in <<<unused").success());
+        let status = shell.eval("read line </tmp/in; echo \"<$line>\"; upper 3</tmp/in <&3 | upper");
+        assert_eq!((status, system.errors()), (ExitStatus(0), Vec::new()));
+        assert_eq!(system.output(), b"TEXT\nTEXT\n<text>\nTEXT\n");
+        assert_eq!(shell.eval("upper </tmp/none; echo x 0>/tmp/in"), ExitStatus(1));
+        let errors = "rush: line 3: /tmp/none: No such file or directory\nrush: line 3: 0: Bad file descriptor\n";
+        assert_eq!(system.errors(), errors.as_bytes());
+        assert_eq!(system.spawned().len(), 4);
+    }
+
+    #[test]
+    fn test_continued_lines() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        assert!(shell.eval("echo 'one\n  two' \"three\nfour\" fi\\\nve \\\nsix").success());
+        // at the end of input the quote stays open
+        assert_eq!(shell.eval("echo 'open\n"), ExitStatus(2));
+        assert_eq!(system.output(), b"one\n  two three\nfour five six\n");
+        assert_eq!(system.errors(), b"rush: line 6: Unterminated single quote\n");
+    }
+
+    #[test]
+    fn test_echo_options() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        assert!(shell.eval(r"echo -n a; echo -nE 'b\tc' -n; echo - -- -x").success());
+        assert!(shell.eval(r"echo -e 'd\te\\\x41\x\0101\q'; echo -en 'f\cg' h; echo -eE 'i\n'").success());
+        assert_eq!(system.output(), b"ab\\tc -n- -- -x\nd\te\\A\\xA\\q\nfi\\n\n");
+        assert!(shell.eval(r"echo -e '\0377\xff'").success());
+        assert!(system.output().ends_with(b"\xff\xff\n"));
+    }
+
+    #[test]
+    fn test_type() {
+        let (mut shell, system) = fake_shell(FakeSystem::new().program("ls", |_, _| (0, Vec::new())));
+        shell.context().path_dirs = vec!["/usr/bin".to_string(), "/bin".to_string()];
+        assert!(shell.eval("alias echo='echo -n' ll='ls -l'; type echo ls").success());
+        assert_eq!(shell.eval("type -a -- echo ll rush-none ls"), ExitStatus(1));
+        assert_eq!(String::from_utf8(system.output()).unwrap(), [
+            "echo is aliased to `echo -n'", "ls is /bin/ls",
+            "echo is aliased to `echo -n'", "echo is a shell builtin", "ll is aliased to `ls -l'", "ls is /bin/ls",
+        ].map(|line| format!("{}\n", line)).concat());
+        assert_eq!(system.errors(), b"rush: line 2: type: rush-none: not found\n");
+        assert_eq!(shell.eval("type -x ls"), ExitStatus(1));
+    }
+
+    #[test]
+    fn test_hash() {
+        let system = FakeSystem::new().program("ls", |_, _| (0, Vec::new())).program("cat", |_, _| (0, Vec::new()));
+        let (mut shell, system) = fake_shell(system);
+        assert!(shell.eval("hash; ls; ls | cat; hash cat echo; hash").success());
+        assert!(shell.eval("hash -t ls; hash -t ls cat; hash -d ls; hash").success());
+        assert_eq!(shell.eval("hash rush-none; hash -t ls"), ExitStatus(1));
+        // another PATH starts over
+        shell.context().path_dirs = vec!["/usr/bin".to_string()];
+        assert!(shell.eval("hash -t cat || hash -r; hash").success());
+        assert_eq!(String::from_utf8(system.output()).unwrap(), [
+            "hash: hash table empty", "hits\tcommand", "   1\t/bin/cat", "   2\t/bin/ls",
+            "/bin/ls", "ls\t/bin/ls", "cat\t/bin/cat", "hits\tcommand", "   1\t/bin/cat", "hash: hash table empty",
+        ].map(|line| format!("{}\n", line)).concat());
+        assert_eq!(String::from_utf8(system.errors()).unwrap(), [
+            "rush: line 3: hash: rush-none: not found", "rush: line 3: hash: ls: not found",
+            "rush: line 4: hash: cat: not found",
+        ].map(|line| format!("{}\n", line)).concat());
+    }
+
+    #[test]
+    fn test_kill() {
+        let (mut shell, system) = fake_shell(FakeSystem::new());
+        assert!(shell.eval("kill -l 143 INT sigkill").success());
+        assert_eq!(shell.eval("kill -s NOPE 1"), ExitStatus(1));
+        assert_eq!(shell.eval("kill %1 abc"), ExitStatus(1));
+        assert_eq!(shell.eval("kill -9"), ExitStatus(1));
+        assert_eq!(system.output(), b"TERM\n2\n9\n");
+        assert_eq!(String::from_utf8(system.errors()).unwrap(), [
+            "rush: line 2: kill: NOPE: invalid signal specification",
+            "rush: line 3: kill: %1: no such job",
+            "rush: line 3: kill: abc: arguments must be process or job IDs",
+            "rush: line 4: kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ...",
+        ].map(|line| format!("{}\n", line)).concat());
+    }
+
+    #[test]
+    fn test_directory_stack() {
+        let system = FakeSystem::new().dir("/home").dir("/home/me").dir("/a").dir("/b").env("HOME", "/home/me");
+        let (mut shell, system) = fake_shell(system);
+        assert!(shell.eval("cd; pushd /a; pushd /b; dirs -v; pushd; pushd +2").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/home/me"));
+        assert!(shell.eval("dirs -l; dirs -1; popd +1; popd").success());
+        assert_eq!(system.current_dir().unwrap(), Path::new("/b"));
+        assert_eq!(shell.eval("popd"), ExitStatus(1));
+        assert_eq!(shell.eval("pushd; pushd +1; pushd /missing"), ExitStatus(1));
+        assert_eq!(String::from_utf8(system.output()).unwrap(), [
+            "/a ~", "/b /a ~", " 0  /b", " 1  /a", " 2  ~", "/a /b ~", "~ /a /b",
+            "/home/me /a /b", "/a", "~ /b", "/b",
+        ].map(|line| format!("{}\n", line)).concat());
+        assert_eq!(String::from_utf8(system.errors()).unwrap(), [
+            "rush: line 3: popd: directory stack empty",
+            "rush: line 4: pushd: no other directory",
+            "rush: line 4: pushd: +1: directory stack index out of range",
+            "rush: line 4: pushd: /missing: No such file or directory",
+        ].map(|line| format!("{}\n", line)).concat());
+    }
+}
@@ -0,0 +1,312 @@
+use crate::arithmetic::{self, arithmetic_command};
+use crate::conditional::{self, conditional_command};
+use crate::control;
+use crate::locale::tr;
+use crate::tokenizer::{
+    quote, split_comment, split_here_inputs, split_lines, split_list, split_pipeline, split_redirections, split_words,
+    tokenize, tokenize_owned, Connector, SyntaxError,
+};
+
+/// What a command line runs, with each word as written in the source.
+#[derive(Debug, PartialEq)]
+pub enum Command<'a> {
+    /// A blank or comment-only line
+    Empty,
+    /// `((expr))`, holding the trimmed expression
+    Arithmetic(&'a str),
+    /// `[[ words ]]`
+    Conditional(Vec<&'a str>),
+    /// Simple commands joined by `|`, one list of words per stage
+    Pipeline(Vec<Vec<&'a str>>),
+    /// Commands joined by `;`, `&`, `&&` and `||`, each with what ends it
+    List(Vec<(Command<'a>, Connector)>),
+}
+
+impl<'a> Command<'a> {
+    /// The command's tokens as written, operators included.
+    pub fn tokens(&self) -> Vec<&'a str> {
+        match self {
+            Command::Empty => Vec::new(),
+            Command::Arithmetic(expr) => vec!["((", expr, "))"],
+            Command::Conditional(words) => [&["[["][..], words, &["]]"]].concat(),
+            Command::Pipeline(stages) => stages.join(&"|"),
+            Command::List(commands) => {
+                let mut tokens = Vec::new();
+                for (i, (command, connector)) in commands.iter().enumerate() {
+                    tokens.extend(command.tokens());
+                    // the end of the line needs no `;`
+                    if i + 1 < commands.len() || *connector != Connector::Sequence {
+                        tokens.push(connector.as_str());
+                    }
+                }
+                tokens
+            }
+        }
+    }
+}
+
+/// A parsed command line: what it runs and the comment after it, including the `#`.
+#[derive(Debug, PartialEq)]
+pub struct Line<'a> {
+    pub command: Command<'a>,
+    pub comment: Option<&'a str>,
+}
+
+impl<'a> Line<'a> {
+    /// The line's tokens as written, operators and the comment included.
+    pub fn tokens(&self) -> Vec<&'a str> {
+        let mut tokens = self.command.tokens();
+        tokens.extend(self.comment);
+        tokens
+    }
+}
+
+/// Describes a parsed line for `--dump-ast`: its tokens and its syntax tree, one per indented line.
+pub fn dump_line(line: &Line) -> String {
+    format!("  tokens: {:?}\n  ast: {:?}\n", line.tokens(), line.command)
+}
+
+/// Parses a line without executing anything, failing with its first syntax error.
+pub fn parse_line(input: &str) -> Result<Line<'_>, SyntaxError> {
+    if let Some(error) = check_line(input).into_iter().next() {
+        return Err(error);
+    }
+    if arithmetic_command(input).is_some() || conditional_command(input).is_some() {
+        return Ok(Line { command: parse_command(input), comment: None });
+    }
+
+    let (code, comment) = split_comment(input);
+    let mut commands: Vec<(Command, Connector)> =
+        split_list(code).into_iter().map(|(command, connector)| (parse_command(command), connector)).collect();
+    // after a trailing `;` or `&`
+    if commands.len() > 1 && commands.last().is_some_and(|(command, _)| *command == Command::Empty) {
+        commands.pop();
+    }
+    let command = match commands.pop() {
+        Some((command, Connector::Sequence)) if commands.is_empty() => command,
+        Some(last) => {
+            commands.push(last);
+            Command::List(commands)
+        }
+        None => Command::Empty,
+    };
+    Ok(Line { command, comment })
+}
+
+// Parses one command of a list, which has no comment
+fn parse_command(input: &str) -> Command<'_> {
+    if let Some(expr) = arithmetic_command(input) {
+        return Command::Arithmetic(expr.trim());
+    }
+    if let Some(expr) = conditional_command(input) {
+        return Command::Conditional(split_words(expr));
+    }
+    let stages: Vec<Vec<&str>> = split_pipeline(input).into_iter().map(split_words).collect();
+    if stages.len() == 1 && stages[0].is_empty() { Command::Empty } else { Command::Pipeline(stages) }
+}
+
+/// Checks a line without executing anything and returns every syntax error found in it.
+pub fn check_line(input: &str) -> Vec<SyntaxError> {
+    if arithmetic_command(input).is_some() || conditional_command(input).is_some() {
+        return check_command(input, input);
+    }
+
+    let commands = split_list(input);
+    let mut errors = Vec::new();
+    for (i, &(command, connector)) in commands.iter().enumerate() {
+        if !split_words(command).is_empty() {
+            errors.extend(check_command(input, command));
+            continue;
+        }
+        // a line may be blank or end with `;` or `&`, but every operator needs a command before it and `&&`
+        // and `||` one after it too
+        let previous = i.checked_sub(1).map(|i| commands[i].1);
+        if i + 1 < commands.len() {
+            let message = tr!("syntax error near unexpected token `{}'", connector.as_str());
+            errors.push(SyntaxError { column: column_of(input, command, command.len()), message });
+        } else if let Some(previous @ (Connector::And | Connector::Or)) = previous {
+            let message = tr!("syntax error near unexpected token `{}'", previous.as_str());
+            errors.push(SyntaxError { column: column_of(input, command, 0) - previous.as_str().len(), message });
+        }
+    }
+    errors
+}
+
+/// Checks a command that can span lines, as read from a script, and returns every syntax error found in it,
+/// each with the number of lines before the one it is on and its column within that line. Compound commands
+/// are checked once every line of them is free of errors, as running them would.
+pub fn check_input(input: &str) -> Vec<(usize, SyntaxError)> {
+    let mut errors = Vec::new();
+    for line in split_lines(input) {
+        let start = column_of(input, line, 0) - 1;
+        for SyntaxError { column, message } in check_line(line) {
+            let (lines, column) = line_and_column(input, start + column - 1);
+            errors.push((lines, SyntaxError { column, message }));
+        }
+    }
+    if errors.is_empty()
+        && let Err(SyntaxError { column, message }) = control::parse(input)
+    {
+        let (lines, column) = line_and_column(input, column - 1);
+        errors.push((lines, SyntaxError { column, message }));
+    }
+    errors
+}
+
+// The number of lines before the character at `offset` in `input` and the 1-based column of that character
+fn line_and_column(input: &str, offset: usize) -> (usize, usize) {
+    let before: String = input.chars().take(offset).collect();
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    (before.matches('\n').count(), column)
+}
+
+// The 1-based character position within `input` of `index` in `part`, which must be a slice of it
+fn column_of(input: &str, part: &str, index: usize) -> usize {
+    let offset = part.as_ptr() as usize - input.as_ptr() as usize + index;
+    input[..offset].chars().count() + 1
+}
+
+// Checks one command of a list, a slice of `input`
+fn check_command(input: &str, command: &str) -> Vec<SyntaxError> {
+    let error = |column: usize, message: String| SyntaxError { column, message };
+
+    if let Some(expr) = arithmetic_command(command) {
+        return match arithmetic::check(expr) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![error(column_of(input, expr, 0), format!("((: {}", e))],
+        };
+    }
+
+    if let Some(expr) = conditional_command(command) {
+        let result = tokenize_owned(expr).map_err(anyhow::Error::from).and_then(|words| conditional::check(&words));
+        return match result {
+            Ok(()) => Vec::new(),
+            Err(e) => {
+                let column = match e.downcast_ref::<SyntaxError>() {
+                    Some(syntax_error) => column_of(input, expr, 0) + syntax_error.column - 1,
+                    None => column_of(input, expr, 0),
+                };
+                vec![error(column, e.to_string())]
+            }
+        };
+    }
+
+    let stages = split_pipeline(command);
+    let mut errors = Vec::new();
+    for (i, stage) in stages.iter().enumerate() {
+        let checked = tokenize(stage)
+            .and_then(|words| split_here_inputs(stage).map(|_| words))
+            .and_then(|words| split_redirections(stage).map(|_| words));
+        match checked {
+            Err(e) => errors.push(error(column_of(input, stage, 0) + e.column - 1, e.message)),
+            Ok(words) if words.is_empty() && stages.len() > 1 => {
+                // point at the `|` that has no command on this side
+                let column = if i + 1 < stages.len() {
+                    column_of(input, stage, stage.len())
+                } else {
+                    column_of(input, stage, 0) - 1
+                };
+                errors.push(error(column, tr!("syntax error near unexpected token `{}'", "|")));
+            }
+            Ok(_) => {}
+        }
+    }
+    errors
+}
+
+// Shows the words the command line splits into, re-quoted where needed, without its redirections
+pub fn display_command(input: &str) -> String {
+    if arithmetic_command(input).is_some() || conditional_command(input).is_some() {
+        return input.trim().to_string();
+    }
+    let mut stages = Vec::new();
+    for stage in split_pipeline(input) {
+        let Ok((_, stage)) = split_here_inputs(stage) else {
+            return input.to_string();
+        };
+        let Ok((_, stage)) = split_redirections(&stage) else {
+            return input.to_string();
+        };
+        match tokenize(&stage) {
+            Ok(words) => stages.push(words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ")),
+            Err(_) => return input.to_string(),
+        }
+    }
+    stages.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::{check_line, parse_line, Command, Line};
+    use crate::tokenizer::Connector;
+
+    fn columns(input: &str) -> Vec<usize> {
+        check_line(input).iter().map(|e| e.column).collect()
+    }
+
+    #[test]
+    fn test_valid_lines() {
+        assert!(check_line("echo 'hello world' | wc -l").is_empty());
+        assert!(check_line("((i++))").is_empty());
+        assert!(check_line("[[ -n x && y == y ]]").is_empty());
+        assert!(check_line("").is_empty());
+    }
+
+    #[test]
+    fn test_error_columns() {
+        assert_eq!(columns("echo 'open"), vec![6]);
+        assert_eq!(columns("ls | grep \"x | wc"), vec![11]);
+        assert_eq!(columns("| wc"), vec![1]);
+        assert_eq!(columns("ls |"), vec![4]);
+        assert_eq!(columns("ls | | wc 'a"), vec![6, 11]);
+        assert_eq!(columns("cat <<EOF; cat <<< | wc"), vec![20]);
+        assert_eq!(columns("echo hi >> | wc"), vec![12]);
+    }
+
+    #[test]
+    fn test_compound_commands() {
+        assert_eq!(columns("((1 +))"), vec![3]);
+        assert_eq!(columns("[[ ( a ]]"), vec![3]);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let line = parse_line(r#"  ls -l "$dir" | wc -l  # count"#).unwrap();
+        assert_eq!(line.command, Command::Pipeline(vec![vec!["ls", "-l", r#""$dir""#], vec!["wc", "-l"]]));
+        assert_eq!(line.comment, Some("# count"));
+        assert_eq!(line.tokens(), vec!["ls", "-l", r#""$dir""#, "|", "wc", "-l", "# count"]);
+        assert_eq!(parse_line("(( i += 1 ))").unwrap().command, Command::Arithmetic("i += 1"));
+        assert_eq!(parse_line("[[ -n 'a b' ]]").unwrap().command, Command::Conditional(vec!["-n", "'a b'"]));
+        assert_eq!(parse_line("   ").unwrap(), Line { command: Command::Empty, comment: None });
+        assert_eq!(parse_line("echo 'open").unwrap_err().column, 6);
+    }
+
+    #[test]
+    fn test_lists() {
+        let line = parse_line("make && ./run || echo failed; sleep 1 & # later").unwrap();
+        assert_eq!(
+            line.command,
+            Command::List(vec![
+                (Command::Pipeline(vec![vec!["make"]]), Connector::And),
+                (Command::Pipeline(vec![vec!["./run"]]), Connector::Or),
+                (Command::Pipeline(vec![vec!["echo", "failed"]]), Connector::Sequence),
+                (Command::Pipeline(vec![vec!["sleep", "1"]]), Connector::Background),
+            ])
+        );
+        assert_eq!(line.tokens(), vec!["make", "&&", "./run", "||", "echo", "failed", ";", "sleep", "1", "&", "# later"]);
+        assert_eq!(parse_line("((x)) && ls;").unwrap().command, Command::List(vec![(Command::Arithmetic("x"), Connector::And), (Command::Pipeline(vec![vec!["ls"]]), Connector::Sequence)]));
+        // a trailing `;` leaves a single command
+        assert_eq!(parse_line("ls;").unwrap().command, Command::Pipeline(vec![vec!["ls"]]));
+    }
+
+    #[test]
+    fn test_list_errors() {
+        assert!(check_line("a; b & c && d || e;").is_empty());
+        assert_eq!(columns("&& ls"), vec![1]);
+        assert_eq!(columns("ls ||"), vec![4]);
+        assert_eq!(columns("ls;; pwd"), vec![4]);
+        assert_eq!(columns("ls && # later"), vec![4]);
+        assert_eq!(columns("((1 +)) && echo 'a"), vec![3, 17]);
+        assert_eq!(check_line("ls &;")[0].message, "syntax error near unexpected token `;'");
+    }
+}
@@ -0,0 +1,840 @@
+//! The shell's view of the operating system: running programs, the working directory and the environment.
+//!
+//! Everything the executor and builtins need from the OS goes through a [`System`]. [`RealSystem`] talks to
+//! the OS; [`FakeSystem`] runs registered closures in memory, so executor tests don't depend on what is
+//! installed, and other backends (sandboxed, remote) can be slotted in the same way.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, PipeReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::process::{Child, Command, Stdio};
+use std::thread::{self, JoinHandle};
+use crate::encoding;
+use crate::interrupt;
+use crate::path_utils::{find_in_path, strip_verbatim};
+use crate::vfs::resolve;
+
+/// Where a spawned program reads its input from.
+pub enum ProcessInput {
+    /// The shell's own stdin
+    Inherit,
+    Null,
+    /// The output of the previous pipeline stage
+    Pipe(PipeReader),
+    /// Output collected from a builtin
+    Bytes(Vec<u8>),
+    /// A file redirected to it with `<`
+    File(File),
+}
+
+/// Where a spawned program writes its output or its error output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessSink {
+    /// The shell's own stdout
+    Stdout,
+    /// The shell's own stderr
+    Stderr,
+    /// A pipe the next pipeline stage reads from, shared by both outputs when both go to it
+    Pipe,
+    /// A file, replacing what it held or, with `append`, after it. Both outputs going to the same file share it.
+    File { path: PathBuf, append: bool },
+}
+
+/// Where a file descriptor above 2 of a spawned program leads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessFd {
+    Output(ProcessSink),
+    /// The shell's own stdin
+    Stdin,
+    /// A file it reads
+    Input(PathBuf),
+}
+
+/// The process group a program starts in, which on Unix decides whether Ctrl-C at the terminal reaches it.
+/// Windows has no process groups to join; a program started outside the shell's is left alone by Ctrl-C.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcessGroup {
+    /// The shell's own, for programs run by a shell that isn't interactive
+    Shell,
+    /// The group of a pipeline an interactive shell waits for: a new one led by the program, which takes over
+    /// the terminal from the shell, or that of the pipeline's first program
+    Foreground(Option<u32>),
+    /// The group of a background job: a new one led by the program, or that of the job's first program
+    Background(Option<u32>),
+}
+
+/// Everything a program printed, collected after it exited.
+pub struct ProcessOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// A running program.
+pub trait Process {
+    fn id(&self) -> u32;
+    /// Waits for the program to exit and returns its exit status.
+    fn wait(&mut self) -> io::Result<i32>;
+    /// The program's exit status if it has exited, without waiting for it.
+    fn try_wait(&mut self) -> io::Result<Option<i32>>;
+    /// Waits for the program to exit or to stop, as Ctrl+Z stops it. Returns the exit status, or None when it
+    /// stopped. Programs only stop where there are signals.
+    fn wait_or_stop(&mut self) -> io::Result<Option<i32>> {
+        self.wait().map(Some)
+    }
+    /// The signal that killed the program, once it has exited that way.
+    fn signal(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// A program started by [`System::spawn`]. When its stdout or stderr was [`ProcessSink::Pipe`], `stdout` is
+/// what the next pipeline stage reads.
+pub struct Spawned {
+    pub process: Box<dyn Process>,
+    pub stdout: Option<ProcessInput>,
+}
+
+pub trait System {
+    /// Finds the program `name` in `path_dirs`.
+    fn find_program(&self, name: &str, path_dirs: &[String]) -> Option<PathBuf>;
+    /// Runs a program to completion and collects its output.
+    fn output(&self, program: &str, args: &[String]) -> io::Result<ProcessOutput>;
+    /// Starts a program without waiting for it. `env` holds the variables to set in its environment on top of
+    /// the shell's own, and `fds` the file descriptors above 2 it gets.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: ProcessInput,
+        stdout: ProcessSink,
+        stderr: ProcessSink,
+        fds: &[(u32, ProcessFd)],
+        group: ProcessGroup,
+    ) -> io::Result<Spawned>;
+    fn current_dir(&self) -> io::Result<PathBuf>;
+    fn set_current_dir(&self, path: &Path) -> io::Result<()>;
+    /// The absolute path of an existing file or directory with symbolic links resolved, relative to the working
+    /// directory when not absolute.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// The names of the entries in a directory, relative to the working directory when not absolute.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>>;
+    fn env_var(&self, name: &str) -> Option<String>;
+    /// The shell's terminal: where commands read from and write to when nothing is redirected.
+    fn stdin(&self) -> Box<dyn Read>;
+    /// May be buffered: flushing it is what makes the output appear, which the shell does before anything
+    /// else can write to the terminal.
+    fn stdout(&self) -> Box<dyn Write>;
+    fn stderr(&self) -> Box<dyn Write>;
+    /// Whether `stdout`, and `stderr` below, write to an actual terminal, which can show colors.
+    fn stdout_is_terminal(&self) -> bool {
+        false
+    }
+    fn stderr_is_terminal(&self) -> bool {
+        false
+    }
+    /// Opens a file for output redirected to it, creating it if needed. Unless `append`, it is emptied first.
+    fn open_output(&self, path: &Path, append: bool) -> io::Result<Box<dyn Write>>;
+    /// Opens a file for input redirected from it.
+    fn open_input(&self, path: &Path) -> io::Result<ProcessInput> {
+        self.read_file(path).map(ProcessInput::Bytes)
+    }
+    /// The contents of a file, relative to the working directory when not absolute.
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>>;
+}
+
+/// A writer appending to a buffer shared with whoever reads the output later.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Everything written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    /// Returns everything written so far and empties the buffer.
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The operating system the shell runs on.
+pub struct RealSystem;
+
+thread_local! {
+    // what the shell writes to stdout, passed on in large chunks instead of a write per line
+    static STDOUT: RefCell<BufWriter<io::Stdout>> = RefCell::new(BufWriter::with_capacity(64 * 1024, io::stdout()));
+}
+
+struct BufferedStdout;
+
+impl Write for BufferedStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        STDOUT.with_borrow_mut(|stdout| stdout.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        STDOUT.with_borrow_mut(|stdout| stdout.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        STDOUT.with_borrow_mut(|stdout| stdout.flush())
+    }
+}
+
+// The shell's stderr: messages keep their place relative to the output before them, so what is buffered for
+// stdout is written out before each of them
+struct OrderedStderr;
+
+impl Write for OrderedStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        BufferedStdout.flush()?;
+        io::stderr().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        BufferedStdout.flush()?;
+        io::stderr().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+// The shell's stdin, read without a buffer: a builtin like `read` takes only the bytes it asks for, and leaves
+// the rest to the programs run after it
+struct UnbufferedStdin;
+
+impl Read for UnbufferedStdin {
+    #[cfg(unix)]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(count as usize)
+    }
+
+    #[cfg(not(unix))]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::stdin().read(buf)
+    }
+}
+
+struct RealProcess {
+    child: Child,
+    // writes builtin output to the child from another thread so a full pipe can't block the shell
+    feeder: Option<JoinHandle<()>>,
+    // the status once `wait_or_stop` saw the program exit, which `child` doesn't know about
+    status: Option<i32>,
+    signal: Option<i32>,
+}
+
+impl Process for RealProcess {
+    fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    fn wait(&mut self) -> io::Result<i32> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+        let status = self.child.wait()?;
+        Ok(self.exited(status))
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<i32>> {
+        if let Some(status) = self.status {
+            return Ok(Some(status));
+        }
+        Ok(self.child.try_wait()?.map(|status| self.exited(status)))
+    }
+
+    fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    #[cfg(unix)]
+    fn wait_or_stop(&mut self) -> io::Result<Option<i32>> {
+        if let Some(status) = self.status {
+            return Ok(Some(status));
+        }
+        let mut raw = 0;
+        while unsafe { libc::waitpid(self.child.id() as libc::pid_t, &mut raw, libc::WUNTRACED) } < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+        if libc::WIFSTOPPED(raw) {
+            return Ok(None);
+        }
+        let status = self.exited(std::os::unix::process::ExitStatusExt::from_raw(raw));
+        self.status = Some(status);
+        Ok(Some(status))
+    }
+}
+
+impl RealProcess {
+    // The shell's status for a program that exited with `status`
+    fn exited(&mut self, status: std::process::ExitStatus) -> i32 {
+        if let Some(feeder) = self.feeder.take() {
+            let _ = feeder.join();
+        }
+        // a program killed by a signal has the status 128 + the signal number, as in other shells
+        #[cfg(unix)]
+        if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+            self.signal = Some(signal);
+            return 128 + signal;
+        }
+        status.code().unwrap_or(1)
+    }
+}
+
+impl System for RealSystem {
+    fn find_program(&self, name: &str, path_dirs: &[String]) -> Option<PathBuf> {
+        let path_dirs: Vec<&str> = path_dirs.iter().map(|s| s.as_str()).collect();
+        find_in_path(name, &path_dirs)
+    }
+
+    fn output(&self, program: &str, args: &[String]) -> io::Result<ProcessOutput> {
+        let child = Command::new(encoding::to_os(program))
+            .args(args.iter().map(|a| encoding::to_os(a)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        interrupt::track(&child);
+        let output = child.wait_with_output()?;
+        Ok(ProcessOutput { status: output.status.code().unwrap_or(1), stdout: output.stdout, stderr: output.stderr })
+    }
+
+    fn spawn(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: ProcessInput,
+        stdout: ProcessSink,
+        stderr: ProcessSink,
+        fds: &[(u32, ProcessFd)],
+        group: ProcessGroup,
+    ) -> io::Result<Spawned> {
+        // exec fails on a directory with EACCES, which doesn't tell what's wrong
+        #[cfg(unix)]
+        if Path::new(program).is_dir() {
+            return Err(io::Error::from_raw_os_error(libc::EISDIR));
+        }
+        let mut cmd = Command::new(encoding::to_os(program));
+        cmd.args(args.iter().map(|a| encoding::to_os(a)));
+        cmd.envs(env.iter().map(|(name, value)| (encoding::to_os(name), encoding::to_os(value))));
+        let mut feed = None;
+        cmd.stdin(match stdin {
+            ProcessInput::Inherit => Stdio::inherit(),
+            ProcessInput::Null => Stdio::null(),
+            ProcessInput::Pipe(pipe) => Stdio::from(pipe),
+            ProcessInput::Bytes(bytes) => {
+                feed = Some(bytes);
+                Stdio::piped()
+            }
+            ProcessInput::File(file) => Stdio::from(file),
+        });
+        let mut pipe = None;
+        let mut file: Option<(PathBuf, File)> = None;
+        let mut stdio = |sink: ProcessSink| -> io::Result<Stdio> {
+            Ok(match sink {
+                ProcessSink::Stdout => Stdio::from(io::stdout()),
+                ProcessSink::Stderr => Stdio::from(io::stderr()),
+                ProcessSink::Pipe => match &pipe {
+                    Some((_, writer)) => Stdio::from(io::PipeWriter::try_clone(writer)?),
+                    None => {
+                        let (reader, writer) = io::pipe()?;
+                        pipe = Some((reader, writer.try_clone()?));
+                        Stdio::from(writer)
+                    }
+                },
+                ProcessSink::File { path, append } => match &file {
+                    Some((opened, handle)) if *opened == path => Stdio::from(handle.try_clone()?),
+                    _ => {
+                        let handle = open_file(&path, append)?;
+                        file = Some((path, handle.try_clone()?));
+                        Stdio::from(handle)
+                    }
+                },
+            })
+        };
+        cmd.stdout(stdio(stdout)?);
+        cmd.stderr(stdio(stderr)?);
+
+        // the other file descriptors are opened here and moved into place in the child, first out of the way of
+        // one another: a file opened for one can have the number another is moved to
+        #[cfg(unix)]
+        let mut opened = Vec::new();
+        #[cfg(unix)]
+        if !fds.is_empty() {
+            use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+            for (fd, target) in fds {
+                let handle: OwnedFd = match target {
+                    ProcessFd::Output(ProcessSink::Stdout) => io::stdout().as_fd().try_clone_to_owned()?,
+                    ProcessFd::Output(ProcessSink::Stderr) => io::stderr().as_fd().try_clone_to_owned()?,
+                    ProcessFd::Stdin => io::stdin().as_fd().try_clone_to_owned()?,
+                    ProcessFd::Output(ProcessSink::Pipe) => match &pipe {
+                        Some((_, writer)) => writer.try_clone()?.into(),
+                        None => {
+                            let (reader, writer) = io::pipe()?;
+                            pipe = Some((reader, writer.try_clone()?));
+                            writer.into()
+                        }
+                    },
+                    ProcessFd::Output(ProcessSink::File { path, append }) => match &file {
+                        Some((opened, handle)) if opened == path => handle.try_clone()?.into(),
+                        _ => open_file(path, *append)?.into(),
+                    },
+                    ProcessFd::Input(path) => File::open(path)?.into(),
+                };
+                opened.push((*fd as i32, handle));
+            }
+            let moves: Vec<(i32, i32)> = opened.iter().map(|(fd, handle)| (*fd, handle.as_raw_fd())).collect();
+            let above = moves.iter().map(|&(fd, _)| fd).max().unwrap_or(2) + 1;
+            let mut moved = vec![0; moves.len()];
+            // Safety: the closure only makes async-signal-safe calls and doesn't allocate
+            unsafe {
+                std::os::unix::process::CommandExt::pre_exec(&mut cmd, move || {
+                    for (i, &(_, handle)) in moves.iter().enumerate() {
+                        moved[i] = libc::fcntl(handle, libc::F_DUPFD, above);
+                        if moved[i] < 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    for (i, &(fd, _)) in moves.iter().enumerate() {
+                        if libc::dup2(moved[i], fd) < 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                        libc::close(moved[i]);
+                    }
+                    Ok(())
+                })
+            };
+        }
+        // Windows programs get no file descriptors beyond the standard ones
+        #[cfg(not(unix))]
+        let _ = fds;
+
+        #[cfg(unix)]
+        match group {
+            ProcessGroup::Shell => {}
+            ProcessGroup::Foreground(None) => {
+                std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+                // the program takes the terminal itself before it can read from it, which would stop it while
+                // it's still in the background. Safety: the closure only makes async-signal-safe calls
+                let shell = unsafe { libc::getpgrp() };
+                unsafe {
+                    std::os::unix::process::CommandExt::pre_exec(&mut cmd, move || {
+                        if libc::tcgetpgrp(libc::STDIN_FILENO) == shell {
+                            crate::jobs::set_foreground(libc::getpgrp());
+                        }
+                        Ok(())
+                    })
+                };
+            }
+            ProcessGroup::Background(None) => {
+                std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+            }
+            ProcessGroup::Foreground(Some(leader)) | ProcessGroup::Background(Some(leader)) => {
+                std::os::unix::process::CommandExt::process_group(&mut cmd, leader as i32);
+            }
+        }
+        // CREATE_NEW_PROCESS_GROUP, which keeps console Ctrl-C events from the program
+        #[cfg(windows)]
+        if matches!(group, ProcessGroup::Background(_)) {
+            std::os::windows::process::CommandExt::creation_flags(&mut cmd, 0x0000_0200);
+        }
+
+        let mut child = cmd.spawn()?;
+        if !matches!(group, ProcessGroup::Background(_)) {
+            interrupt::track(&child);
+        }
+        let feeder = match (feed, child.stdin.take()) {
+            (Some(bytes), Some(mut child_stdin)) => Some(thread::spawn(move || {
+                let _ = child_stdin.write_all(&bytes);
+            })),
+            _ => None,
+        };
+        // the pipe's write ends are dropped here, so the reader sees end of input when the child exits
+        drop(cmd);
+        #[cfg(unix)]
+        drop(opened);
+        let process = Box::new(RealProcess { child, feeder, status: None, signal: None });
+        Ok(Spawned { process, stdout: pipe.map(|(reader, _)| ProcessInput::Pipe(reader)) })
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        let dir = env::current_dir()?;
+        // `cd` to a long path leaves Windows with a \\?\ current directory that `cd ..` can't leave
+        if cfg!(windows) {
+            return Ok(encoding::to_path(&strip_verbatim(&encoding::from_os(dir.as_os_str()))));
+        }
+        Ok(dir)
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        env::set_current_dir(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = std::fs::canonicalize(path)?;
+        if cfg!(windows) {
+            return Ok(encoding::to_path(&strip_verbatim(&encoding::from_os(path.as_os_str()))));
+        }
+        Ok(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>> {
+        std::fs::read_dir(path)?.map(|entry| Ok(encoding::from_os(&entry?.file_name()))).collect()
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        encoding::env_var(name)
+    }
+
+    fn stdin(&self) -> Box<dyn Read> {
+        Box::new(UnbufferedStdin)
+    }
+
+    fn stdout(&self) -> Box<dyn Write> {
+        Box::new(BufferedStdout)
+    }
+
+    fn stderr(&self) -> Box<dyn Write> {
+        Box::new(OrderedStderr)
+    }
+
+    fn stdout_is_terminal(&self) -> bool {
+        io::stdout().is_terminal()
+    }
+
+    fn stderr_is_terminal(&self) -> bool {
+        io::stderr().is_terminal()
+    }
+
+    fn open_output(&self, path: &Path, append: bool) -> io::Result<Box<dyn Write>> {
+        Ok(Box::new(open_file(path, append)?))
+    }
+
+    fn open_input(&self, path: &Path) -> io::Result<ProcessInput> {
+        // File::open succeeds on a directory, which only fails once it's read
+        if path.is_dir() {
+            return Err(io::Error::from(io::ErrorKind::IsADirectory));
+        }
+        File::open(path).map(ProcessInput::File)
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+}
+
+// Everything a program run to completion reads, for systems that do that in [`System::spawn`]
+pub(crate) fn read_input(input: ProcessInput) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match input {
+        ProcessInput::Inherit | ProcessInput::Null => {}
+        ProcessInput::Pipe(mut pipe) => {
+            pipe.read_to_end(&mut bytes)?;
+        }
+        ProcessInput::Bytes(input) => bytes = input,
+        ProcessInput::File(mut file) => {
+            file.read_to_end(&mut bytes)?;
+        }
+    }
+    Ok(bytes)
+}
+
+fn open_file(path: &Path, append: bool) -> io::Result<File> {
+    File::options().create(true).append(append).write(true).truncate(!append).open(path)
+}
+
+// Writes what a program that already ran printed to its stdout and stderr where each goes, for systems that run
+// programs to completion in [`System::spawn`]. Returns what went to the pipe, for the next stage to read.
+pub(crate) fn write_finished(
+    system: &dyn System,
+    outputs: [(ProcessSink, Vec<u8>); 2],
+) -> io::Result<Option<ProcessInput>> {
+    let mut piped: Option<Vec<u8>> = None;
+    let mut files: Vec<(PathBuf, Box<dyn Write>)> = Vec::new();
+    for (sink, bytes) in outputs {
+        match sink {
+            ProcessSink::Stdout => system.stdout().write_all(&bytes)?,
+            ProcessSink::Stderr => system.stderr().write_all(&bytes)?,
+            ProcessSink::Pipe => piped.get_or_insert_default().extend(bytes),
+            ProcessSink::File { path, append } => match files.iter_mut().find(|(opened, _)| *opened == path) {
+                Some((_, file)) => file.write_all(&bytes)?,
+                None => {
+                    let mut file = system.open_output(&path, append)?;
+                    file.write_all(&bytes)?;
+                    files.push((path, file));
+                }
+            },
+        }
+    }
+    Ok(piped.map(ProcessInput::Bytes))
+}
+
+/// A program registered with [`FakeSystem`]: given its arguments and input, it returns its exit status and
+/// output.
+pub type FakeProgram = Box<dyn Fn(&[String], &[u8]) -> (i32, Vec<u8>)>;
+
+/// An in-memory system for tests. Programs are closures, directories are a set of paths, and output that
+/// would go to the terminal is collected.
+pub struct FakeSystem {
+    programs: HashMap<String, FakeProgram>,
+    dirs: HashSet<PathBuf>,
+    files: RefCell<HashMap<PathBuf, SharedBuffer>>,
+    cwd: RefCell<PathBuf>,
+    env: HashMap<String, String>,
+    stdout: SharedBuffer,
+    stderr: SharedBuffer,
+    terminal: bool,
+    spawned: RefCell<Vec<(String, Vec<String>)>>,
+    environments: RefCell<Vec<Vec<(String, String)>>>,
+    next_pid: Cell<u32>,
+}
+
+/// A program that already ran to completion when it was spawned.
+pub struct FinishedProcess {
+    pub id: u32,
+    pub status: i32,
+}
+
+impl Process for FinishedProcess {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn wait(&mut self) -> io::Result<i32> {
+        Ok(self.status)
+    }
+
+    fn try_wait(&mut self) -> io::Result<Option<i32>> {
+        Ok(Some(self.status))
+    }
+}
+
+impl Default for FakeSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeSystem {
+    /// A system with no programs whose only directory is `/`, which is also the working directory.
+    pub fn new() -> Self {
+        FakeSystem {
+            programs: HashMap::new(),
+            dirs: HashSet::from([PathBuf::from("/")]),
+            files: RefCell::new(HashMap::new()),
+            cwd: RefCell::new(PathBuf::from("/")),
+            env: HashMap::new(),
+            stdout: SharedBuffer::default(),
+            stderr: SharedBuffer::default(),
+            terminal: false,
+            spawned: RefCell::new(Vec::new()),
+            environments: RefCell::new(Vec::new()),
+            next_pid: Cell::new(1000),
+        }
+    }
+
+    pub fn program(mut self, name: &str, run: impl Fn(&[String], &[u8]) -> (i32, Vec<u8>) + 'static) -> Self {
+        self.programs.insert(name.to_string(), Box::new(run));
+        self
+    }
+
+    /// Makes the collected output count as written to a terminal, as it would be in an interactive shell.
+    pub fn terminal(mut self) -> Self {
+        self.terminal = true;
+        self
+    }
+
+    pub fn dir(mut self, path: &str) -> Self {
+        self.dirs.insert(PathBuf::from(path));
+        self
+    }
+
+    /// Adds an empty file, which shows up when directories are listed and can be written by redirections.
+    pub fn file(self, path: &str) -> Self {
+        self.files.borrow_mut().insert(PathBuf::from(path), SharedBuffer::default());
+        self
+    }
+
+    /// What was written to a file, relative to the working directory when not absolute.
+    pub fn file_contents(&self, path: &str) -> Option<Vec<u8>> {
+        let path = resolve(&self.cwd.borrow(), path);
+        self.files.borrow().get(&path).map(SharedBuffer::contents)
+    }
+
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// What commands wrote to standard output so far.
+    pub fn output(&self) -> Vec<u8> {
+        self.stdout.contents()
+    }
+
+    /// What commands wrote to standard error so far.
+    pub fn errors(&self) -> Vec<u8> {
+        self.stderr.contents()
+    }
+
+    /// The programs started so far with their arguments, in order.
+    pub fn spawned(&self) -> Vec<(String, Vec<String>)> {
+        self.spawned.borrow().clone()
+    }
+
+    /// The variables the shell set in the environment of each program started so far, in order.
+    pub fn environments(&self) -> Vec<Vec<(String, String)>> {
+        self.environments.borrow().clone()
+    }
+
+    fn run(&self, program: &str, args: &[String], input: &[u8]) -> io::Result<(i32, Vec<u8>)> {
+        // the shell may start a program by the path find_program returned
+        let program = program.rsplit(['/', '\\']).next().unwrap_or(program);
+        self.spawned.borrow_mut().push((program.to_string(), args.to_vec()));
+        match self.programs.get(program) {
+            Some(run) => Ok(run(args, input)),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+}
+
+impl System for FakeSystem {
+    fn find_program(&self, name: &str, _path_dirs: &[String]) -> Option<PathBuf> {
+        self.programs.contains_key(name).then(|| Path::new("/bin").join(name))
+    }
+
+    fn output(&self, program: &str, args: &[String]) -> io::Result<ProcessOutput> {
+        let (status, stdout) = self.run(program, args, &[])?;
+        Ok(ProcessOutput { status, stdout, stderr: Vec::new() })
+    }
+
+    fn spawn(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: ProcessInput,
+        stdout: ProcessSink,
+        stderr: ProcessSink,
+        _fds: &[(u32, ProcessFd)],
+        _group: ProcessGroup,
+    ) -> io::Result<Spawned> {
+        self.environments.borrow_mut().push(env.to_vec());
+        let input = read_input(stdin)?;
+        let (status, output) = self.run(program, args, &input)?;
+
+        let id = self.next_pid.get();
+        self.next_pid.set(id + 1);
+        let stdout = write_finished(self, [(stdout, output), (stderr, Vec::new())])?;
+        Ok(Spawned { process: Box::new(FinishedProcess { id, status }), stdout })
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.cwd.borrow().clone())
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if !self.dirs.contains(&path) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        *self.cwd.borrow_mut() = path;
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if !self.dirs.contains(&path) && !self.files.borrow().contains_key(&path) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        Ok(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if !self.dirs.contains(&path) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        let files = self.files.borrow();
+        let entries = self.dirs.iter().chain(files.keys()).filter(|entry| entry.parent() == Some(&path));
+        Ok(entries.filter_map(|entry| entry.file_name()).map(|name| name.to_string_lossy().into_owned()).collect())
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        self.env.get(name).cloned()
+    }
+
+    fn stdin(&self) -> Box<dyn Read> {
+        Box::new(io::empty())
+    }
+
+    fn stdout(&self) -> Box<dyn Write> {
+        Box::new(self.stdout.clone())
+    }
+
+    fn stderr(&self) -> Box<dyn Write> {
+        Box::new(self.stderr.clone())
+    }
+
+    fn stdout_is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    fn stderr_is_terminal(&self) -> bool {
+        self.terminal
+    }
+
+    fn open_output(&self, path: &Path, append: bool) -> io::Result<Box<dyn Write>> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if self.dirs.contains(&path) {
+            return Err(io::Error::from(io::ErrorKind::IsADirectory));
+        }
+        if !path.parent().is_some_and(|parent| self.dirs.contains(parent)) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        let mut files = self.files.borrow_mut();
+        let file = files.entry(path).or_default();
+        if !append {
+            file.take();
+        }
+        Ok(Box::new(file.clone()))
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if self.dirs.contains(&path) {
+            return Err(io::Error::from(io::ErrorKind::IsADirectory));
+        }
+        let files = self.files.borrow();
+        files.get(&path).map(SharedBuffer::contents).ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
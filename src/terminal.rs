@@ -0,0 +1,95 @@
+//! Getting the terminal ready for an interactive shell, and keeping it from echoing what is typed.
+
+/// Turns on virtual terminal processing for stdout and stderr, so the escape sequences in a colored prompt
+/// or sent by line editing work in the classic Windows console (conhost) as they do in other terminals.
+/// Does nothing on other platforms, or when the stream isn't a console.
+pub fn enable_virtual_terminal() {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{
+            GetConsoleMode, GetStdHandle, SetConsoleMode, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_ERROR_HANDLE,
+            STD_OUTPUT_HANDLE,
+        };
+        for stream in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            unsafe {
+                let handle = GetStdHandle(stream);
+                let mut mode = 0;
+                // fails for redirected streams, which have no console mode to change
+                if GetConsoleMode(handle, &mut mode) != 0 {
+                    SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+                }
+            }
+        }
+    }
+}
+
+/// Echoing of typed characters turned off for as long as this lives, for reading a secret such as a password.
+#[cfg(unix)]
+pub struct NoEcho(libc::termios);
+
+#[cfg(unix)]
+impl NoEcho {
+    /// None when stdin isn't a terminal.
+    pub fn enable() -> Option<Self> {
+        // Safety: termios is plain data, filled in by tcgetattr before it's used
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return None;
+        }
+        let mut silent = original;
+        silent.c_lflag &= !libc::ECHO;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &silent) } != 0 {
+            return None;
+        }
+        Some(NoEcho(original))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.0) };
+    }
+}
+
+#[cfg(windows)]
+pub struct NoEcho(u32);
+
+#[cfg(windows)]
+impl NoEcho {
+    /// None when stdin isn't a console.
+    pub fn enable() -> Option<Self> {
+        use windows_sys::Win32::System::Console::{
+            GetConsoleMode, GetStdHandle, SetConsoleMode, ENABLE_ECHO_INPUT, STD_INPUT_HANDLE,
+        };
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            let mut original = 0;
+            if GetConsoleMode(handle, &mut original) == 0 {
+                return None;
+            }
+            if SetConsoleMode(handle, original & !ENABLE_ECHO_INPUT) == 0 {
+                return None;
+            }
+            Some(NoEcho(original))
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for NoEcho {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Console::{GetStdHandle, SetConsoleMode, STD_INPUT_HANDLE};
+        unsafe { SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), self.0) };
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub struct NoEcho;
+
+#[cfg(not(any(unix, windows)))]
+impl NoEcho {
+    pub fn enable() -> Option<Self> {
+        None
+    }
+}
@@ -0,0 +1,166 @@
+//! Colors for what the shell prints for people to read: the prompt's `\c`, error messages, and the output of
+//! `type` and `jobs`.
+//!
+//! RUSH_COLOR_SCHEME names the colors, one of [`SCHEMES`]: `default`, `bright`, or `none` for no colors at all;
+//! other names mean `default`. RUSH_PROMPT_THEME, when set, names the prompt's colors instead. The config file's
+//! `colors.scheme` and `prompt.theme` set them. Colors are only used on a terminal that can show them, so not
+//! when the output is redirected or TERM is `dumb`. NO_COLOR, when set and not empty, turns them off regardless,
+//! as does CLICOLOR=0, while CLICOLOR_FORCE, unless empty or `0`, turns them on where they wouldn't be.
+
+use crate::builtins::ShellContext;
+
+/// What a piece of text is, which decides its color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The prompt after a command that succeeded, and after one that failed, which is also how a job that
+    /// failed is listed
+    Success,
+    Failure,
+    /// A message about an error
+    Error,
+    /// A command name, by what it runs
+    Alias,
+    Builtin,
+    Program,
+    /// A job, by its state
+    Running,
+    Stopped,
+    Done,
+}
+
+/// Ends the color a role's text starts with.
+pub const RESET: &str = "\x1b[0m";
+
+/// The names RUSH_COLOR_SCHEME and RUSH_PROMPT_THEME take.
+pub const SCHEMES: &[&str] = &["default", "bright", "none"];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Default,
+    Bright,
+    None,
+}
+
+// The colors for the prompt, per `prompt`, or for everything else
+fn scheme(ctx: &ShellContext, prompt: bool) -> Scheme {
+    let set = |name: &str| ctx.variables.get(name).filter(|value| !value.is_empty());
+    let name = prompt.then(|| set("RUSH_PROMPT_THEME")).flatten().or_else(|| set("RUSH_COLOR_SCHEME"));
+    match name.as_deref() {
+        Some("bright") => Scheme::Bright,
+        Some("none") => Scheme::None,
+        _ => Scheme::Default,
+    }
+}
+
+// Whether the environment lets output to a terminal or, without `terminal`, somewhere else be colored
+fn allowed(ctx: &ShellContext, terminal: bool) -> bool {
+    let set = |name: &str| ctx.variables.get(name).filter(|value| !value.is_empty());
+    if set("NO_COLOR").is_some() {
+        return false;
+    }
+    if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+        return true;
+    }
+    terminal && set("CLICOLOR").as_deref() != Some("0") && set("TERM").as_deref() != Some("dumb")
+}
+
+/// Whether to color output that goes to a terminal or, without `terminal`, somewhere else.
+pub fn enabled(ctx: &ShellContext, terminal: bool) -> bool {
+    scheme(ctx, false) != Scheme::None && allowed(ctx, terminal)
+}
+
+/// Whether to color the prompt.
+pub fn prompt_enabled(ctx: &ShellContext) -> bool {
+    scheme(ctx, true) != Scheme::None && allowed(ctx, true)
+}
+
+/// The escape sequence that starts text of `role` in the scheme RUSH_COLOR_SCHEME names, empty for text it
+/// leaves uncolored.
+pub fn color(role: Role, ctx: &ShellContext) -> &'static str {
+    palette(role, scheme(ctx, false))
+}
+
+/// Like [`color`], in the prompt's scheme.
+pub fn prompt_color(role: Role, ctx: &ShellContext) -> &'static str {
+    palette(role, scheme(ctx, true))
+}
+
+fn palette(role: Role, scheme: Scheme) -> &'static str {
+    match (role, scheme) {
+        (_, Scheme::None) => "",
+        (Role::Success | Role::Running | Role::Program, Scheme::Default) => "\x1b[32m",
+        (Role::Failure | Role::Error, Scheme::Default) => "\x1b[31m",
+        (Role::Alias, Scheme::Default) => "\x1b[36m",
+        (Role::Builtin | Role::Stopped, Scheme::Default) => "\x1b[33m",
+        (Role::Done, Scheme::Default) => "",
+        (Role::Success | Role::Running, Scheme::Bright) => "\x1b[1;92m",
+        (Role::Failure | Role::Error, Scheme::Bright) => "\x1b[1;91m",
+        (Role::Alias, Scheme::Bright) => "\x1b[1;96m",
+        (Role::Builtin | Role::Stopped, Scheme::Bright) => "\x1b[1;93m",
+        (Role::Program, Scheme::Bright) => "\x1b[1;94m",
+        (Role::Done, Scheme::Bright) => "\x1b[90m",
+    }
+}
+
+/// `text` in the color of `role` when output to a terminal, per `terminal`, is colored, else as it is.
+pub fn paint(text: &str, role: Role, ctx: &ShellContext, terminal: bool) -> String {
+    let color = color(role, ctx);
+    if color.is_empty() || !enabled(ctx, terminal) {
+        return text.to_string();
+    }
+    format!("{}{}{}", color, text, RESET)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::builtins::ShellContext;
+    use crate::system::FakeSystem;
+    use crate::theme::{enabled, paint, prompt_color, prompt_enabled, Role};
+
+    #[test]
+    fn test_enabled() {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(Rc::new(FakeSystem::new().env("TERM", "xterm")));
+        assert!(enabled(&ctx, true));
+        assert!(!enabled(&ctx, false));
+        ctx.variables.set("TERM", "dumb");
+        assert!(!enabled(&ctx, true));
+        ctx.variables.set("CLICOLOR_FORCE", "1");
+        assert!(enabled(&ctx, false));
+        ctx.variables.set("NO_COLOR", "1");
+        assert!(!enabled(&ctx, true));
+
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(Rc::new(FakeSystem::new()));
+        ctx.variables.set("CLICOLOR", "0");
+        assert!(!enabled(&ctx, true));
+        ctx.variables.set("CLICOLOR", "1");
+        ctx.variables.set("NO_COLOR", "");
+        assert!(enabled(&ctx, true));
+        ctx.variables.set("RUSH_COLOR_SCHEME", "none");
+        assert!(!enabled(&ctx, true));
+        // the prompt has its own scheme when one is set
+        assert!(!prompt_enabled(&ctx));
+        ctx.variables.set("RUSH_PROMPT_THEME", "bright");
+        assert!(prompt_enabled(&ctx));
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut ctx = ShellContext::new(Vec::new());
+        ctx.set_system(Rc::new(FakeSystem::new()));
+        assert_eq!(paint("ls", Role::Alias, &ctx, true), "\x1b[36mls\x1b[0m");
+        assert_eq!(paint("ls", Role::Alias, &ctx, false), "ls");
+        // text a theme leaves uncolored gets no escape sequences at all
+        assert_eq!(paint("[1]+  Done", Role::Done, &ctx, true), "[1]+  Done");
+        ctx.variables.set("RUSH_COLOR_SCHEME", "bright");
+        assert_eq!(paint("ls", Role::Alias, &ctx, true), "\x1b[1;96mls\x1b[0m");
+        assert_eq!(paint("[1]+  Done", Role::Done, &ctx, true), "\x1b[90m[1]+  Done\x1b[0m");
+        // an unknown theme is the default one
+        ctx.variables.set("RUSH_COLOR_SCHEME", "solarized");
+        assert_eq!(paint("ls", Role::Alias, &ctx, true), "\x1b[36mls\x1b[0m");
+        ctx.variables.set("RUSH_PROMPT_THEME", "bright");
+        assert_eq!(prompt_color(Role::Success, &ctx), "\x1b[1;92m");
+    }
+}
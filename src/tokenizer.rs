@@ -1,32 +1,62 @@
-pub fn tokenize(input: &str) -> anyhow::Result<Vec<String>> {
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use crate::locale::tr;
+
+// A tokenizer error with the 1-based column in the input where the offending construct starts
+#[derive(Debug)]
+pub struct SyntaxError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Splits a command line into words, removing quotes and escapes. A word is borrowed from `input` when it
+/// is written without quotes or escapes, so only words that need unquoting are allocated.
+pub fn tokenize(input: &str) -> Result<Vec<Cow<'_, str>>, SyntaxError> {
     let mut tokens = Vec::new();
-    let mut current_token = String::new();
-    let mut has_token = false;
-    let mut chars = input.trim().chars().peekable();
+    let offset = input.len() - input.trim_start().len();
+    let trimmed = input.trim();
+    let mut chars = trimmed.char_indices().peekable();
+    // where the current word starts, and its text once unquoting made it differ from the source
+    let mut start: Option<usize> = None;
+    let mut unquoted: Option<String> = None;
+
+    let error = |index: usize, message: &str| SyntaxError {
+        column: input[..offset + index].chars().count() + 1,
+        message: message.to_string(),
+    };
 
-    while let Some(c) = chars.next() {
+    while let Some((index, c)) = chars.next() {
         match c {
             // --- Single-quoted string: everything is literal until closing ' ---
             '\'' => {
-                has_token = true;
+                let current_token = unquoting(&mut unquoted, &mut start, trimmed, index);
                 loop {
                     match chars.next() {
-                        Some('\'') => break,
-                        Some(ch) => current_token.push(ch),
-                        None => anyhow::bail!("Unterminated single quote"),
+                        Some((_, '\'')) => break,
+                        Some((_, ch)) => current_token.push(ch),
+                        None => return Err(error(index, &tr!("Unterminated single quote"))),
                     }
                 }
             }
             // --- Double-quoted string: literal except \\ \" \$ \` \newline ---
             '"' => {
-                has_token = true;
+                let current_token = unquoting(&mut unquoted, &mut start, trimmed, index);
                 loop {
                     match chars.next() {
-                        Some('"') => break,
-                        Some('\\') => {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => {
                             match chars.peek() {
-                                Some('"') | Some('\\') | Some('$') | Some('`') | Some('\n') => {
-                                    current_token.push(chars.next().unwrap());
+                                Some((_, '"' | '\\' | '$' | '`' | '\n')) => {
+                                    current_token.push(chars.next().unwrap().1);
                                 }
                                 _ => {
                                     // Backslash is literal when not followed by a special char
@@ -34,45 +64,594 @@ pub fn tokenize(input: &str) -> anyhow::Result<Vec<String>> {
                                 }
                             }
                         }
-                        Some(ch) => current_token.push(ch),
-                        None => anyhow::bail!("Unterminated double quote"),
+                        Some((_, ch)) => current_token.push(ch),
+                        None => return Err(error(index, &tr!("Unterminated double quote"))),
                     }
                 }
             }
             // --- Unquoted backslash: next char is literal ---
             '\\' => {
-                has_token = true;
+                let current_token = unquoting(&mut unquoted, &mut start, trimmed, index);
                 match chars.next() {
-                    Some(ch) => current_token.push(ch),
-                    None => anyhow::bail!("Trailing backslash"),
+                    Some((_, ch)) => current_token.push(ch),
+                    None => return Err(error(index, &tr!("Trailing backslash"))),
                 }
             }
             // --- Unquoted whitespace: finalize token ---
             ' ' | '\t' => {
-                if has_token {
-                    tokens.push(current_token);
-                    current_token = String::new();
-                    has_token = false;
+                if let Some(start) = start.take() {
+                    tokens.push(unquoted.take().map_or(Cow::Borrowed(&trimmed[start..index]), Cow::Owned));
                 }
             }
+            // --- Comment: '#' at the start of a word runs to the end of the line ---
+            '#' if start.is_none() => break,
+            // --- Arithmetic expansion: part of the word up to the closing )), spaces and all ---
+            '$' if arithmetic_expansion(&trimmed[index..]).is_some() => {
+                start.get_or_insert(index);
+                let end = index + arithmetic_expansion(&trimmed[index..]).unwrap_or(0);
+                if let Some(current_token) = unquoted.as_mut() {
+                    current_token.push_str(&trimmed[index..end]);
+                }
+                while chars.next_if(|&(i, _)| i < end).is_some() {}
+            }
             // --- Normal character ---
             _ => {
-                has_token = true;
-                current_token.push(c);
+                start.get_or_insert(index);
+                if let Some(current_token) = unquoted.as_mut() {
+                    current_token.push(c);
+                }
             }
         }
     }
 
-    if has_token {
-        tokens.push(current_token);
+    if let Some(start) = start {
+        tokens.push(unquoted.map_or(Cow::Borrowed(&trimmed[start..]), Cow::Owned));
     }
 
+    tracing::debug!(input, ?tokens, "tokenize");
     Ok(tokens)
 }
 
+// The current word as an owned copy of its source up to `index`, made on its first quote or escape so the
+// unquoted text can be appended to it
+fn unquoting<'a>(
+    unquoted: &'a mut Option<String>,
+    start: &mut Option<usize>,
+    source: &str,
+    index: usize,
+) -> &'a mut String {
+    let start = *start.get_or_insert(index);
+    unquoted.get_or_insert_with(|| source[start..index].to_string())
+}
+
+/// Like `tokenize`, for callers that keep the words.
+pub fn tokenize_owned(input: &str) -> Result<Vec<String>, SyntaxError> {
+    Ok(tokenize(input)?.into_iter().map(Cow::into_owned).collect())
+}
+
+/// How part of a word was written, which decides the expansions it undergoes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    /// Bare text: parameters expand and their values are split into fields
+    Unquoted,
+    /// Inside double quotes: parameters expand, and their values stay in one field
+    Double,
+    /// Inside single quotes or escaped with a backslash: taken as is
+    Literal,
+}
+
+/// Like `tokenize`, but keeps track of how each part of a word was quoted: every word is a list of
+/// segments, with neighbouring characters quoted the same way in one segment.
+pub fn tokenize_segments(input: &str) -> Result<Vec<Vec<(Quoting, String)>>, SyntaxError> {
+    let mut words = Vec::new();
+    let mut word: Option<Vec<(Quoting, String)>> = None;
+    let offset = input.len() - input.trim_start().len();
+    let trimmed = input.trim();
+    let mut chars = trimmed.char_indices().peekable();
+
+    let error = |index: usize, message: &str| SyntaxError {
+        column: input[..offset + index].chars().count() + 1,
+        message: message.to_string(),
+    };
+    let push = |word: &mut Option<Vec<(Quoting, String)>>, quoting: Quoting, c: Option<char>| {
+        let segments = word.get_or_insert_default();
+        if segments.last().is_none_or(|(last, _)| *last != quoting) {
+            segments.push((quoting, String::new()));
+        }
+        if let Some(c) = c {
+            segments.last_mut().unwrap().1.push(c);
+        }
+    };
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' => {
+                push(&mut word, Quoting::Literal, None);
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, ch)) => push(&mut word, Quoting::Literal, Some(ch)),
+                        None => return Err(error(index, &tr!("Unterminated single quote"))),
+                    }
+                }
+            }
+            '"' => {
+                push(&mut word, Quoting::Double, None);
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.peek() {
+                            Some((_, '"' | '\\' | '$' | '`' | '\n')) => {
+                                push(&mut word, Quoting::Literal, Some(chars.next().unwrap().1));
+                            }
+                            _ => push(&mut word, Quoting::Double, Some('\\')),
+                        },
+                        Some((_, ch)) => push(&mut word, Quoting::Double, Some(ch)),
+                        None => return Err(error(index, &tr!("Unterminated double quote"))),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some((_, ch)) => push(&mut word, Quoting::Literal, Some(ch)),
+                None => return Err(error(index, &tr!("Trailing backslash"))),
+            },
+            ' ' | '\t' => words.extend(word.take()),
+            '#' if word.is_none() => break,
+            '$' if arithmetic_expansion(&trimmed[index..]).is_some() => {
+                let end = index + arithmetic_expansion(&trimmed[index..]).unwrap_or(0);
+                push(&mut word, Quoting::Unquoted, Some(c));
+                while let Some((_, ch)) = chars.next_if(|&(i, _)| i < end) {
+                    push(&mut word, Quoting::Unquoted, Some(ch));
+                }
+            }
+            _ => push(&mut word, Quoting::Unquoted, Some(c)),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// Calls `f` with the byte index of every character that is not quoted, escaped, a quote or a backslash,
+// stopping at a comment. An arithmetic expansion is passed over as a whole, like a quoted string. Returns the
+// index where the comment starts.
+fn scan_unquoted(input: &str, mut f: impl FnMut(usize, char)) -> Option<usize> {
+    let mut in_quote: Option<char> = None;
+    let mut escaped = false;
+    let mut at_word_start = true;
+    // where the arithmetic expansion being passed over ends
+    let mut skip_to = 0;
+
+    for (i, c) in input.char_indices() {
+        if i < skip_to {
+            continue;
+        }
+        if escaped {
+            escaped = false;
+            at_word_start = false;
+            continue;
+        }
+        match in_quote {
+            Some('\'') => {
+                if c == '\'' {
+                    in_quote = None;
+                }
+            }
+            Some(_) => match c {
+                '\\' => escaped = true,
+                '"' => in_quote = None,
+                _ => {}
+            },
+            None => match c {
+                '\\' => escaped = true,
+                '\'' | '"' => in_quote = Some(c),
+                '#' if at_word_start => return Some(i),
+                '$' if arithmetic_expansion(&input[i..]).is_some() => {
+                    skip_to = i + arithmetic_expansion(&input[i..]).unwrap_or(0);
+                }
+                _ => f(i, c),
+            },
+        }
+        at_word_start = in_quote.is_none() && matches!(c, ' ' | '\t' | '|' | ';' | '&');
+    }
+    None
+}
+
+/// The length in bytes of the arithmetic expansion `$((expression))` that `text` starts with, if it does and
+/// the expansion is closed. Parentheses in the expression nest.
+pub fn arithmetic_expansion(text: &str) -> Option<usize> {
+    let expr = text.strip_prefix("$((")?;
+    let mut depth = 0usize;
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => return chars.next_if(|&(_, c)| c == ')').map(|_| i + "$(())".len()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// How a line that ends before its command does goes on in the next line.
+#[derive(Debug, PartialEq)]
+pub enum Continuation {
+    /// The line ends inside quotes, which take in the newline
+    Quote,
+    /// The line ends with a backslash, which goes away along with the newline
+    Backslash,
+    /// The line opens a compound command like `if` without closing it; the newline ends a command in it
+    Compound,
+}
+
+/// Whether `input` ends inside quotes or after an unquoted backslash and so needs the next line to be
+/// complete. A comment ends a line whatever is in it.
+pub fn continuation(input: &str) -> Option<Continuation> {
+    let mut in_quote: Option<char> = None;
+    let mut escaped = false;
+    let mut at_word_start = true;
+    for c in input.chars() {
+        if escaped {
+            escaped = false;
+            at_word_start = false;
+            continue;
+        }
+        if let Some(quote) = in_quote {
+            match c {
+                '\\' if quote == '"' => escaped = true,
+                _ if c == quote => in_quote = None,
+                _ => {}
+            }
+        } else {
+            match c {
+                '\\' => escaped = true,
+                '\'' | '"' => in_quote = Some(c),
+                '#' if at_word_start => return None,
+                _ => {}
+            }
+        }
+        at_word_start = in_quote.is_none() && matches!(c, ' ' | '\t' | '|' | ';' | '&');
+    }
+    match (in_quote, escaped) {
+        (Some(_), _) => Some(Continuation::Quote),
+        (None, true) => Some(Continuation::Backslash),
+        (None, false) => None,
+    }
+}
+
+// Splits a command line into pipeline stages on `|` characters that are not quoted, escaped,
+// or inside a comment.
+pub fn split_pipeline(input: &str) -> Vec<&str> {
+    let mut stages = Vec::new();
+    let mut start = 0;
+    scan_unquoted(input, |i, c| {
+        if c == '|' {
+            stages.push(&input[start..i]);
+            start = i + 1;
+        }
+    });
+    stages.push(&input[start..]);
+    stages
+}
+
+/// Splits input that spans lines, as a compound command read over several lines does, at the newlines that are
+/// not quoted or escaped. A comment ends with its line.
+pub fn split_lines(input: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut rest = input;
+    loop {
+        let mut newline = None;
+        let comment = scan_unquoted(rest, |i, c| {
+            if c == '\n' && newline.is_none() {
+                newline = Some(i);
+            }
+        });
+        // the scan stops at a comment, so a newline it found comes before any comment
+        let end = match (newline, comment) {
+            (Some(newline), _) => Some(newline),
+            (None, Some(comment)) => rest[comment..].find('\n').map(|i| comment + i),
+            (None, None) => None,
+        };
+        let Some(end) = end else {
+            lines.push(rest);
+            return lines;
+        };
+        lines.push(&rest[..end]);
+        rest = &rest[end + 1..];
+    }
+}
+
+// Splits off a trailing `# comment`, returning the code before it and the comment including the `#`.
+pub fn split_comment(input: &str) -> (&str, Option<&str>) {
+    match scan_unquoted(input, |_, _| {}) {
+        Some(i) => (&input[..i], Some(&input[i..])),
+        None => (input, None),
+    }
+}
+
+/// What ends a command in a list, which decides whether the next one runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// `;` or the end of the line: the next command runs regardless
+    Sequence,
+    /// `&&`: the next command runs if this one succeeded
+    And,
+    /// `||`: the next command runs if this one failed
+    Or,
+    /// `&`: the command runs in the background and the next one right away
+    Background,
+}
+
+impl Connector {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Connector::Sequence => ";",
+            Connector::And => "&&",
+            Connector::Or => "||",
+            Connector::Background => "&",
+        }
+    }
+}
+
+// Splits a command line into the commands of a list on `;`, `&`, `&&` and `||` that are not quoted, escaped,
+// in a comment or inside `(( ))` or `[[ ]]`, returning each command with what ends it. The last command
+// keeps the comment, and is empty when the line ends with `;` or `&`.
+pub fn split_list(input: &str) -> Vec<(&str, Connector)> {
+    let bytes = input.as_bytes();
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut parentheses = 0usize;
+    let mut conditional = false;
+    scan_unquoted(input, |i, c| {
+        // the second character of `&&` or `||` was dealt with along with the first
+        if i < start {
+            return;
+        }
+        let next = bytes.get(i + 1).copied();
+        let connector = match (c, next) {
+            ('(', _) => {
+                parentheses += 1;
+                return;
+            }
+            (')', _) => {
+                parentheses = parentheses.saturating_sub(1);
+                return;
+            }
+            ('[', Some(b'[')) if input[start..i].trim().is_empty() => {
+                conditional = true;
+                return;
+            }
+            (']', Some(b']')) => {
+                conditional = false;
+                return;
+            }
+            _ if parentheses > 0 || conditional => return,
+            ('&', Some(b'&')) => Connector::And,
+            ('|', Some(b'|')) => Connector::Or,
+            // `&>`, `>&`, `<&` and `&>>` redirect
+            ('&', Some(b'>')) => return,
+            ('&', _) if i > 0 && matches!(bytes[i - 1], b'>' | b'<') => return,
+            ('&', _) => Connector::Background,
+            (';', _) => Connector::Sequence,
+            _ => return,
+        };
+        commands.push((&input[start..i], connector));
+        start = i + connector.as_str().len();
+    });
+    commands.push((&input[start..], Connector::Sequence));
+    commands
+}
+
+// Splits a command into the source text of its words, quotes and escapes intact, leaving out any comment.
+pub fn split_words(input: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let end = scan_unquoted(input, |i, c| {
+        if matches!(c, ' ' | '\t') {
+            words.push(&input[start..i]);
+            start = i + 1;
+        }
+    });
+    words.push(&input[start..end.unwrap_or(input.len())]);
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+// Splits off the `NAME=value` words that start a command, returning each name with the source text of its
+// value, and the rest of the command.
+pub fn split_assignments(input: &str) -> (Vec<(&str, &str)>, &str) {
+    let mut assignments = Vec::new();
+    for word in split_words(input) {
+        match word.split_once('=') {
+            Some((name, value)) if is_name(name) => assignments.push((name, value)),
+            _ => {
+                let start = word.as_ptr() as usize - input.as_ptr() as usize;
+                return (assignments, &input[start..]);
+            }
+        }
+    }
+    (assignments, "")
+}
+
+/// Input a command takes from the command line itself instead of from a file.
+#[derive(Debug, PartialEq)]
+pub enum HereInput<'a> {
+    /// `<<word`: the lines after the command line up to one that is the delimiter `word` names, with leading
+    /// tabs stripped from them for `<<-word`
+    Document { word: &'a str, strip_tabs: bool },
+    /// `<<<word`: the word followed by a newline
+    String(&'a str),
+}
+
+impl HereInput<'_> {
+    /// The delimiter of a here-document written `word`, and whether it is quoted. Quoting any part of it
+    /// keeps the document from being expanded.
+    pub fn delimiter(word: &str) -> (String, bool) {
+        let delimiter = tokenize(word).map(|words| words.concat()).unwrap_or_else(|_| word.to_string());
+        (delimiter, word.contains(['\'', '"', '\\']))
+    }
+}
+
+// Splits the `<<`, `<<-` and `<<<` redirections off a command, returning them in order with the source text
+// of their words, and the command without them. An operator without a word is a syntax error.
+pub fn split_here_inputs(input: &str) -> Result<(Vec<HereInput<'_>>, String), SyntaxError> {
+    let operator = |i: usize| {
+        let rest = &input[i..];
+        let len = if rest.starts_with("<<<") || rest.starts_with("<<-") {
+            3
+        } else if rest.starts_with("<<") {
+            2
+        } else {
+            return None;
+        };
+        Some(i..i + len)
+    };
+    let (operators, rest) = split_operators(input, operator)?;
+    let inputs = operators.into_iter().map(|(operator, word)| match operator {
+        "<<<" => HereInput::String(word),
+        operator => HereInput::Document { word, strip_tabs: operator == "<<-" },
+    });
+    Ok((inputs.collect(), rest))
+}
+
+/// Where one of a command's file descriptors leads instead of the terminal.
+#[derive(Debug, PartialEq)]
+pub struct Redirection<'a> {
+    /// The file descriptor redirected: 1, the output, or 0, the input, for `<`, unless the operator starts with
+    /// another number
+    pub fd: u32,
+    /// The source text of the file name, or of the file descriptor copied for `>&` and `<&`
+    pub word: &'a str,
+    /// `<`: the file is read rather than written
+    pub input: bool,
+    /// `>>`: the output is added to the end of the file instead of replacing what it held
+    pub append: bool,
+    /// `>&` and `<&`: the file descriptor goes wherever the one `word` names goes
+    pub duplicate: bool,
+}
+
+// Splits the `>`, `>>`, `>&`, `<` and `<&` redirections off a command, returning them in order and the command
+// without them. Each operator can start with the number of the file descriptor it redirects, and `&>` and `&>>`
+// redirect both the output and the error output. The `<<` operators of here-documents are left in the command.
+// An operator without a word is a syntax error.
+pub fn split_redirections(input: &str) -> Result<(Vec<Redirection<'_>>, String), SyntaxError> {
+    let operator = |i: usize| {
+        let rest = &input[i..];
+        let len = if rest.starts_with(">>") || rest.starts_with(">&") || rest.starts_with("<&") {
+            2
+        } else if rest.starts_with("<<") || input[..i].ends_with('<') {
+            return None;
+        } else if rest.starts_with(['>', '<']) {
+            1
+        } else {
+            return None;
+        };
+        // a number right before the operator is part of it when it makes up a whole word
+        let before = &input[..i];
+        let text = before.trim_end_matches(|c: char| c.is_ascii_digit());
+        let start = if text.len() < before.len() {
+            let is_fd = before[text.len()..].parse::<u32>().is_ok();
+            if is_fd && (text.is_empty() || text.ends_with([' ', '\t'])) { text.len() } else { i }
+        } else if rest.starts_with('>') {
+            before.strip_suffix('&').map_or(i, str::len)
+        } else {
+            i
+        };
+        Some(start..i + len)
+    };
+    let (operators, rest) = split_operators(input, operator)?;
+    let mut redirections = Vec::new();
+    for (operator, word) in operators {
+        let both = operator.starts_with('&');
+        let operator = operator.trim_start_matches('&');
+        let digits = operator.trim_start_matches(|c: char| c.is_ascii_digit());
+        let input = digits.starts_with('<');
+        let fd = operator[..operator.len() - digits.len()].parse().unwrap_or(if input { 0 } else { 1 });
+        let (append, duplicate) = (digits == ">>", digits.ends_with('&'));
+        redirections.push(Redirection { fd, word, input, append, duplicate });
+        if both {
+            redirections.push(Redirection { fd: 2, word: "1", input: false, append: false, duplicate: true });
+        }
+    }
+    Ok((redirections, rest))
+}
+
+// Operators split off a command, each with the source text of its word
+type Operators<'a> = Vec<(&'a str, &'a str)>;
+
+// Splits operators off a command, each with the source text of the word after it, and returns them in order
+// along with the command without them. `operator` gives the byte range of the operator found at the `<` or
+// `>` at a byte index, if it is one; the range can start before that character. An operator without a word
+// is a syntax error.
+fn split_operators(
+    input: &str,
+    operator: impl Fn(usize) -> Option<Range<usize>>,
+) -> Result<(Operators<'_>, String), SyntaxError> {
+    let mut operators = Vec::new();
+    let mut next = 0;
+    scan_unquoted(input, |i, c| {
+        if matches!(c, '<' | '>')
+            && i >= next
+            && let Some(range) = operator(i)
+        {
+            next = range.end;
+            operators.push(range);
+        }
+    });
+
+    let mut found = Vec::new();
+    let mut rest = String::new();
+    let mut end = 0;
+    for range in operators {
+        if range.start < end {
+            continue;
+        }
+        let after = &input[range.end..];
+        let start = range.end + after.len() - after.trim_start_matches([' ', '\t']).len();
+        let mut word_len = None;
+        scan_unquoted(&input[start..], |j, c| {
+            if word_len.is_none() && matches!(c, ' ' | '\t' | '<' | '>') {
+                word_len = Some(j);
+            }
+        });
+        let word = &input[start..start + word_len.unwrap_or(input.len() - start)];
+        if word.is_empty() {
+            let token = if start < input.len() { &input[start..start + 1] } else { "newline" };
+            let message = tr!("syntax error near unexpected token `{}'", token);
+            return Err(SyntaxError { column: input[..start].chars().count() + 1, message });
+        }
+        rest.push_str(&input[end..range.start]);
+        found.push((&input[range], word));
+        end = start + word.len();
+    }
+    rest.push_str(&input[end..]);
+    Ok((found, rest))
+}
+
+/// Whether `name` can name a variable: letters, digits and underscores, not starting with a digit.
+pub fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Quotes a word so that tokenize() turns it back into the same single word.
+pub fn quote(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::tokenizer::tokenize;
+    use std::borrow::Cow;
+    use crate::tokenizer::{
+        continuation, quote, split_assignments, split_comment, split_here_inputs, split_lines, split_list,
+        split_pipeline, split_redirections, split_words, tokenize, tokenize_segments, Connector, Continuation,
+        HereInput, Quoting, Redirection,
+    };
     #[test]
     fn test_simple() {
         assert_eq!(
@@ -114,6 +693,17 @@ mod tests {
         assert!(tokenize("echo 'unclosed").is_err());
     }
 
+    #[test]
+    fn test_continuation() {
+        assert_eq!(continuation("echo 'a"), Some(Continuation::Quote));
+        assert_eq!(continuation("echo \"a\\\" b"), Some(Continuation::Quote));
+        assert_eq!(continuation("echo a \\"), Some(Continuation::Backslash));
+        assert_eq!(continuation("echo 'a\\'"), None);
+        assert_eq!(continuation("echo a\\\\"), None);
+        assert_eq!(continuation("echo a # it's"), None);
+        assert_eq!(continuation("echo a\\ #'"), Some(Continuation::Quote));
+    }
+
     #[test]
     fn test_backslash_escape() {
         assert_eq!(
@@ -228,4 +818,165 @@ mod tests {
         // A trailing backslash with nothing after it should be an error
         assert!(tokenize(r"echo hello\").is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            tokenize("echo hello # a comment").unwrap(),
+            vec!["echo", "hello"]
+        );
+        assert_eq!(tokenize("#!/usr/bin/env rush").unwrap(), Vec::<String>::new());
+        // '#' inside a word or quotes is literal
+        assert_eq!(
+            tokenize("echo a#b '#c'").unwrap(),
+            vec!["echo", "a#b", "#c"]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        // an arithmetic expansion is part of a word, spaces, operators and all
+        let input = "echo x$(( (1 + 2) * 3 ))y $((i < 2 | i > 4 ; j & 1 #)) $((open";
+        let words = ["echo", "x$(( (1 + 2) * 3 ))y", "$((i < 2 | i > 4 ; j & 1 #))", "$((open"];
+        assert_eq!(tokenize(input).unwrap(), words);
+        assert_eq!(split_words(input), words);
+        assert_eq!(split_list(input), [(input, Connector::Sequence)]);
+        assert_eq!(split_pipeline(input), [input]);
+        assert_eq!(split_redirections(input).unwrap(), (Vec::new(), input.to_string()));
+        let segments = tokenize_segments("\"$((1 + 2))\"$(( 3 ))").unwrap();
+        let expected = [(Quoting::Double, "$((1 + 2))".to_string()), (Quoting::Unquoted, "$(( 3 ))".to_string())];
+        assert_eq!(segments, [expected]);
+    }
+
+    #[test]
+    fn test_split_pipeline() {
+        assert_eq!(split_pipeline("ls"), vec!["ls"]);
+        assert_eq!(split_pipeline("ls -l | grep rs | wc -l"), vec!["ls -l ", " grep rs ", " wc -l"]);
+        assert_eq!(split_pipeline(r#"echo 'a|b' "c|d" e\|f"#), vec![r#"echo 'a|b' "c|d" e\|f"#]);
+        assert_eq!(split_pipeline("echo a # x | y"), vec!["echo a # x | y"]);
+        assert_eq!(split_pipeline("a || b"), vec!["a ", "", " b"]);
+    }
+
+    #[test]
+    fn test_split_here_inputs() {
+        let (inputs, rest) = split_here_inputs("cat <<EOF -n <<- 'END'x <<<\"$a b\" '<<no'").unwrap();
+        assert_eq!(inputs, [
+            HereInput::Document { word: "EOF", strip_tabs: false },
+            HereInput::Document { word: "'END'x", strip_tabs: true },
+            HereInput::String("\"$a b\""),
+        ]);
+        assert_eq!(rest, "cat  -n   '<<no'");
+        assert_eq!(HereInput::delimiter("'END'x"), ("ENDx".to_string(), true));
+        assert_eq!(HereInput::delimiter("EOF"), ("EOF".to_string(), false));
+        assert_eq!(split_here_inputs("a<<b<<<c").unwrap().1, "a");
+        assert_eq!(split_here_inputs("cat <<").unwrap_err().to_string(), "syntax error near unexpected token `newline'");
+    }
+
+    #[test]
+    fn test_split_redirections() {
+        let (redirections, rest) = split_redirections("echo a>out >> 'log file' b '>no'").unwrap();
+        assert_eq!(redirections, [
+            Redirection { fd: 1, word: "out", input: false, append: false, duplicate: false },
+            Redirection { fd: 1, word: "'log file'", input: false, append: true, duplicate: false },
+        ]);
+        assert_eq!(rest, "echo a  b '>no'");
+        let (redirections, rest) = split_redirections("cmd 2>err 3>>log >&2 2>&1 a2>b").unwrap();
+        assert_eq!(redirections, [
+            Redirection { fd: 2, word: "err", input: false, append: false, duplicate: false },
+            Redirection { fd: 3, word: "log", input: false, append: true, duplicate: false },
+            Redirection { fd: 1, word: "2", input: false, append: false, duplicate: true },
+            Redirection { fd: 2, word: "1", input: false, append: false, duplicate: true },
+            Redirection { fd: 1, word: "b", input: false, append: false, duplicate: false },
+        ]);
+        assert_eq!(rest, "cmd     a2");
+        let (redirections, rest) = split_redirections("make &>>log").unwrap();
+        assert_eq!(redirections, [
+            Redirection { fd: 1, word: "log", input: false, append: true, duplicate: false },
+            Redirection { fd: 2, word: "1", input: false, append: false, duplicate: true },
+        ]);
+        assert_eq!(rest, "make ");
+        assert_eq!(split_here_inputs("cat <<EOF>out").unwrap().1, "cat >out");
+        let (redirections, rest) = split_redirections("cat <in 3< x <&3 <<EOF <<<'s'").unwrap();
+        assert_eq!(redirections, [
+            Redirection { fd: 0, word: "in", input: true, append: false, duplicate: false },
+            Redirection { fd: 3, word: "x", input: true, append: false, duplicate: false },
+            Redirection { fd: 0, word: "3", input: true, append: false, duplicate: true },
+        ]);
+        assert_eq!(rest, "cat    <<EOF <<<'s'");
+        assert_eq!(split_redirections("echo >").unwrap_err().column, 7);
+    }
+
+    #[test]
+    fn test_split_comment_and_words() {
+        assert_eq!(split_comment("echo a#b 'c # d' # note"), ("echo a#b 'c # d' ", Some("# note")));
+        assert_eq!(split_comment("echo"), ("echo", None));
+        assert_eq!(split_words(r#"  echo  "a b"\ c 'd'e # f"#), vec!["echo", r#""a b"\ c"#, "'d'e"]);
+        assert!(split_words("# only a comment").is_empty());
+    }
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(split_lines("ls"), vec!["ls"]);
+        assert_eq!(split_lines("if true\nthen echo 'a\nb'\nfi\n"), vec!["if true", "then echo 'a\nb'", "fi", ""]);
+        // a quote in a comment doesn't hide the newline that ends it
+        assert_eq!(split_lines("echo a # it's\necho b\\\nc"), vec!["echo a # it's", "echo b\\\nc"]);
+    }
+
+    #[test]
+    fn test_split_list() {
+        use Connector::*;
+        assert_eq!(split_list("ls"), vec![("ls", Sequence)]);
+        assert_eq!(
+            split_list("make && ./run || echo failed; echo done"),
+            vec![("make ", And), (" ./run ", Or), (" echo failed", Sequence), (" echo done", Sequence)]
+        );
+        assert_eq!(split_list("sleep 10 &"), vec![("sleep 10 ", Background), ("", Sequence)]);
+        assert_eq!(split_list("a&b;#c;d"), vec![("a", Background), ("b", Sequence), ("#c;d", Sequence)]);
+        assert_eq!(split_list(r"echo '&&' \; \&"), vec![(r"echo '&&' \; \&", Sequence)]);
+        assert_eq!(split_list("ls | wc"), vec![("ls | wc", Sequence)]);
+        assert_eq!(split_list("make &>log 2>&1 <&3 &"), vec![("make &>log 2>&1 <&3 ", Background), ("", Sequence)]);
+        // operators inside compound commands belong to them
+        assert_eq!(split_list("((a || b)) && [[ -n x && -z y ]]"), vec![("((a || b)) ", And), (" [[ -n x && -z y ]]", Sequence)]);
+    }
+
+    #[test]
+    fn test_split_assignments() {
+        assert_eq!(split_assignments("a=1 _b='x y' env a=2"), (vec![("a", "1"), ("_b", "'x y'")], "env a=2"));
+        assert_eq!(split_assignments(" x= "), (vec![("x", "")], ""));
+        assert_eq!(split_assignments("1x=2 'a'=b"), (vec![], "1x=2 'a'=b"));
+        assert_eq!(split_assignments("ls # a=b"), (vec![], "ls # a=b"));
+    }
+
+    #[test]
+    fn test_quote_round_trip() {
+        for word in ["plain", "", "hello world", "it's", r#"a"b\c"#, "#x"] {
+            assert_eq!(tokenize(&quote(word)).unwrap(), vec![word]);
+        }
+        assert_eq!(quote("plain"), "plain");
+    }
+
+    #[test]
+    fn test_plain_words_are_borrowed() {
+        let tokens = tokenize("  ls -l 'a b' c\\d e''").unwrap();
+        assert_eq!(tokens, vec!["ls", "-l", "a b", "cd", "e"]);
+        let borrowed: Vec<bool> = tokens.iter().map(|token| matches!(token, Cow::Borrowed(_))).collect();
+        assert_eq!(borrowed, vec![true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_segments() {
+        use Quoting::*;
+        let words = tokenize_segments(r#"a$x"b\$c"'$d'\e '' # f"#).unwrap();
+        let expected = vec![
+            vec![(Unquoted, "a$x".to_string()), (Double, "b".to_string()), (Literal, "$".to_string()), (Double, "c".to_string()), (Literal, "$de".to_string())],
+            vec![(Literal, String::new())],
+        ];
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_syntax_error_column() {
+        assert_eq!(tokenize("echo   'open").unwrap_err().column, 8);
+        assert_eq!(tokenize("  ab\\").unwrap_err().column, 5);
+    }
+}
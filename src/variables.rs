@@ -0,0 +1,218 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+// std's clocks panic on wasm32-unknown-unknown; web-time reads the browser's instead
+#[cfg(target_arch = "wasm32")]
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::system::{RealSystem, System};
+
+pub struct Variables {
+    values: HashMap<String, String>,
+    arrays: HashMap<String, Vec<String>>,
+    // names that go into the environment of the programs the shell starts
+    exported: HashSet<String>,
+    pub line_number: usize,
+    seconds_base: u64,
+    seconds_start: Instant,
+    random_state: Cell<u32>,
+    // where unset variables are looked up in the environment
+    system: Rc<dyn System>,
+}
+
+impl Default for Variables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0)
+            ^ std::process::id();
+        Variables {
+            values: HashMap::new(),
+            arrays: HashMap::new(),
+            exported: HashSet::new(),
+            line_number: 0,
+            seconds_base: 0,
+            seconds_start: Instant::now(),
+            random_state: Cell::new(seed),
+            system: Rc::new(RealSystem),
+        }
+    }
+
+    /// Looks up a shell variable, computing dynamic ones and falling back to the environment.
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "RANDOM" => Some(self.next_random().to_string()),
+            "SECONDS" => Some((self.seconds_base + self.seconds_start.elapsed().as_secs()).to_string()),
+            "EPOCHSECONDS" => Some(
+                SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0).to_string(),
+            ),
+            "LINENO" => Some(self.line_number.to_string()),
+            _ => self.values.get(name).cloned()
+                // an array referenced without a subscript means its first element
+                .or_else(|| self.arrays.get(name).and_then(|values| values.first().cloned()))
+                .or_else(|| self.system.env_var(name)),
+        }
+    }
+
+    pub fn set_system(&mut self, system: Rc<dyn System>) {
+        self.system = system;
+    }
+
+    pub fn get_array(&self, name: &str) -> Option<&[String]> {
+        self.arrays.get(name).map(|values| values.as_slice())
+    }
+
+    pub fn set_array(&mut self, name: &str, values: Vec<String>) {
+        self.values.remove(name);
+        self.arrays.insert(name.to_string(), values);
+    }
+
+    /// Sets a shell variable. Assigning RANDOM seeds the generator and assigning SECONDS
+    /// restarts the count from the given value, as in bash; other dynamic variables ignore assignment.
+    pub fn set(&mut self, name: &str, value: &str) {
+        match name {
+            "RANDOM" => self.random_state.set(value.trim().parse().unwrap_or(0)),
+            "SECONDS" => {
+                self.seconds_base = value.trim().parse().unwrap_or(0);
+                self.seconds_start = Instant::now();
+            }
+            "EPOCHSECONDS" | "LINENO" => {}
+            _ => {
+                self.arrays.remove(name);
+                self.values.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+
+    /// The value the shell itself holds for a variable, leaving out dynamic variables and the environment.
+    pub fn stored(&self, name: &str) -> Option<String> {
+        self.values.get(name).cloned()
+    }
+
+    /// Removes a variable from the shell, which leaves its value in the environment, if any, visible again.
+    pub fn unset(&mut self, name: &str) {
+        self.values.remove(name);
+        self.arrays.remove(name);
+    }
+
+    /// Puts a variable in the environment of the programs the shell starts, or takes it out with `false`. A
+    /// variable exported before it is set is exported once it is.
+    pub fn set_exported(&mut self, name: &str, exported: bool) {
+        if exported {
+            self.exported.insert(name.to_string());
+        } else {
+            self.exported.remove(name);
+        }
+    }
+
+    pub fn is_exported(&self, name: &str) -> bool {
+        self.exported.contains(name)
+    }
+
+    /// Names of the variables exported with `export`, sorted.
+    pub fn exported(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.exported.iter().collect();
+        names.sort();
+        names
+    }
+
+    /// The variables to set in the environment of a program the shell starts, on top of the shell's own
+    /// environment: those exported and those that came from the environment and changed since.
+    pub fn environment(&self) -> Vec<(String, String)> {
+        let mut environment: Vec<(String, String)> = self.values.iter()
+            .filter(|(name, _)| self.exported.contains(*name) || self.system.env_var(name).is_some())
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        environment.sort();
+        environment
+    }
+
+    /// Names of the stored (non-dynamic) variables, sorted.
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.values.keys().collect();
+        names.sort();
+        names
+    }
+
+    // Linear congruential generator yielding 0..=32767 like bash's RANDOM
+    fn next_random(&self) -> u32 {
+        let state = self.random_state.get().wrapping_mul(1103515245).wrapping_add(12345);
+        self.random_state.set(state);
+        (state >> 16) & 0x7fff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::variables::Variables;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut vars = Variables::new();
+        vars.set("greeting", "hello");
+        assert_eq!(vars.get("greeting").as_deref(), Some("hello"));
+        assert_eq!(vars.get("rush_test_unset_variable"), None);
+        assert_eq!(vars.names(), vec!["greeting"]);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut vars = Variables::new();
+        vars.set("rush_test_local", "1");
+        vars.set_exported("rush_test_exported", true);
+        assert!(vars.environment().is_empty());
+        vars.set("rush_test_exported", "a b");
+        // PATH comes from the environment, so a new value goes to programs without exporting it
+        vars.set("PATH", "/rush/test/bin");
+        assert_eq!(vars.environment(), [("PATH".to_string(), "/rush/test/bin".to_string()), ("rush_test_exported".to_string(), "a b".to_string())]);
+        vars.unset("PATH");
+        vars.set_exported("rush_test_exported", false);
+        assert!(vars.environment().is_empty());
+        assert_ne!(vars.get("PATH").as_deref(), Some("/rush/test/bin"));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let mut vars = Variables::new();
+        vars.set_array("PIPESTATUS", vec!["0".to_string(), "1".to_string()]);
+        assert_eq!(vars.get_array("PIPESTATUS").unwrap(), ["0", "1"]);
+        assert_eq!(vars.get("PIPESTATUS").as_deref(), Some("0"));
+
+        vars.set("PIPESTATUS", "3");
+        assert_eq!(vars.get_array("PIPESTATUS"), None);
+        assert_eq!(vars.get("PIPESTATUS").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_random_range_and_seed() {
+        let mut vars = Variables::new();
+        vars.set("RANDOM", "42");
+        let first: Vec<String> = (0..5).map(|_| vars.get("RANDOM").unwrap()).collect();
+        vars.set("RANDOM", "42");
+        let second: Vec<String> = (0..5).map(|_| vars.get("RANDOM").unwrap()).collect();
+        assert_eq!(first, second);
+        assert!(first.iter().all(|n| n.parse::<u32>().unwrap() <= 32767));
+    }
+
+    #[test]
+    fn test_seconds_assignment() {
+        let mut vars = Variables::new();
+        vars.set("SECONDS", "100");
+        let seconds: u64 = vars.get("SECONDS").unwrap().parse().unwrap();
+        assert!((100..102).contains(&seconds));
+    }
+
+    #[test]
+    fn test_lineno_is_read_only() {
+        let mut vars = Variables::new();
+        vars.line_number = 7;
+        vars.set("LINENO", "1");
+        assert_eq!(vars.get("LINENO").as_deref(), Some("7"));
+        assert!(vars.names().is_empty());
+    }
+}
@@ -0,0 +1,501 @@
+//! An in-memory filesystem and a [`System`] that runs commands against it.
+//!
+//! [`VirtualSystem`] has no access to the OS: its commands are Rust functions registered by name, files live
+//! in a [`VirtualFs`], and everything printed is collected in one terminal buffer. It is what the WASM build
+//! runs on, and works the same natively.
+
+use std::cell::{Cell, RefCell, RefMut};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use crate::system::{
+    read_input, write_finished, FinishedProcess, ProcessFd, ProcessGroup, ProcessInput, ProcessOutput, ProcessSink,
+    SharedBuffer, Spawned, System,
+};
+
+enum Node {
+    File(Vec<u8>),
+    Dir,
+}
+
+fn error(kind: io::ErrorKind, message: &str) -> io::Error {
+    io::Error::new(kind, message)
+}
+
+fn not_found() -> io::Error {
+    error(io::ErrorKind::NotFound, "No such file or directory")
+}
+
+/// Files and directories keyed by absolute, normalized path.
+pub struct VirtualFs {
+    nodes: BTreeMap<PathBuf, Node>,
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualFs {
+    /// A filesystem holding only the root directory.
+    pub fn new() -> Self {
+        VirtualFs { nodes: BTreeMap::from([(PathBuf::from("/"), Node::Dir)]) }
+    }
+
+    pub fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.nodes.get(path), Some(Node::Dir))
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.nodes.contains_key(path)
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.nodes.get(path) {
+            Some(Node::File(contents)) => Ok(contents.clone()),
+            Some(Node::Dir) => Err(error(io::ErrorKind::IsADirectory, "Is a directory")),
+            None => Err(not_found()),
+        }
+    }
+
+    /// Creates or replaces a file. Its directory must already exist.
+    pub fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check_parent(path)?;
+        if self.is_dir(path) {
+            return Err(error(io::ErrorKind::IsADirectory, "Is a directory"));
+        }
+        self.nodes.insert(path.to_path_buf(), Node::File(contents.to_vec()));
+        Ok(())
+    }
+
+    /// Adds to the end of a file, creating it if needed. Its directory must already exist.
+    pub fn append(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        match self.nodes.get_mut(path) {
+            Some(Node::File(file)) => {
+                file.extend_from_slice(contents);
+                Ok(())
+            }
+            _ => self.write(path, contents),
+        }
+    }
+
+    pub fn create_dir(&mut self, path: &Path) -> io::Result<()> {
+        self.check_parent(path)?;
+        if self.exists(path) {
+            return Err(error(io::ErrorKind::AlreadyExists, "File exists"));
+        }
+        self.nodes.insert(path.to_path_buf(), Node::Dir);
+        Ok(())
+    }
+
+    /// Removes a file or an empty directory.
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        if !self.exists(path) {
+            return Err(not_found());
+        }
+        if self.is_dir(path) && !self.list(path)?.is_empty() {
+            return Err(error(io::ErrorKind::DirectoryNotEmpty, "Directory not empty"));
+        }
+        self.nodes.remove(path);
+        Ok(())
+    }
+
+    /// Names of the entries in a directory, sorted.
+    pub fn list(&self, dir: &Path) -> io::Result<Vec<String>> {
+        if !self.is_dir(dir) {
+            return Err(if self.exists(dir) { error(io::ErrorKind::NotADirectory, "Not a directory") } else { not_found() });
+        }
+        Ok(self.nodes.keys()
+            .filter(|path| path.parent() == Some(dir))
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if self.is_dir(parent) => Ok(()),
+            Some(parent) if self.exists(parent) => Err(error(io::ErrorKind::NotADirectory, "Not a directory")),
+            _ => Err(not_found()),
+        }
+    }
+}
+
+/// Resolves `path` against `cwd` and removes `.` and `..` components, without looking at the filesystem.
+pub fn resolve(cwd: &Path, path: &str) -> PathBuf {
+    let mut resolved = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+    resolved
+}
+
+/// What a virtual command gets to work with. Output is collected and handed to the shell when it returns.
+pub struct CommandEnv<'a> {
+    pub args: &'a [String],
+    pub stdin: &'a [u8],
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub fs: &'a mut VirtualFs,
+    pub cwd: &'a Path,
+}
+
+impl CommandEnv<'_> {
+    /// Resolves a path argument against the working directory.
+    pub fn path(&self, path: &str) -> PathBuf {
+        resolve(self.cwd, path)
+    }
+
+    /// Writes `name: message` to stderr and returns 1, for use as `return env.fail(...)`.
+    pub fn fail(&mut self, name: &str, message: impl std::fmt::Display) -> i32 {
+        let _ = writeln!(self.stderr, "{}: {}", name, message);
+        1
+    }
+}
+
+/// A command implemented in Rust; it returns its exit status.
+pub type VirtualCommand = Box<dyn Fn(&mut CommandEnv) -> i32>;
+
+/// A [`System`] backed by a [`VirtualFs`] and a registry of commands.
+pub struct VirtualSystem {
+    fs: Rc<RefCell<VirtualFs>>,
+    cwd: RefCell<PathBuf>,
+    env: HashMap<String, String>,
+    commands: HashMap<String, VirtualCommand>,
+    terminal: SharedBuffer,
+    next_pid: Cell<u32>,
+}
+
+impl Default for VirtualSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualSystem {
+    /// An empty filesystem with the standard commands: cat, false, ls, mkdir, rm, tee, touch, true and wc.
+    pub fn new() -> Self {
+        VirtualSystem {
+            fs: Rc::new(RefCell::new(VirtualFs::new())),
+            cwd: RefCell::new(PathBuf::from("/")),
+            env: HashMap::new(),
+            commands: HashMap::new(),
+            terminal: SharedBuffer::default(),
+            next_pid: Cell::new(1),
+        }
+        .command("cat", cat)
+        .command("ls", ls)
+        .command("mkdir", mkdir)
+        .command("rm", rm)
+        .command("tee", tee)
+        .command("touch", touch)
+        .command("wc", wc)
+        .command("true", |_| 0)
+        .command("false", |_| 1)
+    }
+
+    /// Registers a command, replacing any earlier one with the same name.
+    pub fn command(mut self, name: &str, run: impl Fn(&mut CommandEnv) -> i32 + 'static) -> Self {
+        self.commands.insert(name.to_string(), Box::new(run));
+        self
+    }
+
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn fs(&self) -> RefMut<'_, VirtualFs> {
+        self.fs.borrow_mut()
+    }
+
+    /// Returns everything written to the terminal since the last call.
+    pub fn take_output(&self) -> Vec<u8> {
+        self.terminal.take()
+    }
+
+    fn run(&self, program: &str, args: &[String], stdin: &[u8]) -> io::Result<ProcessOutput> {
+        let command = self.commands.get(program).ok_or_else(not_found)?;
+        let cwd = self.cwd.borrow().clone();
+        let mut fs = self.fs.borrow_mut();
+        let mut env = CommandEnv { args, stdin, stdout: Vec::new(), stderr: Vec::new(), fs: &mut fs, cwd: &cwd };
+        let status = command(&mut env);
+        Ok(ProcessOutput { status, stdout: env.stdout, stderr: env.stderr })
+    }
+}
+
+impl System for VirtualSystem {
+    fn find_program(&self, name: &str, _path_dirs: &[String]) -> Option<PathBuf> {
+        self.commands.contains_key(name).then(|| Path::new("/bin").join(name))
+    }
+
+    fn output(&self, program: &str, args: &[String]) -> io::Result<ProcessOutput> {
+        self.run(program, args, &[])
+    }
+
+    fn spawn(
+        &self,
+        program: &str,
+        args: &[String],
+        _env: &[(String, String)],
+        stdin: ProcessInput,
+        stdout: ProcessSink,
+        stderr: ProcessSink,
+        _fds: &[(u32, ProcessFd)],
+        _group: ProcessGroup,
+    ) -> io::Result<Spawned> {
+        let input = read_input(stdin)?;
+        let output = self.run(program, args, &input)?;
+        let stdout = write_finished(self, [(stdout, output.stdout), (stderr, output.stderr)])?;
+
+        let id = self.next_pid.get();
+        self.next_pid.set(id + 1);
+        Ok(Spawned { process: Box::new(FinishedProcess { id, status: output.status }), stdout })
+    }
+
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.cwd.borrow().clone())
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        let fs = self.fs.borrow();
+        if !fs.is_dir(&path) {
+            return Err(if fs.exists(&path) { error(io::ErrorKind::NotADirectory, "Not a directory") } else { not_found() });
+        }
+        *self.cwd.borrow_mut() = path;
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        if !self.fs.borrow().exists(&path) {
+            return Err(not_found());
+        }
+        Ok(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<String>> {
+        self.fs.borrow().list(&resolve(&self.cwd.borrow(), &path.to_string_lossy()))
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        self.env.get(name).cloned()
+    }
+
+    fn stdin(&self) -> Box<dyn Read> {
+        Box::new(io::empty())
+    }
+
+    fn stdout(&self) -> Box<dyn Write> {
+        Box::new(self.terminal.clone())
+    }
+
+    fn stderr(&self) -> Box<dyn Write> {
+        Box::new(self.terminal.clone())
+    }
+
+    fn open_output(&self, path: &Path, append: bool) -> io::Result<Box<dyn Write>> {
+        let path = resolve(&self.cwd.borrow(), &path.to_string_lossy());
+        let mut fs = self.fs.borrow_mut();
+        if append { fs.append(&path, &[])? } else { fs.write(&path, &[])? }
+        Ok(Box::new(FileWriter { fs: Rc::clone(&self.fs), path }))
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.fs.borrow().read(&resolve(&self.cwd.borrow(), &path.to_string_lossy()))
+    }
+}
+
+// Output redirected to a file of a virtual filesystem, which gets each write as it is made
+struct FileWriter {
+    fs: Rc<RefCell<VirtualFs>>,
+    path: PathBuf,
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fs.borrow_mut().append(&self.path, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn cat(env: &mut CommandEnv) -> i32 {
+    if env.args.is_empty() {
+        env.stdout = env.stdin.to_vec();
+        return 0;
+    }
+    let mut status = 0;
+    for arg in env.args {
+        match env.fs.read(&env.path(arg)) {
+            Ok(contents) => env.stdout.extend_from_slice(&contents),
+            Err(e) => status = env.fail("cat", format_args!("{}: {}", arg, e)),
+        }
+    }
+    status
+}
+
+fn ls(env: &mut CommandEnv) -> i32 {
+    let dirs: Vec<String> = if env.args.is_empty() { vec![".".to_string()] } else { env.args.to_vec() };
+    let mut status = 0;
+    for dir in &dirs {
+        let path = env.path(dir);
+        if env.fs.exists(&path) && !env.fs.is_dir(&path) {
+            let _ = writeln!(env.stdout, "{}", dir);
+            continue;
+        }
+        match env.fs.list(&path) {
+            Ok(names) => {
+                if dirs.len() > 1 {
+                    let _ = writeln!(env.stdout, "{}:", dir);
+                }
+                for name in names {
+                    let _ = writeln!(env.stdout, "{}", name);
+                }
+            }
+            Err(e) => status = env.fail("ls", format_args!("cannot access '{}': {}", dir, e)),
+        }
+    }
+    status
+}
+
+fn mkdir(env: &mut CommandEnv) -> i32 {
+    let parents = env.args.iter().any(|arg| arg == "-p");
+    let mut status = 0;
+    for arg in env.args.iter().filter(|arg| *arg != "-p") {
+        let path = env.path(arg);
+        let result = if parents {
+            let mut dirs: Vec<&Path> = path.ancestors().filter(|dir| !env.fs.is_dir(dir)).collect();
+            dirs.reverse();
+            dirs.into_iter().try_for_each(|dir| env.fs.create_dir(dir))
+        } else {
+            env.fs.create_dir(&path)
+        };
+        if let Err(e) = result {
+            status = env.fail("mkdir", format_args!("cannot create directory '{}': {}", arg, e));
+        }
+    }
+    status
+}
+
+fn rm(env: &mut CommandEnv) -> i32 {
+    let mut status = 0;
+    for arg in env.args {
+        if let Err(e) = env.fs.remove(&env.path(arg)) {
+            status = env.fail("rm", format_args!("cannot remove '{}': {}", arg, e));
+        }
+    }
+    status
+}
+
+// Copies stdin to stdout and to each file, replacing the files' contents
+fn tee(env: &mut CommandEnv) -> i32 {
+    env.stdout = env.stdin.to_vec();
+    let mut status = 0;
+    for arg in env.args {
+        if let Err(e) = env.fs.write(&env.path(arg), env.stdin) {
+            status = env.fail("tee", format_args!("{}: {}", arg, e));
+        }
+    }
+    status
+}
+
+fn touch(env: &mut CommandEnv) -> i32 {
+    let mut status = 0;
+    for arg in env.args {
+        let path = env.path(arg);
+        if env.fs.exists(&path) {
+            continue;
+        }
+        if let Err(e) = env.fs.write(&path, &[]) {
+            status = env.fail("touch", format_args!("cannot touch '{}': {}", arg, e));
+        }
+    }
+    status
+}
+
+fn wc(env: &mut CommandEnv) -> i32 {
+    let count = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes);
+        (bytes.iter().filter(|&&b| b == b'\n').count(), text.split_whitespace().count(), bytes.len())
+    };
+    if env.args.is_empty() {
+        let (lines, words, bytes) = count(env.stdin);
+        let _ = writeln!(env.stdout, "{} {} {}", lines, words, bytes);
+        return 0;
+    }
+    let mut status = 0;
+    for arg in env.args {
+        match env.fs.read(&env.path(arg)) {
+            Ok(contents) => {
+                let (lines, words, bytes) = count(&contents);
+                let _ = writeln!(env.stdout, "{} {} {} {}", lines, words, bytes, arg);
+            }
+            Err(e) => status = env.fail("wc", format_args!("{}: {}", arg, e)),
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::rc::Rc;
+    use crate::shell::{ExitStatus, Shell, ShellConfig};
+    use crate::vfs::{resolve, VirtualFs, VirtualSystem};
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(Path::new("/home/user"), "docs/../notes.txt"), Path::new("/home/user/notes.txt"));
+        assert_eq!(resolve(Path::new("/home/user"), "/etc/./motd"), Path::new("/etc/motd"));
+        assert_eq!(resolve(Path::new("/"), "../.."), Path::new("/"));
+    }
+
+    #[test]
+    fn test_fs() {
+        let mut fs = VirtualFs::new();
+        fs.create_dir(Path::new("/docs")).unwrap();
+        fs.write(Path::new("/docs/a.txt"), b"hello").unwrap();
+        assert_eq!(fs.read(Path::new("/docs/a.txt")).unwrap(), b"hello");
+        assert_eq!(fs.list(Path::new("/docs")).unwrap(), ["a.txt"]);
+        assert_eq!(fs.write(Path::new("/missing/b.txt"), b"").unwrap_err().to_string(), "No such file or directory");
+        assert_eq!(fs.remove(Path::new("/docs")).unwrap_err().to_string(), "Directory not empty");
+    }
+
+    #[test]
+    fn test_shell_on_virtual_system() {
+        let system = Rc::new(VirtualSystem::new());
+        let mut shell = Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() });
+        assert!(shell.eval("mkdir -p /home/user/docs\ncd /home/user").success());
+        assert!(shell.eval("echo hello world | tee docs/greeting.txt | wc").success());
+        assert_eq!(system.take_output(), b"1 2 12\n");
+
+        shell.eval("cat docs/greeting.txt\nls docs\npwd\ntype ls");
+        assert_eq!(String::from_utf8(system.take_output()).unwrap(), "hello world\ngreeting.txt\n/home/user\nls is /bin/ls\n");
+
+        assert_eq!(shell.eval("cat nope"), ExitStatus(1));
+        assert_eq!(system.take_output(), b"cat: nope: No such file or directory\n");
+        assert_eq!(shell.eval("vim"), ExitStatus(127));
+    }
+
+    #[test]
+    fn test_redirection_on_virtual_system() {
+        let system = Rc::new(VirtualSystem::new());
+        let mut shell = Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() });
+        assert!(shell.eval("echo hello > greeting.txt\nls >> greeting.txt\nwc greeting.txt >/count.txt").success());
+        assert_eq!(system.fs().read(Path::new("/greeting.txt")).unwrap(), b"hello\ngreeting.txt\n");
+        assert_eq!(shell.eval("echo x > /missing/file"), ExitStatus(1));
+    }
+}
@@ -0,0 +1,70 @@
+//! JavaScript bindings for running rush in a browser, on a [`VirtualSystem`].
+//!
+//! The library is only built as a `cdylib` for the browser, so native builds don't produce one. Build it
+//! with `cargo rustc --release --lib --target wasm32-unknown-unknown --crate-type cdylib`, generate the
+//! bindings with `wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/rush.wasm`,
+//! then:
+//!
+//! ```js
+//! import init, { WebShell } from "./pkg/rush.js";
+//! await init();
+//! const shell = new WebShell();
+//! shell.eval("mkdir docs\necho hello | tee docs/hello.txt");
+//! console.log(shell.takeOutput());
+//! ```
+
+use std::path::Path;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use crate::shell::{Shell, ShellConfig};
+use crate::vfs::VirtualSystem;
+
+const HOME: &str = "/home/user";
+
+#[wasm_bindgen]
+pub struct WebShell {
+    shell: Shell,
+    system: Rc<VirtualSystem>,
+}
+
+#[wasm_bindgen]
+impl WebShell {
+    /// A shell in an empty home directory, with the standard virtual commands.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WebShell {
+        let system = Rc::new(VirtualSystem::new().env("HOME", HOME));
+        system.fs().create_dir(Path::new("/home")).unwrap();
+        system.fs().create_dir(Path::new(HOME)).unwrap();
+        let mut shell = Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() });
+        shell.eval(&format!("cd {}", HOME));
+        WebShell { shell, system }
+    }
+
+    /// Runs `input` line by line and returns the status of the last command.
+    pub fn eval(&mut self, input: &str) -> i32 {
+        self.shell.eval(input).code()
+    }
+
+    /// Everything commands printed since the last call, stdout and stderr interleaved as on a terminal.
+    #[wasm_bindgen(js_name = takeOutput)]
+    pub fn take_output(&self) -> String {
+        String::from_utf8_lossy(&self.system.take_output()).into_owned()
+    }
+
+    #[wasm_bindgen(js_name = writeFile)]
+    pub fn write_file(&self, path: &str, contents: &str) -> Result<(), JsError> {
+        Ok(self.system.fs().write(Path::new(path), contents.as_bytes())?)
+    }
+
+    #[wasm_bindgen(js_name = readFile)]
+    pub fn read_file(&self, path: &str) -> Option<String> {
+        let contents = self.system.fs().read(Path::new(path)).ok()?;
+        Some(String::from_utf8_lossy(&contents).into_owned())
+    }
+}
+
+impl Default for WebShell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
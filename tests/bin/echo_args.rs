@@ -0,0 +1,10 @@
+//! A program for the tests that shows the arguments it gets exactly: their number, then each one in angle
+//! brackets on a line of its own.
+
+fn main() {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    println!("{}", args.len());
+    for arg in args {
+        println!("<{}>", arg.to_string_lossy());
+    }
+}
@@ -0,0 +1,129 @@
+//! Runs rush inside a pseudo-terminal so tests see it exactly as a user at a terminal would.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The prompt an interactive shell prints before reading a line.
+pub const PROMPT: &str = "$ ";
+
+/// The control character a terminal sends for `^C`, `^D`, `^Z`, ...
+pub fn ctrl(key: char) -> String {
+    ((key.to_ascii_uppercase() as u8 & 0x1f) as char).to_string()
+}
+
+pub struct PtySession {
+    child: Box<dyn Child + Send + Sync>,
+    // kept alive so the terminal stays open until the session is dropped
+    _master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    output: Receiver<Vec<u8>>,
+    // everything read so far that `expect` hasn't consumed
+    pending: String,
+    home: PathBuf,
+}
+
+impl PtySession {
+    /// Starts rush with `args` in an empty home directory, so the user's own startup files don't interfere.
+    pub fn spawn(args: &[&str]) -> Self {
+        let home = std::env::temp_dir().join(format!("rush-pty-{}-{:?}", std::process::id(), thread::current().id()));
+        std::fs::create_dir_all(&home).unwrap();
+
+        let pair = native_pty_system()
+            .openpty(PtySize { rows: 24, cols: 80, pixel_width: 0, pixel_height: 0 })
+            .unwrap();
+        let mut command = CommandBuilder::new(env!("CARGO_BIN_EXE_rush"));
+        command.args(args);
+        command.env("HOME", &home);
+        command.env("TERM", "dumb");
+        command.env_remove("XDG_CONFIG_HOME");
+        command.env_remove("RUSH_ENV");
+        // the default prompt shows the directory, which the tests would have to know
+        command.env("PS1", PROMPT);
+        command.cwd(&home);
+        let child = pair.slave.spawn_command(command).unwrap();
+        // only the child should hold the terminal's other end, so reads see EOF once it exits
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            while let Ok(n) = reader.read(&mut buffer) {
+                if n == 0 || sender.send(buffer[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let writer = pair.master.take_writer().unwrap();
+        PtySession { child, _master: pair.master, writer, output, pending: String::new(), home }
+    }
+
+    /// Types `keys` without pressing enter.
+    pub fn send(&mut self, keys: &str) {
+        self.writer.write_all(keys.as_bytes()).unwrap();
+        self.writer.flush().unwrap();
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        self.send(&format!("{}\r", line));
+    }
+
+    /// Waits until `text` appears in the output and returns everything up to and including it. Output before
+    /// the match is consumed, so successive calls match successive output.
+    pub fn expect(&mut self, text: &str) -> String {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(index) = self.pending.find(text) {
+                let end = index + text.len();
+                let seen = self.pending[..end].to_string();
+                self.pending.drain(..end);
+                return seen;
+            }
+            match self.output.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(bytes) => self.pending.push_str(&String::from_utf8_lossy(&bytes)),
+                Err(RecvTimeoutError::Timeout) => panic!("timed out waiting for {:?}; output so far: {:?}", text, self.pending),
+                Err(RecvTimeoutError::Disconnected) => panic!("rush exited before printing {:?}; output: {:?}", text, self.pending),
+            }
+        }
+    }
+
+    /// Waits for the next prompt, then runs `line` and returns its output (without the echoed input).
+    pub fn run(&mut self, line: &str) -> String {
+        self.expect(PROMPT);
+        self.send_line(line);
+        // the terminal echoes what was typed; output starts on the next line
+        self.expect(&format!("{}\r\n", line));
+        let output = self.expect(PROMPT);
+        // leave the prompt for the next command to find
+        self.pending.insert_str(0, PROMPT);
+        output[..output.len() - PROMPT.len()].replace("\r\n", "\n")
+    }
+
+    /// Waits for rush to exit and returns its exit status.
+    pub fn wait(&mut self) -> u32 {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status.exit_code();
+            }
+            if Instant::now() > deadline {
+                panic!("rush didn't exit; output so far: {:?}", self.pending);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
@@ -0,0 +1,60 @@
+//! Drives the shell engine through the library's public API, the way a program embedding rush does.
+
+use std::io::Write;
+use std::rc::Rc;
+use rush::system::FakeSystem;
+use rush::completion::complete_command;
+use rush::{tokenize, Execute, ExitStatus, Io, RushError, Shell, ShellConfig, ShellContext};
+
+fn shell() -> (Shell, Rc<FakeSystem>) {
+    let system = Rc::new(FakeSystem::new().program("upper", |_, input| (0, input.to_ascii_uppercase())));
+    (Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() }), system)
+}
+
+#[test]
+fn test_execute_line() {
+    let (mut shell, system) = shell();
+    assert!(shell.execute_line("greeting='hello\nthere'").success());
+    assert!(shell.execute_line("echo \"$greeting\" | upper").success());
+    assert_eq!(shell.execute_line("rush-no-such-command"), ExitStatus(127));
+    assert!(matches!(shell.last_error(), Some(RushError::CommandNotFound(_))));
+    assert_eq!(system.output(), b"HELLO\nTHERE\n");
+    assert_eq!(system.errors(), b"rush: line 3: rush-no-such-command: command not found\n");
+}
+
+#[test]
+fn test_context_is_shared() {
+    let (mut shell, system) = shell();
+    let ctx: &mut ShellContext = shell.context();
+    ctx.variables.set("name", "embedder");
+    ctx.aliases.insert("greet".to_string(), "echo hi".to_string());
+    assert!(shell.eval("greet $name\nanswer=42").success());
+    assert_eq!(shell.context().variables.get("answer").as_deref(), Some("42"));
+    assert_eq!(system.output(), b"hi embedder\n");
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(tokenize(r#"echo "a b" c\ d 'e' # f"#).unwrap(), ["echo", "a b", "c d", "e"]);
+    assert_eq!(tokenize("echo 'open").unwrap_err().column, 6);
+}
+
+struct Shout;
+
+impl Execute for Shout {
+    fn execute(&self, args: &[String], _ctx: &mut ShellContext, io: &mut Io) -> anyhow::Result<()> {
+        writeln!(io.stdout, "{}!", args.join(" ").to_uppercase())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_register_builtin() {
+    let (mut shell, system) = shell();
+    shell.register_builtin("shout", Shout, "shout [word ...]", "Say it louder");
+    assert!(shell.eval("shout hello there\ntype shout\nhelp shout").success());
+    let output = String::from_utf8(system.output()).unwrap();
+    assert!(output.starts_with("HELLO THERE!\nshout is a shell builtin\n"), "{output}");
+    assert!(output.contains("shout [word ...]"), "{output}");
+    assert_eq!(complete_command("sho", shell.context()), ["shout"]);
+}
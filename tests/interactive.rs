@@ -0,0 +1,317 @@
+mod common;
+
+use common::{ctrl, PtySession, PROMPT};
+
+#[test]
+fn test_prompt_and_output() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    assert_eq!(rush.run("echo hello world"), "hello world\n");
+    assert_eq!(rush.run("echo one | cat"), "one\n");
+}
+
+#[test]
+fn test_exit_status() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("ls /rush-no-such-dir");
+    rush.expect(PROMPT);
+    rush.send_line("exit");
+    assert_eq!(rush.wait(), 2);
+}
+
+#[test]
+fn test_end_of_input_exits() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("true");
+    rush.expect(PROMPT);
+    rush.send(&ctrl('d'));
+    assert_eq!(rush.wait(), 0);
+}
+
+#[test]
+fn test_command_not_found() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    assert_eq!(rush.run("rush-no-such-command"), "rush: rush-no-such-command: command not found\n");
+    rush.expect(PROMPT);
+    rush.send_line("exit");
+    assert_eq!(rush.wait(), 127);
+}
+
+#[test]
+fn test_rc_file() {
+    let rc = std::env::temp_dir().join(format!("rush-pty-rc-{}", std::process::id()));
+    std::fs::write(&rc, "alias greet='echo hi from rc'\nexport RUSH_RC=1 RUSH_RC_LINES=2\n").unwrap();
+    let mut rush = PtySession::spawn(&["--rcfile", rc.to_str().unwrap()]);
+    assert_eq!(rush.run("greet"), "hi from rc\n");
+    assert_eq!(rush.run("sh -c 'echo $RUSH_RC $RUSH_RC_LINES'"), "1 2\n");
+    // --norc wins over --rcfile
+    let mut norc = PtySession::spawn(&["--norc", "--rcfile", rc.to_str().unwrap()]);
+    assert_eq!(norc.run("echo [$RUSH_RC]"), "[]\n");
+    std::fs::remove_file(&rc).unwrap();
+}
+
+#[test]
+fn test_prompt_template() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    rush.send_line(r"PS1='\w \?> '");
+    rush.expect("~ 0> ");
+    rush.send_line("false");
+    rush.expect("~ 1> ");
+}
+
+#[test]
+fn test_here_document() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    // the lines of the document are read at a `> ` prompt
+    rush.send_line("tr a-z A-Z <<END");
+    rush.expect("\r\n> ");
+    rush.send_line("quiet");
+    rush.expect("\r\n> ");
+    rush.send_line("END");
+    rush.expect("QUIET\r\n");
+    assert_eq!(rush.run("echo done"), "done\n");
+}
+
+#[test]
+fn test_continued_lines() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    rush.send_line("echo 'first");
+    rush.expect("\r\n> ");
+    rush.send_line("second' \\");
+    rush.expect("\r\n> ");
+    rush.send_line("third");
+    rush.expect("first\r\nsecond third\r\n");
+    // so does an `if` until its `fi`
+    rush.expect(PROMPT);
+    rush.send_line("if ((1)); then");
+    rush.expect("\r\n> ");
+    rush.send_line("echo inside");
+    rush.expect("\r\n> ");
+    rush.send_line("fi");
+    rush.expect("inside\r\n");
+    assert_eq!(rush.run("echo done"), "done\n");
+}
+
+#[test]
+fn test_read() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    rush.send_line("read -p 'Name: ' first last");
+    rush.expect("\r\nName: ");
+    rush.send_line("Ada King Lovelace");
+    rush.expect("Ada King Lovelace\r\n");
+    assert_eq!(rush.run("echo $last, $first"), "King Lovelace, Ada\n");
+    // what is typed for -s isn't shown
+    rush.expect(PROMPT);
+    rush.send_line("read -s secret; echo \"[$secret]\"");
+    rush.expect("\"[$secret]\"\r\n");
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    rush.send_line("hidden");
+    assert_eq!(rush.expect(PROMPT), format!("\r\n[hidden]\r\n{}", PROMPT));
+}
+
+#[test]
+fn test_programs_use_the_terminal() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    // cat reads from the terminal and its output shows up before it exits
+    rush.send_line("cat");
+    rush.expect("cat\r\n");
+    rush.send_line("typed line");
+    rush.expect("typed line\r\ntyped line\r\n");
+    // Ctrl-C ends cat, not the shell, which prompts for the next command
+    rush.send(&ctrl('c'));
+    assert_eq!(rush.run("echo $?"), "130\n");
+}
+
+#[test]
+fn test_interrupts() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    // Ctrl-C at the prompt drops the line
+    rush.expect(PROMPT);
+    rush.send("echo typed");
+    rush.send(&ctrl('c'));
+    rush.expect("^C\r\n");
+    // it interrupts every program of a pipeline
+    rush.expect(PROMPT);
+    rush.send_line("sleep 10 | sleep 10");
+    rush.expect("sleep 10 | sleep 10\r\n");
+    // once they've started
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    rush.send(&ctrl('c'));
+    assert_eq!(rush.run("echo $?"), "130\n");
+    // and the rest of the line
+    rush.expect(PROMPT);
+    rush.send_line("sleep 10; echo not reached");
+    rush.expect("not reached\r\n");
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    rush.send(&ctrl('c'));
+    assert_eq!(rush.run("echo $?"), "130\n");
+    // an interrupt sent to the shell itself goes to the program it waits for
+    assert_eq!(rush.run("sh -c 'kill -INT $PPID; sleep 10; echo not interrupted'"), "");
+    assert_eq!(rush.run("echo alive"), "alive\n");
+}
+
+#[test]
+fn test_line_editing() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.expect(PROMPT);
+    // typing in the middle, end, backspace, home and delete
+    rush.send("eecho wld");
+    rush.send("\x1b[D\x1b[Dor");
+    rush.send("\x1b[Fl\x7f!");
+    rush.send(&format!("{}\x1b[3~", ctrl('a')));
+    rush.send("\r");
+    // the line is redrawn as it's edited; the command's output follows it
+    rush.expect("\r\n");
+    assert_eq!(rush.expect(PROMPT), "world!\r\n$ ");
+}
+
+#[test]
+fn test_history() {
+    let mut first = PtySession::spawn(&["--norc"]);
+    first.run("echo one");
+    first.run("echo two");
+    // up twice recalls the command before last
+    first.expect(PROMPT);
+    first.send("\x1b[A\x1b[A\r");
+    first.expect("\r\n");
+    assert_eq!(first.expect(PROMPT), "one\r\n$ ");
+
+    // another shell started in the same home reads the history file
+    let mut second = PtySession::spawn(&["--norc"]);
+    assert_eq!(second.run("history"), "    1  echo one\n    2  echo two\n    3  echo one\n    4  history\n");
+    assert_eq!(second.run("history 1"), "    5  history 1\n");
+}
+
+#[test]
+fn test_history_search() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("echo one");
+    rush.run("echo two");
+    rush.run("echo three");
+    rush.expect(PROMPT);
+    // the newest entry holding what's typed shows as it's typed; Ctrl-R again finds the one before, and
+    // once there is none the one found stays
+    rush.send(&format!("{}t", ctrl('r')));
+    rush.expect("(reverse-i-search)`t': echo three");
+    rush.send(&ctrl('r'));
+    rush.expect("(reverse-i-search)`t': echo two");
+    rush.send(&ctrl('r'));
+    rush.expect("(failed reverse-i-search)`t': echo two");
+    rush.send("\r");
+    rush.expect("\r\n");
+    assert_eq!(rush.expect(PROMPT), "two\r\n$ ");
+
+    // Esc brings back the line as it was, and another key takes the entry found to edit
+    rush.send(&format!("echo kept{}on", ctrl('r')));
+    rush.expect("`on': echo one");
+    rush.send("\x1b");
+    rush.expect("$ echo kept");
+    rush.send(&format!(" {}hr", ctrl('r')));
+    rush.expect("`hr': echo three");
+    rush.send("\x1b[D\x1b[D\x1b[D\x7f\r");
+    rush.expect("\r\n");
+    assert_eq!(rush.expect(PROMPT), "tree\r\n$ ");
+}
+
+#[test]
+fn test_command_completion() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("alias rush-greet='echo hi'; alias rush-great='echo great'");
+    rush.expect(PROMPT);
+    // Tab completes a command name, and lists the names it could be once they part ways
+    rush.send("ech\t");
+    rush.expect("$ echo ");
+    rush.send("one; rush-g\t\t");
+    rush.expect("rush-great  rush-greet\r\n");
+    rush.send("e\t\r");
+    rush.expect("\r\n");
+    assert_eq!(rush.expect(PROMPT), "one\r\nhi\r\n$ ");
+}
+
+#[test]
+fn test_argument_completion() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("mkdir -p projects/rush; echo notes >notes.txt");
+    rush.expect(PROMPT);
+    // cd completes only directories, into which completion goes on; other commands complete files
+    rush.send("cd p\t");
+    rush.expect("$ cd projects/");
+    rush.send("r\t");
+    rush.expect("$ cd projects/rush/");
+    rush.send("\r");
+    rush.expect(PROMPT);
+    rush.send("cat ../../no\t");
+    rush.expect("$ cat ../../notes.txt ");
+    rush.send("\r");
+    rush.expect("\r\n");
+    assert_eq!(rush.expect(PROMPT), "notes\r\n$ ");
+}
+
+#[test]
+fn test_history_expansion() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    rush.run("echo one");
+    rush.run("echo two");
+    // the line is shown as expanded before it runs, and goes in the history that way
+    assert_eq!(rush.run("!!"), "echo two\ntwo\n");
+    assert_eq!(rush.run("!1 | tr a-z A-Z"), "echo one | tr a-z A-Z\nONE\n");
+    assert_eq!(rush.run("!-2"), "echo two\ntwo\n");
+    assert_eq!(rush.run("!echo 'x!'"), "echo two 'x!'\ntwo x!\n");
+    assert_eq!(rush.run("!nope"), "rush: !nope: event not found\n");
+    assert_eq!(rush.run("history 2"), "    6  echo two 'x!'\n    7  history 2\n");
+}
+
+#[test]
+fn test_background_jobs() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    // the prompt comes back while the job runs
+    assert!(rush.run("sleep 0.5 &").starts_with("[1] "));
+    assert_eq!(rush.run("jobs"), "[1]+  Running                 sleep 0.5 &\n");
+    assert_eq!(rush.run("fg %1"), "sleep 0.5\n");
+    // a job that finished is reported before the next prompt
+    rush.run("sleep 0.1 &");
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert_eq!(rush.run(""), "[1]+  Done                    sleep 0.1\n");
+}
+
+#[test]
+fn test_suspend() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    // Ctrl+Z stops the program in the foreground and brings back the prompt
+    rush.expect(PROMPT);
+    rush.send_line("sleep 1 | cat");
+    rush.expect("sleep 1 | cat\r\n");
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    rush.send(&ctrl('z'));
+    rush.expect("[1]+  Stopped                 sleep 1 | cat\r\n");
+    assert_eq!(rush.run("echo $?"), "148\n");
+    assert_eq!(rush.run("jobs"), "[1]+  Stopped                 sleep 1 | cat\n");
+    // `fg` continues it in the foreground
+    assert_eq!(rush.run("fg"), "sleep 1 | cat\n");
+    assert_eq!(rush.run("jobs"), "");
+}
+
+#[test]
+fn test_kill() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    assert!(rush.run("sleep 10 &").starts_with("[1] "));
+    assert_eq!(rush.run("kill -STOP %1; jobs"), "[1]+  Stopped                 sleep 10\n");
+    // the stopped job continues to take the signal; it may be reported as soon as the next prompt
+    let killed = rush.run("kill %1");
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert_eq!(killed + &rush.run("jobs"), "[1]+  Terminated              sleep 10\n");
+}
+
+#[test]
+fn test_output_order() {
+    let mut rush = PtySession::spawn(&["--norc"]);
+    // output the shell buffers is written before an error that follows it
+    assert_eq!(
+        rush.run("type cd nosuch echo"),
+        "cd is a shell builtin\nrush: type: nosuch: not found\necho is a shell builtin\n"
+    );
+}
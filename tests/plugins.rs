@@ -0,0 +1,66 @@
+//! Builds the sample C plugin in tests/plugins and loads it into rush.
+
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use rush::plugin::load_plugins;
+use rush::system::FakeSystem;
+use rush::{ExitStatus, Shell, ShellConfig};
+
+// Compiles tests/plugins/hello.c into `<config>/rush/plugins` and returns that directory, or None when
+// there is no C compiler
+fn build_plugin(name: &str) -> Option<PathBuf> {
+    let config = std::env::temp_dir().join(format!("rush-plugin-{}-{}", name, std::process::id()));
+    let plugins = config.join("rush").join("plugins");
+    std::fs::create_dir_all(&plugins).unwrap();
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let status = Command::new("cc")
+        .args(["-shared", "-fPIC", "-I"])
+        .arg(root.join("include"))
+        .arg("-o")
+        .arg(plugins.join(format!("hello.{}", std::env::consts::DLL_EXTENSION)))
+        .arg(root.join("tests/plugins/hello.c"))
+        .status()
+        .ok()?;
+    assert!(status.success(), "failed to compile the sample plugin");
+    Some(config)
+}
+
+#[test]
+fn test_plugin_registrations() {
+    let Some(config) = build_plugin("registrations") else {
+        eprintln!("no C compiler, skipping");
+        return;
+    };
+    let system = Rc::new(FakeSystem::new());
+    let mut shell = Shell::new(ShellConfig { system: system.clone(), ..ShellConfig::default() });
+    load_plugins(&config.join("rush").join("plugins"), shell.context());
+
+    assert!(shell.eval("hello big world").success());
+    assert_eq!(shell.eval("hello --fail"), ExitStatus(3));
+    assert_eq!(system.output(), b"hello big world\n");
+    assert!(system.errors().is_empty());
+
+    shell.context().variables.set("PS1", "$ ");
+    assert_eq!(shell.prompt(), "(plugin) $ ");
+    let words = ["greet".to_string(), "w".to_string()];
+    assert_eq!(shell.context().completions["greet"].complete(&words), ["world", "there"]);
+    std::fs::remove_dir_all(&config).unwrap();
+}
+
+#[test]
+fn test_plugins_load_at_startup() {
+    let Some(config) = build_plugin("startup") else {
+        eprintln!("no C compiler, skipping");
+        return;
+    };
+    let script = config.join("script.rush");
+    std::fs::write(&script, "hello from script\nhelp hello\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rush")).env("XDG_CONFIG_HOME", &config).arg(&script).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("hello from script\nhello [name ...]"), "{}", stdout);
+    assert!(stdout.contains("Say hello"), "{}", stdout);
+    std::fs::remove_dir_all(&config).unwrap();
+}
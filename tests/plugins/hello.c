@@ -0,0 +1,41 @@
+/* A sample plugin: a `hello` builtin, a prompt segment and completions for `greet`. */
+#include <string.h>
+#include "rush_plugin.h"
+
+static void write_str(void *sink, RushWriteFn write, const char *s) {
+    write(sink, (const uint8_t *)s, strlen(s));
+}
+
+static int hello(size_t argc, const char *const *argv, void *sink, RushWriteFn write) {
+    if (argc > 0 && strcmp(argv[0], "--fail") == 0) {
+        return 3;
+    }
+    write_str(sink, write, "hello");
+    for (size_t i = 0; i < argc; i++) {
+        write_str(sink, write, " ");
+        write_str(sink, write, argv[i]);
+    }
+    write_str(sink, write, "\n");
+    return 0;
+}
+
+static void segment(void *sink, RushWriteFn write) {
+    write_str(sink, write, "(plugin) ");
+}
+
+static void complete_greet(size_t argc, const char *const *argv, void *sink, RushWriteFn write) {
+    (void)argc;
+    (void)argv;
+    write_str(sink, write, "world\nthere\n");
+}
+
+uint32_t rush_plugin_abi_version(void) {
+    return RUSH_PLUGIN_ABI_VERSION;
+}
+
+int rush_plugin_init(const RushPluginHost *host) {
+    host->register_builtin(host->host, "hello", "hello [name ...]", "Say hello", hello);
+    host->register_prompt_segment(host->host, "sample", segment);
+    host->register_completion(host->host, "greet", complete_greet);
+    return 0;
+}
@@ -0,0 +1,200 @@
+//! Runs rush the way scripts use it: on a script file, with -c, or reading commands piped to it.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn rush(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rush"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_command_string() {
+    let output = rush(&["-c", "echo $0 $1; rush-no-such-command", "name", "one"], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "name one\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "name: line 1: rush-no-such-command: command not found\n");
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[test]
+fn test_script_file() {
+    let script = std::env::temp_dir().join(format!("rush-script-{}.sh", std::process::id()));
+    let contents = "echo $0 $1 $#\nshift\nfor arg in \"$@\"; do echo \"<$arg>\"; done\n((1 > 2))\n";
+    std::fs::write(&script, contents).unwrap();
+    let output = rush(&[script.to_str().unwrap(), "arg", "two words", ""], "");
+    std::fs::remove_file(&script).unwrap();
+    let expected = format!("{} arg 3\n<two words>\n<>\n", script.display());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_arguments_reach_programs_as_words() {
+    // echo_args shows the arguments it gets, one per line
+    let words = r#"'' "a  b" c\ d "*" '$x' e"" $unset "$unset" "$@" "$*""#;
+    let script = format!("set -- '' 'x  y'\n'{}' {}\n", env!("CARGO_BIN_EXE_echo_args"), words);
+    let output = rush(&["--norc"], &script);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "10\n<>\n<a  b>\n<c d>\n<*>\n<$x>\n<e>\n<>\n<>\n<x  y>\n< x  y>\n",
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_commands_from_stdin() {
+    // no prompts when the commands don't come from a terminal
+    let output = rush(&["--norc"], "echo piped\n((0))\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "piped\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_signal_traps() {
+    use std::os::unix::process::ExitStatusExt;
+    let script = "trap 'echo bye' EXIT\ntrap 'echo caught $?' TERM\nkill -TERM $$\necho after\ntrap - TERM\nkill $$\necho gone\n";
+    let output = rush(&["--norc"], script);
+    // a trapped signal runs its trap and the shell goes on; once the trap is gone the signal ends the shell
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "caught 0\nafter\n");
+    assert_eq!(output.status.signal(), Some(15));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_path_changes() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("rush-path-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("rush-greet");
+    std::fs::write(&program, "#!/bin/sh\necho hello\n").unwrap();
+    std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+    // lookups follow PATH as the shell changes it, including programs it found before
+    let script = format!("rush-greet\nexport PATH={}:$PATH\nrush-greet\nPATH=/nowhere\nrush-greet\n", dir.display());
+    // a name with a slash is a path of its own
+    let script = format!("{}cd {}; ./rush-greet\n", script, dir.display());
+    let output = rush(&["--norc"], &script);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\nhello\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rush: line 1: rush-greet: command not found\nrush: line 5: rush-greet: command not found\n",
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cd_through_symbolic_links() {
+    let dir = std::env::temp_dir().join(format!("rush-links-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("real/sub")).unwrap();
+    std::os::unix::fs::symlink(dir.join("real/sub"), dir.join("link")).unwrap();
+    let dir = dir.canonicalize().unwrap();
+    // `..` leaves a link the way it came in, unless -P resolves the link first
+    let script = format!("cd {}/link; echo $PWD; cd ..; echo $PWD; cd -P link/..; echo $PWD\n", dir.display());
+    // pwd shows the directory the way cd got there, or with -P where the links lead
+    let output = rush(&["--norc"], &format!("{}cd ../link; pwd; pwd -P; PWD=/; pwd -L\n", script));
+    std::fs::remove_dir_all(&dir).unwrap();
+    let dir = dir.display();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{dir}/link\n{dir}\n{dir}/real\n{dir}/link\n{dir}/real/sub\n{dir}/real/sub\n"),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_error_redirection() {
+    let file = std::env::temp_dir().join(format!("rush-both-{}", std::process::id()));
+    // a program's error output goes down the pipe, or shares the file its output goes to
+    let script = format!(
+        "sh -c 'echo err >&2' 2>&1 | tr a-z A-Z\nsh -c 'echo out; echo err >&2; echo end' >{0} 2>&1\ncat {0}\n\
+         sh -c 'echo quiet >&2' 2>/dev/null; echo loud >&2\n",
+        file.display(),
+    );
+    let output = rush(&["--norc"], &script);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ERR\nout\nerr\nend\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "loud\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_other_file_descriptors() {
+    let file = std::env::temp_dir().join(format!("rush-fd-{}", std::process::id()));
+    // a program gets the file descriptors above 2 it's given, and a third one swaps the outputs
+    let script = format!(
+        "sh -c 'echo three >&3; echo out' 3>{0}\ncat <{0}\nsh -c 'echo out; echo err >&2' 3>&1 1>&2 2>&3\n\
+         tr a-z A-Z 0<{0} | cat; sh -c 'cat <&4' 4<{0}\n",
+        file.display(),
+    );
+    let output = rush(&["--norc"], &script);
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "out\nthree\nerr\nTHREE\nthree\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "out\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exit_statuses() {
+    let dir = std::env::temp_dir().join(format!("rush-statuses-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("plain"), "echo hi\n").unwrap();
+    std::fs::write(dir.join("lost"), "#!/rush-no-such-interpreter\n").unwrap();
+    std::fs::set_permissions(dir.join("lost"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    // a command that is found but can't run fails with 126, one that isn't found, or whose interpreter isn't,
+    // with 127, a program with its own status and one killed by a signal with 128 plus the signal's number
+    let script = format!(
+        "cd {}\n./plain; echo $?\nPATH=. plain; echo $?\n./sub; echo $?\n./missing; echo $?\n\
+         sh -c 'exit 3'; echo $?\nsh -c 'kill -TERM $$'; echo $?\n./lost; echo $?\n",
+        dir.display(),
+    );
+    let output = rush(&["--norc"], &script);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "126\n126\n126\n127\n3\n143\n127\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "rush: line 2: failed to execute ./plain: Permission denied\n\
+         rush: line 3: failed to execute plain: Permission denied\n\
+         rush: line 4: failed to execute ./sub: Is a directory\n\
+         rush: line 5: ./missing: command not found\n\
+         rush: line 8: ./lost: command not found\n",
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_leaves_the_rest_of_stdin() {
+    // read takes only its line, so the program after it gets the next one
+    let output = rush(&["--norc", "-c", "read a; echo \"<$a>\"; cat"], "l1\nl2\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "<l1>\nl2\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_checking_without_running() {
+    // the body of a here-document is text, not commands to check
+    let script = "cat <<EOF\nit's | not code\nEOF\necho 'open\n";
+    let output = rush(&["-n"], script);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "stdin:4:6: Unterminated single quote\n");
+    assert_eq!(output.status.code(), Some(2));
+    // nor is the rest of a quoted word or of a line ending with a backslash
+    let output = rush(&["-n"], "echo \"a\n|\" \\\n  b\necho 'c\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "stdin:4:6: Unterminated single quote\n");
+    // compound commands have to be complete
+    let output = rush(&["-n"], "if true\nthen echo yes\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "stdin:2:14: syntax error: unexpected end of file\n");
+    assert_eq!(output.status.code(), Some(2));
+    let output = rush(&["-n"], "echo a\n  done\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "stdin:2:3: syntax error near unexpected token `done'\n");
+    let output = rush(&["--dump-ast"], "cat <<EOF\n| x\nEOF\n");
+    let dump = String::from_utf8_lossy(&output.stdout);
+    assert!(dump.starts_with("stdin:1: cat <<EOF\n") && !dump.contains("stdin:2"));
+    assert_eq!(output.status.code(), Some(0));
+}